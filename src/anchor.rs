@@ -0,0 +1,23 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use cosmwasm_std::{to_json_binary, Addr, Coin, StdResult, WasmMsg};
+
+// The messages of the Anchor money market. Only what is needed to deposit stable coins is
+// defined here.
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum MarketExecuteMsg {
+    // DepositStable deposits the attached stable coin, the market mints aTerra to the caller
+    DepositStable {},
+}
+
+// deposit_msg deposits coin into market, the aTerra is minted to the caller
+pub fn deposit_msg(market: &Addr, coin: Coin) -> StdResult<WasmMsg> {
+    Ok(WasmMsg::Execute {
+        contract_addr: market.to_string(),
+        msg: to_json_binary(&MarketExecuteMsg::DepositStable {})?,
+        funds: vec![coin],
+    })
+}
@@ -0,0 +1,36 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use cosmwasm_std::{Addr, QuerierWrapper, StdResult};
+
+// The credential query of attestation registries. Only what is needed to check a receiver
+// is defined here.
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum RegistryQueryMsg {
+    // HasCredential returns whether address holds a valid credential
+    HasCredential { address: String, credential: String },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct CredentialResponse {
+    pub valid: bool,
+}
+
+// has_credential asks registry whether address holds credential
+pub fn has_credential(
+    querier: &QuerierWrapper,
+    registry: &Addr,
+    address: &Addr,
+    credential: &str,
+) -> StdResult<bool> {
+    let res: CredentialResponse = querier.query_wasm_smart(
+        registry,
+        &RegistryQueryMsg::HasCredential {
+            address: address.to_string(),
+            credential: credential.to_string(),
+        },
+    )?;
+    Ok(res.valid)
+}
@@ -1,231 +1,18663 @@
+#[cfg(not(feature = "library"))]
+use cosmwasm_std::entry_point;
 use cosmwasm_std::{
-    log, to_binary, Api, BankMsg, Binary, CanonicalAddr, CosmosMsg, Env, Extern, HandleResponse,
-    HumanAddr, InitResponse, Querier, StdError, StdResult, Storage, Uint128,
+    attr, from_json, to_json_binary, to_json_vec, Addr, Api, Attribute, BankMsg, Binary, BlockInfo,
+    Coin, CosmosMsg, Decimal, Deps, DepsMut, DistributionMsg, Empty, Env, MessageInfo, Order,
+    QuerierWrapper, Reply, Response, StakingMsg, StdError, StdResult, Storage, SubMsg,
+    SubMsgResult, Uint128, WasmMsg,
 };
+use cw2::{get_contract_version, set_contract_version};
+use cw20::{Cw20ExecuteMsg, Cw20QueryMsg, Cw20ReceiveMsg};
+use cw_storage_plus::{Bound, Map, MultiIndex};
+use serde::de::IgnoredAny;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use terra_cosmwasm::{TerraQuerier, TerraQueryWrapper};
 
-use crate::msg::{HandleMsg, InitMsg, QueryMsg, ReceiverResponse};
-use crate::state::{config, config_read, State};
+use crate::anchor::deposit_msg;
+use crate::attestation::has_credential;
+use crate::error::ContractError;
+use crate::ibc::{transfer_amount, transfer_msg, MSG_TRANSFER};
+use crate::msg::{
+    AdminAction, AdminProposalInfo, AdminProposalsResponse, AliasInfo, AliasesResponse,
+    AllowanceResponse, AllowedMethodsResponse, AllowedSendersResponse, AnchorSettings,
+    AttestationSettings, BlockedSendersResponse, CampaignDeposit, CampaignDepositsResponse,
+    CampaignResponse, CampaignStatus, CategoriesResponse, CategoryInfo, Charge, ClaimableResponse,
+    ConfigResponse, ConfigUpdate, ContributionsResponse, Contributor, DenomReconciliation,
+    DenomRuleSettings, DestinationInfo, DestinationsResponse, DustResponse,
+    EffectiveMinimumResponse, EscrowInfo, EscrowsResponse, ExecuteMsg, FailedPayoutInfo,
+    FailedPayoutsResponse, GovernanceSettings, IbcLifecycleComplete, InstantiateMsg, InvoiceInfo,
+    InvoiceStatus, InvoicesResponse, LedgerInfo, MigrateMsg, MultisigSettings, OwnershipResponse,
+    PausedResponse, Payment, PayoutQueueResponse, PendingEmergencyResponse, PendingForwardInfo,
+    PendingForwardsResponse, PendingReceiverResponse, Permission, PermissionsResponse, Permit,
+    PermitNonceResponse, ProposalInfo, ProposalStatus, ProposalsResponse, QueryMsg,
+    QueuedPayoutInfo, QuotaResponse, RebatePoolResponse, ReceiptInfo, ReceiptNftSettings,
+    ReceiptsResponse, ReceiverChangeInfo, ReceiverContractInfo, ReceiverHistoryResponse,
+    ReceiverResponse, ReceiverStatusResponse, ReconciliationResponse, ReferralInfo,
+    ReferralsResponse, RefundInfo, RefundsResponse, RotationEntry, RotationResponse, RouteInfo,
+    RouteSettings, RouteStatsResponse, RoutesResponse, SendReceipt, Share, SimulateSendResponse,
+    SplitPreviewResponse, StatsResponse, StreamInfo, StreamResponse, StreamsResponse,
+    SubscriptionInfo, SudoMsg, SwapSettings, TopContributorsResponse, TransferHistoryResponse,
+    TransferInfo, UpcomingChargesResponse, UsdFloorSettings, VestingSchedule, WaterfallResponse,
+    WaterfallTier, WindDownResponse, WorkDone,
+};
+use crate::nft::mint_msg;
+use crate::oracle::usd_value;
+use crate::split::{split_amount, Rounding};
+use crate::state::{
+    contributions, escrows, invoices, move_singletons, subscriptions, AdminProposal, AnchorConfig,
+    AnchorDeposit, Attestation, Campaign, CampaignProgress, Category, Config, Congestion,
+    Contribution, DenomRouting, DenomRule, Destination, Duration, EmergencyWithdrawal, Escrow,
+    Expiration, FundsPolicy, GovernanceChange, GovernanceConfig, IbcForwarding, IbcTransfer,
+    Invoice, MultisigConfig, Overpayment, Payout, PendingForward, PendingReceiver, Proposal,
+    RateLimit, ReceiptNft, ReceiptStatus, ReceiverChange, Referral, Retention, Route,
+    ScheduledReceiver, Stats, Stream, Subscription, SwapConfig, Traffic, Transfer, Unbonding,
+    Usage, UsdFloor, WindDown, ADMIN_PROPOSALS, ADMIN_PROPOSAL_COUNT, ALIASES, ALLOWED_METHODS,
+    ALLOWED_SENDERS, ANCHOR_DEPOSIT, BLOCKED_SENDERS, CAMPAIGN_DEPOSITS, CAMPAIGN_PROGRESS,
+    CATEGORIES, CATEGORY_STATS, CHARGES, CLAIMABLE, CLAIMED_AT, CONFIG, DAILY_USAGE, DESTINATIONS,
+    DUST, FAILED_PAYOUTS, HELD, HELD_RECEIPTS, IBC_SEQUENCES, IBC_TRANSFERS, IBC_TRANSFER_COUNT,
+    INVOICE_COUNT, LEGACY_CONFIG, LIFETIME_CAPS, MEMO_TRANSFERS, PAUSED_ROUTES, PAYOUTS,
+    PAYOUT_COUNT, PAYOUT_QUEUE, PAYOUT_QUEUE_COUNT, PAYOUT_QUEUE_CURSOR, PAYOUT_SENDER,
+    PENDING_EMERGENCY, PENDING_FORWARDS, PENDING_RECEIVER, PERMISSIONS, PERMIT_NONCE, PROPOSALS,
+    PROPOSAL_COUNT, REBATE_POOL, RECEIVER_CHANGES, RECEIVER_CHANGE_COUNT, RECEIVER_TRANSFERS,
+    REFERRALS, REFUNDS, ROTATION, ROUTES, ROUTE_STATS, SENDER_TRANSFERS, SENDER_USAGE, STATS,
+    STREAMS, SUBSCRIPTION_COUNT, SWAP_BALANCE, TRAFFIC, TRANSFERS, TRANSFERS_PRUNED,
+    TRANSFER_COUNT, UNBONDING, VOTES, WATERFALL_FILLED, WIND_DOWN,
+};
+use crate::swap::swap_msg;
+
+// version info for migration info
+const CONTRACT_NAME: &str = "crates.io:spar-test";
+const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+// length of the window of the daily cap in seconds
+const DAY: u64 = 24 * 60 * 60;
+
+// reply id of the swaps selling a deposit
+const SWAP_REPLY_ID: u64 = 1;
+// reply id of receipt nft mints, a failed mint does not fail the deposit
+const MINT_REPLY_ID: u64 = 2;
+// reply id of the deposits into the Anchor money market
+const ANCHOR_REPLY_ID: u64 = 3;
+// reply ids of payouts are the payout id added to PAYOUT_REPLY_ID
+const PAYOUT_REPLY_ID: u64 = 1 << 32;
+// reply ids of cw20 subscription charges are the subscription id added to CHARGE_REPLY_ID
+const CHARGE_REPLY_ID: u64 = 1 << 40;
+// reply ids of IBC transfers are the transfer id added to IBC_REPLY_ID
+const IBC_REPLY_ID: u64 = 1 << 48;
+
+// blocks between announcing an emergency withdrawal and executing it, about a week
+const EMERGENCY_DELAY: u64 = 100_800;
+// blocks between winding down and terminating in which funds can be claimed, about two weeks
+const CLAIM_PERIOD: u64 = 201_600;
+
+// seconds it takes to unbond a delegation, 21 days on Terra
+const UNBONDING_PERIOD: u64 = 21 * DAY;
+
+// most transfers pruned from the history when a new one is recorded
+const PRUNE_BATCH: u64 = 10;
+
+static DECIMAL_FRACTION: Uint128 = Uint128::new(1_000_000_000_000_000_000u128);
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn instantiate(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    msg: InstantiateMsg,
+) -> Result<Response, ContractError> {
+    let fee_collector = match msg.fee_collector {
+        Some(addr) => Some(deps.api.addr_validate(&addr)?),
+        None => None,
+    };
+    validate_fee(msg.fee_rate, &fee_collector)?;
+    let denom_rules = validate_denom_rules(deps.api, &env, &fee_collector, msg.denom_rules)?;
+    let state = Config {
+        receiver: validate_receiver(deps.api, &env, &msg.receiver)?,
+        owner: Some(info.sender),
+        receivers: validate_receivers(deps.api, &env, msg.receivers)?,
+        fee_rate: msg.fee_rate,
+        fee_collector,
+        fee_cap: msg.fee_cap,
+        paused: false,
+        pull: msg.pull,
+        min_amount: msg.min_amount,
+        accepted_denoms: msg.accepted_denoms,
+        funds_policy: msg.funds_policy,
+        allowlist: validate_addresses(deps.api, msg.allowlist)?,
+        hold: msg.hold,
+        flush_threshold: msg.flush_threshold,
+        receiver_delay: msg.receiver_delay,
+        restrict_senders: msg.restrict_senders,
+        rate_limit: msg.rate_limit,
+        escrow: msg.escrow,
+        stream_duration: msg.stream_duration,
+        approval_threshold: msg.approval_threshold,
+        callbacks: validate_callbacks(deps.api, &deps.querier, msg.callbacks)?,
+        validator: msg.validator,
+        swap: validate_swap(deps.api, msg.swap)?,
+        hold_failed: msg.hold_failed,
+        fallback_receiver: match msg.fallback_receiver {
+            Some(addr) => Some(validate_receiver(deps.api, &env, &addr)?),
+            None => None,
+        },
+        require_acceptance: msg.require_acceptance,
+        recovery_address: match msg.recovery_address {
+            Some(addr) => Some(deps.api.addr_validate(&addr)?),
+            None => None,
+        },
+        dust_threshold: msg.dust_threshold,
+        max_amount: msg.max_amount,
+        waterfall: validate_waterfall(deps.api, &env, msg.waterfall)?,
+        governance: validate_governance(deps.api, msg.governance)?,
+        multisig: validate_multisig(deps.api, msg.multisig)?,
+        campaign: msg.campaign,
+        receipt_nft: validate_receipt_nft(deps.api, msg.receipt_nft)?,
+        mint_receipts: msg.mint_receipts,
+        referral_rate: validate_referral_rate(msg.referral_rate)?,
+        usd_floor: validate_usd_floor(deps.api, msg.usd_floor)?,
+        max_payouts: msg.max_payouts,
+        claim_rebate: msg.claim_rebate,
+        retention: msg.retention,
+        ownership_expires_at: msg.ownership_expires_at,
+        rounding: msg.rounding,
+        refund_failed: msg.refund_failed,
+        anchor: validate_anchor(deps.api, msg.anchor)?,
+        claim_expiry: msg.claim_expiry,
+        denom_rules,
+        attestation: validate_attestation(deps.api, msg.attestation)?,
+        owner_pubkey: msg.owner_pubkey,
+        congestion: validate_congestion(msg.congestion)?,
+        ibc: validate_ibc(msg.ibc)?,
+    };
+
+    CONFIG.save(deps.storage, &state)?;
+    set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
+
+    Ok(Response::default())
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn migrate(deps: DepsMut, _env: Env, _msg: MigrateMsg) -> Result<Response, ContractError> {
+    move_singletons(deps.storage);
+    match get_contract_version(deps.storage) {
+        Ok(version) => {
+            if version.contract != CONTRACT_NAME {
+                return Err(ContractError::CannotMigrate {
+                    contract: version.contract,
+                });
+            }
+        }
+        // instances deployed before version tracking still store the single receiver
+        // layout, move them over to the current config with all new settings disabled
+        Err(_) => {
+            let legacy = LEGACY_CONFIG.load(deps.storage)?;
+            let state = Config {
+                receiver: deps.api.addr_humanize(&legacy.receiver)?,
+                owner: Some(deps.api.addr_humanize(&legacy.owner)?),
+                receivers: vec![],
+                fee_rate: Decimal::zero(),
+                fee_collector: None,
+                fee_cap: vec![],
+                paused: false,
+                pull: false,
+                min_amount: vec![],
+                accepted_denoms: vec![],
+                funds_policy: FundsPolicy::ForwardAccepted,
+                allowlist: vec![],
+                hold: false,
+                flush_threshold: vec![],
+                receiver_delay: None,
+                restrict_senders: false,
+                rate_limit: None,
+                escrow: None,
+                stream_duration: None,
+                approval_threshold: vec![],
+                callbacks: vec![],
+                validator: None,
+                swap: None,
+                hold_failed: false,
+                fallback_receiver: None,
+                require_acceptance: false,
+                recovery_address: None,
+                dust_threshold: vec![],
+                max_amount: vec![],
+                waterfall: vec![],
+                governance: None,
+                multisig: None,
+                campaign: None,
+                receipt_nft: None,
+                mint_receipts: false,
+                referral_rate: Decimal::zero(),
+                usd_floor: None,
+                max_payouts: None,
+                claim_rebate: None,
+                retention: None,
+                ownership_expires_at: None,
+                rounding: Rounding::default(),
+                refund_failed: false,
+                anchor: None,
+                claim_expiry: None,
+                denom_rules: vec![],
+                attestation: None,
+                owner_pubkey: None,
+                congestion: None,
+                ibc: None,
+            };
+            CONFIG.save(deps.storage, &state)?;
+        }
+    }
+    set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
+
+    Ok(Response::default())
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn execute(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    msg: ExecuteMsg,
+) -> Result<Response, ContractError> {
+    expire_ownership(deps.storage, &env.block)?;
+    check_wind_down(deps.storage, &msg)?;
+    match msg {
+        ExecuteMsg::TokenSend {
+            recipient,
+            route,
+            memo,
+            on_behalf_of,
+            category,
+        } => {
+            let opts = DepositOptions {
+                recipient,
+                route,
+                memo,
+                on_behalf_of,
+                category,
+                ..Default::default()
+            };
+            try_tokensend(deps, env, info, opts)
+        }
+        ExecuteMsg::ForwardExec { msg } => {
+            let opts = DepositOptions {
+                exec: Some(msg),
+                ..Default::default()
+            };
+            try_tokensend(deps, env, info, opts)
+        }
+        ExecuteMsg::HashlockSend {
+            hash,
+            timeout,
+            memo,
+        } => {
+            let opts = DepositOptions {
+                memo,
+                lock: Some(Lock::Hashlock { hash, timeout }),
+                ..Default::default()
+            };
+            try_tokensend(deps, env, info, opts)
+        }
+        ExecuteMsg::Vest { schedule, memo } => {
+            let opts = DepositOptions {
+                memo,
+                lock: Some(Lock::Vesting(schedule)),
+                ..Default::default()
+            };
+            try_tokensend(deps, env, info, opts)
+        }
+        ExecuteMsg::BatchSend { payments, memo } => try_batch_send(deps, env, info, payments, memo),
+        ExecuteMsg::Revoke { id } => try_revoke(deps, env, info, id),
+        ExecuteMsg::ResetReceiver { receiver } => {
+            check_no_multisig(deps.storage)?;
+            try_reset(deps, env, info, receiver)
+        }
+        ExecuteMsg::SetRotation { rotation } => {
+            check_no_multisig(deps.storage)?;
+            try_set_rotation(deps, env, info, rotation)
+        }
+        ExecuteMsg::SetAlias { name, address } => {
+            check_no_multisig(deps.storage)?;
+            try_set_alias(deps, env, info, name, address)
+        }
+        ExecuteMsg::SetDestination { name, destination } => {
+            check_no_multisig(deps.storage)?;
+            try_set_destination(deps, env, info, name, destination)
+        }
+        ExecuteMsg::RemoveAlias { name } => try_remove_alias(deps, info, name),
+        ExecuteMsg::SetRoute { name, route } => {
+            check_no_multisig(deps.storage)?;
+            try_set_route(deps, env, info, name, route)
+        }
+        ExecuteMsg::RemoveRoute { name } => try_remove_route(deps, info, name),
+        ExecuteMsg::SetCategory { name, receiver } => {
+            check_no_multisig(deps.storage)?;
+            try_set_category(deps, env, info, name, receiver)
+        }
+        ExecuteMsg::RemoveCategory { name } => try_remove_category(deps, info, name),
+        ExecuteMsg::PauseRoute { name } => try_set_route_paused(deps, info, name, true),
+        ExecuteMsg::UnpauseRoute { name } => try_set_route_paused(deps, info, name, false),
+        ExecuteMsg::RegisterReferral { code, referrer } => {
+            try_register_referral(deps, info, code, referrer)
+        }
+        ExecuteMsg::RemoveReferral { code } => try_remove_referral(deps, info, code),
+        ExecuteMsg::SetReferralRate { referral_rate } => {
+            try_set_referral_rate(deps, info, referral_rate)
+        }
+        ExecuteMsg::SetReceiverDelay { receiver_delay } => {
+            try_set_receiver_delay(deps, info, receiver_delay)
+        }
+        ExecuteMsg::SetRequireAcceptance { require_acceptance } => {
+            try_set_require_acceptance(deps, info, require_acceptance)
+        }
+        ExecuteMsg::AcceptReceivership {} => try_accept_receivership(deps, env, info),
+        ExecuteMsg::SetGovernance { governance } => try_set_governance(deps, info, governance),
+        ExecuteMsg::ProposeReceiver { receiver } => try_propose_receiver(deps, env, info, receiver),
+        ExecuteMsg::ProposeGovernance { governance } => {
+            try_propose_governance(deps, env, info, governance)
+        }
+        ExecuteMsg::Vote {
+            proposal_id,
+            approve,
+        } => try_vote(deps, env, info, proposal_id, approve),
+        ExecuteMsg::ExecuteProposal { proposal_id } => {
+            try_execute_proposal(deps, env, info, proposal_id)
+        }
+        ExecuteMsg::SetMultisig { multisig } => {
+            check_no_multisig(deps.storage)?;
+            try_set_multisig(deps, info, multisig)
+        }
+        ExecuteMsg::ProposeAction { action } => try_propose_action(deps, env, info, action),
+        ExecuteMsg::ApproveAction { id } => try_approve_action(deps, env, info, id),
+        ExecuteMsg::GrantPermissions {
+            address,
+            permissions,
+        } => try_update_permissions(deps, info, address, permissions, true),
+        ExecuteMsg::RevokePermissions {
+            address,
+            permissions,
+        } => try_update_permissions(deps, info, address, permissions, false),
+        ExecuteMsg::SetReceivers { receivers } => {
+            check_no_multisig(deps.storage)?;
+            try_set_receivers(deps, env, info, receivers)
+        }
+        ExecuteMsg::SetWaterfall { tiers } => {
+            check_no_multisig(deps.storage)?;
+            try_set_waterfall(deps, env, info, tiers)
+        }
+        ExecuteMsg::ResetWaterfall {} => try_reset_waterfall(deps, info),
+        ExecuteMsg::SetFee {
+            fee_rate,
+            fee_collector,
+            fee_cap,
+        } => {
+            check_no_multisig(deps.storage)?;
+            try_set_fee(deps, info, fee_rate, fee_collector, fee_cap)
+        }
+        ExecuteMsg::Pause {} => try_set_paused(deps, info, true),
+        ExecuteMsg::Unpause {} => try_set_paused(deps, info, false),
+        ExecuteMsg::SetPullMode { pull } => try_set_pull_mode(deps, info, pull),
+        ExecuteMsg::SetMinAmount { min_amount } => try_set_min_amount(deps, info, min_amount),
+        ExecuteMsg::SetUsdFloor { usd_floor } => try_set_usd_floor(deps, info, usd_floor),
+        ExecuteMsg::SetMaxAmount { max_amount } => try_set_max_amount(deps, info, max_amount),
+        ExecuteMsg::SetAcceptedDenoms { accepted_denoms } => {
+            try_set_accepted_denoms(deps, info, accepted_denoms)
+        }
+        ExecuteMsg::SetFundsPolicy { funds_policy } => {
+            try_set_funds_policy(deps, info, funds_policy)
+        }
+        ExecuteMsg::SetAllowlist { allowlist } => try_set_allowlist(deps, info, allowlist),
+        ExecuteMsg::SetHold {
+            hold,
+            flush_threshold,
+        } => try_set_hold(deps, info, hold, flush_threshold),
+        ExecuteMsg::Flush {} => try_flush(deps, env),
+        ExecuteMsg::SetDustThreshold { dust_threshold } => {
+            try_set_dust_threshold(deps, info, dust_threshold)
+        }
+        ExecuteMsg::FlushDust {} => try_flush_dust(deps, env),
+        ExecuteMsg::SetReceiptNft { receipt_nft } => try_set_receipt_nft(deps, info, receipt_nft),
+        ExecuteMsg::SetMintReceipts { mint_receipts } => {
+            try_set_mint_receipts(deps, info, mint_receipts)
+        }
+        ExecuteMsg::SetCampaign { campaign } => try_set_campaign(deps, info, campaign),
+        ExecuteMsg::ClaimCampaign {} => try_claim_campaign(deps, env, info),
+        ExecuteMsg::RefundCampaign {} => try_refund_campaign(deps, env, info),
+        ExecuteMsg::SetRestrictSenders { restrict_senders } => {
+            try_set_restrict_senders(deps, info, restrict_senders)
+        }
+        ExecuteMsg::AddSender { address } => try_update_sender(deps, info, address, true),
+        ExecuteMsg::RemoveSender { address } => try_update_sender(deps, info, address, false),
+        ExecuteMsg::BlockSender { address } => try_update_blocklist(deps, info, address, true),
+        ExecuteMsg::UnblockSender { address } => try_update_blocklist(deps, info, address, false),
+        ExecuteMsg::AllowMethod { method } => try_update_method(deps, info, method, true),
+        ExecuteMsg::DisallowMethod { method } => try_update_method(deps, info, method, false),
+        ExecuteMsg::SetRateLimit { rate_limit } => try_set_rate_limit(deps, info, rate_limit),
+        ExecuteMsg::SetCongestion { congestion } => try_set_congestion(deps, info, congestion),
+        ExecuteMsg::Withdraw { denom, amount } => try_withdraw(deps, env, info, denom, amount),
+        ExecuteMsg::SetEscrow { escrow } => try_set_escrow(deps, info, escrow),
+        ExecuteMsg::Release { id, preimage } => try_release(deps, env, info, id, preimage),
+        ExecuteMsg::Refund { id } => try_refund(deps, env, info, id),
+        ExecuteMsg::ArbitratedSend {
+            arbiter,
+            timeout,
+            memo,
+        } => {
+            let arbiter = deps.api.addr_validate(&arbiter)?;
+            let opts = DepositOptions {
+                memo,
+                lock: Some(Lock::Arbitrated { arbiter, timeout }),
+                ..Default::default()
+            };
+            try_tokensend(deps, env, info, opts)
+        }
+        ExecuteMsg::Dispute { id } => try_dispute(deps, info, id),
+        ExecuteMsg::Resolve { id, release } => try_resolve(deps, env, info, id, release),
+        ExecuteMsg::SetStreamDuration { stream_duration } => {
+            try_set_stream_duration(deps, info, stream_duration)
+        }
+        ExecuteMsg::Claim {} => try_claim(deps, env),
+        ExecuteMsg::Subscribe { amount, interval } => {
+            try_subscribe(deps, env, info, amount, interval)
+        }
+        ExecuteMsg::SubscribeCw20 {
+            token,
+            amount,
+            interval,
+        } => try_subscribe_cw20(deps, env, info, token, amount, interval),
+        ExecuteMsg::CreateInvoice {
+            payer,
+            amount,
+            expires,
+            overpayment,
+        } => try_create_invoice(deps, env, info, payer, amount, expires, overpayment),
+        ExecuteMsg::PayInvoice { id } => try_pay_invoice(deps, env, info, id),
+        ExecuteMsg::FundSubscription { id } => try_fund_subscription(deps, info, id),
+        ExecuteMsg::CancelSubscription { id } => try_cancel_subscription(deps, info, id),
+        ExecuteMsg::ProcessSubscriptions { limit } => try_process_subscriptions(deps, env, limit),
+        ExecuteMsg::ProcessDue { limit } => try_process_due(deps, env, limit),
+        ExecuteMsg::FlushIfAboveThreshold {} => try_flush_if_above_threshold(deps, env),
+        ExecuteMsg::SetApprovalThreshold { approval_threshold } => {
+            try_set_approval_threshold(deps, info, approval_threshold)
+        }
+        ExecuteMsg::Approve { id } => try_approve(deps, env, info, id),
+        ExecuteMsg::Reject { id } => try_reject(deps, info, id),
+        ExecuteMsg::SetCallbacks { callbacks } => try_set_callbacks(deps, info, callbacks),
+        ExecuteMsg::SetValidator { validator } => try_set_validator(deps, env, info, validator),
+        ExecuteMsg::Undelegate { amount } => try_undelegate(deps, env, info, amount),
+        ExecuteMsg::WithdrawRewards {} => try_withdraw_rewards(deps, env, info),
+        ExecuteMsg::ForwardRewards {} => try_forward_rewards(deps, env, info),
+        ExecuteMsg::SetSwap { swap } => try_set_swap(deps, info, swap),
+        ExecuteMsg::SetHoldFailed { hold_failed } => try_set_hold_failed(deps, info, hold_failed),
+        ExecuteMsg::SetAnchor { anchor } => try_set_anchor(deps, info, anchor),
+        ExecuteMsg::SetIbc { ibc } => {
+            check_no_multisig(deps.storage)?;
+            try_set_ibc(deps, info, ibc)
+        }
+        ExecuteMsg::SetMaxPayouts { max_payouts } => try_set_max_payouts(deps, info, max_payouts),
+        ExecuteMsg::SetClaimRebate { claim_rebate } => {
+            try_set_claim_rebate(deps, info, claim_rebate)
+        }
+        ExecuteMsg::TopUpRebates {} => try_top_up_rebates(deps, info),
+        ExecuteMsg::SetRetention { retention } => try_set_retention(deps, info, retention),
+        ExecuteMsg::Prune { up_to_id } => try_prune(deps, info, up_to_id),
+        ExecuteMsg::Distribute { limit } => try_distribute(deps, env, info, limit),
+        ExecuteMsg::RetryFailed { id } => try_retry_failed(deps, id),
+        ExecuteMsg::SetRefundFailed { refund_failed } => {
+            try_set_refund_failed(deps, info, refund_failed)
+        }
+        ExecuteMsg::SetClaimExpiry { claim_expiry } => {
+            try_set_claim_expiry(deps, info, claim_expiry)
+        }
+        ExecuteMsg::SetDenomRules { denom_rules } => {
+            check_no_multisig(deps.storage)?;
+            try_set_denom_rules(deps, env, info, denom_rules)
+        }
+        ExecuteMsg::SetAttestation { attestation } => try_set_attestation(deps, info, attestation),
+        ExecuteMsg::SetOwnerPubkey { owner_pubkey } => {
+            try_set_owner_pubkey(deps, info, owner_pubkey)
+        }
+        ExecuteMsg::ExecPermit { permit } => {
+            check_no_multisig(deps.storage)?;
+            try_exec_permit(deps, env, permit)
+        }
+        ExecuteMsg::RedirectStale { receiver } => try_redirect_stale(deps, env, info, receiver),
+        ExecuteMsg::ClaimRefund {} => try_claim_refund(deps, info),
+        ExecuteMsg::SetFallbackReceiver { fallback_receiver } => {
+            check_no_multisig(deps.storage)?;
+            try_set_fallback_receiver(deps, env, info, fallback_receiver)
+        }
+        ExecuteMsg::SetRecoveryAddress { recovery_address } => {
+            try_set_recovery_address(deps, info, recovery_address)
+        }
+        ExecuteMsg::AnnounceEmergencyWithdraw {} => {
+            check_no_multisig(deps.storage)?;
+            try_announce_emergency(deps, env, info)
+        }
+        ExecuteMsg::EmergencyWithdraw {} => {
+            check_no_multisig(deps.storage)?;
+            try_emergency_withdraw(deps, env, info)
+        }
+        ExecuteMsg::CancelEmergencyWithdraw {} => try_cancel_emergency(deps, info),
+        ExecuteMsg::RenounceOwnership {} => {
+            check_no_multisig(deps.storage)?;
+            try_renounce_ownership(deps, info)
+        }
+        ExecuteMsg::SetOwnershipExpiry {
+            ownership_expires_at,
+        } => try_set_ownership_expiry(deps, info, ownership_expires_at),
+        ExecuteMsg::WindDown {} => {
+            check_no_multisig(deps.storage)?;
+            try_wind_down(deps, env, info)
+        }
+        ExecuteMsg::Terminate {} => try_terminate(deps, env, info),
+        ExecuteMsg::Sweep { denom } => try_sweep(deps, env, info, denom),
+        ExecuteMsg::SweepCw20 { token } => try_sweep_cw20(deps, env, info, token),
+        ExecuteMsg::PullCw20 { token, amount } => try_pull_cw20(deps, env, info, token, amount),
+        ExecuteMsg::SetLifetimeCap { address, cap } => {
+            try_set_lifetime_cap(deps, info, address, cap)
+        }
+        ExecuteMsg::UpdateConfig(update) => {
+            if update.needs_approval() {
+                check_no_multisig(deps.storage)?;
+            }
+            try_update_config(deps, env, info, update)
+        }
+        ExecuteMsg::Receive(msg) => try_receive(deps, env, info, msg),
+    }
+}
+
+// validate_receiver only accepts addresses that survive a canonicalization round-trip,
+// funds are never forwarded back to the contract itself
+fn validate_receiver(api: &dyn Api, env: &Env, receiver: &str) -> Result<Addr, ContractError> {
+    let canonical = api.addr_canonicalize(receiver)?;
+    let addr = api.addr_humanize(&canonical)?;
+    if addr.as_str() != receiver {
+        return Err(ContractError::InvalidReceiver {
+            address: receiver.to_string(),
+        });
+    }
+    if addr == env.contract.address {
+        return Err(ContractError::SelfReceiver {});
+    }
+    Ok(addr)
+}
+
+fn validate_receivers(
+    api: &dyn Api,
+    env: &Env,
+    receivers: Vec<(String, Decimal)>,
+) -> Result<Vec<(Addr, Decimal)>, ContractError> {
+    let mut total = Decimal::zero();
+    let mut validated = Vec::with_capacity(receivers.len());
+    for (receiver, weight) in receivers {
+        if weight.is_zero() {
+            return Err(ContractError::ZeroWeight {});
+        }
+        total += weight;
+        validated.push((validate_receiver(api, env, &receiver)?, weight));
+    }
+    if !validated.is_empty() && total != Decimal::one() {
+        return Err(ContractError::InvalidWeights {});
+    }
+    Ok(validated)
+}
+
+fn validate_waterfall(
+    api: &dyn Api,
+    env: &Env,
+    tiers: Vec<(String, Vec<Coin>)>,
+) -> Result<Vec<(Addr, Vec<Coin>)>, ContractError> {
+    tiers
+        .into_iter()
+        .map(|(receiver, cap)| Ok((validate_receiver(api, env, &receiver)?, cap)))
+        .collect()
+}
+
+fn validate_governance(
+    api: &dyn Api,
+    governance: Option<GovernanceSettings>,
+) -> Result<Option<GovernanceConfig>, ContractError> {
+    let governance = match governance {
+        Some(governance) => governance,
+        None => return Ok(None),
+    };
+    if governance.voters.is_empty() || governance.quorum > Decimal::one() {
+        return Err(ContractError::InvalidGovernance {});
+    }
+    let voters = governance
+        .voters
+        .into_iter()
+        .map(|(voter, weight)| {
+            if weight == 0 {
+                return Err(ContractError::ZeroWeight {});
+            }
+            Ok((api.addr_validate(&voter)?, weight))
+        })
+        .collect::<Result<_, ContractError>>()?;
+    Ok(Some(GovernanceConfig {
+        voters,
+        quorum: governance.quorum,
+        voting_period: governance.voting_period,
+    }))
+}
+
+fn validate_multisig(
+    api: &dyn Api,
+    multisig: Option<MultisigSettings>,
+) -> Result<Option<MultisigConfig>, ContractError> {
+    let multisig = match multisig {
+        Some(multisig) => multisig,
+        None => return Ok(None),
+    };
+    let mut admins: Vec<Addr> = multisig
+        .admins
+        .iter()
+        .map(|admin| api.addr_validate(admin))
+        .collect::<StdResult<_>>()?;
+    admins.sort();
+    admins.dedup();
+    if multisig.threshold == 0 || multisig.threshold > admins.len() as u64 {
+        return Err(ContractError::InvalidMultisig {});
+    }
+    Ok(Some(MultisigConfig {
+        admins,
+        threshold: multisig.threshold,
+    }))
+}
+
+// check_permission fails unless sender is the owner or was granted permission. Granted
+// permissions lapse once ownership is renounced
+fn check_permission(
+    storage: &dyn Storage,
+    sender: &Addr,
+    permission: Permission,
+) -> Result<(), ContractError> {
+    let state = CONFIG.load(storage)?;
+    if state.is_owner(sender) {
+        return Ok(());
+    }
+    let mask = PERMISSIONS.may_load(storage, sender)?.unwrap_or_default();
+    if state.owner.is_none() || mask & permission.bit() == 0 {
+        return Err(ContractError::Unauthorized {});
+    }
+    Ok(())
+}
+
+// check_wind_down rejects every message once the contract is terminated, and deposits while
+// it winds down
+fn check_wind_down(storage: &dyn Storage, msg: &ExecuteMsg) -> Result<(), ContractError> {
+    let wind_down = match WIND_DOWN.may_load(storage)? {
+        Some(wind_down) => wind_down,
+        None => return Ok(()),
+    };
+    if wind_down.terminated {
+        return Err(ContractError::Terminated {});
+    }
+    match msg {
+        ExecuteMsg::TokenSend { .. }
+        | ExecuteMsg::ForwardExec { .. }
+        | ExecuteMsg::HashlockSend { .. }
+        | ExecuteMsg::ArbitratedSend { .. }
+        | ExecuteMsg::Vest { .. }
+        | ExecuteMsg::BatchSend { .. }
+        | ExecuteMsg::Subscribe { .. }
+        | ExecuteMsg::SubscribeCw20 { .. }
+        | ExecuteMsg::FundSubscription { .. }
+        | ExecuteMsg::PayInvoice { .. }
+        | ExecuteMsg::PullCw20 { .. }
+        | ExecuteMsg::Receive(_) => Err(ContractError::WindingDown {}),
+        _ => Ok(()),
+    }
+}
+
+// check_no_multisig rejects sensitive actions sent directly once a multisig is configured,
+// they have to go through ProposeAction
+fn check_no_multisig(storage: &dyn Storage) -> Result<(), ContractError> {
+    if CONFIG.load(storage)?.multisig.is_some() {
+        return Err(ContractError::ApprovalRequired {});
+    }
+    Ok(())
+}
+
+fn validate_receipt_nft(
+    api: &dyn Api,
+    receipt_nft: Option<ReceiptNftSettings>,
+) -> StdResult<Option<ReceiptNft>> {
+    receipt_nft
+        .map(|nft| {
+            Ok(ReceiptNft {
+                contract: api.addr_validate(&nft.contract)?,
+                threshold: nft.threshold,
+            })
+        })
+        .transpose()
+}
+
+fn validate_referral_rate(referral_rate: Decimal) -> Result<Decimal, ContractError> {
+    if referral_rate > Decimal::one() {
+        return Err(ContractError::ReferralRateTooHigh {});
+    }
+    Ok(referral_rate)
+}
+
+fn validate_usd_floor(
+    api: &dyn Api,
+    usd_floor: Option<UsdFloorSettings>,
+) -> StdResult<Option<UsdFloor>> {
+    usd_floor
+        .map(|floor| {
+            Ok(UsdFloor {
+                oracle: api.addr_validate(&floor.oracle)?,
+                min_value: floor.min_value,
+            })
+        })
+        .transpose()
+}
+
+// check_usd_floor values funds with the oracle of the usd floor and rejects them if they are
+// worth less than its minimum. It returns the value to log it
+fn check_usd_floor(
+    querier: &QuerierWrapper,
+    state: &Config,
+    funds: &[Coin],
+) -> Result<Option<Uint128>, ContractError> {
+    let floor = match &state.usd_floor {
+        Some(floor) => floor,
+        None => return Ok(None),
+    };
+    let value = usd_value(querier, &floor.oracle, funds)?;
+    if value < floor.min_value {
+        return Err(ContractError::BelowUsdFloor {
+            value,
+            min: floor.min_value,
+        });
+    }
+    Ok(Some(value))
+}
+
+fn validate_fee(fee_rate: Decimal, fee_collector: &Option<Addr>) -> Result<(), ContractError> {
+    if fee_rate > Decimal::one() {
+        return Err(ContractError::FeeTooHigh {});
+    }
+    if !fee_rate.is_zero() && fee_collector.is_none() {
+        return Err(ContractError::NoFeeCollector {});
+    }
+    Ok(())
+}
+
+// validate_denom_rules checks every denom has at most one rule and its fee rate is valid
+fn validate_denom_rules(
+    api: &dyn Api,
+    env: &Env,
+    fee_collector: &Option<Addr>,
+    denom_rules: Vec<DenomRuleSettings>,
+) -> Result<Vec<DenomRule>, ContractError> {
+    let mut rules: Vec<DenomRule> = vec![];
+    for rule in denom_rules {
+        if rules.iter().any(|other| other.denom == rule.denom) {
+            return Err(ContractError::DuplicateDenomRule { denom: rule.denom });
+        }
+        if let Some(fee_rate) = rule.fee_rate {
+            validate_fee(fee_rate, fee_collector)?;
+        }
+        rules.push(DenomRule {
+            denom: rule.denom,
+            fee_rate: rule.fee_rate,
+            receiver: match rule.receiver {
+                Some(addr) => Some(validate_receiver(api, env, &addr)?),
+                None => None,
+            },
+        });
+    }
+    Ok(rules)
+}
+
+// validate_denom_fees checks the fee rates of denom_rules are still valid with
+// fee_collector
+fn validate_denom_fees(
+    denom_rules: &[DenomRule],
+    fee_collector: &Option<Addr>,
+) -> Result<(), ContractError> {
+    for fee_rate in denom_rules.iter().filter_map(|rule| rule.fee_rate) {
+        validate_fee(fee_rate, fee_collector)?;
+    }
+    Ok(())
+}
+
+// validate_limits fails if the minimum amount of a denom is above its maximum
+fn validate_limits(min_amount: &[Coin], max_amount: &[Coin]) -> Result<(), ContractError> {
+    for min in min_amount {
+        if let Some(max) = max_amount.iter().find(|max| max.denom == min.denom) {
+            if min.amount > max.amount {
+                return Err(ContractError::InvalidLimits {
+                    denom: min.denom.clone(),
+                });
+            }
+        }
+    }
+    Ok(())
+}
+
+fn validate_addresses(api: &dyn Api, addresses: Vec<String>) -> StdResult<Vec<Addr>> {
+    addresses
+        .iter()
+        .map(|addr| api.addr_validate(addr))
+        .collect()
+}
+
+// validate_callbacks checks every callback goes to a contract, a plain account
+// cannot be executed
+fn validate_callbacks(
+    api: &dyn Api,
+    querier: &QuerierWrapper,
+    callbacks: Vec<(String, Binary)>,
+) -> Result<Vec<(Addr, Binary)>, ContractError> {
+    callbacks
+        .into_iter()
+        .map(|(addr, msg)| {
+            let addr = api.addr_validate(&addr)?;
+            if querier.query_wasm_contract_info(addr.as_str()).is_err() {
+                return Err(ContractError::NotAContract {
+                    address: addr.into_string(),
+                });
+            }
+            Ok((addr, msg))
+        })
+        .collect()
+}
+
+// callback returns the message receiver is executed with, if it has one
+fn callback<'a>(state: &'a Config, receiver: &Addr) -> Option<&'a Binary> {
+    state
+        .callbacks
+        .iter()
+        .find(|(addr, _)| addr == receiver)
+        .map(|(_, msg)| msg)
+}
+
+fn validate_swap(api: &dyn Api, swap: Option<SwapSettings>) -> StdResult<Option<SwapConfig>> {
+    let swap = match swap {
+        Some(swap) => swap,
+        None => return Ok(None),
+    };
+    let pairs = swap
+        .pairs
+        .into_iter()
+        .map(|(denom, pair)| Ok((denom, api.addr_validate(&pair)?)))
+        .collect::<StdResult<_>>()?;
+    Ok(Some(SwapConfig {
+        target_denom: swap.target_denom,
+        pairs,
+        max_slippage: swap.max_slippage,
+    }))
+}
+
+fn validate_attestation(
+    api: &dyn Api,
+    attestation: Option<AttestationSettings>,
+) -> StdResult<Option<Attestation>> {
+    attestation
+        .map(|attestation| {
+            Ok(Attestation {
+                registry: api.addr_validate(&attestation.registry)?,
+                credential: attestation.credential,
+            })
+        })
+        .transpose()
+}
+
+// check_attestation rejects receiver unless the registry of the attestation attests it holds
+// the credential
+fn check_attestation(
+    querier: &QuerierWrapper,
+    state: &Config,
+    receiver: &Addr,
+) -> Result<(), ContractError> {
+    let attestation = match &state.attestation {
+        Some(attestation) => attestation,
+        None => return Ok(()),
+    };
+    if !has_credential(
+        querier,
+        &attestation.registry,
+        receiver,
+        &attestation.credential,
+    )? {
+        return Err(ContractError::MissingCredential {
+            address: receiver.to_string(),
+            credential: attestation.credential.clone(),
+        });
+    }
+    Ok(())
+}
+
+fn validate_anchor(
+    api: &dyn Api,
+    anchor: Option<AnchorSettings>,
+) -> StdResult<Option<AnchorConfig>> {
+    let anchor = match anchor {
+        Some(anchor) => anchor,
+        None => return Ok(None),
+    };
+    Ok(Some(AnchorConfig {
+        money_market: api.addr_validate(&anchor.money_market)?,
+        aterra: api.addr_validate(&anchor.aterra)?,
+        denom: anchor.denom,
+    }))
+}
+
+fn validate_ibc(ibc: Option<IbcForwarding>) -> Result<Option<IbcForwarding>, ContractError> {
+    if let Some(ibc) = &ibc {
+        if ibc.channel.is_empty() || ibc.remote_receiver.is_empty() || ibc.timeout == 0 {
+            return Err(ContractError::InvalidIbc {});
+        }
+    }
+    Ok(ibc)
+}
+
+fn is_accepted(state: &Config, denom: &str) -> bool {
+    state.accepted_denoms.is_empty() || state.accepted_denoms.iter().any(|d| d == denom)
+}
+
+// apply_funds_policy splits the attached funds into what is deposited and what goes back
+// to the sender. A deposit needs at least one accepted coin
+fn apply_funds_policy(
+    state: &Config,
+    funds: Vec<Coin>,
+) -> Result<(Vec<Coin>, Vec<Coin>), ContractError> {
+    let funds: Vec<Coin> = funds
+        .into_iter()
+        .filter(|coin| !coin.amount.is_zero())
+        .collect();
+    let first = match funds.first() {
+        Some(coin) => coin,
+        None => return Err(ContractError::NoFundsSent {}),
+    };
+    if !funds.iter().any(|coin| is_accepted(state, &coin.denom)) {
+        return Err(ContractError::UnsupportedDenom {
+            denom: first.denom.clone(),
+        });
+    }
+    match state.funds_policy {
+        FundsPolicy::ForwardAll => Ok((funds, vec![])),
+        FundsPolicy::ForwardAccepted => Ok(funds
+            .into_iter()
+            .partition(|coin| is_accepted(state, &coin.denom))),
+        FundsPolicy::RejectMixed => {
+            if funds.iter().any(|coin| !is_accepted(state, &coin.denom)) {
+                return Err(ContractError::MixedDenomsNotAllowed {});
+            }
+            Ok((funds, vec![]))
+        }
+    }
+}
+
+// deduct_fee returns the fee charged on amount and what is left to forward. The fee is
+// fee_rate of the amount, but never more than the cap configured for the denom.
+fn deduct_fee(state: &Config, denom: &str, amount: Uint128) -> StdResult<(Uint128, Uint128)> {
+    let fee_rate = state
+        .denom_rules
+        .iter()
+        .find(|rule| rule.denom == denom)
+        .and_then(|rule| rule.fee_rate)
+        .unwrap_or(state.fee_rate);
+    let mut fee = amount * fee_rate;
+    if let Some(cap) = state.fee_cap.iter().find(|cap| cap.denom == denom) {
+        if fee > cap.amount {
+            fee = cap.amount;
+        }
+    }
+    Ok((fee, amount.checked_sub(fee)?))
+}
+
+// is_dust tells if coin is below the dust threshold of its denom
+fn is_dust(state: &Config, coin: &Coin) -> bool {
+    state
+        .dust_threshold
+        .iter()
+        .any(|threshold| threshold.denom == coin.denom && coin.amount < threshold.amount)
+}
+
+// check_amount checks amount against the limits of denom, its minimum raised by multiplier
+fn check_amount(
+    state: &Config,
+    denom: &str,
+    amount: Uint128,
+    multiplier: Decimal,
+) -> Result<(), ContractError> {
+    if let Some(min) = state.min_amount.iter().find(|min| min.denom == denom) {
+        let min = min.amount * multiplier;
+        if amount < min {
+            return Err(ContractError::BelowMinimum {
+                denom: denom.to_string(),
+                min,
+                got: amount,
+            });
+        }
+    }
+    if let Some(max) = state.max_amount.iter().find(|max| max.denom == denom) {
+        if amount > max.amount {
+            return Err(ContractError::AmountTooLarge {
+                denom: max.denom.clone(),
+                max: max.amount,
+            });
+        }
+    }
+    Ok(())
+}
+
+fn validate_congestion(
+    congestion: Option<Congestion>,
+) -> Result<Option<Congestion>, ContractError> {
+    if let Some(congestion) = &congestion {
+        let ascending = congestion
+            .tiers
+            .windows(2)
+            .all(|tiers| tiers[0].0 < tiers[1].0);
+        if congestion.window == 0
+            || !ascending
+            || congestion
+                .tiers
+                .iter()
+                .any(|(_, multiplier)| *multiplier < Decimal::one())
+        {
+            return Err(ContractError::InvalidCongestion {});
+        }
+    }
+    Ok(congestion)
+}
+
+// current_traffic returns the deposits forwarded in the congestion window time falls in
+fn current_traffic(
+    storage: &dyn Storage,
+    congestion: &Congestion,
+    time: u64,
+) -> StdResult<Traffic> {
+    let traffic = TRAFFIC.may_load(storage)?.unwrap_or_default();
+    if time >= traffic.window_start + congestion.window {
+        return Ok(Traffic {
+            window_start: time,
+            forwards: 0,
+        });
+    }
+    Ok(traffic)
+}
+
+// min_multiplier is what the minimum deposits are multiplied by at time
+fn min_multiplier(storage: &dyn Storage, state: &Config, time: u64) -> StdResult<Decimal> {
+    match &state.congestion {
+        Some(congestion) => {
+            let traffic = current_traffic(storage, congestion, time)?;
+            Ok(congestion.multiplier(traffic.forwards))
+        }
+        None => Ok(Decimal::one()),
+    }
+}
+
+// record_traffic counts a forwarded deposit towards congestion
+fn record_traffic(storage: &mut dyn Storage, state: &Config, time: u64) -> StdResult<()> {
+    if let Some(congestion) = &state.congestion {
+        let mut traffic = current_traffic(storage, congestion, time)?;
+        traffic.forwards += 1;
+        TRAFFIC.save(storage, &traffic)?;
+    }
+    Ok(())
+}
+
+// compute_tax returns the stability tax Terra charges on top of a bank send, so that coin
+// covers both the sent amount and its tax: min(tax_cap, amount * tax_rate / (1 + tax_rate))
+fn compute_tax(querier: &QuerierWrapper, coin: &Coin) -> StdResult<Uint128> {
+    // luna is exempt from the stability tax
+    if coin.denom == "uluna" {
+        return Ok(Uint128::zero());
+    }
+    let querier = QuerierWrapper::<TerraQueryWrapper>::new(&**querier);
+    let terra_querier = TerraQuerier::new(&querier);
+    let tax_rate: Decimal = terra_querier.query_tax_rate()?.rate;
+    let tax_cap: Uint128 = terra_querier.query_tax_cap(coin.denom.as_str())?.cap;
+    let amount = coin.amount;
+    Ok(std::cmp::min(
+        amount.checked_sub(amount.multiply_ratio(
+            DECIMAL_FRACTION,
+            DECIMAL_FRACTION * tax_rate + DECIMAL_FRACTION,
+        ))?,
+        tax_cap,
+    ))
+}
+
+// deduct_tax returns what can be sent out of coins once the tax is paid, the tax is added
+// to taxes
+fn deduct_tax(
+    querier: &QuerierWrapper,
+    coins: Vec<Coin>,
+    taxes: &mut Vec<Coin>,
+) -> StdResult<Vec<Coin>> {
+    let mut sent = vec![];
+    for coin in coins {
+        let tax = compute_tax(querier, &coin)?;
+        if !tax.is_zero() {
+            add_coins(
+                taxes,
+                vec![Coin {
+                    denom: coin.denom.clone(),
+                    amount: tax,
+                }],
+            );
+        }
+        sent.push(Coin {
+            denom: coin.denom,
+            amount: coin.amount.checked_sub(tax)?,
+        });
+    }
+    Ok(sent)
+}
+
+fn add_coins(balance: &mut Vec<Coin>, coins: Vec<Coin>) {
+    for coin in coins {
+        match balance.iter_mut().find(|c| c.denom == coin.denom) {
+            Some(existing) => existing.amount += coin.amount,
+            None => balance.push(coin),
+        }
+    }
+}
+
+// record_transfer appends the transfer to the history and returns its id
+fn record_transfer(storage: &mut dyn Storage, transfer: &Transfer) -> StdResult<u64> {
+    let id = TRANSFER_COUNT.may_load(storage)?.unwrap_or_default() + 1;
+    TRANSFER_COUNT.save(storage, &id)?;
+    TRANSFERS.save(storage, id, transfer)?;
+    if let Some(memo) = &transfer.memo {
+        MEMO_TRANSFERS.save(storage, (memo, id), &Empty {})?;
+    }
+    SENDER_TRANSFERS.save(storage, (&transfer.sender, id), &Empty {})?;
+    for recipient in transfer.recipients.iter() {
+        RECEIVER_TRANSFERS.save(storage, (recipient, id), &Empty {})?;
+    }
+
+    // only a few transfers are pruned per write, so recording one stays cheap
+    match CONFIG.load(storage)?.retention {
+        Some(Retention::MaxEntries { count }) => {
+            prune_transfers(storage, id.saturating_sub(count), u64::MAX, PRUNE_BATCH)?;
+        }
+        Some(Retention::MaxAge { blocks }) => {
+            let before = transfer.height.saturating_sub(blocks);
+            prune_transfers(storage, id, before, PRUNE_BATCH)?;
+        }
+        None => {}
+    }
+    Ok(id)
+}
+
+// prune_transfers removes up to limit transfers after the last pruned one from the history,
+// up to id up_to. It stops at the first transfer recorded at or after height before, and
+// returns how many were pruned
+fn prune_transfers(
+    storage: &mut dyn Storage,
+    up_to: u64,
+    before: u64,
+    limit: u64,
+) -> StdResult<u64> {
+    let mut id = TRANSFERS_PRUNED.may_load(storage)?.unwrap_or_default();
+    let mut pruned = 0;
+    while id < up_to && pruned < limit {
+        if let Some(transfer) = TRANSFERS.may_load(storage, id + 1)? {
+            if transfer.height >= before {
+                break;
+            }
+            if let Some(memo) = &transfer.memo {
+                MEMO_TRANSFERS.remove(storage, (memo, id + 1));
+            }
+            SENDER_TRANSFERS.remove(storage, (&transfer.sender, id + 1));
+            for recipient in transfer.recipients.iter() {
+                RECEIVER_TRANSFERS.remove(storage, (recipient, id + 1));
+            }
+            TRANSFERS.remove(storage, id + 1);
+        }
+        id += 1;
+        pruned += 1;
+    }
+    TRANSFERS_PRUNED.save(storage, &id)?;
+    Ok(pruned)
+}
+
+// record_stats counts a forward of funds at height
+fn record_stats(storage: &mut dyn Storage, funds: &[Coin], height: u64) -> StdResult<()> {
+    let mut stats = STATS.may_load(storage)?.unwrap_or_default();
+    add_coins(&mut stats.forwarded, funds.to_vec());
+    stats.forwards += 1;
+    stats.last_height = Some(height);
+    STATS.save(storage, &stats, height)
+}
+
+// record_named_stats counts a deposit of funds sent through a route or tagged with a
+// category at height
+fn record_named_stats(
+    storage: &mut dyn Storage,
+    map: Map<&str, Stats>,
+    name: &str,
+    funds: &[Coin],
+    height: u64,
+) -> StdResult<()> {
+    let mut stats = map.may_load(storage, name)?.unwrap_or_default();
+    add_coins(&mut stats.forwarded, funds.to_vec());
+    stats.forwards += 1;
+    stats.last_height = Some(height);
+    map.save(storage, name, &stats)
+}
+
+// settle_receipt adds recipients to who deposit id went to once it is released, approved or
+// refunded, records its status and returns the updated receipt
+fn settle_receipt(
+    storage: &mut dyn Storage,
+    id: u64,
+    recipients: Vec<Addr>,
+    status: ReceiptStatus,
+) -> StdResult<Option<SendReceipt>> {
+    let mut transfer = match TRANSFERS.may_load(storage, id)? {
+        Some(transfer) => transfer,
+        None => return Ok(None),
+    };
+    for recipient in recipients.iter() {
+        RECEIVER_TRANSFERS.save(storage, (recipient, id), &Empty {})?;
+    }
+    transfer.recipients.extend(recipients);
+    transfer.status = status;
+    TRANSFERS.save(storage, id, &transfer)?;
+    Ok(Some(send_receipt(id, &transfer)))
+}
+
+// settle_held settles the held deposits as forwarded to recipients once none of their
+// denoms is left in held
+fn settle_held(storage: &mut dyn Storage, held: &[Coin], recipients: &[Addr]) -> StdResult<()> {
+    let mut ids = HELD_RECEIPTS.may_load(storage)?.unwrap_or_default();
+    let mut settled = vec![];
+    for id in ids.iter().copied() {
+        let flushed = match TRANSFERS.may_load(storage, id)? {
+            Some(transfer) => !is_held(&transfer.amount, held),
+            // pruned from the history
+            None => true,
+        };
+        if flushed {
+            settle_receipt(storage, id, recipients.to_vec(), ReceiptStatus::Forwarded)?;
+            settled.push(id);
+        }
+    }
+    if !settled.is_empty() {
+        ids.retain(|id| !settled.contains(id));
+        HELD_RECEIPTS.save(storage, &ids)?;
+    }
+    Ok(())
+}
+
+// is_held tells if any denom of amount is still in held
+fn is_held(amount: &[Coin], held: &[Coin]) -> bool {
+    amount.iter().any(|coin| {
+        held.iter()
+            .any(|held| held.denom == coin.denom && !held.amount.is_zero())
+    })
+}
+
+fn send_receipt(id: u64, transfer: &Transfer) -> SendReceipt {
+    SendReceipt {
+        id,
+        sender: transfer.sender.to_string(),
+        recipients: transfer
+            .recipients
+            .iter()
+            .map(|recipient| recipient.to_string())
+            .collect(),
+        amount: transfer.amount.clone(),
+        height: transfer.height,
+        minted: transfer.minted.clone(),
+        category: transfer.category.clone(),
+        items: transfer.items.clone(),
+    }
+}
+
+fn coins_to_string(coins: &[Coin]) -> String {
+    coins
+        .iter()
+        .map(|coin| format!("{}{}", coin.amount, coin.denom))
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+// split divides amount between the weighted receivers. Every receiver gets its share
+// rounded down and state.rounding hands out the remainder: all of it to the first receiver,
+// one unit each to the largest remainders, or none with Floor, which returns it as left
+// over to be kept as dust. Without a split everything goes to the receiver.
+fn split(state: &Config, amount: Uint128) -> StdResult<(Vec<(Addr, Uint128)>, Uint128)> {
+    if state.receivers.is_empty() {
+        return Ok((vec![(state.receiver.clone(), amount)], Uint128::zero()));
+    }
+    let weights: Vec<Decimal> = state.receivers.iter().map(|(_, weight)| *weight).collect();
+    let (shares, left_over) = split_amount(amount, &weights, state.rounding)?;
+    let parts = state
+        .receivers
+        .iter()
+        .map(|(receiver, _)| receiver.clone())
+        .zip(shares)
+        .collect();
+    Ok((parts, left_over))
+}
+
+// route pays the waterfall tiers in order until each got its cap of denom, and splits what
+// is left between the receivers. It also returns what the rounding of the split left over
+fn route(
+    storage: &mut dyn Storage,
+    state: &Config,
+    denom: &str,
+    amount: Uint128,
+) -> StdResult<(Vec<(Addr, Uint128)>, Uint128)> {
+    let (parts, filled, left_over) = plan_route(storage, state, denom, amount)?;
+    for (receiver, filled) in filled {
+        WATERFALL_FILLED.save(storage, (&receiver, denom), &filled)?;
+    }
+    Ok((parts, left_over))
+}
+
+// plan_route returns the parts route pays, how far every tier it pays is filled then and
+// what is left over, without recording anything
+#[allow(clippy::type_complexity)]
+fn plan_route(
+    storage: &dyn Storage,
+    state: &Config,
+    denom: &str,
+    amount: Uint128,
+) -> StdResult<(Vec<(Addr, Uint128)>, Vec<(Addr, Uint128)>, Uint128)> {
+    // a denom with its own receiver skips the waterfall and the split
+    let rule = state.denom_rules.iter().find(|rule| rule.denom == denom);
+    if let Some(receiver) = rule.and_then(|rule| rule.receiver.clone()) {
+        return Ok((vec![(receiver, amount)], vec![], Uint128::zero()));
+    }
+    let mut parts = vec![];
+    let mut filled_up = vec![];
+    let mut rest = amount;
+    for (receiver, cap) in state.waterfall.iter() {
+        let cap = match cap.iter().find(|cap| cap.denom == denom) {
+            Some(cap) => cap.amount,
+            None => continue,
+        };
+        let filled = WATERFALL_FILLED
+            .may_load(storage, (receiver, denom))?
+            .unwrap_or_default();
+        let part = cap.saturating_sub(filled).min(rest);
+        if part.is_zero() {
+            continue;
+        }
+        filled_up.push((receiver.clone(), filled + part));
+        parts.push((receiver.clone(), part));
+        rest -= part;
+    }
+    let mut left_over = Uint128::zero();
+    if !rest.is_zero() || parts.is_empty() {
+        let (shares, left) = split(state, rest)?;
+        parts.extend(shares);
+        left_over = left;
+    }
+    Ok((parts, filled_up, left_over))
+}
+
+// load_config loads the config and lets a pending receiver take over once it is ready,
+// then the scheduled receivers whose height was reached
+fn load_config(storage: &mut dyn Storage, block: &BlockInfo) -> StdResult<Config> {
+    let mut state = CONFIG.load(storage)?;
+    if let Some(pending) = PENDING_RECEIVER.may_load(storage)? {
+        if pending.ready_at.is_expired(block) && !pending.awaiting_acceptance {
+            let actor = pending.proposer.as_ref().unwrap_or(&pending.receiver);
+            record_receiver_change(storage, block, &state.receiver, &pending.receiver, actor)?;
+            state.receiver = pending.receiver;
+            CONFIG.save(storage, &state)?;
+            PENDING_RECEIVER.remove(storage);
+        }
+    }
+    let mut rotation = ROTATION.may_load(storage)?.unwrap_or_default();
+    let due = rotation
+        .iter()
+        .take_while(|entry| entry.active_from <= block.height)
+        .count();
+    if due > 0 {
+        for entry in rotation.drain(..due) {
+            record_receiver_change(
+                storage,
+                block,
+                &state.receiver,
+                &entry.receiver,
+                &entry.scheduled_by,
+            )?;
+            state.receiver = entry.receiver;
+        }
+        CONFIG.save(storage, &state)?;
+        ROTATION.save(storage, &rotation)?;
+    }
+    Ok(state)
+}
+
+// record_receiver_change appends receiver taking over from old to the receiver history
+fn record_receiver_change(
+    storage: &mut dyn Storage,
+    block: &BlockInfo,
+    old: &Addr,
+    receiver: &Addr,
+    actor: &Addr,
+) -> StdResult<ReceiverChangeInfo> {
+    let id = RECEIVER_CHANGE_COUNT.may_load(storage)?.unwrap_or_default() + 1;
+    RECEIVER_CHANGE_COUNT.save(storage, &id)?;
+    let change = ReceiverChange {
+        old: old.clone(),
+        receiver: receiver.clone(),
+        actor: actor.clone(),
+        height: block.height,
+        time: block.time.seconds(),
+    };
+    RECEIVER_CHANGES.save(storage, id, &change)?;
+    Ok(receiver_change_info(id, change))
+}
+
+fn receiver_change_info(id: u64, change: ReceiverChange) -> ReceiverChangeInfo {
+    ReceiverChangeInfo {
+        id,
+        old_receiver: change.old.to_string(),
+        new_receiver: change.receiver.to_string(),
+        actor: change.actor.to_string(),
+        height: change.height,
+        time: change.time,
+    }
+}
+
+// check_sender fails if senders are restricted and sender is not on the allowlist
+fn check_sender(storage: &dyn Storage, state: &Config, sender: &Addr) -> Result<(), ContractError> {
+    if BLOCKED_SENDERS.has(storage, sender) {
+        return Err(ContractError::SenderBlocked {});
+    }
+    if state.restrict_senders && !ALLOWED_SENDERS.has(storage, sender) {
+        return Err(ContractError::SenderNotAllowed {});
+    }
+    Ok(())
+}
+
+// consume_quota counts funds against the rate limit of sender and the daily cap, failing
+// if either would be exceeded
+fn consume_quota(
+    storage: &mut dyn Storage,
+    state: &Config,
+    sender: &Addr,
+    funds: &[Coin],
+    time: u64,
+) -> Result<(), ContractError> {
+    if let Some((usage, daily)) = check_quota(storage, state, sender, funds, time)? {
+        SENDER_USAGE.save(storage, sender, &usage)?;
+        DAILY_USAGE.save(storage, &daily)?;
+    }
+    Ok(())
+}
+
+// check_quota returns the usage of sender and the daily usage once funds are counted,
+// failing if either limit would be exceeded
+fn check_quota(
+    storage: &dyn Storage,
+    state: &Config,
+    sender: &Addr,
+    funds: &[Coin],
+    time: u64,
+) -> Result<Option<(Usage, Usage)>, ContractError> {
+    let limit = match &state.rate_limit {
+        Some(limit) => limit,
+        None => return Ok(None),
+    };
+
+    let mut usage = SENDER_USAGE.may_load(storage, sender)?.unwrap_or_default();
+    if time >= usage.window_start + limit.window {
+        usage = Usage {
+            window_start: time,
+            used: vec![],
+        };
+    }
+    let mut daily = DAILY_USAGE.may_load(storage)?.unwrap_or_default();
+    if time >= daily.window_start + DAY {
+        daily = Usage {
+            window_start: time - time % DAY,
+            used: vec![],
+        };
+    }
+
+    for coin in funds {
+        let left = remaining(&limit.per_sender, &usage.used);
+        if let Some(left) = left.iter().find(|left| left.denom == coin.denom) {
+            if coin.amount > left.amount {
+                return Err(ContractError::RateLimitExceeded {
+                    denom: coin.denom.clone(),
+                    remaining: left.amount,
+                });
+            }
+        }
+        let left = remaining(&limit.daily_cap, &daily.used);
+        if let Some(left) = left.iter().find(|left| left.denom == coin.denom) {
+            if coin.amount > left.amount {
+                return Err(ContractError::DailyCapExceeded {
+                    denom: coin.denom.clone(),
+                    remaining: left.amount,
+                });
+            }
+        }
+    }
+    add_coins(&mut usage.used, funds.to_vec());
+    add_coins(&mut daily.used, funds.to_vec());
+    Ok(Some((usage, daily)))
+}
+
+// record_contribution adds funds to what sender contributed, failing if that exceeds the
+// lifetime cap of sender
+fn record_contribution(
+    storage: &mut dyn Storage,
+    sender: &Addr,
+    funds: &[Coin],
+) -> Result<(), ContractError> {
+    for contribution in check_contribution(storage, sender, funds)? {
+        let key = (sender, contribution.denom.as_str());
+        contributions().save(storage, key, &contribution)?;
+    }
+    Ok(())
+}
+
+// check_contribution returns what sender contributed of every denom of funds once they
+// are added, failing if that exceeds the lifetime cap of sender
+fn check_contribution(
+    storage: &dyn Storage,
+    sender: &Addr,
+    funds: &[Coin],
+) -> Result<Vec<Contribution>, ContractError> {
+    let mut contributed = vec![];
+    let cap = LIFETIME_CAPS.may_load(storage, sender)?.unwrap_or_default();
+    for coin in funds {
+        let key = (sender, coin.denom.as_str());
+        let amount = contributions()
+            .may_load(storage, key)?
+            .map(|contribution| contribution.amount)
+            .unwrap_or_default();
+        if let Some(cap) = cap.iter().find(|cap| cap.denom == coin.denom) {
+            let left = cap.amount.saturating_sub(amount);
+            if coin.amount > left {
+                return Err(ContractError::LifetimeCapExceeded {
+                    denom: coin.denom.clone(),
+                    remaining: left,
+                });
+            }
+        }
+        contributed.push(Contribution {
+            sender: sender.clone(),
+            denom: coin.denom.clone(),
+            amount: amount.checked_add(coin.amount)?,
+        });
+    }
+    Ok(contributed)
+}
+
+// remaining returns what is left of every limit after used
+fn remaining(limits: &[Coin], used: &[Coin]) -> Vec<Coin> {
+    limits
+        .iter()
+        .map(|limit| {
+            let used = used
+                .iter()
+                .find(|coin| coin.denom == limit.denom)
+                .map(|coin| coin.amount)
+                .unwrap_or_default();
+            Coin {
+                denom: limit.denom.clone(),
+                amount: limit.amount.saturating_sub(used),
+            }
+        })
+        .collect()
+}
+
+fn ready_at_string(ready_at: &Expiration) -> String {
+    match ready_at {
+        Expiration::AtHeight(height) => format!("height {}", height),
+        Expiration::AtTime(time) => format!("time {}", time),
+    }
+}
+
+// config_response logs a config change made by sender
+fn config_response(action: &str, sender: &Addr) -> Response {
+    Response::new().add_attributes(vec![
+        attr("action", action),
+        attr("sender", sender.as_str()),
+    ])
+}
+
+// hold adds funds to what the contract holds and takes out every denom that reached its
+// flush threshold. It returns the coins to forward now and the remaining held balance.
+fn hold(
+    storage: &mut dyn Storage,
+    state: &Config,
+    funds: Vec<Coin>,
+) -> StdResult<(Vec<Coin>, Vec<Coin>)> {
+    let (forwarded, held) = plan_hold(storage, state, funds)?;
+    HELD.save(storage, &held)?;
+    Ok((forwarded, held))
+}
+
+// plan_hold returns the coins hold forwards and keeps, without recording anything
+fn plan_hold(
+    storage: &dyn Storage,
+    state: &Config,
+    funds: Vec<Coin>,
+) -> StdResult<(Vec<Coin>, Vec<Coin>)> {
+    let mut held = HELD.may_load(storage)?.unwrap_or_default();
+    add_coins(&mut held, funds);
+    Ok(held.into_iter().partition(|coin| {
+        state
+            .flush_threshold
+            .iter()
+            .any(|threshold| threshold.denom == coin.denom && coin.amount >= threshold.amount)
+    }))
+}
+
+// Forwarded is what forward returns: the messages, attributes and receivers that were paid,
+// and how each denom was routed
+type Forwarded = (Vec<SubMsg>, Vec<Attribute>, Vec<Addr>, Vec<DenomRouting>);
+
+// Distribution is how forward pays out funds
+struct Distribution {
+    fees: Vec<Coin>,
+    payouts: Vec<(Addr, Vec<Coin>)>,
+    dust: Vec<Coin>,
+    // how far every waterfall tier that is paid is filled then, by denom
+    filled: Vec<(Addr, String, Uint128)>,
+    items: Vec<DenomRouting>,
+}
+
+// distribute collects the fees and what every receiver gets, so each of them is paid with
+// a single send. Fees and shares too small to send are kept as dust
+fn distribute(storage: &dyn Storage, state: &Config, funds: &[Coin]) -> StdResult<Distribution> {
+    let mut fees: Vec<Coin> = vec![];
+    let mut payouts: Vec<(Addr, Vec<Coin>)> = vec![];
+    let mut dust: Vec<Coin> = vec![];
+    let mut filled = vec![];
+    let mut items = vec![];
+    for coin in funds.iter() {
+        let (fee, net) = deduct_fee(state, &coin.denom, coin.amount)?;
+        let mut item = DenomRouting {
+            denom: coin.denom.clone(),
+            amount: coin.amount,
+            fee: Uint128::zero(),
+            payouts: vec![],
+        };
+        if !fee.is_zero() {
+            let fee = Coin {
+                denom: coin.denom.clone(),
+                amount: fee,
+            };
+            if is_dust(state, &fee) {
+                add_coins(&mut dust, vec![fee]);
+            } else {
+                item.fee = fee.amount;
+                fees.push(fee);
+            }
+        }
+        let (parts, filled_up, left_over) = plan_route(storage, state, &coin.denom, net)?;
+        if !left_over.is_zero() {
+            add_coins(
+                &mut dust,
+                vec![Coin {
+                    denom: coin.denom.clone(),
+                    amount: left_over,
+                }],
+            );
+        }
+        filled.extend(
+            filled_up
+                .into_iter()
+                .map(|(receiver, amount)| (receiver, coin.denom.clone(), amount)),
+        );
+        for (receiver, amount) in parts {
+            if amount.is_zero() {
+                continue;
+            }
+            let part = Coin {
+                denom: coin.denom.clone(),
+                amount,
+            };
+            if !state.pull && is_dust(state, &part) {
+                add_coins(&mut dust, vec![part]);
+                continue;
+            }
+            item.payouts.push((receiver.clone(), amount));
+            match payouts.iter_mut().find(|(addr, _)| *addr == receiver) {
+                Some((_, coins)) => coins.push(part),
+                None => payouts.push((receiver, vec![part])),
+            }
+        }
+        items.push(item);
+    }
+    Ok(Distribution {
+        fees,
+        payouts,
+        dust,
+        filled,
+        items,
+    })
+}
+
+// forward pays out funds the contract has received: the fee goes to the fee collector and
+// the rest is split between the receivers, or kept for them to withdraw in pull mode. It
+// returns the messages, attributes and receivers that were paid, and how each denom was
+// routed.
+fn forward(
+    deps: &mut DepsMut,
+    env: &Env,
+    state: &Config,
+    funds: Vec<Coin>,
+    taxes: &mut Vec<Coin>,
+) -> StdResult<Forwarded> {
+    if !funds.is_empty() {
+        record_stats(deps.storage, &funds, env.block.height)?;
+    }
+    let Distribution {
+        fees,
+        payouts,
+        dust,
+        filled,
+        items,
+    } = distribute(deps.storage, state, &funds)?;
+    for (receiver, denom, filled) in filled {
+        WATERFALL_FILLED.save(deps.storage, (&receiver, &denom), &filled)?;
+    }
+
+    let mut messages = vec![];
+    let mut attrs = vec![];
+    if !dust.is_empty() {
+        attrs.push(attr("dust", coins_to_string(&dust)));
+        let mut balance = DUST.may_load(deps.storage)?.unwrap_or_default();
+        add_coins(&mut balance, dust);
+        DUST.save(deps.storage, &balance)?;
+    }
+    if let Some(collector) = &state.fee_collector {
+        if !fees.is_empty() {
+            attrs.push(attr("fee", coins_to_string(&fees)));
+            attrs.push(attr("fee_collector", collector.as_str()));
+            messages.push(SubMsg::new(BankMsg::Send {
+                to_address: collector.to_string(),
+                amount: deduct_tax(&deps.querier, fees, taxes)?,
+            }));
+        }
+    }
+    // paying more receivers than max_payouts at once could run out of gas, Distribute pays
+    // them in batches instead
+    let queue = !state.pull
+        && state
+            .max_payouts
+            .is_some_and(|max_payouts| payouts.len() > max_payouts as usize);
+    if queue {
+        attrs.push(attr("queued", payouts.len().to_string()));
+    }
+    let mut recipients = vec![];
+    for (receiver, amount) in payouts {
+        attrs.push(attr("recipient", receiver.as_str()));
+        if state.pull {
+            // the funds stay here until the receiver withdraws them
+            credit_claimable(deps.storage, &receiver, amount, env.block.time.seconds())?;
+        } else if queue {
+            let id = PAYOUT_QUEUE_COUNT
+                .may_load(deps.storage)?
+                .unwrap_or_default()
+                + 1;
+            PAYOUT_QUEUE_COUNT.save(deps.storage, &id)?;
+            PAYOUT_QUEUE.save(deps.storage, id, &(receiver.clone(), amount))?;
+        } else {
+            messages.extend(send_payout(
+                deps.storage,
+                &deps.querier,
+                env,
+                state,
+                &receiver,
+                amount,
+                taxes,
+            )?);
+        }
+        recipients.push(receiver);
+    }
+    Ok((messages, attrs, recipients, items))
+}
+
+// send_payout pays amount to receiver once the tax is paid, calling its callback if it has
+// one. In IBC mode what the receiver is paid is transferred to the remote receiver
+fn send_payout(
+    storage: &mut dyn Storage,
+    querier: &QuerierWrapper,
+    env: &Env,
+    state: &Config,
+    receiver: &Addr,
+    amount: Vec<Coin>,
+    taxes: &mut Vec<Coin>,
+) -> StdResult<Vec<SubMsg>> {
+    let amount = deduct_tax(querier, amount, taxes)?;
+    if let (Some(ibc), true) = (&state.ibc, *receiver == state.receiver) {
+        return ibc_transfer(storage, env, ibc, amount);
+    }
+    let msg = match callback(state, receiver) {
+        Some(msg) => CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: receiver.to_string(),
+            msg: msg.clone(),
+            funds: amount,
+        }),
+        None => CosmosMsg::Bank(BankMsg::Send {
+            to_address: receiver.to_string(),
+            amount,
+        }),
+    };
+    Ok(vec![payout(storage, state, receiver, msg)?])
+}
+
+// ibc_transfer sends amount to the remote receiver, one transfer per coin. Every transfer is
+// kept until its ack, so the funds of one that fails or times out can be refunded
+fn ibc_transfer(
+    storage: &mut dyn Storage,
+    env: &Env,
+    ibc: &IbcForwarding,
+    amount: Vec<Coin>,
+) -> StdResult<Vec<SubMsg>> {
+    let sender = PAYOUT_SENDER.may_load(storage)?;
+    let mut messages = vec![];
+    for coin in amount {
+        let id = IBC_TRANSFER_COUNT.may_load(storage)?.unwrap_or_default() + 1;
+        IBC_TRANSFER_COUNT.save(storage, &id)?;
+        let msg = transfer_msg(
+            &env.contract.address,
+            &ibc.channel,
+            &ibc.remote_receiver,
+            &coin,
+            env.block.time.plus_seconds(ibc.timeout),
+        );
+        let transfer = IbcTransfer {
+            sender: sender.clone(),
+            amount: coin,
+            channel: ibc.channel.clone(),
+            sequence: None,
+        };
+        IBC_TRANSFERS.save(storage, id, &transfer)?;
+        messages.push(SubMsg::reply_on_success(msg, IBC_REPLY_ID + id));
+    }
+    Ok(messages)
+}
+
+// release pays out funds to destination: the fee goes to the fee collector and the rest is
+// burned or funds the community pool. It returns the messages and attributes, no receiver
+// is paid
+fn release(
+    deps: &mut DepsMut,
+    block: &BlockInfo,
+    state: &Config,
+    destination: Destination,
+    funds: Vec<Coin>,
+    taxes: &mut Vec<Coin>,
+) -> StdResult<(Vec<SubMsg>, Vec<Attribute>, Vec<Addr>)> {
+    if !funds.is_empty() {
+        record_stats(deps.storage, &funds, block.height)?;
+    }
+    let mut fees = vec![];
+    let mut net = vec![];
+    for coin in funds {
+        let (fee, rest) = deduct_fee(state, &coin.denom, coin.amount)?;
+        if !fee.is_zero() {
+            fees.push(Coin {
+                denom: coin.denom.clone(),
+                amount: fee,
+            });
+        }
+        if !rest.is_zero() {
+            net.push(Coin {
+                denom: coin.denom,
+                amount: rest,
+            });
+        }
+    }
+
+    let mut messages = vec![];
+    let mut attrs = vec![];
+    if let (Some(collector), false) = (&state.fee_collector, fees.is_empty()) {
+        attrs.push(attr("fee", coins_to_string(&fees)));
+        attrs.push(attr("fee_collector", collector.as_str()));
+        messages.push(SubMsg::new(BankMsg::Send {
+            to_address: collector.to_string(),
+            amount: deduct_tax(&deps.querier, fees, taxes)?,
+        }));
+    }
+    if net.is_empty() {
+        return Ok((messages, attrs, vec![]));
+    }
+    let mut recipients = vec![];
+    match destination {
+        Destination::Burn => {
+            attrs.push(attr("burned", coins_to_string(&net)));
+            messages.push(SubMsg::new(BankMsg::Burn { amount: net }));
+        }
+        Destination::CommunityPool => {
+            attrs.push(attr("community_pool", coins_to_string(&net)));
+            messages.push(SubMsg::new(DistributionMsg::FundCommunityPool {
+                amount: net,
+            }));
+        }
+        Destination::Wallet(addr) => {
+            attrs.push(attr("wallet", addr.as_str()));
+            let msg = BankMsg::Send {
+                to_address: addr.to_string(),
+                amount: deduct_tax(&deps.querier, net, taxes)?,
+            };
+            messages.push(payout(deps.storage, state, &addr, msg.into())?);
+            recipients.push(addr);
+        }
+        Destination::Contract { addr, msg } => {
+            attrs.push(attr("contract", addr.as_str()));
+            let msg = WasmMsg::Execute {
+                contract_addr: addr.to_string(),
+                msg,
+                funds: deduct_tax(&deps.querier, net, taxes)?,
+            };
+            messages.push(payout(deps.storage, state, &addr, msg.into())?);
+            recipients.push(addr);
+        }
+        // only the bonded denom can be delegated, the rest goes to the receiver
+        Destination::Validator(validator) => {
+            let bonded = deps.querier.query_bonded_denom()?;
+            let (staked, rest): (Vec<Coin>, Vec<Coin>) =
+                net.into_iter().partition(|coin| coin.denom == bonded);
+            if !staked.is_empty() {
+                attrs.push(attr("delegated", coins_to_string(&staked)));
+                attrs.push(attr("validator", validator.as_str()));
+                messages.extend(staked.into_iter().map(|amount| {
+                    SubMsg::new(StakingMsg::Delegate {
+                        validator: validator.clone(),
+                        amount,
+                    })
+                }));
+            }
+            if !rest.is_empty() {
+                let msg = BankMsg::Send {
+                    to_address: state.receiver.to_string(),
+                    amount: deduct_tax(&deps.querier, rest, taxes)?,
+                };
+                messages.push(payout(deps.storage, state, &state.receiver, msg.into())?);
+                recipients.push(state.receiver.clone());
+            }
+        }
+    }
+    Ok((messages, attrs, recipients))
+}
+
+// payout wraps the message paying recipient. With hold_failed, refund_failed or a fallback
+// receiver it is sent as a submessage and kept until its reply, so a failing payout goes to
+// the fallback receiver, is owed back to the sender or is held for RetryFailed instead of
+// reverting the deposit
+fn payout(
+    storage: &mut dyn Storage,
+    state: &Config,
+    recipient: &Addr,
+    msg: CosmosMsg,
+) -> StdResult<SubMsg> {
+    if !state.hold_failed && !state.refund_failed && state.fallback_receiver.is_none() {
+        return Ok(SubMsg::new(msg));
+    }
+    let id = PAYOUT_COUNT.may_load(storage)?.unwrap_or_default() + 1;
+    PAYOUT_COUNT.save(storage, &id)?;
+    let payout = Payout {
+        recipient: recipient.clone(),
+        msg,
+        error: None,
+        sender: PAYOUT_SENDER.may_load(storage)?,
+    };
+    PAYOUTS.save(storage, id, &payout)?;
+    Ok(SubMsg::reply_always(payout.msg, PAYOUT_REPLY_ID + id))
+}
+
+// cw20_amount returns the amount a cw20 payout message moves
+fn cw20_amount(msg: &Binary) -> Option<Uint128> {
+    match from_json(msg).ok()? {
+        Cw20ExecuteMsg::Transfer { amount, .. } | Cw20ExecuteMsg::Send { amount, .. } => {
+            Some(amount)
+        }
+        _ => None,
+    }
+}
+
+// redirect returns a message paying what msg pays to fallback instead. Native funds are
+// sent as they are, cw20 tokens are transferred
+fn redirect(msg: &CosmosMsg, fallback: &Addr) -> StdResult<CosmosMsg> {
+    let funds = match msg {
+        CosmosMsg::Bank(BankMsg::Send { amount, .. }) => amount.clone(),
+        CosmosMsg::Wasm(WasmMsg::Execute { funds, .. }) if !funds.is_empty() => funds.clone(),
+        // with no funds attached this executes a cw20 token contract
+        CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr, msg, ..
+        }) => {
+            let amount = cw20_amount(msg)
+                .ok_or_else(|| StdError::generic_err("Payout cannot be redirected"))?;
+            return Ok(CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: contract_addr.clone(),
+                msg: to_json_binary(&Cw20ExecuteMsg::Transfer {
+                    recipient: fallback.to_string(),
+                    amount,
+                })?,
+                funds: vec![],
+            }));
+        }
+        _ => return Err(StdError::generic_err("Payout cannot be redirected")),
+    };
+    Ok(CosmosMsg::Bank(BankMsg::Send {
+        to_address: fallback.to_string(),
+        amount: funds,
+    }))
+}
+
+// sent returns the native funds messages take out of the contract
+fn sent(messages: &[SubMsg]) -> Vec<Coin> {
+    let mut sent = vec![];
+    for msg in messages {
+        match &msg.msg {
+            CosmosMsg::Bank(BankMsg::Send { amount, .. }) => add_coins(&mut sent, amount.clone()),
+            CosmosMsg::Wasm(WasmMsg::Execute { funds, .. }) => add_coins(&mut sent, funds.clone()),
+            CosmosMsg::Stargate { type_url, value } if type_url == MSG_TRANSFER => {
+                add_coins(&mut sent, transfer_amount(value).into_iter().collect())
+            }
+            _ => {}
+        }
+    }
+    sent
+}
+
+// receipt_mint mints receipt id to sender if a deposited coin reaches the threshold of the
+// receipt nft
+fn receipt_mint(
+    state: &Config,
+    id: u64,
+    sender: &Addr,
+    funds: &[Coin],
+) -> StdResult<Option<SubMsg>> {
+    let nft = match (&state.receipt_nft, state.mint_receipts) {
+        (Some(nft), true) => nft,
+        _ => return Ok(None),
+    };
+    let qualifies = funds.iter().any(|coin| {
+        nft.threshold
+            .iter()
+            .any(|threshold| threshold.denom == coin.denom && coin.amount >= threshold.amount)
+    });
+    if !qualifies {
+        return Ok(None);
+    }
+    let msg = mint_msg(&nft.contract, id.to_string(), sender, funds)?;
+    Ok(Some(SubMsg::reply_on_error(msg, MINT_REPLY_ID)))
+}
+
+// needs_approval returns whether a coin of funds reaches the approval threshold of its denom
+fn needs_approval(state: &Config, funds: &[Coin]) -> bool {
+    funds.iter().any(|coin| {
+        state
+            .approval_threshold
+            .iter()
+            .any(|threshold| threshold.denom == coin.denom && coin.amount >= threshold.amount)
+    })
+}
+
+// Lock holds back a deposit instead of forwarding it
+pub enum Lock {
+    Vesting(VestingSchedule),
+    Hashlock { hash: String, timeout: Duration },
+    Arbitrated { arbiter: Addr, timeout: Duration },
+}
+
+// check_exec checks receiver is a contract and msg calls a method the owner allowed, msg
+// must be a json object with the method as its only key
+fn check_exec(deps: Deps, receiver: &Addr, msg: &Binary) -> Result<(), ContractError> {
+    let fields: BTreeMap<String, IgnoredAny> =
+        from_json(msg).map_err(|_| ContractError::InvalidExecMsg {})?;
+    let method = match fields.into_keys().collect::<Vec<_>>().as_slice() {
+        [method] => method.clone(),
+        _ => return Err(ContractError::InvalidExecMsg {}),
+    };
+    if !ALLOWED_METHODS.has(deps.storage, &method) {
+        return Err(ContractError::MethodNotAllowed { method });
+    }
+    if deps
+        .querier
+        .query_wasm_contract_info(receiver.as_str())
+        .is_err()
+    {
+        return Err(ContractError::NotAContract {
+            address: receiver.to_string(),
+        });
+    }
+    Ok(())
+}
+
+// validate_hash checks that hash is a hex encoded SHA-256 hash and returns it in lower case
+fn validate_hash(hash: &str) -> Result<String, ContractError> {
+    match hex::decode(hash) {
+        Ok(bytes) if bytes.len() == 32 => Ok(hash.to_lowercase()),
+        _ => Err(ContractError::InvalidHash {}),
+    }
+}
+
+// DepositOptions are what the deposit messages add to the sent funds, execute builds them
+// from the message that was sent
+#[derive(Default)]
+pub struct DepositOptions {
+    // a one-off recipient, only honored for the owner and allowlisted senders
+    pub recipient: Option<String>,
+    // a named route, alias or special destination the deposit is sent to
+    pub route: Option<String>,
+    pub memo: Option<String>,
+    // the user an allowlisted contract deposits for
+    pub on_behalf_of: Option<String>,
+    pub category: Option<String>,
+    // a lock holds the deposit until it vests or is released
+    pub lock: Option<Lock>,
+    // a message to execute the receiver contract with
+    pub exec: Option<Binary>,
+}
+
+pub fn try_tokensend(
+    mut deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    opts: DepositOptions,
+) -> Result<Response, ContractError> {
+    let DepositOptions {
+        recipient,
+        route,
+        memo,
+        on_behalf_of,
+        category,
+        lock,
+        exec,
+    } = opts;
+    let mut state = load_config(deps.storage, &env.block)?;
+    if state.paused {
+        return Err(ContractError::Paused {});
+    }
+    let caller = info.sender;
+    check_sender(deps.storage, &state, &caller)?;
+    // a deposit an allowlisted contract makes for a user is recorded as theirs, refunds go
+    // back to the contract
+    let sender = match on_behalf_of {
+        Some(user) => {
+            if !state.allowlist.contains(&caller) {
+                return Err(ContractError::Unauthorized {});
+            }
+            let user = deps.api.addr_validate(&user)?;
+            if BLOCKED_SENDERS.has(deps.storage, &user) {
+                return Err(ContractError::SenderBlocked {});
+            }
+            user
+        }
+        None => caller.clone(),
+    };
+    if let Some(campaign) = &state.campaign {
+        if !campaign.is_open(&env.block) {
+            return Err(ContractError::CampaignNotOpen {});
+        }
+    }
+
+    // an authorized sender can pass everything to a one-off recipient, skipping the split.
+    // Anyone can route a deposit to a special destination
+    let mut overridden = false;
+    let mut destination = None;
+    let executed = exec.is_some();
+    let mut named_route = None;
+    if let Some(route) = route {
+        destination = DESTINATIONS.may_load(deps.storage, &route)?;
+        state.receivers = vec![];
+        state.waterfall = vec![];
+        if let Some(named) = ROUTES.may_load(deps.storage, &route)? {
+            if PAUSED_ROUTES.has(deps.storage, &route) {
+                return Err(ContractError::RoutePaused { name: route });
+            }
+            named_route = Some(route);
+            state.receiver = named.receiver;
+            state.receivers = named.receivers;
+            state.fee_rate = named.fee_rate;
+            state.fee_collector = named.fee_collector;
+            state.fee_cap = named.fee_cap;
+            state.accepted_denoms = named.accepted_denoms;
+            state.funds_policy = named.funds_policy;
+        } else if destination.is_none() {
+            state.receiver = ALIASES
+                .may_load(deps.storage, &route)?
+                .ok_or(ContractError::UnknownAlias { name: route })?;
+        }
+        overridden = true;
+    } else if let Some(recipient) = recipient {
+        if state.is_owner(&caller) || state.allowlist.contains(&caller) {
+            state.receiver = match ALIASES.may_load(deps.storage, &recipient)? {
+                Some(addr) => addr,
+                None => deps.api.addr_validate(&recipient)?,
+            };
+            state.receivers = vec![];
+            state.waterfall = vec![];
+            overridden = true;
+        }
+    }
+    // a category with a receiver forwards the deposit there, unless it was routed already
+    if let Some(name) = &category {
+        let tag = CATEGORIES
+            .may_load(deps.storage, name)?
+            .ok_or_else(|| ContractError::UnknownCategory { name: name.clone() })?;
+        if let (Some(receiver), false) = (tag.receiver, overridden) {
+            state.receiver = receiver;
+            state.receivers = vec![];
+            state.waterfall = vec![];
+            overridden = true;
+        }
+    }
+    // a deposit executing the receiver is paid to it right away, whatever the split or mode
+    if let Some(msg) = exec {
+        check_exec(deps.as_ref(), &state.receiver, &msg)?;
+        let receiver = state.receiver.clone();
+        state.callbacks.retain(|(addr, _)| *addr != receiver);
+        state.callbacks.push((receiver, msg));
+        state.receivers = vec![];
+        state.waterfall = vec![];
+        state.pull = false;
+        overridden = true;
+        destination = None;
+    }
+
+    // one-off and routed recipients are paid on this chain
+    if overridden {
+        state.ibc = None;
+    }
+
+    let (funds, refund) = apply_funds_policy(&state, info.funds)?;
+
+    let multiplier = min_multiplier(deps.storage, &state, env.block.time.seconds())?;
+    for coin in funds.iter() {
+        check_amount(&state, &coin.denom, coin.amount, multiplier)?;
+    }
+    let usd_value = check_usd_floor(&deps.querier, &state, &funds)?;
+    consume_quota(
+        deps.storage,
+        &state,
+        &sender,
+        &funds,
+        env.block.time.seconds(),
+    )?;
+    record_traffic(deps.storage, &state, env.block.time.seconds())?;
+    record_contribution(deps.storage, &sender, &funds)?;
+    if let Some(route) = &named_route {
+        record_named_stats(deps.storage, ROUTE_STATS, route, &funds, env.block.height)?;
+    }
+    if let Some(name) = &category {
+        record_named_stats(deps.storage, CATEGORY_STATS, name, &funds, env.block.height)?;
+    }
+
+    // a locked deposit vests on its schedule or waits for the preimage of its hash. Otherwise
+    // a campaign keeps the deposit until it ends, a deposit above the approval threshold
+    // waits for the owner, in escrow mode the deposit waits for the receiver to release it,
+    // in stream mode it vests over time
+    let now = env.block.time.seconds();
+    let mut raised = false;
+    let mut pending = None;
+    let mut escrow = None;
+    let mut stream = None;
+    match lock {
+        Some(Lock::Vesting(schedule)) => {
+            if schedule.cliff < now || schedule.end < schedule.cliff {
+                return Err(ContractError::InvalidSchedule {});
+            }
+            stream = Some(Stream {
+                sender: sender.clone(),
+                amount: funds.clone(),
+                claimed: vec![],
+                start: now,
+                cliff: schedule.cliff,
+                end: schedule.end,
+                revocable: schedule.revocable,
+            });
+        }
+        Some(Lock::Hashlock { hash, timeout }) => {
+            escrow = Some(Escrow {
+                sender: sender.clone(),
+                receiver: state.receiver.clone(),
+                amount: funds.clone(),
+                expires: timeout.after(&env.block),
+                hashlock: Some(validate_hash(&hash)?),
+                arbiter: None,
+                disputed: false,
+            });
+        }
+        Some(Lock::Arbitrated { arbiter, timeout }) => {
+            escrow = Some(Escrow {
+                sender: sender.clone(),
+                receiver: state.receiver.clone(),
+                amount: funds.clone(),
+                expires: timeout.after(&env.block),
+                hashlock: None,
+                arbiter: Some(arbiter),
+                disputed: false,
+            });
+        }
+        // a deposit to a special destination or executing the receiver is paid right away
+        None if destination.is_some() || executed => {}
+        None => {
+            if state.campaign.is_some() && !overridden {
+                raise(deps.storage, &sender, &funds)?;
+                raised = true;
+            } else if needs_approval(&state, &funds) {
+                pending = Some(PendingForward {
+                    sender: sender.clone(),
+                    recipient: overridden.then(|| state.receiver.clone()),
+                    amount: funds.clone(),
+                });
+            } else if let Some(timeout) = state.escrow {
+                escrow = Some(Escrow {
+                    sender: sender.clone(),
+                    receiver: state.receiver.clone(),
+                    amount: funds.clone(),
+                    expires: timeout.after(&env.block),
+                    hashlock: None,
+                    arbiter: None,
+                    disputed: false,
+                });
+            } else if let Some(duration) = state.stream_duration {
+                stream = Some(Stream {
+                    sender: sender.clone(),
+                    amount: funds.clone(),
+                    claimed: vec![],
+                    start: now,
+                    cliff: now,
+                    end: now + duration,
+                    revocable: false,
+                });
+            }
+        }
+    }
+    // in staking mode the bonded denom is delegated. In hold mode deposits wait in the
+    // contract until their denom reaches the flush threshold, one-off recipients are always
+    // paid right away
+    let locked = raised || pending.is_some() || escrow.is_some() || stream.is_some();
+    let (staked, mut unstaked) = match &state.validator {
+        Some(_) if !locked && !overridden => {
+            let bonded = deps.querier.query_bonded_denom()?;
+            funds.iter().cloned().partition(|coin| coin.denom == bonded)
+        }
+        _ => (vec![], funds.clone()),
+    };
+
+    // a memo naming a referral code pays the referrer its share of what this deposit
+    // forwards, before it joins what is held. Locked deposits can still go back to their
+    // sender, they neither pay a commission nor count for the referral
+    let mut commission = vec![];
+    let referral = match (&memo, locked) {
+        (Some(code), false) => REFERRALS
+            .may_load(deps.storage, code)?
+            .map(|referral| (code, referral)),
+        _ => None,
+    };
+    let referrer = match referral {
+        Some((code, mut referral)) => {
+            referral.deposits += 1;
+            add_coins(&mut referral.volume, unstaked.clone());
+            for coin in unstaked.iter_mut() {
+                let amount = coin.amount * state.referral_rate;
+                if !amount.is_zero() {
+                    coin.amount -= amount;
+                    commission.push(Coin {
+                        denom: coin.denom.clone(),
+                        amount,
+                    });
+                }
+            }
+            unstaked.retain(|coin| !coin.amount.is_zero());
+            add_coins(&mut referral.commission, commission.clone());
+            REFERRALS.save(deps.storage, code, &referral)?;
+            Some(referral.referrer)
+        }
+        None => None,
+    };
+
+    let (mut forwarded, held) = if locked {
+        (vec![], vec![])
+    } else if state.hold && !overridden {
+        hold(deps.storage, &state, unstaked)?
+    } else {
+        (unstaked, vec![])
+    };
+
+    // coins with a pair are swapped first, reply forwards the proceeds
+    let mut swaps = vec![];
+    let mut swapped = vec![];
+    if let (Some(swap), false) = (&state.swap, overridden) {
+        for coin in std::mem::take(&mut forwarded) {
+            match swap.pairs.iter().find(|(denom, _)| *denom == coin.denom) {
+                Some((_, pair)) => {
+                    swapped.push(coin.clone());
+                    let msg = swap_msg(pair, coin, swap.max_slippage)?;
+                    swaps.push(SubMsg::reply_on_success(msg, SWAP_REPLY_ID));
+                }
+                None => forwarded.push(coin),
+            }
+        }
+        if !swaps.is_empty() {
+            let balance = deps
+                .querier
+                .query_balance(&env.contract.address, &swap.target_denom)?;
+            SWAP_BALANCE.save(deps.storage, &balance.amount)?;
+        }
+    }
+
+    // in anchor mode the coins of its denom are deposited into the money market, reply
+    // forwards the minted aTerra
+    let mut deposit = None;
+    if let (Some(anchor), false) = (&state.anchor, overridden) {
+        if let Some(i) = forwarded.iter().position(|coin| coin.denom == anchor.denom) {
+            let coin = forwarded.remove(i);
+            let msg = deposit_msg(&anchor.money_market, coin.clone())?;
+            swaps.push(SubMsg::reply_on_success(msg, ANCHOR_REPLY_ID));
+            let balance: cw20::BalanceResponse = deps.querier.query_wasm_smart(
+                &anchor.aterra,
+                &Cw20QueryMsg::Balance {
+                    address: env.contract.address.to_string(),
+                },
+            )?;
+            deposit = Some((coin, balance.balance));
+        }
+    }
+
+    // payouts made for the deposit remember its sender, so a failed one can be refunded
+    PAYOUT_SENDER.save(deps.storage, &sender)?;
+    let mut taxes = vec![];
+    // the receipt itemizes how each denom forwarded to the receivers was routed
+    let (mut messages, fwd_attrs, recipients, items) = match destination {
+        Some(destination) => {
+            let (messages, attrs, recipients) = release(
+                &mut deps,
+                &env.block,
+                &state,
+                destination,
+                forwarded,
+                &mut taxes,
+            )?;
+            (messages, attrs, recipients, vec![])
+        }
+        None => forward(&mut deps, &env, &state, forwarded, &mut taxes)?,
+    };
+
+    let action = if raised {
+        "campaign"
+    } else if pending.is_some() {
+        "pending"
+    } else if escrow.is_some() {
+        "escrow"
+    } else if stream.is_some() {
+        "stream"
+    } else if state.pull {
+        "deposit"
+    } else {
+        "send"
+    };
+    let mut attrs = vec![
+        attr("action", action),
+        attr("sender", sender.as_str()),
+        attr("amount", coins_to_string(&funds)),
+    ];
+    if caller != sender {
+        attrs.push(attr("caller", caller.as_str()));
+    }
+    if let Some(value) = usd_value {
+        attrs.push(attr("usd_value", value));
+    }
+    attrs.extend(fwd_attrs);
+    if let (Some(referrer), false) = (referrer, commission.is_empty()) {
+        attrs.push(attr("referrer", referrer.as_str()));
+        attrs.push(attr("commission", coins_to_string(&commission)));
+        let msg = BankMsg::Send {
+            to_address: referrer.to_string(),
+            amount: deduct_tax(&deps.querier, commission, &mut taxes)?,
+        };
+        messages.push(payout(deps.storage, &state, &referrer, msg.into())?);
+    }
+    if !swapped.is_empty() {
+        attrs.push(attr("swapped", coins_to_string(&swapped)));
+    }
+    if let Some((coin, _)) = &deposit {
+        attrs.push(attr("anchor_deposit", coin.to_string()));
+    }
+    if let (Some(validator), false) = (&state.validator, staked.is_empty()) {
+        attrs.push(attr("delegated", coins_to_string(&staked)));
+        attrs.push(attr("validator", validator));
+        messages.extend(staked.into_iter().map(|amount| {
+            SubMsg::new(StakingMsg::Delegate {
+                validator: validator.clone(),
+                amount,
+            })
+        }));
+    }
+    if !held.is_empty() {
+        attrs.push(attr("held", coins_to_string(&held)));
+    }
+    if !refund.is_empty() {
+        attrs.push(attr("refund", coins_to_string(&refund)));
+        messages.push(SubMsg::new(BankMsg::Send {
+            to_address: caller.to_string(),
+            amount: deduct_tax(&deps.querier, refund, &mut taxes)?,
+        }));
+    }
+    if !taxes.is_empty() {
+        attrs.push(attr("tax", coins_to_string(&taxes)));
+    }
+
+    PAYOUT_SENDER.remove(deps.storage);
+
+    // held deposits the flush of this one paid out are forwarded now
+    let holding = state.hold && !overridden && !locked;
+    if holding {
+        settle_held(deps.storage, &held, &recipients)?;
+    }
+    let status = if pending.is_some() || escrow.is_some() {
+        ReceiptStatus::Pending
+    } else if raised || stream.is_some() {
+        ReceiptStatus::Locked
+    } else if holding && is_held(&funds, &held) {
+        ReceiptStatus::Held
+    } else {
+        ReceiptStatus::Forwarded
+    };
+    let transfer = Transfer {
+        sender,
+        amount: funds,
+        recipients,
+        height: env.block.height,
+        memo,
+        status,
+        minted: None,
+        category,
+        items,
+    };
+    let id = record_transfer(deps.storage, &transfer)?;
+    attrs.push(attr("receipt_id", id.to_string()));
+    if status == ReceiptStatus::Held {
+        let mut ids = HELD_RECEIPTS.may_load(deps.storage)?.unwrap_or_default();
+        ids.push(id);
+        HELD_RECEIPTS.save(deps.storage, &ids)?;
+    }
+    if let Some((_, balance)) = deposit {
+        let deposit = AnchorDeposit {
+            balance,
+            receipt_id: id,
+        };
+        ANCHOR_DEPOSIT.save(deps.storage, &deposit)?;
+    }
+    messages.extend(receipt_mint(
+        &state,
+        id,
+        &transfer.sender,
+        &transfer.amount,
+    )?);
+    if let Some(memo) = &transfer.memo {
+        attrs.push(attr("memo", memo));
+    }
+    if let Some(category) = &transfer.category {
+        attrs.push(attr("category", category));
+    }
+    if let Some(pending) = pending {
+        PENDING_FORWARDS.save(deps.storage, id, &pending)?;
+    }
+    if let Some(escrow) = escrow {
+        attrs.push(attr("receiver", escrow.receiver.as_str()));
+        attrs.push(attr("expires", ready_at_string(&escrow.expires)));
+        if let Some(hashlock) = &escrow.hashlock {
+            attrs.push(attr("hashlock", hashlock));
+        }
+        escrows().save(deps.storage, id, &escrow)?;
+    }
+    if let Some(stream) = stream {
+        attrs.push(attr("stream_end", stream.end.to_string()));
+        STREAMS.save(deps.storage, id, &stream)?;
+    }
+
+    // the swaps and the anchor deposit go first, so their replies see no other balance change
+    Ok(Response::new()
+        .add_submessages(swaps)
+        .add_submessages(messages)
+        .add_attributes(attrs)
+        .set_data(to_json_binary(&send_receipt(id, &transfer))?))
+}
+
+// try_batch_send pays every payment with one send per recipient and records them as a
+// single transfer
+pub fn try_batch_send(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    payments: Vec<Payment>,
+    memo: Option<String>,
+) -> Result<Response, ContractError> {
+    let state = load_config(deps.storage, &env.block)?;
+    if state.paused {
+        return Err(ContractError::Paused {});
+    }
+    let sender = info.sender;
+    check_sender(deps.storage, &state, &sender)?;
+
+    let mut total = vec![];
+    let mut payouts: Vec<(Addr, Vec<Coin>)> = vec![];
+    for payment in payments {
+        if payment.amount.is_zero() {
+            return Err(ContractError::NoFundsSent {});
+        }
+        let recipient = deps.api.addr_validate(&payment.recipient)?;
+        let coin = Coin {
+            denom: payment.denom,
+            amount: payment.amount,
+        };
+        add_coins(&mut total, vec![coin.clone()]);
+        match payouts.iter_mut().find(|(addr, _)| *addr == recipient) {
+            Some((_, coins)) => add_coins(coins, vec![coin]),
+            None => payouts.push((recipient, vec![coin])),
+        }
+    }
+    let mut funds = vec![];
+    add_coins(&mut funds, info.funds);
+    if total.is_empty()
+        || total.len() != funds.len()
+        || total.iter().any(|coin| !funds.contains(coin))
+    {
+        return Err(ContractError::BatchMismatch {});
+    }
+
+    consume_quota(
+        deps.storage,
+        &state,
+        &sender,
+        &funds,
+        env.block.time.seconds(),
+    )?;
+    record_contribution(deps.storage, &sender, &funds)?;
+    record_stats(deps.storage, &funds, env.block.height)?;
+
+    let mut messages = vec![];
+    let mut attrs = vec![
+        attr("action", "batch_send"),
+        attr("sender", sender.as_str()),
+        attr("amount", coins_to_string(&funds)),
+    ];
+    let mut taxes = vec![];
+    let mut recipients = vec![];
+    for (recipient, amount) in payouts {
+        attrs.push(attr("recipient", recipient.as_str()));
+        let msg = CosmosMsg::Bank(BankMsg::Send {
+            to_address: recipient.to_string(),
+            amount: deduct_tax(&deps.querier, amount, &mut taxes)?,
+        });
+        messages.push(payout(deps.storage, &state, &recipient, msg)?);
+        recipients.push(recipient);
+    }
+    if !taxes.is_empty() {
+        attrs.push(attr("tax", coins_to_string(&taxes)));
+    }
+
+    let transfer = Transfer {
+        sender,
+        amount: funds,
+        recipients,
+        height: env.block.height,
+        memo,
+        status: ReceiptStatus::Forwarded,
+        minted: None,
+        category: None,
+        items: vec![],
+    };
+    let id = record_transfer(deps.storage, &transfer)?;
+    attrs.push(attr("receipt_id", id.to_string()));
+    if let Some(memo) = &transfer.memo {
+        attrs.push(attr("memo", memo));
+    }
+
+    Ok(Response::new()
+        .add_submessages(messages)
+        .add_attributes(attrs)
+        .set_data(to_json_binary(&send_receipt(id, &transfer))?))
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn sudo(deps: DepsMut, env: Env, msg: SudoMsg) -> Result<Response, ContractError> {
+    match msg {
+        SudoMsg::SetReceiver { receiver } => sudo_set_receiver(deps, env, receiver),
+        SudoMsg::Pause {} => sudo_set_paused(deps, true),
+        SudoMsg::Unpause {} => sudo_set_paused(deps, false),
+        SudoMsg::IbcLifecycleComplete(complete) => sudo_ibc_complete(deps, complete),
+    }
+}
+
+// sudo_set_receiver replaces the receiver and drops any pending change. The change is
+// recorded with the contract itself as actor
+fn sudo_set_receiver(deps: DepsMut, env: Env, receiver: String) -> Result<Response, ContractError> {
+    let mut state = CONFIG.load(deps.storage)?;
+    let receiver = match ALIASES.may_load(deps.storage, &receiver)? {
+        Some(addr) => addr,
+        None => validate_receiver(deps.api, &env, &receiver)?,
+    };
+    check_attestation(&deps.querier, &state, &receiver)?;
+    record_receiver_change(
+        deps.storage,
+        &env.block,
+        &state.receiver,
+        &receiver,
+        &env.contract.address,
+    )?;
+    state.receiver = receiver;
+    CONFIG.save(deps.storage, &state)?;
+    PENDING_RECEIVER.remove(deps.storage);
+
+    Ok(Response::new().add_attributes(vec![
+        attr("action", "sudo_set_receiver"),
+        attr("recipient", state.receiver.as_str()),
+    ]))
+}
+
+fn sudo_set_paused(deps: DepsMut, paused: bool) -> Result<Response, ContractError> {
+    CONFIG.update(deps.storage, |mut state| -> StdResult<_> {
+        state.paused = paused;
+        Ok(state)
+    })?;
+    let action = if paused { "sudo_pause" } else { "sudo_unpause" };
+    Ok(Response::new().add_attribute("action", action))
+}
+
+// sudo_ibc_complete drops the record of an IBC transfer that was acked. The funds of one that
+// failed or timed out came back, they are owed to the sender of the deposit or held again to
+// be forwarded with the next flush
+fn sudo_ibc_complete(
+    deps: DepsMut,
+    complete: IbcLifecycleComplete,
+) -> Result<Response, ContractError> {
+    let (channel, sequence, error) = match complete {
+        IbcLifecycleComplete::IbcAck {
+            channel,
+            sequence,
+            ack,
+            success,
+        } => (channel, sequence, (!success).then_some(ack)),
+        IbcLifecycleComplete::IbcTimeout { channel, sequence } => {
+            (channel, sequence, Some("timeout".to_string()))
+        }
+    };
+    let id = IBC_SEQUENCES
+        .may_load(deps.storage, (&channel, sequence))?
+        .ok_or_else(|| ContractError::UnknownIbcTransfer {
+            channel: channel.clone(),
+            sequence,
+        })?;
+    let transfer = IBC_TRANSFERS.load(deps.storage, id)?;
+    IBC_SEQUENCES.remove(deps.storage, (&channel, sequence));
+    IBC_TRANSFERS.remove(deps.storage, id);
+    let error = match error {
+        Some(error) => error,
+        None => {
+            return Ok(Response::new().add_attributes(vec![
+                attr("action", "ibc_acked"),
+                attr("transfer_id", id.to_string()),
+            ]))
+        }
+    };
+
+    let (action, refund_to) = match &transfer.sender {
+        Some(sender) => {
+            let mut owed = REFUNDS.may_load(deps.storage, sender)?.unwrap_or_default();
+            add_coins(&mut owed, vec![transfer.amount.clone()]);
+            REFUNDS.save(deps.storage, sender, &owed)?;
+            ("ibc_refundable", Some(sender))
+        }
+        None => {
+            let mut held = HELD.may_load(deps.storage)?.unwrap_or_default();
+            add_coins(&mut held, vec![transfer.amount.clone()]);
+            HELD.save(deps.storage, &held)?;
+            ("ibc_held", None)
+        }
+    };
+    let mut attrs = vec![
+        attr("action", action),
+        attr("transfer_id", id.to_string()),
+        attr("amount", transfer.amount.to_string()),
+        attr("error", error),
+    ];
+    if let Some(sender) = refund_to {
+        attrs.push(attr("refund_to", sender.as_str()));
+    }
+    Ok(Response::new().add_attributes(attrs))
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn reply(deps: DepsMut, env: Env, msg: Reply) -> Result<Response, ContractError> {
+    match msg.id {
+        SWAP_REPLY_ID => reply_swap(deps, env),
+        ANCHOR_REPLY_ID => reply_anchor(deps, env),
+        MINT_REPLY_ID => reply_mint(msg.result),
+        id if id > IBC_REPLY_ID => reply_ibc(deps, id - IBC_REPLY_ID, msg.result),
+        id if id > CHARGE_REPLY_ID => reply_charge(deps, env, id - CHARGE_REPLY_ID, msg.result),
+        id if id > PAYOUT_REPLY_ID => reply_payout(deps, id - PAYOUT_REPLY_ID, msg.result),
+        id => Err(ContractError::UnknownReply { id }),
+    }
+}
+
+// reply_mint only runs for failed mints, the deposit goes through without a receipt
+fn reply_mint(result: SubMsgResult) -> Result<Response, ContractError> {
+    let error = match result {
+        SubMsgResult::Ok(_) => return Ok(Response::default()),
+        SubMsgResult::Err(error) => error,
+    };
+    Ok(Response::new().add_attribute("mint_failed", error))
+}
+
+// reply_charge forwards the installment a cw20 subscription was charged once the tokens
+// arrived. A charge the token rejected marks the subscription delinquent, the installment
+// is skipped like one the allowance could not cover
+fn reply_charge(
+    deps: DepsMut,
+    env: Env,
+    id: u64,
+    result: SubMsgResult,
+) -> Result<Response, ContractError> {
+    let amount = CHARGES.load(deps.storage, id)?;
+    CHARGES.remove(deps.storage, id);
+    let mut subscription = subscriptions().load(deps.storage, id)?;
+    let token = subscription
+        .token
+        .clone()
+        .ok_or(ContractError::UnknownReply {
+            id: CHARGE_REPLY_ID + id,
+        })?;
+    if let SubMsgResult::Err(error) = result {
+        subscription.delinquent = true;
+        subscriptions().save(deps.storage, id, &subscription)?;
+        return Ok(Response::new().add_attributes(vec![
+            attr("action", "charge_failed"),
+            attr("subscription_delinquent", id.to_string()),
+            attr("error", error),
+        ]));
+    }
+
+    let state = load_config(deps.storage, &env.block)?;
+    let (messages, fwd_attrs, recipients) =
+        forward_cw20(deps.storage, &env.block, &state, &token, amount)?;
+    let transfer = Transfer {
+        sender: subscription.sender,
+        amount: vec![Coin {
+            denom: token.to_string(),
+            amount,
+        }],
+        recipients,
+        height: env.block.height,
+        memo: None,
+        status: ReceiptStatus::Forwarded,
+        minted: None,
+        category: None,
+        items: vec![],
+    };
+    let transfer_id = record_transfer(deps.storage, &transfer)?;
+    let mut attrs = vec![
+        attr("action", "charge"),
+        attr("subscription_id", id.to_string()),
+    ];
+    attrs.extend(fwd_attrs);
+    attrs.push(attr("receipt_id", transfer_id.to_string()));
+
+    Ok(Response::new()
+        .add_submessages(messages)
+        .add_attributes(attrs))
+}
+
+// reply_ibc records the packet sequence an IBC transfer was sent with, so its ack can be
+// matched to it
+fn reply_ibc(deps: DepsMut, id: u64, result: SubMsgResult) -> Result<Response, ContractError> {
+    let mut transfer = IBC_TRANSFERS.load(deps.storage, id)?;
+    let sequence = result
+        .into_result()
+        .map_err(StdError::generic_err)?
+        .events
+        .into_iter()
+        .filter(|event| event.ty == "send_packet")
+        .flat_map(|event| event.attributes)
+        .find(|attr| attr.key == "packet_sequence")
+        .and_then(|attr| attr.value.parse::<u64>().ok())
+        .ok_or_else(|| StdError::generic_err("IBC transfer sent no packet"))?;
+    IBC_SEQUENCES.save(deps.storage, (&transfer.channel, sequence), &id)?;
+    transfer.sequence = Some(sequence);
+    IBC_TRANSFERS.save(deps.storage, id, &transfer)?;
+
+    Ok(Response::new().add_attributes(vec![
+        attr("action", "ibc_transfer"),
+        attr("transfer_id", id.to_string()),
+        attr("sequence", sequence.to_string()),
+    ]))
+}
+
+// reply_payout drops the record of a payout that went through, a failed one is held until
+// it is retried
+fn reply_payout(deps: DepsMut, id: u64, result: SubMsgResult) -> Result<Response, ContractError> {
+    let mut payout = PAYOUTS.load(deps.storage, id)?;
+    PAYOUTS.remove(deps.storage, id);
+    let error = match result {
+        SubMsgResult::Ok(_) => return Ok(Response::default()),
+        SubMsgResult::Err(error) => error,
+    };
+
+    // the fallback receiver gets what the receiver could not be paid, if it fails as well
+    // the payout is held like any other
+    let state = CONFIG.load(deps.storage)?;
+    if let Some(fallback) = state
+        .fallback_receiver
+        .as_ref()
+        .filter(|fallback| **fallback != payout.recipient)
+    {
+        let msg = redirect(&payout.msg, fallback)?;
+        return Ok(Response::new()
+            .add_submessage(self::payout(deps.storage, &state, fallback, msg)?)
+            .add_attributes(vec![
+                attr("action", "fallback"),
+                attr("payout_id", id.to_string()),
+                attr("failed_recipient", payout.recipient.as_str()),
+                attr("recipient", fallback.as_str()),
+                attr("error", error.as_str()),
+            ]));
+    }
+    // with refund_failed a bank payout is owed back to the sender of the deposit
+    if let (true, Some(sender), CosmosMsg::Bank(BankMsg::Send { amount, .. })) =
+        (state.refund_failed, &payout.sender, &payout.msg)
+    {
+        let mut owed = REFUNDS.may_load(deps.storage, sender)?.unwrap_or_default();
+        add_coins(&mut owed, amount.clone());
+        REFUNDS.save(deps.storage, sender, &owed)?;
+        return Ok(Response::new().add_attributes(vec![
+            attr("action", "payout_refundable"),
+            attr("payout_id", id.to_string()),
+            attr("recipient", payout.recipient.as_str()),
+            attr("refund_to", sender.as_str()),
+            attr("amount", coins_to_string(amount)),
+            attr("error", error.as_str()),
+        ]));
+    }
+    if !state.hold_failed {
+        return Err(ContractError::PayoutFailed { error });
+    }
+    let attrs = vec![
+        attr("action", "payout_failed"),
+        attr("payout_id", id.to_string()),
+        attr("recipient", payout.recipient.as_str()),
+        attr("error", error.as_str()),
+    ];
+    payout.error = Some(error);
+    FAILED_PAYOUTS.save(deps.storage, id, &payout)?;
+
+    Ok(Response::new().add_attributes(attrs))
+}
+
+// reply_swap forwards what a swap returned, which is what the target denom balance grew by.
+// The proceeds that stay in the contract are added to the balance the next swap of the
+// deposit is measured against
+fn reply_swap(mut deps: DepsMut, env: Env) -> Result<Response, ContractError> {
+    let state = load_config(deps.storage, &env.block)?;
+    let swap = state
+        .swap
+        .as_ref()
+        .ok_or(ContractError::UnknownReply { id: SWAP_REPLY_ID })?;
+    let before = SWAP_BALANCE.load(deps.storage)?;
+    let balance = deps
+        .querier
+        .query_balance(&env.contract.address, &swap.target_denom)?;
+    let proceeds = Coin {
+        denom: balance.denom,
+        amount: balance.amount.saturating_sub(before),
+    };
+    if proceeds.amount.is_zero() {
+        return Ok(Response::new().add_attribute("action", "swap"));
+    }
+
+    let mut taxes = vec![];
+    let (messages, fwd_attrs, _, _) =
+        forward(&mut deps, &env, &state, vec![proceeds.clone()], &mut taxes)?;
+    let mut paid = sent(&messages);
+    add_coins(&mut paid, taxes.clone());
+    let paid: Uint128 = paid
+        .iter()
+        .filter(|coin| coin.denom == proceeds.denom)
+        .map(|coin| coin.amount)
+        .sum();
+    SWAP_BALANCE.save(deps.storage, &balance.amount.saturating_sub(paid))?;
+    let mut attrs = vec![
+        attr("action", "swap"),
+        attr("proceeds", proceeds.to_string()),
+    ];
+    attrs.extend(fwd_attrs);
+    if !taxes.is_empty() {
+        attrs.push(attr("tax", coins_to_string(&taxes)));
+    }
+
+    Ok(Response::new()
+        .add_submessages(messages)
+        .add_attributes(attrs))
+}
+
+// reply_anchor forwards the aTerra the money market minted for a deposit, which is what the
+// aTerra balance grew by, and adds it to the receipt of the deposit
+fn reply_anchor(deps: DepsMut, env: Env) -> Result<Response, ContractError> {
+    let state = load_config(deps.storage, &env.block)?;
+    let anchor = state.anchor.as_ref().ok_or(ContractError::UnknownReply {
+        id: ANCHOR_REPLY_ID,
+    })?;
+    let deposit = ANCHOR_DEPOSIT.load(deps.storage)?;
+    ANCHOR_DEPOSIT.remove(deps.storage);
+    let balance: cw20::BalanceResponse = deps.querier.query_wasm_smart(
+        &anchor.aterra,
+        &Cw20QueryMsg::Balance {
+            address: env.contract.address.to_string(),
+        },
+    )?;
+    let minted = balance.balance.saturating_sub(deposit.balance);
+    if minted.is_zero() {
+        return Ok(Response::new().add_attribute("action", "anchor_deposit"));
+    }
+
+    let (messages, fwd_attrs, recipients) =
+        forward_cw20(deps.storage, &env.block, &state, &anchor.aterra, minted)?;
+    let mut transfer = TRANSFERS.load(deps.storage, deposit.receipt_id)?;
+    transfer.minted = Some(Coin {
+        denom: anchor.aterra.to_string(),
+        amount: minted,
+    });
+    TRANSFERS.save(deps.storage, deposit.receipt_id, &transfer)?;
+    let receipt = settle_receipt(
+        deps.storage,
+        deposit.receipt_id,
+        recipients,
+        ReceiptStatus::Forwarded,
+    )?;
+    let mut attrs = vec![
+        attr("action", "anchor_deposit"),
+        attr("receipt_id", deposit.receipt_id.to_string()),
+        attr("minted", minted),
+        attr("denom", anchor.aterra.as_str()),
+    ];
+    attrs.extend(fwd_attrs);
+
+    let mut res = Response::new()
+        .add_submessages(messages)
+        .add_attributes(attrs);
+    if let Some(receipt) = receipt {
+        res = res.set_data(to_json_binary(&receipt)?);
+    }
+    Ok(res)
+}
+
+pub fn try_flush(mut deps: DepsMut, env: Env) -> Result<Response, ContractError> {
+    let state = load_config(deps.storage, &env.block)?;
+    let held = HELD.may_load(deps.storage)?.unwrap_or_default();
+    if held.is_empty() {
+        return Err(ContractError::NothingToFlush {});
+    }
+    HELD.save(deps.storage, &vec![])?;
+
+    let mut taxes = vec![];
+    let (messages, fwd_attrs, recipients, _) = forward(&mut deps, &env, &state, held, &mut taxes)?;
+    settle_held(deps.storage, &[], &recipients)?;
+    let mut attrs = vec![attr("action", "flush")];
+    attrs.extend(fwd_attrs);
+    if !taxes.is_empty() {
+        attrs.push(attr("tax", coins_to_string(&taxes)));
+    }
+
+    Ok(Response::new()
+        .add_submessages(messages)
+        .add_attributes(attrs))
+}
+
+pub fn try_set_dust_threshold(
+    deps: DepsMut,
+    info: MessageInfo,
+    dust_threshold: Vec<Coin>,
+) -> Result<Response, ContractError> {
+    CONFIG.update(deps.storage, |mut state| {
+        if !state.is_owner(&info.sender) {
+            return Err(ContractError::Unauthorized {});
+        }
+        state.dust_threshold = dust_threshold;
+        Ok(state)
+    })?;
+    Ok(config_response("set_dust_threshold", &info.sender))
+}
+
+// raise keeps a campaign deposit of sender
+fn raise(storage: &mut dyn Storage, sender: &Addr, funds: &[Coin]) -> StdResult<()> {
+    let mut progress = CAMPAIGN_PROGRESS.may_load(storage)?.unwrap_or_default();
+    add_coins(&mut progress.raised, funds.to_vec());
+    CAMPAIGN_PROGRESS.save(storage, &progress)?;
+    let mut deposit = CAMPAIGN_DEPOSITS
+        .may_load(storage, sender)?
+        .unwrap_or_default();
+    add_coins(&mut deposit, funds.to_vec());
+    CAMPAIGN_DEPOSITS.save(storage, sender, &deposit)
+}
+
+pub fn try_set_usd_floor(
+    deps: DepsMut,
+    info: MessageInfo,
+    usd_floor: Option<UsdFloorSettings>,
+) -> Result<Response, ContractError> {
+    let usd_floor = validate_usd_floor(deps.api, usd_floor)?;
+    CONFIG.update(deps.storage, |mut state| {
+        if !state.is_owner(&info.sender) {
+            return Err(ContractError::Unauthorized {});
+        }
+        state.usd_floor = usd_floor;
+        Ok(state)
+    })?;
+    Ok(config_response("set_usd_floor", &info.sender))
+}
+
+pub fn try_set_receipt_nft(
+    deps: DepsMut,
+    info: MessageInfo,
+    receipt_nft: Option<ReceiptNftSettings>,
+) -> Result<Response, ContractError> {
+    let receipt_nft = validate_receipt_nft(deps.api, receipt_nft)?;
+    CONFIG.update(deps.storage, |mut state| {
+        if !state.is_owner(&info.sender) {
+            return Err(ContractError::Unauthorized {});
+        }
+        state.receipt_nft = receipt_nft;
+        Ok(state)
+    })?;
+    Ok(config_response("set_receipt_nft", &info.sender))
+}
+
+pub fn try_set_mint_receipts(
+    deps: DepsMut,
+    info: MessageInfo,
+    mint_receipts: bool,
+) -> Result<Response, ContractError> {
+    CONFIG.update(deps.storage, |mut state| {
+        if !state.is_owner(&info.sender) {
+            return Err(ContractError::Unauthorized {});
+        }
+        state.mint_receipts = mint_receipts;
+        Ok(state)
+    })?;
+    Ok(config_response("set_mint_receipts", &info.sender))
+}
+
+pub fn try_set_campaign(
+    deps: DepsMut,
+    info: MessageInfo,
+    campaign: Option<Campaign>,
+) -> Result<Response, ContractError> {
+    let mut state = CONFIG.load(deps.storage)?;
+    if !state.is_owner(&info.sender) {
+        return Err(ContractError::Unauthorized {});
+    }
+    // a new campaign can only start once the deposits of the last one were paid out
+    let progress = CAMPAIGN_PROGRESS
+        .may_load(deps.storage)?
+        .unwrap_or_default();
+    if !progress.raised.is_empty() && !progress.claimed {
+        return Err(ContractError::CampaignActive {});
+    }
+    CAMPAIGN_PROGRESS.remove(deps.storage);
+    CAMPAIGN_DEPOSITS.clear(deps.storage);
+    state.campaign = campaign;
+    CONFIG.save(deps.storage, &state)?;
+    Ok(config_response("set_campaign", &info.sender))
+}
+
+// try_claim_campaign lets the receiver take what a successful campaign raised, the fee and
+// split apply like to any deposit
+pub fn try_claim_campaign(
+    mut deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+) -> Result<Response, ContractError> {
+    let state = load_config(deps.storage, &env.block)?;
+    if info.sender != state.receiver {
+        return Err(ContractError::Unauthorized {});
+    }
+    let campaign = state.campaign.clone().ok_or(ContractError::NoCampaign {})?;
+    let mut progress = CAMPAIGN_PROGRESS
+        .may_load(deps.storage)?
+        .unwrap_or_default();
+    match campaign_status(&campaign, &progress, &env.block) {
+        CampaignStatus::Succeeded => {}
+        CampaignStatus::Failed => return Err(ContractError::GoalNotMet {}),
+        CampaignStatus::Claimed => return Err(ContractError::NothingToClaim {}),
+        _ => return Err(ContractError::CampaignNotEnded {}),
+    }
+    progress.claimed = true;
+    CAMPAIGN_PROGRESS.save(deps.storage, &progress)?;
+
+    let mut taxes = vec![];
+    let (messages, fwd_attrs, _, _) =
+        forward(&mut deps, &env, &state, progress.raised.clone(), &mut taxes)?;
+    let mut attrs = vec![
+        attr("action", "claim_campaign"),
+        attr("sender", info.sender.as_str()),
+        attr("amount", coins_to_string(&progress.raised)),
+    ];
+    attrs.extend(fwd_attrs);
+    if !taxes.is_empty() {
+        attrs.push(attr("tax", coins_to_string(&taxes)));
+    }
+
+    Ok(Response::new()
+        .add_submessages(messages)
+        .add_attributes(attrs))
+}
+
+// try_refund_campaign returns the deposits of the sender once a campaign failed
+pub fn try_refund_campaign(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+) -> Result<Response, ContractError> {
+    let state = CONFIG.load(deps.storage)?;
+    let campaign = state.campaign.ok_or(ContractError::NoCampaign {})?;
+    let mut progress = CAMPAIGN_PROGRESS
+        .may_load(deps.storage)?
+        .unwrap_or_default();
+    match campaign_status(&campaign, &progress, &env.block) {
+        CampaignStatus::Failed => {}
+        CampaignStatus::Succeeded | CampaignStatus::Claimed => {
+            return Err(ContractError::GoalMet {})
+        }
+        _ => return Err(ContractError::CampaignNotEnded {}),
+    }
+    let deposit = CAMPAIGN_DEPOSITS
+        .may_load(deps.storage, &info.sender)?
+        .ok_or(ContractError::NothingToWithdraw {})?;
+    CAMPAIGN_DEPOSITS.remove(deps.storage, &info.sender);
+    progress.raised = remaining(&progress.raised, &deposit)
+        .into_iter()
+        .filter(|coin| !coin.amount.is_zero())
+        .collect();
+    CAMPAIGN_PROGRESS.save(deps.storage, &progress)?;
+
+    let mut taxes = vec![];
+    let amount = deduct_tax(&deps.querier, deposit.clone(), &mut taxes)?;
+    let mut attrs = vec![
+        attr("action", "refund_campaign"),
+        attr("sender", info.sender.as_str()),
+        attr("amount", coins_to_string(&deposit)),
+    ];
+    if !taxes.is_empty() {
+        attrs.push(attr("tax", coins_to_string(&taxes)));
+    }
+    Ok(Response::new()
+        .add_message(BankMsg::Send {
+            to_address: info.sender.to_string(),
+            amount,
+        })
+        .add_attributes(attrs))
+}
+
+fn campaign_status(
+    campaign: &Campaign,
+    progress: &CampaignProgress,
+    block: &BlockInfo,
+) -> CampaignStatus {
+    if !campaign.start.is_expired(block) {
+        CampaignStatus::Upcoming
+    } else if !campaign.end.is_expired(block) {
+        CampaignStatus::Open
+    } else if progress.claimed {
+        CampaignStatus::Claimed
+    } else if progress
+        .raised
+        .iter()
+        .any(|coin| coin.denom == campaign.goal.denom && coin.amount >= campaign.goal.amount)
+    {
+        CampaignStatus::Succeeded
+    } else {
+        CampaignStatus::Failed
+    }
+}
+
+// try_flush_dust pays the collected dust to the receiver in one payout, the split does not
+// apply. It is credited in pull mode and transferred to the remote receiver in IBC mode
+pub fn try_flush_dust(deps: DepsMut, env: Env) -> Result<Response, ContractError> {
+    let state = load_config(deps.storage, &env.block)?;
+    let dust = DUST.may_load(deps.storage)?.unwrap_or_default();
+    if dust.is_empty() {
+        return Err(ContractError::NothingToFlush {});
+    }
+    DUST.remove(deps.storage);
+
+    let mut taxes = vec![];
+    let mut attrs = vec![
+        attr("action", "flush_dust"),
+        attr("recipient", state.receiver.as_str()),
+        attr("amount", coins_to_string(&dust)),
+    ];
+    let mut messages = vec![];
+    if state.pull {
+        credit_claimable(
+            deps.storage,
+            &state.receiver,
+            dust,
+            env.block.time.seconds(),
+        )?;
+    } else {
+        messages = send_payout(
+            deps.storage,
+            &deps.querier,
+            &env,
+            &state,
+            &state.receiver,
+            dust,
+            &mut taxes,
+        )?;
+    }
+    if !taxes.is_empty() {
+        attrs.push(attr("tax", coins_to_string(&taxes)));
+    }
+
+    Ok(Response::new()
+        .add_submessages(messages)
+        .add_attributes(attrs))
+}
+
+// tracked returns the native funds the contract keeps for someone, what all its ledgers add
+// up to
+// tracked sums the ledgers that are part of the balance, undelegations still unbonding are
+// not
+fn tracked(storage: &dyn Storage, now: u64) -> StdResult<Vec<Coin>> {
+    let mut tracked = vec![];
+    for (name, amount) in ledgers(storage, now)? {
+        if name == "unbonding" {
+            continue;
+        }
+        add_coins(&mut tracked, amount);
+    }
+    Ok(tracked)
+}
+
+// ledgers returns the native funds the contract keeps for someone by ledger: held,
+// claimable, escrowed, streaming, pending and subscription balances, failed payouts and the
+// undelegations of receivers. Undelegations completed by now are in the balance, the others
+// are still unbonding
+fn ledgers(storage: &dyn Storage, now: u64) -> StdResult<Vec<(&'static str, Vec<Coin>)>> {
+    let mut progress = CAMPAIGN_PROGRESS.may_load(storage)?.unwrap_or_default();
+    if progress.claimed {
+        progress.raised = vec![];
+    }
+    let mut claimable = vec![];
+    for item in CLAIMABLE.range(storage, None, None, Order::Ascending) {
+        add_coins(&mut claimable, item?.1);
+    }
+    let mut escrowed = vec![];
+    for item in escrows().range(storage, None, None, Order::Ascending) {
+        add_coins(&mut escrowed, item?.1.amount);
+    }
+    let mut streaming = vec![];
+    for item in STREAMS.range(storage, None, None, Order::Ascending) {
+        let (_, stream) = item?;
+        add_coins(&mut streaming, remaining(&stream.amount, &stream.claimed));
+    }
+    let mut pending = vec![];
+    for item in PENDING_FORWARDS.range(storage, None, None, Order::Ascending) {
+        add_coins(&mut pending, item?.1.amount);
+    }
+    let mut subscribed = vec![];
+    for item in subscriptions().range(storage, None, None, Order::Ascending) {
+        let (_, subscription) = item?;
+        if subscription.token.is_some() {
+            continue;
+        }
+        add_coins(
+            &mut subscribed,
+            vec![Coin {
+                denom: subscription.amount.denom,
+                amount: subscription.balance,
+            }],
+        );
+    }
+    let mut queued = vec![];
+    for item in PAYOUT_QUEUE.range(storage, None, None, Order::Ascending) {
+        let (_, (_, amount)) = item?;
+        add_coins(&mut queued, amount);
+    }
+    let mut refunds = vec![];
+    for item in REFUNDS.range(storage, None, None, Order::Ascending) {
+        add_coins(&mut refunds, item?.1);
+    }
+    let mut failed = vec![];
+    for item in FAILED_PAYOUTS.range(storage, None, None, Order::Ascending) {
+        match item?.1.msg {
+            CosmosMsg::Bank(BankMsg::Send { amount, .. }) => add_coins(&mut failed, amount),
+            CosmosMsg::Wasm(WasmMsg::Execute { funds, .. }) => add_coins(&mut failed, funds),
+            _ => {}
+        }
+    }
+    let mut unbonding = vec![];
+    let mut unbonded = vec![];
+    for item in UNBONDING.range(storage, None, None, Order::Ascending) {
+        for entry in item?.1 {
+            if entry.completes_at <= now {
+                add_coins(&mut unbonded, vec![entry.amount]);
+            } else {
+                add_coins(&mut unbonding, vec![entry.amount]);
+            }
+        }
+    }
+    Ok(vec![
+        ("held", HELD.may_load(storage)?.unwrap_or_default()),
+        ("dust", DUST.may_load(storage)?.unwrap_or_default()),
+        (
+            "rebate_pool",
+            REBATE_POOL.may_load(storage)?.unwrap_or_default(),
+        ),
+        ("campaign", progress.raised),
+        ("claimable", claimable),
+        ("escrows", escrowed),
+        ("streams", streaming),
+        ("pending_forwards", pending),
+        ("subscriptions", subscribed),
+        ("payout_queue", queued),
+        ("refunds", refunds),
+        ("failed_payouts", failed),
+        ("unbonding", unbonding),
+        ("unbonded", unbonded),
+    ])
+}
+
+// try_sweep forwards native funds that were sent to the contract directly. Everything the
+// contract keeps for someone stays, only the rest of the balance is swept
+pub fn try_sweep(
+    mut deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    denom: Option<String>,
+) -> Result<Response, ContractError> {
+    check_permission(deps.storage, &info.sender, Permission::Operate)?;
+    let state = load_config(deps.storage, &env.block)?;
+    let balance = match denom {
+        Some(denom) => vec![deps.querier.query_balance(&env.contract.address, denom)?],
+        None => deps.querier.query_all_balances(&env.contract.address)?,
+    };
+    let tracked = tracked(deps.storage, env.block.time.seconds())?;
+    let residue: Vec<Coin> = balance
+        .into_iter()
+        .map(|coin| {
+            let kept = tracked
+                .iter()
+                .find(|tracked| tracked.denom == coin.denom)
+                .map(|tracked| tracked.amount)
+                .unwrap_or_default();
+            Coin {
+                amount: coin.amount.saturating_sub(kept),
+                denom: coin.denom,
+            }
+        })
+        .filter(|coin| !coin.amount.is_zero())
+        .collect();
+    if residue.is_empty() {
+        return Err(ContractError::NothingToSweep {});
+    }
+
+    let mut taxes = vec![];
+    let (messages, fwd_attrs, _, _) =
+        forward(&mut deps, &env, &state, residue.clone(), &mut taxes)?;
+    let mut attrs = vec![
+        attr("action", "sweep"),
+        attr("sender", info.sender.as_str()),
+        attr("amount", coins_to_string(&residue)),
+    ];
+    attrs.extend(fwd_attrs);
+    if !taxes.is_empty() {
+        attrs.push(attr("tax", coins_to_string(&taxes)));
+    }
+
+    Ok(Response::new()
+        .add_submessages(messages)
+        .add_attributes(attrs))
+}
+
+pub fn try_receive(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    wrapper: Cw20ReceiveMsg,
+) -> Result<Response, ContractError> {
+    let sender = deps.api.addr_validate(&wrapper.sender)?;
+    // the cw20 contract calling Receive is the token that was sent to us
+    deposit_cw20(deps, env, sender, info.sender, wrapper.amount)
+}
+
+// try_pull_cw20 moves amount of token from the sender to the contract using the allowance
+// the sender granted, then forwards it like a deposit of the sender
+pub fn try_pull_cw20(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    token: String,
+    amount: Uint128,
+) -> Result<Response, ContractError> {
+    let token = deps.api.addr_validate(&token)?;
+    // an allowance is granted to the contract, not to its owner, only the owner of the
+    // tokens may spend it
+    let owner = info.sender;
+    let transfer_from = WasmMsg::Execute {
+        contract_addr: token.to_string(),
+        msg: to_json_binary(&Cw20ExecuteMsg::TransferFrom {
+            owner: owner.to_string(),
+            recipient: env.contract.address.to_string(),
+            amount,
+        })?,
+        funds: vec![],
+    };
+    // the tokens have to arrive before they are forwarded
+    let mut res = deposit_cw20(deps, env, owner, token, amount)?;
+    res.messages.insert(0, SubMsg::new(transfer_from));
+    Ok(res)
+}
+
+// deposit_cw20 forwards a deposit of amount of token by sender
+fn deposit_cw20(
+    deps: DepsMut,
+    env: Env,
+    sender: Addr,
+    token: Addr,
+    amount: Uint128,
+) -> Result<Response, ContractError> {
+    let state = load_config(deps.storage, &env.block)?;
+    if state.paused {
+        return Err(ContractError::Paused {});
+    }
+    check_sender(deps.storage, &state, &sender)?;
+    if !is_accepted(&state, token.as_str()) {
+        return Err(ContractError::UnsupportedDenom {
+            denom: token.to_string(),
+        });
+    }
+
+    if amount.is_zero() {
+        return Err(ContractError::NoFundsSent {});
+    }
+
+    let multiplier = min_multiplier(deps.storage, &state, env.block.time.seconds())?;
+    check_amount(&state, token.as_str(), amount, multiplier)?;
+    let deposit = Coin {
+        denom: token.to_string(),
+        amount,
+    };
+    let usd_value = check_usd_floor(&deps.querier, &state, std::slice::from_ref(&deposit))?;
+    consume_quota(
+        deps.storage,
+        &state,
+        &sender,
+        std::slice::from_ref(&deposit),
+        env.block.time.seconds(),
+    )?;
+    record_traffic(deps.storage, &state, env.block.time.seconds())?;
+    record_contribution(deps.storage, &sender, std::slice::from_ref(&deposit))?;
+    let (messages, fwd_attrs, recipients) =
+        forward_cw20(deps.storage, &env.block, &state, &token, amount)?;
+    let mut attrs = vec![
+        attr("action", "send"),
+        attr("sender", sender.as_str()),
+        attr("amount", amount),
+        attr("denom", token.as_str()),
+    ];
+    if let Some(value) = usd_value {
+        attrs.push(attr("usd_value", value));
+    }
+    attrs.extend(fwd_attrs);
+
+    let transfer = Transfer {
+        sender,
+        amount: vec![deposit],
+        recipients,
+        height: env.block.height,
+        memo: None,
+        status: ReceiptStatus::Forwarded,
+        minted: None,
+        category: None,
+        items: vec![],
+    };
+    let id = record_transfer(deps.storage, &transfer)?;
+    attrs.push(attr("receipt_id", id.to_string()));
+    let mint = receipt_mint(&state, id, &transfer.sender, &transfer.amount)?;
+
+    Ok(Response::new()
+        .add_submessages(messages)
+        .add_submessages(mint)
+        .add_attributes(attrs)
+        .set_data(to_json_binary(&send_receipt(id, &transfer))?))
+}
+
+// forward_cw20 pays out amount of token the contract holds like forward does for native
+// funds. Pull mode does not apply, the receivers are always paid right away
+fn forward_cw20(
+    storage: &mut dyn Storage,
+    block: &BlockInfo,
+    state: &Config,
+    token: &Addr,
+    amount: Uint128,
+) -> StdResult<(Vec<SubMsg>, Vec<Attribute>, Vec<Addr>)> {
+    let total = Coin {
+        denom: token.to_string(),
+        amount,
+    };
+    record_stats(storage, std::slice::from_ref(&total), block.height)?;
+    let (fee, net) = deduct_fee(state, token.as_str(), amount)?;
+    let mut messages = vec![];
+    let mut attrs = vec![];
+    if let Some(collector) = &state.fee_collector {
+        if !fee.is_zero() {
+            attrs.push(attr("fee", fee));
+            attrs.push(attr("fee_collector", collector.as_str()));
+            messages.push(SubMsg::new(WasmMsg::Execute {
+                contract_addr: token.to_string(),
+                msg: to_json_binary(&Cw20ExecuteMsg::Transfer {
+                    recipient: collector.to_string(),
+                    amount: fee,
+                })?,
+                funds: vec![],
+            }));
+        }
+    }
+    // there is no dust of cw20 tokens, what the rounding leaves over stays for SweepCw20
+    let (parts, _) = route(storage, state, token.as_str(), net)?;
+    let mut recipients = vec![];
+    for (receiver, amount) in parts {
+        if amount.is_zero() {
+            continue;
+        }
+        attrs.push(attr("recipient", receiver.as_str()));
+        let msg = match callback(state, &receiver) {
+            Some(msg) => Cw20ExecuteMsg::Send {
+                contract: receiver.to_string(),
+                amount,
+                msg: msg.clone(),
+            },
+            None => Cw20ExecuteMsg::Transfer {
+                recipient: receiver.to_string(),
+                amount,
+            },
+        };
+        let msg = CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: token.to_string(),
+            msg: to_json_binary(&msg)?,
+            funds: vec![],
+        });
+        messages.push(payout(storage, state, &receiver, msg)?);
+        recipients.push(receiver);
+    }
+    Ok((messages, attrs, recipients))
+}
+
+// try_sweep_cw20 forwards the balance of token the contract holds, e.g. tokens that were
+// transferred instead of sent. Failed payouts of the token stay. Owner only
+pub fn try_sweep_cw20(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    token: String,
+) -> Result<Response, ContractError> {
+    check_permission(deps.storage, &info.sender, Permission::Operate)?;
+    let state = load_config(deps.storage, &env.block)?;
+    let token = deps.api.addr_validate(&token)?;
+    let balance: cw20::BalanceResponse = deps.querier.query_wasm_smart(
+        &token,
+        &Cw20QueryMsg::Balance {
+            address: env.contract.address.to_string(),
+        },
+    )?;
+    let mut kept = Uint128::zero();
+    for item in FAILED_PAYOUTS.range(deps.storage, None, None, Order::Ascending) {
+        if let CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr, msg, ..
+        }) = item?.1.msg
+        {
+            if contract_addr == token.as_str() {
+                kept += cw20_amount(&msg).unwrap_or_default();
+            }
+        }
+    }
+    let residue = balance.balance.saturating_sub(kept);
+    if residue.is_zero() {
+        return Err(ContractError::NothingToSweep {});
+    }
+
+    let (messages, fwd_attrs, _) = forward_cw20(deps.storage, &env.block, &state, &token, residue)?;
+    let mut attrs = vec![
+        attr("action", "sweep"),
+        attr("sender", info.sender.as_str()),
+        attr("amount", residue),
+        attr("denom", token.as_str()),
+    ];
+    attrs.extend(fwd_attrs);
+
+    Ok(Response::new()
+        .add_submessages(messages)
+        .add_attributes(attrs))
+}
+
+pub fn try_reset(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    receiver: String,
+) -> Result<Response, ContractError> {
+    let state = CONFIG.load(deps.storage)?;
+    if !state.is_owner(&info.sender) {
+        return Err(ContractError::Unauthorized {});
+    }
+    if state.governance.is_some() {
+        return Err(ContractError::Governed {});
+    }
+    let receiver = match ALIASES.may_load(deps.storage, &receiver)? {
+        Some(addr) => addr,
+        None => validate_receiver(deps.api, &env, &receiver)?,
+    };
+    change_receiver(deps, &env, state, receiver, &info.sender)
+}
+
+pub fn try_set_rotation(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    rotation: Vec<RotationEntry>,
+) -> Result<Response, ContractError> {
+    let state = load_config(deps.storage, &env.block)?;
+    if !state.is_owner(&info.sender) {
+        return Err(ContractError::Unauthorized {});
+    }
+    if state.governance.is_some() {
+        return Err(ContractError::Governed {});
+    }
+    let mut after = env.block.height;
+    let mut schedule = Vec::with_capacity(rotation.len());
+    for entry in rotation {
+        if entry.active_from <= after {
+            return Err(ContractError::InvalidRotation {});
+        }
+        after = entry.active_from;
+        let receiver = match ALIASES.may_load(deps.storage, &entry.receiver)? {
+            Some(addr) => addr,
+            None => validate_receiver(deps.api, &env, &entry.receiver)?,
+        };
+        check_attestation(&deps.querier, &state, &receiver)?;
+        schedule.push(ScheduledReceiver {
+            receiver,
+            active_from: entry.active_from,
+            scheduled_by: info.sender.clone(),
+        });
+    }
+    ROTATION.save(deps.storage, &schedule)?;
+    Ok(config_response("set_rotation", &info.sender)
+        .add_attribute("scheduled", schedule.len().to_string()))
+}
+
+// change_receiver proposes receiver, it takes over once the receiver delay passed and it
+// accepted if that is required. With an attestation the receiver must hold its credential
+fn change_receiver(
+    deps: DepsMut,
+    env: &Env,
+    mut state: Config,
+    receiver: Addr,
+    sender: &Addr,
+) -> Result<Response, ContractError> {
+    check_attestation(&deps.querier, &state, &receiver)?;
+    // without a delay the receiver changes right away, unless it has to accept first. The
+    // change record is set as data
+    let ready_at = match state.receiver_delay {
+        Some(delay) => delay.after(&env.block),
+        None if state.require_acceptance => Expiration::AtHeight(env.block.height),
+        None => {
+            let change = record_receiver_change(
+                deps.storage,
+                &env.block,
+                &state.receiver,
+                &receiver,
+                sender,
+            )?;
+            state.receiver = receiver;
+            CONFIG.save(deps.storage, &state)?;
+            PENDING_RECEIVER.remove(deps.storage);
+            return Ok(config_response("reset_receiver", sender)
+                .add_attribute("recipient", state.receiver.as_str())
+                .add_attribute("old", &change.old_receiver)
+                .add_attribute("new", &change.new_receiver)
+                .add_attribute("actor", &change.actor)
+                .set_data(to_json_binary(&change)?));
+        }
+    };
+    let pending = PendingReceiver {
+        receiver,
+        ready_at,
+        awaiting_acceptance: state.require_acceptance,
+        proposer: Some(sender.clone()),
+    };
+    PENDING_RECEIVER.save(deps.storage, &pending)?;
+
+    Ok(Response::new().add_attributes(vec![
+        attr("action", "propose_receiver"),
+        attr("sender", sender.as_str()),
+        attr("recipient", pending.receiver.as_str()),
+        attr("ready_at", ready_at_string(&pending.ready_at)),
+    ]))
+}
+
+// try_set_alias registers name for address, replacing what it pointed to before
+pub fn try_set_alias(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    name: String,
+    address: String,
+) -> Result<Response, ContractError> {
+    check_permission(deps.storage, &info.sender, Permission::ManageAliases)?;
+    let state = CONFIG.load(deps.storage)?;
+    if state.governance.is_some() {
+        return Err(ContractError::Governed {});
+    }
+    if ROUTES.has(deps.storage, &name) {
+        return Err(ContractError::NameTaken { name });
+    }
+    let address = validate_receiver(deps.api, &env, &address)?;
+    check_attestation(&deps.querier, &state, &address)?;
+    ALIASES.save(deps.storage, &name, &address)?;
+    DESTINATIONS.remove(deps.storage, &name);
+    Ok(config_response("set_alias", &info.sender)
+        .add_attribute("name", name)
+        .add_attribute("address", address))
+}
+
+// try_set_destination registers name as a route to destination, replacing what it pointed
+// to before
+pub fn try_set_destination(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    name: String,
+    destination: Destination,
+) -> Result<Response, ContractError> {
+    check_permission(deps.storage, &info.sender, Permission::ManageAliases)?;
+    let state = CONFIG.load(deps.storage)?;
+    if state.governance.is_some() {
+        return Err(ContractError::Governed {});
+    }
+    if ROUTES.has(deps.storage, &name) {
+        return Err(ContractError::NameTaken { name });
+    }
+    // addresses come in unchecked
+    match &destination {
+        Destination::Wallet(addr) | Destination::Contract { addr, .. } => {
+            let addr = validate_receiver(deps.api, &env, addr.as_str())?;
+            check_attestation(&deps.querier, &state, &addr)?;
+        }
+        Destination::Burn | Destination::CommunityPool | Destination::Validator(_) => {}
+    }
+    DESTINATIONS.save(deps.storage, &name, &destination)?;
+    ALIASES.remove(deps.storage, &name);
+    Ok(config_response("set_destination", &info.sender)
+        .add_attribute("name", name)
+        .add_attribute("destination", destination.as_str()))
+}
+
+pub fn try_remove_alias(
+    deps: DepsMut,
+    info: MessageInfo,
+    name: String,
+) -> Result<Response, ContractError> {
+    check_permission(deps.storage, &info.sender, Permission::ManageAliases)?;
+    if !ALIASES.has(deps.storage, &name) && !DESTINATIONS.has(deps.storage, &name) {
+        return Err(ContractError::UnknownAlias { name });
+    }
+    ALIASES.remove(deps.storage, &name);
+    DESTINATIONS.remove(deps.storage, &name);
+    Ok(config_response("remove_alias", &info.sender).add_attribute("name", name))
+}
+
+// try_set_route registers a named route, replacing the route of that name
+pub fn try_set_route(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    name: String,
+    route: RouteSettings,
+) -> Result<Response, ContractError> {
+    let state = CONFIG.load(deps.storage)?;
+    if !state.is_owner(&info.sender) {
+        return Err(ContractError::Unauthorized {});
+    }
+    if state.governance.is_some() {
+        return Err(ContractError::Governed {});
+    }
+    if ALIASES.has(deps.storage, &name) || DESTINATIONS.has(deps.storage, &name) {
+        return Err(ContractError::NameTaken { name });
+    }
+    let fee_collector = match route.fee_collector {
+        Some(addr) => Some(deps.api.addr_validate(&addr)?),
+        None => None,
+    };
+    validate_fee(route.fee_rate, &fee_collector)?;
+    let route = Route {
+        receiver: validate_receiver(deps.api, &env, &route.receiver)?,
+        receivers: validate_receivers(deps.api, &env, route.receivers)?,
+        fee_rate: route.fee_rate,
+        fee_collector,
+        fee_cap: route.fee_cap,
+        accepted_denoms: route.accepted_denoms,
+        funds_policy: route.funds_policy,
+    };
+    check_attestation(&deps.querier, &state, &route.receiver)?;
+    for (receiver, _) in route.receivers.iter() {
+        check_attestation(&deps.querier, &state, receiver)?;
+    }
+    ROUTES.save(deps.storage, &name, &route)?;
+    Ok(config_response("set_route", &info.sender)
+        .add_attribute("name", name)
+        .add_attribute("receiver", route.receiver))
+}
+
+pub fn try_remove_route(
+    deps: DepsMut,
+    info: MessageInfo,
+    name: String,
+) -> Result<Response, ContractError> {
+    let state = CONFIG.load(deps.storage)?;
+    if !state.is_owner(&info.sender) {
+        return Err(ContractError::Unauthorized {});
+    }
+    if !ROUTES.has(deps.storage, &name) {
+        return Err(ContractError::UnknownAlias { name });
+    }
+    ROUTES.remove(deps.storage, &name);
+    PAUSED_ROUTES.remove(deps.storage, &name);
+    Ok(config_response("remove_route", &info.sender).add_attribute("name", name))
+}
+
+pub fn try_set_category(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    name: String,
+    receiver: Option<String>,
+) -> Result<Response, ContractError> {
+    let state = CONFIG.load(deps.storage)?;
+    if !state.is_owner(&info.sender) {
+        return Err(ContractError::Unauthorized {});
+    }
+    if state.governance.is_some() {
+        return Err(ContractError::Governed {});
+    }
+    let receiver = match receiver {
+        Some(addr) => Some(validate_receiver(deps.api, &env, &addr)?),
+        None => None,
+    };
+    if let Some(receiver) = &receiver {
+        check_attestation(&deps.querier, &state, receiver)?;
+    }
+    let mut res = config_response("set_category", &info.sender).add_attribute("name", &name);
+    if let Some(receiver) = &receiver {
+        res = res.add_attribute("receiver", receiver);
+    }
+    CATEGORIES.save(deps.storage, &name, &Category { receiver })?;
+    Ok(res)
+}
+
+pub fn try_remove_category(
+    deps: DepsMut,
+    info: MessageInfo,
+    name: String,
+) -> Result<Response, ContractError> {
+    let state = CONFIG.load(deps.storage)?;
+    if !state.is_owner(&info.sender) {
+        return Err(ContractError::Unauthorized {});
+    }
+    if !CATEGORIES.has(deps.storage, &name) {
+        return Err(ContractError::UnknownCategory { name });
+    }
+    CATEGORIES.remove(deps.storage, &name);
+    Ok(config_response("remove_category", &info.sender).add_attribute("name", name))
+}
+
+pub fn try_set_route_paused(
+    deps: DepsMut,
+    info: MessageInfo,
+    name: String,
+    paused: bool,
+) -> Result<Response, ContractError> {
+    check_permission(deps.storage, &info.sender, Permission::Pause)?;
+    if !ROUTES.has(deps.storage, &name) {
+        return Err(ContractError::UnknownAlias { name });
+    }
+    if paused {
+        PAUSED_ROUTES.save(deps.storage, &name, &Empty {})?;
+    } else {
+        PAUSED_ROUTES.remove(deps.storage, &name);
+    }
+    let action = if paused {
+        "pause_route"
+    } else {
+        "unpause_route"
+    };
+    Ok(config_response(action, &info.sender).add_attribute("name", name))
+}
+
+pub fn try_register_referral(
+    deps: DepsMut,
+    info: MessageInfo,
+    code: String,
+    referrer: String,
+) -> Result<Response, ContractError> {
+    let state = CONFIG.load(deps.storage)?;
+    if !state.is_owner(&info.sender) {
+        return Err(ContractError::Unauthorized {});
+    }
+    // referrers are paid a share of the deposits
+    if state.governance.is_some() {
+        return Err(ContractError::Governed {});
+    }
+    let referrer = deps.api.addr_validate(&referrer)?;
+    // registering a code again only changes its referrer
+    let referral = match REFERRALS.may_load(deps.storage, &code)? {
+        Some(referral) => Referral {
+            referrer,
+            ..referral
+        },
+        None => Referral {
+            referrer,
+            deposits: 0,
+            volume: vec![],
+            commission: vec![],
+        },
+    };
+    REFERRALS.save(deps.storage, &code, &referral)?;
+    Ok(config_response("register_referral", &info.sender)
+        .add_attribute("code", code)
+        .add_attribute("referrer", referral.referrer))
+}
+
+pub fn try_remove_referral(
+    deps: DepsMut,
+    info: MessageInfo,
+    code: String,
+) -> Result<Response, ContractError> {
+    let state = CONFIG.load(deps.storage)?;
+    if !state.is_owner(&info.sender) {
+        return Err(ContractError::Unauthorized {});
+    }
+    if !REFERRALS.has(deps.storage, &code) {
+        return Err(ContractError::UnknownReferral { code });
+    }
+    REFERRALS.remove(deps.storage, &code);
+    Ok(config_response("remove_referral", &info.sender).add_attribute("code", code))
+}
+
+pub fn try_set_referral_rate(
+    deps: DepsMut,
+    info: MessageInfo,
+    referral_rate: Decimal,
+) -> Result<Response, ContractError> {
+    let referral_rate = validate_referral_rate(referral_rate)?;
+    CONFIG.update(deps.storage, |mut state| {
+        if !state.is_owner(&info.sender) {
+            return Err(ContractError::Unauthorized {});
+        }
+        state.referral_rate = referral_rate;
+        Ok(state)
+    })?;
+    Ok(config_response("set_referral_rate", &info.sender))
+}
+
+pub fn try_set_require_acceptance(
+    deps: DepsMut,
+    info: MessageInfo,
+    require_acceptance: bool,
+) -> Result<Response, ContractError> {
+    CONFIG.update(deps.storage, |mut state| {
+        if !state.is_owner(&info.sender) {
+            return Err(ContractError::Unauthorized {});
+        }
+        state.require_acceptance = require_acceptance;
+        Ok(state)
+    })?;
+    Ok(config_response("set_require_acceptance", &info.sender))
+}
+
+// try_accept_receivership lets the proposed receiver take over, right away if the receiver
+// delay already passed
+pub fn try_accept_receivership(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+) -> Result<Response, ContractError> {
+    let mut pending = PENDING_RECEIVER
+        .may_load(deps.storage)?
+        .ok_or(ContractError::Unauthorized {})?;
+    if info.sender != pending.receiver {
+        return Err(ContractError::Unauthorized {});
+    }
+    pending.awaiting_acceptance = false;
+    PENDING_RECEIVER.save(deps.storage, &pending)?;
+    let state = load_config(deps.storage, &env.block)?;
+
+    Ok(Response::new().add_attributes(vec![
+        attr("action", "accept_receivership"),
+        attr("sender", info.sender.as_str()),
+        attr("active", (state.receiver == info.sender).to_string()),
+    ]))
+}
+
+pub fn try_set_governance(
+    deps: DepsMut,
+    info: MessageInfo,
+    governance: Option<GovernanceSettings>,
+) -> Result<Response, ContractError> {
+    let governance = validate_governance(deps.api, governance)?;
+    CONFIG.update(deps.storage, |mut state| {
+        if !state.is_owner(&info.sender) {
+            return Err(ContractError::Unauthorized {});
+        }
+        // the voters decide on their own replacement
+        if state.governance.is_some() {
+            return Err(ContractError::Governed {});
+        }
+        state.governance = governance;
+        Ok(state)
+    })?;
+    Ok(config_response("set_governance", &info.sender))
+}
+
+// try_propose_receiver opens a vote on receiver, the proposer votes yes right away
+pub fn try_propose_receiver(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    receiver: String,
+) -> Result<Response, ContractError> {
+    let state = CONFIG.load(deps.storage)?;
+    let governance = state.governance.ok_or(ContractError::NoGovernance {})?;
+    let weight = governance
+        .weight(&info.sender)
+        .ok_or(ContractError::Unauthorized {})?;
+    let receiver = match ALIASES.may_load(deps.storage, &receiver)? {
+        Some(addr) => addr,
+        None => validate_receiver(deps.api, &env, &receiver)?,
+    };
+
+    let proposal = Proposal {
+        receiver: Some(receiver.clone()),
+        governance: None,
+        proposer: info.sender.clone(),
+        yes: weight,
+        no: 0,
+        ends: governance.voting_period.after(&env.block),
+        executed: false,
+    };
+    let id = open_proposal(deps.storage, &info.sender, &proposal)?;
+
+    Ok(Response::new().add_attributes(vec![
+        attr("action", "propose"),
+        attr("sender", info.sender.as_str()),
+        attr("proposal_id", id.to_string()),
+        attr("recipient", receiver.as_str()),
+        attr("ends", ready_at_string(&proposal.ends)),
+    ]))
+}
+
+// try_propose_governance opens a vote on replacing the governance settings, the proposer
+// votes yes right away
+pub fn try_propose_governance(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    governance: Option<GovernanceSettings>,
+) -> Result<Response, ContractError> {
+    let state = CONFIG.load(deps.storage)?;
+    let current = state.governance.ok_or(ContractError::NoGovernance {})?;
+    let weight = current
+        .weight(&info.sender)
+        .ok_or(ContractError::Unauthorized {})?;
+    let governance = validate_governance(deps.api, governance)?;
+
+    let proposal = Proposal {
+        receiver: None,
+        governance: Some(GovernanceChange { governance }),
+        proposer: info.sender.clone(),
+        yes: weight,
+        no: 0,
+        ends: current.voting_period.after(&env.block),
+        executed: false,
+    };
+    let id = open_proposal(deps.storage, &info.sender, &proposal)?;
+
+    Ok(Response::new().add_attributes(vec![
+        attr("action", "propose_governance"),
+        attr("sender", info.sender.as_str()),
+        attr("proposal_id", id.to_string()),
+        attr("ends", ready_at_string(&proposal.ends)),
+    ]))
+}
+
+fn open_proposal(
+    storage: &mut dyn Storage,
+    proposer: &Addr,
+    proposal: &Proposal,
+) -> StdResult<u64> {
+    let id = PROPOSAL_COUNT.may_load(storage)?.unwrap_or_default() + 1;
+    PROPOSAL_COUNT.save(storage, &id)?;
+    PROPOSALS.save(storage, id, proposal)?;
+    VOTES.save(storage, (id, proposer), &true)?;
+    Ok(id)
+}
+
+// try_vote counts the vote of the sender with their current weight, every voter votes once
+pub fn try_vote(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    proposal_id: u64,
+    approve: bool,
+) -> Result<Response, ContractError> {
+    let state = CONFIG.load(deps.storage)?;
+    let governance = state.governance.ok_or(ContractError::NoGovernance {})?;
+    let weight = governance
+        .weight(&info.sender)
+        .ok_or(ContractError::Unauthorized {})?;
+    let mut proposal = PROPOSALS.load(deps.storage, proposal_id)?;
+    if proposal.executed || proposal.ends.is_expired(&env.block) {
+        return Err(ContractError::VotingClosed { id: proposal_id });
+    }
+    if VOTES.has(deps.storage, (proposal_id, &info.sender)) {
+        return Err(ContractError::AlreadyVoted { id: proposal_id });
+    }
+    VOTES.save(deps.storage, (proposal_id, &info.sender), &approve)?;
+    if approve {
+        proposal.yes += weight;
+    } else {
+        proposal.no += weight;
+    }
+    PROPOSALS.save(deps.storage, proposal_id, &proposal)?;
+
+    Ok(Response::new().add_attributes(vec![
+        attr("action", "vote"),
+        attr("sender", info.sender.as_str()),
+        attr("proposal_id", proposal_id.to_string()),
+        attr("approve", approve.to_string()),
+    ]))
+}
+
+// try_execute_proposal changes the receiver like ResetReceiver does, so the receiver delay
+// and acceptance still apply. Governance proposals replace the governance settings
+pub fn try_execute_proposal(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    proposal_id: u64,
+) -> Result<Response, ContractError> {
+    let state = CONFIG.load(deps.storage)?;
+    let mut proposal = PROPOSALS.load(deps.storage, proposal_id)?;
+    if proposal_status(&proposal, state.governance.as_ref(), &env.block) != ProposalStatus::Passed {
+        return Err(ContractError::ProposalNotPassed { id: proposal_id });
+    }
+    proposal.executed = true;
+    PROPOSALS.save(deps.storage, proposal_id, &proposal)?;
+
+    let res = match (proposal.receiver, proposal.governance) {
+        (Some(receiver), _) => change_receiver(deps, &env, state, receiver, &info.sender)?,
+        (None, Some(change)) => {
+            let state = Config {
+                governance: change.governance,
+                ..state
+            };
+            CONFIG.save(deps.storage, &state)?;
+            config_response("set_governance", &info.sender)
+        }
+        (None, None) => return Err(ContractError::NothingToDo {}),
+    };
+    Ok(res.add_attribute("proposal_id", proposal_id.to_string()))
+}
+
+fn proposal_status(
+    proposal: &Proposal,
+    governance: Option<&GovernanceConfig>,
+    block: &BlockInfo,
+) -> ProposalStatus {
+    if proposal.executed {
+        return ProposalStatus::Executed;
+    }
+    if !proposal.ends.is_expired(block) {
+        return ProposalStatus::Open;
+    }
+    // proposals cannot pass once governance is turned off
+    let governance = match governance {
+        Some(governance) => governance,
+        None => return ProposalStatus::Rejected,
+    };
+    let turnout = Decimal::from_ratio(proposal.yes + proposal.no, governance.total_weight());
+    if turnout >= governance.quorum && proposal.yes > proposal.no {
+        ProposalStatus::Passed
+    } else {
+        ProposalStatus::Rejected
+    }
+}
+
+pub fn try_set_multisig(
+    deps: DepsMut,
+    info: MessageInfo,
+    multisig: Option<MultisigSettings>,
+) -> Result<Response, ContractError> {
+    let multisig = validate_multisig(deps.api, multisig)?;
+    CONFIG.update(deps.storage, |mut state| {
+        if !state.is_owner(&info.sender) {
+            return Err(ContractError::Unauthorized {});
+        }
+        state.multisig = multisig;
+        Ok(state)
+    })?;
+    Ok(config_response("set_multisig", &info.sender))
+}
+
+// try_propose_action records action with the approval of the proposer, it runs right away
+// if that is enough
+pub fn try_propose_action(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    action: AdminAction,
+) -> Result<Response, ContractError> {
+    let state = CONFIG.load(deps.storage)?;
+    let multisig = state.multisig.ok_or(ContractError::NoMultisig {})?;
+    if !multisig.admins.contains(&info.sender) {
+        return Err(ContractError::Unauthorized {});
+    }
+    let id = ADMIN_PROPOSAL_COUNT
+        .may_load(deps.storage)?
+        .unwrap_or_default()
+        + 1;
+    ADMIN_PROPOSAL_COUNT.save(deps.storage, &id)?;
+    let proposal = AdminProposal {
+        action,
+        proposer: info.sender.clone(),
+        approvals: vec![info.sender.clone()],
+        executed: false,
+    };
+    approve_action(deps, env, &info.sender, &multisig, id, proposal)
+}
+
+pub fn try_approve_action(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    id: u64,
+) -> Result<Response, ContractError> {
+    let state = CONFIG.load(deps.storage)?;
+    let multisig = state.multisig.ok_or(ContractError::NoMultisig {})?;
+    if !multisig.admins.contains(&info.sender) {
+        return Err(ContractError::Unauthorized {});
+    }
+    let mut proposal = ADMIN_PROPOSALS.load(deps.storage, id)?;
+    if proposal.executed {
+        return Err(ContractError::AlreadyExecuted { id });
+    }
+    if proposal.approvals.contains(&info.sender) {
+        return Err(ContractError::AlreadyApproved { id });
+    }
+    proposal.approvals.push(info.sender.clone());
+    approve_action(deps, env, &info.sender, &multisig, id, proposal)
+}
+
+// approve_action saves proposal id and runs its action once threshold current admins
+// approved it
+fn approve_action(
+    deps: DepsMut,
+    env: Env,
+    sender: &Addr,
+    multisig: &MultisigConfig,
+    id: u64,
+    mut proposal: AdminProposal,
+) -> Result<Response, ContractError> {
+    let approvals = proposal
+        .approvals
+        .iter()
+        .filter(|admin| multisig.admins.contains(admin))
+        .count() as u64;
+    proposal.executed = approvals >= multisig.threshold;
+    ADMIN_PROPOSALS.save(deps.storage, id, &proposal)?;
+    if !proposal.executed {
+        return Ok(Response::new().add_attributes(vec![
+            attr("action", "approve_action"),
+            attr("sender", sender.as_str()),
+            attr("admin_proposal_id", id.to_string()),
+            attr("approvals", approvals.to_string()),
+        ]));
+    }
+
+    let owner = CONFIG
+        .load(deps.storage)?
+        .owner
+        .ok_or(ContractError::Unauthorized {})?;
+    let info = MessageInfo {
+        sender: owner,
+        funds: vec![],
+    };
+    let res = match proposal.action {
+        AdminAction::ResetReceiver { receiver } => try_reset(deps, env, info, receiver),
+        AdminAction::SetRotation { rotation } => try_set_rotation(deps, env, info, rotation),
+        AdminAction::SetReceivers { receivers } => try_set_receivers(deps, env, info, receivers),
+        AdminAction::SetWaterfall { tiers } => try_set_waterfall(deps, env, info, tiers),
+        AdminAction::SetRoute { name, route } => try_set_route(deps, env, info, name, route),
+        AdminAction::SetCategory { name, receiver } => {
+            try_set_category(deps, env, info, name, receiver)
+        }
+        AdminAction::SetAlias { name, address } => try_set_alias(deps, env, info, name, address),
+        AdminAction::SetDestination { name, destination } => {
+            try_set_destination(deps, env, info, name, destination)
+        }
+        AdminAction::SetDenomRules { denom_rules } => {
+            try_set_denom_rules(deps, env, info, denom_rules)
+        }
+        AdminAction::SetFallbackReceiver { fallback_receiver } => {
+            try_set_fallback_receiver(deps, env, info, fallback_receiver)
+        }
+        AdminAction::SetIbc { ibc } => try_set_ibc(deps, info, ibc),
+        AdminAction::SetFee {
+            fee_rate,
+            fee_collector,
+            fee_cap,
+        } => try_set_fee(deps, info, fee_rate, fee_collector, fee_cap),
+        AdminAction::AnnounceEmergencyWithdraw {} => try_announce_emergency(deps, env, info),
+        AdminAction::EmergencyWithdraw {} => try_emergency_withdraw(deps, env, info),
+        AdminAction::SetMultisig { multisig } => try_set_multisig(deps, info, multisig),
+        AdminAction::RenounceOwnership {} => try_renounce_ownership(deps, info),
+        AdminAction::WindDown {} => try_wind_down(deps, env, info),
+    }?;
+    Ok(res.add_attribute("admin_proposal_id", id.to_string()))
+}
+
+// try_update_permissions grants permissions to address, or revokes them when grant is false.
+// Owner only
+pub fn try_update_permissions(
+    deps: DepsMut,
+    info: MessageInfo,
+    address: String,
+    permissions: Vec<Permission>,
+    grant: bool,
+) -> Result<Response, ContractError> {
+    let state = CONFIG.load(deps.storage)?;
+    if !state.is_owner(&info.sender) {
+        return Err(ContractError::Unauthorized {});
+    }
+    let addr = deps.api.addr_validate(&address)?;
+    let bits = permissions
+        .iter()
+        .fold(0, |bits, permission| bits | permission.bit());
+    let mask = PERMISSIONS
+        .may_load(deps.storage, &addr)?
+        .unwrap_or_default();
+    let mask = if grant { mask | bits } else { mask & !bits };
+    if mask == 0 {
+        PERMISSIONS.remove(deps.storage, &addr);
+    } else {
+        PERMISSIONS.save(deps.storage, &addr, &mask)?;
+    }
+
+    let action = if grant {
+        "grant_permissions"
+    } else {
+        "revoke_permissions"
+    };
+    Ok(config_response(action, &info.sender)
+        .add_attribute("address", address)
+        .add_attribute("permissions", mask.to_string()))
+}
+
+pub fn try_set_receiver_delay(
+    deps: DepsMut,
+    info: MessageInfo,
+    receiver_delay: Option<Duration>,
+) -> Result<Response, ContractError> {
+    CONFIG.update(deps.storage, |mut state| {
+        if !state.is_owner(&info.sender) {
+            return Err(ContractError::Unauthorized {});
+        }
+        state.receiver_delay = receiver_delay;
+        Ok(state)
+    })?;
+    Ok(config_response("set_receiver_delay", &info.sender))
+}
+
+pub fn try_set_receivers(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    receivers: Vec<(String, Decimal)>,
+) -> Result<Response, ContractError> {
+    let mut state = CONFIG.load(deps.storage)?;
+    if !state.is_owner(&info.sender) {
+        return Err(ContractError::Unauthorized {});
+    }
+    if state.governance.is_some() {
+        return Err(ContractError::Governed {});
+    }
+    let receivers = validate_receivers(deps.api, &env, receivers)?;
+    for (receiver, _) in receivers.iter() {
+        check_attestation(&deps.querier, &state, receiver)?;
+    }
+    state.receivers = receivers;
+    CONFIG.save(deps.storage, &state)?;
+    Ok(config_response("set_receivers", &info.sender))
+}
+
+pub fn try_set_waterfall(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    tiers: Vec<(String, Vec<Coin>)>,
+) -> Result<Response, ContractError> {
+    let mut state = CONFIG.load(deps.storage)?;
+    if !state.is_owner(&info.sender) {
+        return Err(ContractError::Unauthorized {});
+    }
+    if state.governance.is_some() {
+        return Err(ContractError::Governed {});
+    }
+    let tiers = validate_waterfall(deps.api, &env, tiers)?;
+    for (receiver, _) in tiers.iter() {
+        check_attestation(&deps.querier, &state, receiver)?;
+    }
+    state.waterfall = tiers;
+    CONFIG.save(deps.storage, &state)?;
+    Ok(config_response("set_waterfall", &info.sender))
+}
+
+pub fn try_reset_waterfall(deps: DepsMut, info: MessageInfo) -> Result<Response, ContractError> {
+    let state = CONFIG.load(deps.storage)?;
+    if !state.is_owner(&info.sender) {
+        return Err(ContractError::Unauthorized {});
+    }
+    WATERFALL_FILLED.clear(deps.storage);
+    Ok(config_response("reset_waterfall", &info.sender))
+}
+
+pub fn try_set_fee(
+    deps: DepsMut,
+    info: MessageInfo,
+    fee_rate: Decimal,
+    fee_collector: Option<String>,
+    fee_cap: Vec<Coin>,
+) -> Result<Response, ContractError> {
+    let fee_collector = match fee_collector {
+        Some(addr) => Some(deps.api.addr_validate(&addr)?),
+        None => None,
+    };
+    validate_fee(fee_rate, &fee_collector)?;
+    CONFIG.update(deps.storage, |mut state| {
+        if !state.is_owner(&info.sender) {
+            return Err(ContractError::Unauthorized {});
+        }
+        // the denom rules charge their fees to the collector as well
+        validate_denom_fees(&state.denom_rules, &fee_collector)?;
+        state.fee_rate = fee_rate;
+        state.fee_collector = fee_collector;
+        state.fee_cap = fee_cap;
+        Ok(state)
+    })?;
+    Ok(config_response("set_fee", &info.sender))
+}
+
+// try_update_config applies the settings of update and validates the result before saving
+// it, so either all of them change or none. Owner only
+pub fn try_update_config(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    update: ConfigUpdate,
+) -> Result<Response, ContractError> {
+    let mut state = CONFIG.load(deps.storage)?;
+    if !state.is_owner(&info.sender) {
+        return Err(ContractError::Unauthorized {});
+    }
+    if let Some(fee_rate) = update.fee_rate {
+        state.fee_rate = fee_rate;
+    }
+    if let Some(fee_collector) = update.fee_collector {
+        state.fee_collector = Some(deps.api.addr_validate(&fee_collector)?);
+    }
+    if let Some(fee_cap) = update.fee_cap {
+        state.fee_cap = fee_cap;
+    }
+    if let Some(min_amount) = update.min_amount {
+        state.min_amount = min_amount;
+    }
+    if let Some(max_amount) = update.max_amount {
+        state.max_amount = max_amount;
+    }
+    if let Some(accepted_denoms) = update.accepted_denoms {
+        state.accepted_denoms = accepted_denoms;
+    }
+    if let Some(funds_policy) = update.funds_policy {
+        state.funds_policy = funds_policy;
+    }
+    if let Some(paused) = update.paused {
+        state.paused = paused;
+    }
+    if let Some(fallback_receiver) = update.fallback_receiver {
+        if state.governance.is_some() {
+            return Err(ContractError::Governed {});
+        }
+        let fallback_receiver = validate_receiver(deps.api, &env, &fallback_receiver)?;
+        check_attestation(&deps.querier, &state, &fallback_receiver)?;
+        state.fallback_receiver = Some(fallback_receiver);
+    }
+    validate_fee(state.fee_rate, &state.fee_collector)?;
+    validate_denom_fees(&state.denom_rules, &state.fee_collector)?;
+    validate_limits(&state.min_amount, &state.max_amount)?;
+    CONFIG.save(deps.storage, &state)?;
+    Ok(config_response("update_config", &info.sender))
+}
+
+pub fn try_set_paused(
+    deps: DepsMut,
+    info: MessageInfo,
+    paused: bool,
+) -> Result<Response, ContractError> {
+    check_permission(deps.storage, &info.sender, Permission::Pause)?;
+    CONFIG.update(deps.storage, |mut state| -> StdResult<_> {
+        state.paused = paused;
+        Ok(state)
+    })?;
+    let action = if paused { "pause" } else { "unpause" };
+    Ok(config_response(action, &info.sender))
+}
+
+pub fn try_set_pull_mode(
+    deps: DepsMut,
+    info: MessageInfo,
+    pull: bool,
+) -> Result<Response, ContractError> {
+    CONFIG.update(deps.storage, |mut state| {
+        if !state.is_owner(&info.sender) {
+            return Err(ContractError::Unauthorized {});
+        }
+        state.pull = pull;
+        Ok(state)
+    })?;
+    Ok(config_response("set_pull_mode", &info.sender))
+}
+
+pub fn try_set_min_amount(
+    deps: DepsMut,
+    info: MessageInfo,
+    min_amount: Vec<Coin>,
+) -> Result<Response, ContractError> {
+    check_permission(deps.storage, &info.sender, Permission::UpdateLimits)?;
+    CONFIG.update(deps.storage, |mut state| -> StdResult<_> {
+        state.min_amount = min_amount;
+        Ok(state)
+    })?;
+    Ok(config_response("set_min_amount", &info.sender))
+}
+
+pub fn try_set_max_amount(
+    deps: DepsMut,
+    info: MessageInfo,
+    max_amount: Vec<Coin>,
+) -> Result<Response, ContractError> {
+    check_permission(deps.storage, &info.sender, Permission::UpdateLimits)?;
+    CONFIG.update(deps.storage, |mut state| -> StdResult<_> {
+        state.max_amount = max_amount;
+        Ok(state)
+    })?;
+    Ok(config_response("set_max_amount", &info.sender))
+}
+
+pub fn try_set_accepted_denoms(
+    deps: DepsMut,
+    info: MessageInfo,
+    accepted_denoms: Vec<String>,
+) -> Result<Response, ContractError> {
+    check_permission(deps.storage, &info.sender, Permission::UpdateDenoms)?;
+    CONFIG.update(deps.storage, |mut state| -> StdResult<_> {
+        state.accepted_denoms = accepted_denoms;
+        Ok(state)
+    })?;
+    Ok(config_response("set_accepted_denoms", &info.sender))
+}
+
+pub fn try_set_funds_policy(
+    deps: DepsMut,
+    info: MessageInfo,
+    funds_policy: FundsPolicy,
+) -> Result<Response, ContractError> {
+    check_permission(deps.storage, &info.sender, Permission::UpdateDenoms)?;
+    CONFIG.update(deps.storage, |mut state| -> StdResult<_> {
+        state.funds_policy = funds_policy;
+        Ok(state)
+    })?;
+    Ok(config_response("set_funds_policy", &info.sender))
+}
+
+pub fn try_set_allowlist(
+    deps: DepsMut,
+    info: MessageInfo,
+    allowlist: Vec<String>,
+) -> Result<Response, ContractError> {
+    let allowlist = validate_addresses(deps.api, allowlist)?;
+    CONFIG.update(deps.storage, |mut state| {
+        if !state.is_owner(&info.sender) {
+            return Err(ContractError::Unauthorized {});
+        }
+        state.allowlist = allowlist;
+        Ok(state)
+    })?;
+    Ok(config_response("set_allowlist", &info.sender))
+}
+
+pub fn try_set_hold(
+    deps: DepsMut,
+    info: MessageInfo,
+    hold: bool,
+    flush_threshold: Vec<Coin>,
+) -> Result<Response, ContractError> {
+    CONFIG.update(deps.storage, |mut state| {
+        if !state.is_owner(&info.sender) {
+            return Err(ContractError::Unauthorized {});
+        }
+        state.hold = hold;
+        state.flush_threshold = flush_threshold;
+        Ok(state)
+    })?;
+    Ok(config_response("set_hold", &info.sender))
+}
+
+pub fn try_set_restrict_senders(
+    deps: DepsMut,
+    info: MessageInfo,
+    restrict_senders: bool,
+) -> Result<Response, ContractError> {
+    check_permission(deps.storage, &info.sender, Permission::UpdateSenders)?;
+    CONFIG.update(deps.storage, |mut state| -> StdResult<_> {
+        state.restrict_senders = restrict_senders;
+        Ok(state)
+    })?;
+    Ok(config_response("set_restrict_senders", &info.sender))
+}
+
+// try_update_sender adds address to the sender allowlist, or removes it when allowed is false
+pub fn try_update_sender(
+    deps: DepsMut,
+    info: MessageInfo,
+    address: String,
+    allowed: bool,
+) -> Result<Response, ContractError> {
+    check_permission(deps.storage, &info.sender, Permission::UpdateSenders)?;
+    let sender = deps.api.addr_validate(&address)?;
+    if allowed {
+        ALLOWED_SENDERS.save(deps.storage, &sender, &Empty {})?;
+    } else {
+        ALLOWED_SENDERS.remove(deps.storage, &sender);
+    }
+
+    let action = if allowed {
+        "add_sender"
+    } else {
+        "remove_sender"
+    };
+    Ok(config_response(action, &info.sender).add_attribute("address", address))
+}
+
+// try_update_blocklist blocks address from depositing, or unblocks it when blocked is false
+pub fn try_update_blocklist(
+    deps: DepsMut,
+    info: MessageInfo,
+    address: String,
+    blocked: bool,
+) -> Result<Response, ContractError> {
+    check_permission(deps.storage, &info.sender, Permission::UpdateSenders)?;
+    let sender = deps.api.addr_validate(&address)?;
+    if blocked {
+        BLOCKED_SENDERS.save(deps.storage, &sender, &Empty {})?;
+    } else {
+        BLOCKED_SENDERS.remove(deps.storage, &sender);
+    }
+
+    let action = if blocked {
+        "block_sender"
+    } else {
+        "unblock_sender"
+    };
+    Ok(config_response(action, &info.sender).add_attribute("address", address))
+}
+
+// try_update_method lets ForwardExec call method, or stops it when allowed is false
+pub fn try_update_method(
+    deps: DepsMut,
+    info: MessageInfo,
+    method: String,
+    allowed: bool,
+) -> Result<Response, ContractError> {
+    let state = CONFIG.load(deps.storage)?;
+    if !state.is_owner(&info.sender) {
+        return Err(ContractError::Unauthorized {});
+    }
+    if allowed {
+        ALLOWED_METHODS.save(deps.storage, &method, &Empty {})?;
+    } else {
+        ALLOWED_METHODS.remove(deps.storage, &method);
+    }
+
+    let action = if allowed {
+        "allow_method"
+    } else {
+        "disallow_method"
+    };
+    Ok(config_response(action, &info.sender).add_attribute("method", method))
+}
+
+// try_set_lifetime_cap limits what address may contribute in total, an empty cap removes it
+pub fn try_set_lifetime_cap(
+    deps: DepsMut,
+    info: MessageInfo,
+    address: String,
+    cap: Vec<Coin>,
+) -> Result<Response, ContractError> {
+    check_permission(deps.storage, &info.sender, Permission::UpdateLimits)?;
+    let sender = deps.api.addr_validate(&address)?;
+    if cap.is_empty() {
+        LIFETIME_CAPS.remove(deps.storage, &sender);
+    } else {
+        LIFETIME_CAPS.save(deps.storage, &sender, &cap)?;
+    }
+    Ok(config_response("set_lifetime_cap", &info.sender)
+        .add_attribute("address", address)
+        .add_attribute("cap", coins_to_string(&cap)))
+}
+
+pub fn try_set_rate_limit(
+    deps: DepsMut,
+    info: MessageInfo,
+    rate_limit: Option<RateLimit>,
+) -> Result<Response, ContractError> {
+    check_permission(deps.storage, &info.sender, Permission::UpdateLimits)?;
+    CONFIG.update(deps.storage, |mut state| -> StdResult<_> {
+        state.rate_limit = rate_limit;
+        Ok(state)
+    })?;
+    Ok(config_response("set_rate_limit", &info.sender))
+}
+
+pub fn try_set_congestion(
+    deps: DepsMut,
+    info: MessageInfo,
+    congestion: Option<Congestion>,
+) -> Result<Response, ContractError> {
+    check_permission(deps.storage, &info.sender, Permission::UpdateLimits)?;
+    let congestion = validate_congestion(congestion)?;
+    CONFIG.update(deps.storage, |mut state| -> StdResult<_> {
+        state.congestion = congestion;
+        Ok(state)
+    })?;
+    Ok(config_response("set_congestion", &info.sender))
+}
+
+pub fn try_withdraw(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    denom: Option<String>,
+    amount: Option<Uint128>,
+) -> Result<Response, ContractError> {
+    let receiver = info.sender;
+    complete_unbonding(deps.storage, &receiver, env.block.time.seconds())?;
+    let mut balance = CLAIMABLE
+        .may_load(deps.storage, &receiver)?
+        .unwrap_or_default();
+
+    let withdrawn = match (denom, amount) {
+        (None, None) => std::mem::take(&mut balance),
+        (None, Some(_)) => return Err(ContractError::WithdrawDenomRequired {}),
+        (Some(denom), amount) => {
+            let available = balance
+                .iter_mut()
+                .find(|coin| coin.denom == denom)
+                .ok_or(ContractError::NothingToWithdraw {})?;
+            let amount = amount.unwrap_or(available.amount);
+            if amount > available.amount {
+                return Err(ContractError::InsufficientClaimable {});
+            }
+            available.amount = available.amount.checked_sub(amount)?;
+            balance.retain(|coin| !coin.amount.is_zero());
+            vec![Coin { denom, amount }]
+        }
+    };
+    if withdrawn.iter().all(|coin| coin.amount.is_zero()) {
+        return Err(ContractError::NothingToWithdraw {});
+    }
+
+    if balance.is_empty() {
+        CLAIMABLE.remove(deps.storage, &receiver);
+        CLAIMED_AT.remove(deps.storage, &receiver);
+    } else {
+        CLAIMABLE.save(deps.storage, &receiver, &balance)?;
+        CLAIMED_AT.save(deps.storage, &receiver, &env.block.time.seconds())?;
+    }
+
+    let mut attrs = vec![
+        attr("action", "withdraw"),
+        attr("sender", receiver.as_str()),
+        attr("recipient", receiver.as_str()),
+        attr("amount", coins_to_string(&withdrawn)),
+    ];
+    let mut withdrawn = withdrawn;
+    if let Some(rebate) = claim_rebate(deps.storage)? {
+        attrs.push(attr("rebate", rebate.to_string()));
+        add_coins(&mut withdrawn, vec![rebate]);
+    }
+    let mut taxes = vec![];
+    let amount = deduct_tax(&deps.querier, withdrawn, &mut taxes)?;
+    if !taxes.is_empty() {
+        attrs.push(attr("tax", coins_to_string(&taxes)));
+    }
+
+    Ok(Response::new()
+        .add_message(BankMsg::Send {
+            to_address: receiver.to_string(),
+            amount,
+        })
+        .add_attributes(attrs))
+}
+
+// claim_rebate takes the rebate for a withdrawal out of the rebate pool, it is capped by
+// what is left in the pool
+fn claim_rebate(storage: &mut dyn Storage) -> StdResult<Option<Coin>> {
+    let limit = match CONFIG.load(storage)?.claim_rebate {
+        Some(limit) => limit,
+        None => return Ok(None),
+    };
+    let mut pool = REBATE_POOL.may_load(storage)?.unwrap_or_default();
+    let available = match pool.iter_mut().find(|coin| coin.denom == limit.denom) {
+        Some(available) => available,
+        None => return Ok(None),
+    };
+    let amount = available.amount.min(limit.amount);
+    if amount.is_zero() {
+        return Ok(None);
+    }
+    available.amount -= amount;
+    pool.retain(|coin| !coin.amount.is_zero());
+    REBATE_POOL.save(storage, &pool)?;
+    Ok(Some(Coin {
+        denom: limit.denom,
+        amount,
+    }))
+}
+
+pub fn try_set_escrow(
+    deps: DepsMut,
+    info: MessageInfo,
+    escrow: Option<Duration>,
+) -> Result<Response, ContractError> {
+    CONFIG.update(deps.storage, |mut state| {
+        if !state.is_owner(&info.sender) {
+            return Err(ContractError::Unauthorized {});
+        }
+        state.escrow = escrow;
+        Ok(state)
+    })?;
+    Ok(config_response("set_escrow", &info.sender))
+}
+
+// try_release forwards escrow id like a regular deposit. The split only applies while the
+// escrow is for the current receiver, otherwise everything goes to the escrow receiver.
+pub fn try_release(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    id: u64,
+    preimage: Option<String>,
+) -> Result<Response, ContractError> {
+    let escrow = escrows().load(deps.storage, id)?;
+    if info.sender != escrow.receiver {
+        return Err(ContractError::Unauthorized {});
+    }
+    if escrow.disputed {
+        return Err(ContractError::EscrowDisputed {});
+    }
+    if let Some(hashlock) = &escrow.hashlock {
+        if escrow.expires.is_expired(&env.block) {
+            return Err(ContractError::HashlockExpired {});
+        }
+        let preimage = preimage
+            .as_deref()
+            .and_then(|preimage| hex::decode(preimage).ok())
+            .ok_or(ContractError::WrongPreimage {})?;
+        if hex::encode(Sha256::digest(preimage)) != *hashlock {
+            return Err(ContractError::WrongPreimage {});
+        }
+    }
+    let mut attrs = vec![
+        attr("action", "release"),
+        attr("sender", info.sender.as_str()),
+        attr("escrow_id", id.to_string()),
+        attr("receipt_id", id.to_string()),
+    ];
+    // the preimage is published so the other side of a swap can claim with it
+    if let (Some(_), Some(preimage)) = (&escrow.hashlock, preimage) {
+        attrs.push(attr("preimage", preimage));
+    }
+    release_escrow(deps, &env, id, escrow, attrs)
+}
+
+// release_escrow forwards escrow id to its receiver, after attrs
+fn release_escrow(
+    mut deps: DepsMut,
+    env: &Env,
+    id: u64,
+    escrow: Escrow,
+    mut attrs: Vec<Attribute>,
+) -> Result<Response, ContractError> {
+    let mut state = load_config(deps.storage, &env.block)?;
+    if escrow.receiver != state.receiver {
+        state.receiver = escrow.receiver.clone();
+        state.receivers = vec![];
+        state.waterfall = vec![];
+    }
+    escrows().remove(deps.storage, id)?;
+
+    let mut taxes = vec![];
+    let (messages, fwd_attrs, recipients, _) =
+        forward(&mut deps, env, &state, escrow.amount, &mut taxes)?;
+    let receipt = settle_receipt(deps.storage, id, recipients, ReceiptStatus::Forwarded)?;
+    attrs.extend(fwd_attrs);
+    if !taxes.is_empty() {
+        attrs.push(attr("tax", coins_to_string(&taxes)));
+    }
+
+    let mut res = Response::new()
+        .add_submessages(messages)
+        .add_attributes(attrs);
+    if let Some(receipt) = receipt {
+        res = res.set_data(to_json_binary(&receipt)?);
+    }
+    Ok(res)
+}
+
+pub fn try_refund(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    id: u64,
+) -> Result<Response, ContractError> {
+    let escrow = escrows().load(deps.storage, id)?;
+    if info.sender != escrow.sender {
+        return Err(ContractError::Unauthorized {});
+    }
+    if escrow.disputed {
+        return Err(ContractError::EscrowDisputed {});
+    }
+    // while the contract winds down escrows can be refunded right away
+    if !escrow.expires.is_expired(&env.block) && !WIND_DOWN.exists(deps.storage) {
+        return Err(ContractError::EscrowNotExpired {});
+    }
+    let attrs = vec![
+        attr("action", "refund"),
+        attr("sender", info.sender.as_str()),
+        attr("escrow_id", id.to_string()),
+        attr("receipt_id", id.to_string()),
+    ];
+    refund_escrow(deps, id, escrow, attrs)
+}
+
+// refund_escrow returns escrow id to its sender, after attrs
+fn refund_escrow(
+    deps: DepsMut,
+    id: u64,
+    escrow: Escrow,
+    mut attrs: Vec<Attribute>,
+) -> Result<Response, ContractError> {
+    escrows().remove(deps.storage, id)?;
+
+    attrs.push(attr("recipient", escrow.sender.as_str()));
+    attrs.push(attr("amount", coins_to_string(&escrow.amount)));
+    let receipt = settle_receipt(deps.storage, id, vec![], ReceiptStatus::Refunded)?;
+    let mut taxes = vec![];
+    let amount = deduct_tax(&deps.querier, escrow.amount, &mut taxes)?;
+    if !taxes.is_empty() {
+        attrs.push(attr("tax", coins_to_string(&taxes)));
+    }
+
+    let mut res = Response::new();
+    if let Some(receipt) = receipt {
+        res = res.set_data(to_json_binary(&receipt)?);
+    }
+    Ok(res
+        .add_message(BankMsg::Send {
+            to_address: escrow.sender.to_string(),
+            amount,
+        })
+        .add_attributes(attrs))
+}
+
+pub fn try_dispute(deps: DepsMut, info: MessageInfo, id: u64) -> Result<Response, ContractError> {
+    let mut escrow = escrows().load(deps.storage, id)?;
+    if info.sender != escrow.sender && info.sender != escrow.receiver {
+        return Err(ContractError::Unauthorized {});
+    }
+    let arbiter = escrow.arbiter.clone().ok_or(ContractError::NoArbiter {})?;
+    if escrow.disputed {
+        return Err(ContractError::EscrowDisputed {});
+    }
+    escrow.disputed = true;
+    escrows().save(deps.storage, id, &escrow)?;
+
+    Ok(Response::new().add_attributes(vec![
+        attr("action", "dispute"),
+        attr("sender", info.sender.as_str()),
+        attr("escrow_id", id.to_string()),
+        attr("arbiter", arbiter.as_str()),
+    ]))
+}
+
+// try_resolve settles a disputed escrow the way its arbiter decided, a release is forwarded
+// with the fee and split like any other
+pub fn try_resolve(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    id: u64,
+    release: bool,
+) -> Result<Response, ContractError> {
+    let escrow = escrows().load(deps.storage, id)?;
+    if escrow.arbiter.as_ref() != Some(&info.sender) {
+        return Err(ContractError::Unauthorized {});
+    }
+    if !escrow.disputed {
+        return Err(ContractError::NotDisputed {});
+    }
+    let attrs = vec![
+        attr("action", "resolve"),
+        attr("sender", info.sender.as_str()),
+        attr("escrow_id", id.to_string()),
+        attr("receipt_id", id.to_string()),
+        attr("resolution", if release { "release" } else { "refund" }),
+    ];
+    if release {
+        release_escrow(deps, &env, id, escrow, attrs)
+    } else {
+        refund_escrow(deps, id, escrow, attrs)
+    }
+}
+
+pub fn try_set_approval_threshold(
+    deps: DepsMut,
+    info: MessageInfo,
+    approval_threshold: Vec<Coin>,
+) -> Result<Response, ContractError> {
+    CONFIG.update(deps.storage, |mut state| {
+        if !state.is_owner(&info.sender) {
+            return Err(ContractError::Unauthorized {});
+        }
+        state.approval_threshold = approval_threshold;
+        Ok(state)
+    })?;
+    Ok(config_response("set_approval_threshold", &info.sender))
+}
+
+// try_approve forwards pending deposit id to the current receivers, or to its one-off
+// recipient
+pub fn try_approve(
+    mut deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    id: u64,
+) -> Result<Response, ContractError> {
+    let mut state = load_config(deps.storage, &env.block)?;
+    check_permission(deps.storage, &info.sender, Permission::ApproveForwards)?;
+    let pending = PENDING_FORWARDS.load(deps.storage, id)?;
+    if let Some(recipient) = pending.recipient {
+        state.receiver = recipient;
+        state.receivers = vec![];
+        state.waterfall = vec![];
+    }
+    PENDING_FORWARDS.remove(deps.storage, id);
+
+    let mut taxes = vec![];
+    let (messages, fwd_attrs, recipients, _) =
+        forward(&mut deps, &env, &state, pending.amount, &mut taxes)?;
+    let receipt = settle_receipt(deps.storage, id, recipients, ReceiptStatus::Forwarded)?;
+    let mut attrs = vec![
+        attr("action", "approve"),
+        attr("sender", info.sender.as_str()),
+        attr("pending_id", id.to_string()),
+        attr("receipt_id", id.to_string()),
+    ];
+    attrs.extend(fwd_attrs);
+    if !taxes.is_empty() {
+        attrs.push(attr("tax", coins_to_string(&taxes)));
+    }
+
+    let mut res = Response::new()
+        .add_submessages(messages)
+        .add_attributes(attrs);
+    if let Some(receipt) = receipt {
+        res = res.set_data(to_json_binary(&receipt)?);
+    }
+    Ok(res)
+}
+
+// try_reject returns pending deposit id to its sender
+pub fn try_reject(deps: DepsMut, info: MessageInfo, id: u64) -> Result<Response, ContractError> {
+    check_permission(deps.storage, &info.sender, Permission::ApproveForwards)?;
+    let pending = PENDING_FORWARDS.load(deps.storage, id)?;
+    PENDING_FORWARDS.remove(deps.storage, id);
+
+    let mut attrs = vec![
+        attr("action", "reject"),
+        attr("sender", info.sender.as_str()),
+        attr("pending_id", id.to_string()),
+        attr("receipt_id", id.to_string()),
+        attr("recipient", pending.sender.as_str()),
+        attr("amount", coins_to_string(&pending.amount)),
+    ];
+    let receipt = settle_receipt(deps.storage, id, vec![], ReceiptStatus::Refunded)?;
+    let mut taxes = vec![];
+    let amount = deduct_tax(&deps.querier, pending.amount, &mut taxes)?;
+    if !taxes.is_empty() {
+        attrs.push(attr("tax", coins_to_string(&taxes)));
+    }
+
+    let mut res = Response::new();
+    if let Some(receipt) = receipt {
+        res = res.set_data(to_json_binary(&receipt)?);
+    }
+    Ok(res
+        .add_message(BankMsg::Send {
+            to_address: pending.sender.to_string(),
+            amount,
+        })
+        .add_attributes(attrs))
+}
+
+pub fn try_set_callbacks(
+    deps: DepsMut,
+    info: MessageInfo,
+    callbacks: Vec<(String, Binary)>,
+) -> Result<Response, ContractError> {
+    let callbacks = validate_callbacks(deps.api, &deps.querier, callbacks)?;
+    CONFIG.update(deps.storage, |mut state| {
+        if !state.is_owner(&info.sender) {
+            return Err(ContractError::Unauthorized {});
+        }
+        if state.governance.is_some() {
+            return Err(ContractError::Governed {});
+        }
+        state.callbacks = callbacks;
+        Ok(state)
+    })?;
+    Ok(config_response("set_callbacks", &info.sender))
+}
+
+// try_set_validator redelegates everything staked with the current validator to validator,
+// or undelegates it to the receiver when staking mode is turned off
+pub fn try_set_validator(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    validator: Option<String>,
+) -> Result<Response, ContractError> {
+    let mut state = load_config(deps.storage, &env.block)?;
+    if !state.is_owner(&info.sender) {
+        return Err(ContractError::Unauthorized {});
+    }
+    let mut messages = vec![];
+    let mut attrs = vec![
+        attr("action", "set_validator"),
+        attr("sender", info.sender.as_str()),
+    ];
+    if let Some(current) = &state.validator {
+        let delegation = deps
+            .querier
+            .query_delegation(&env.contract.address, current)?;
+        if let Some(delegation) = delegation.filter(|d| !d.amount.amount.is_zero()) {
+            match &validator {
+                Some(validator) if validator != current => {
+                    attrs.push(attr("redelegated", delegation.amount.to_string()));
+                    messages.push(StakingMsg::Redelegate {
+                        src_validator: current.clone(),
+                        dst_validator: validator.clone(),
+                        amount: delegation.amount,
+                    });
+                }
+                Some(_) => {}
+                None => {
+                    attrs.push(attr("undelegated", delegation.amount.to_string()));
+                    credit_unbonding(
+                        deps.storage,
+                        &state.receiver,
+                        &delegation.amount,
+                        env.block.time.seconds(),
+                    )?;
+                    messages.push(StakingMsg::Undelegate {
+                        validator: current.clone(),
+                        amount: delegation.amount,
+                    });
+                }
+            }
+        }
+    }
+    if let Some(validator) = &validator {
+        attrs.push(attr("validator", validator));
+    }
+    state.validator = validator;
+    CONFIG.save(deps.storage, &state)?;
+
+    Ok(Response::new().add_messages(messages).add_attributes(attrs))
+}
+
+// credit_claimable adds amount to what receiver can withdraw, an empty balance starts its
+// claim expiry at now
+fn credit_claimable(
+    storage: &mut dyn Storage,
+    receiver: &Addr,
+    amount: Vec<Coin>,
+    now: u64,
+) -> StdResult<()> {
+    CLAIMABLE.update(storage, receiver, |balance| -> StdResult<_> {
+        let mut balance = balance.unwrap_or_default();
+        add_coins(&mut balance, amount);
+        Ok(balance)
+    })?;
+    if !CLAIMED_AT.has(storage, receiver) {
+        CLAIMED_AT.save(storage, receiver, &now)?;
+    }
+    Ok(())
+}
+
+// credit_unbonding lets receiver withdraw amount once it finished unbonding. Until then
+// the funds are not in the contract, crediting them right away would pay them out of what
+// it keeps for others
+fn credit_unbonding(
+    storage: &mut dyn Storage,
+    receiver: &Addr,
+    amount: &Coin,
+    now: u64,
+) -> StdResult<()> {
+    UNBONDING.update(storage, receiver, |unbonding| -> StdResult<_> {
+        let mut unbonding = unbonding.unwrap_or_default();
+        unbonding.push(Unbonding {
+            amount: amount.clone(),
+            completes_at: now + UNBONDING_PERIOD,
+        });
+        Ok(unbonding)
+    })?;
+    Ok(())
+}
+
+// complete_unbonding makes the undelegations of receiver that completed by now claimable
+fn complete_unbonding(storage: &mut dyn Storage, receiver: &Addr, now: u64) -> StdResult<()> {
+    let unbonding = UNBONDING.may_load(storage, receiver)?.unwrap_or_default();
+    let (completed, unbonding): (Vec<_>, Vec<_>) =
+        unbonding.into_iter().partition(|u| u.completes_at <= now);
+    if completed.is_empty() {
+        return Ok(());
+    }
+    if unbonding.is_empty() {
+        UNBONDING.remove(storage, receiver);
+    } else {
+        UNBONDING.save(storage, receiver, &unbonding)?;
+    }
+    let amount = completed.into_iter().map(|u| u.amount).collect();
+    credit_claimable(storage, receiver, amount, now)
+}
+
+pub fn try_undelegate(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    amount: Coin,
+) -> Result<Response, ContractError> {
+    let state = load_config(deps.storage, &env.block)?;
+    if !state.is_owner(&info.sender) {
+        return Err(ContractError::Unauthorized {});
+    }
+    let validator = state.validator.ok_or(ContractError::NoValidator {})?;
+    credit_unbonding(
+        deps.storage,
+        &state.receiver,
+        &amount,
+        env.block.time.seconds(),
+    )?;
+
+    Ok(Response::new()
+        .add_attributes(vec![
+            attr("action", "undelegate"),
+            attr("sender", info.sender.as_str()),
+            attr("validator", validator.as_str()),
+            attr("amount", amount.to_string()),
+            attr("recipient", state.receiver.as_str()),
+        ])
+        .add_message(StakingMsg::Undelegate { validator, amount }))
+}
+
+// try_withdraw_rewards makes the receiver the withdraw address, so the rewards of this and
+// every later delegation are paid to it
+pub fn try_withdraw_rewards(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+) -> Result<Response, ContractError> {
+    check_permission(deps.storage, &info.sender, Permission::Operate)?;
+    let state = load_config(deps.storage, &env.block)?;
+    let validator = state.validator.ok_or(ContractError::NoValidator {})?;
+
+    Ok(Response::new()
+        .add_attributes(vec![
+            attr("action", "withdraw_rewards"),
+            attr("sender", info.sender.as_str()),
+            attr("validator", validator.as_str()),
+            attr("recipient", state.receiver.as_str()),
+        ])
+        .add_message(DistributionMsg::SetWithdrawAddress {
+            address: state.receiver.to_string(),
+        })
+        .add_message(DistributionMsg::WithdrawDelegatorReward { validator }))
+}
+
+// try_forward_rewards withdraws the rewards of all delegations to the contract itself, the
+// withdrawals run before the payouts so the receivers get them in the same transaction
+pub fn try_forward_rewards(
+    mut deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+) -> Result<Response, ContractError> {
+    check_permission(deps.storage, &info.sender, Permission::Operate)?;
+    let state = load_config(deps.storage, &env.block)?;
+    let mut messages = vec![SubMsg::new(DistributionMsg::SetWithdrawAddress {
+        address: env.contract.address.to_string(),
+    })];
+    let mut rewards = vec![];
+    for delegation in deps.querier.query_all_delegations(&env.contract.address)? {
+        let accumulated = deps
+            .querier
+            .query_delegation(&env.contract.address, &delegation.validator)?
+            .map(|delegation| delegation.accumulated_rewards)
+            .unwrap_or_default();
+        let accumulated: Vec<Coin> = accumulated
+            .into_iter()
+            .filter(|coin| !coin.amount.is_zero())
+            .collect();
+        if accumulated.is_empty() {
+            continue;
+        }
+        add_coins(&mut rewards, accumulated);
+        messages.push(SubMsg::new(DistributionMsg::WithdrawDelegatorReward {
+            validator: delegation.validator,
+        }));
+    }
+    if rewards.is_empty() {
+        return Err(ContractError::NoRewards {});
+    }
+
+    let mut taxes = vec![];
+    let (fwd_messages, fwd_attrs, _, _) =
+        forward(&mut deps, &env, &state, rewards.clone(), &mut taxes)?;
+    messages.extend(fwd_messages);
+    let mut attrs = vec![
+        attr("action", "forward_rewards"),
+        attr("sender", info.sender.as_str()),
+        attr("amount", coins_to_string(&rewards)),
+    ];
+    attrs.extend(fwd_attrs);
+    if !taxes.is_empty() {
+        attrs.push(attr("tax", coins_to_string(&taxes)));
+    }
+
+    Ok(Response::new()
+        .add_submessages(messages)
+        .add_attributes(attrs))
+}
+
+pub fn try_set_swap(
+    deps: DepsMut,
+    info: MessageInfo,
+    swap: Option<SwapSettings>,
+) -> Result<Response, ContractError> {
+    let swap = validate_swap(deps.api, swap)?;
+    CONFIG.update(deps.storage, |mut state| {
+        if !state.is_owner(&info.sender) {
+            return Err(ContractError::Unauthorized {});
+        }
+        state.swap = swap;
+        Ok(state)
+    })?;
+    Ok(config_response("set_swap", &info.sender))
+}
+
+pub fn try_set_anchor(
+    deps: DepsMut,
+    info: MessageInfo,
+    anchor: Option<AnchorSettings>,
+) -> Result<Response, ContractError> {
+    let anchor = validate_anchor(deps.api, anchor)?;
+    CONFIG.update(deps.storage, |mut state| {
+        if !state.is_owner(&info.sender) {
+            return Err(ContractError::Unauthorized {});
+        }
+        state.anchor = anchor;
+        Ok(state)
+    })?;
+    Ok(config_response("set_anchor", &info.sender))
+}
+
+pub fn try_set_ibc(
+    deps: DepsMut,
+    info: MessageInfo,
+    ibc: Option<IbcForwarding>,
+) -> Result<Response, ContractError> {
+    let ibc = validate_ibc(ibc)?;
+    CONFIG.update(deps.storage, |mut state| {
+        if !state.is_owner(&info.sender) {
+            return Err(ContractError::Unauthorized {});
+        }
+        if state.governance.is_some() {
+            return Err(ContractError::Governed {});
+        }
+        state.ibc = ibc;
+        Ok(state)
+    })?;
+    Ok(config_response("set_ibc", &info.sender))
+}
+
+pub fn try_set_hold_failed(
+    deps: DepsMut,
+    info: MessageInfo,
+    hold_failed: bool,
+) -> Result<Response, ContractError> {
+    CONFIG.update(deps.storage, |mut state| {
+        if !state.is_owner(&info.sender) {
+            return Err(ContractError::Unauthorized {});
+        }
+        state.hold_failed = hold_failed;
+        Ok(state)
+    })?;
+    Ok(config_response("set_hold_failed", &info.sender))
+}
+
+pub fn try_set_fallback_receiver(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    fallback_receiver: Option<String>,
+) -> Result<Response, ContractError> {
+    let mut state = CONFIG.load(deps.storage)?;
+    if !state.is_owner(&info.sender) {
+        return Err(ContractError::Unauthorized {});
+    }
+    if state.governance.is_some() {
+        return Err(ContractError::Governed {});
+    }
+    state.fallback_receiver = match fallback_receiver {
+        Some(addr) => {
+            let addr = validate_receiver(deps.api, &env, &addr)?;
+            check_attestation(&deps.querier, &state, &addr)?;
+            Some(addr)
+        }
+        None => None,
+    };
+    CONFIG.save(deps.storage, &state)?;
+    Ok(config_response("set_fallback_receiver", &info.sender))
+}
+
+pub fn try_set_recovery_address(
+    deps: DepsMut,
+    info: MessageInfo,
+    recovery_address: Option<String>,
+) -> Result<Response, ContractError> {
+    let recovery_address = match recovery_address {
+        Some(addr) => Some(deps.api.addr_validate(&addr)?),
+        None => None,
+    };
+    CONFIG.update(deps.storage, |mut state| {
+        if !state.is_owner(&info.sender) {
+            return Err(ContractError::Unauthorized {});
+        }
+        state.recovery_address = recovery_address;
+        Ok(state)
+    })?;
+    Ok(config_response("set_recovery_address", &info.sender))
+}
+
+// try_announce_emergency starts the delay of an emergency withdrawal to the current recovery
+// address, announcing again restarts it
+pub fn try_announce_emergency(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+) -> Result<Response, ContractError> {
+    let state = CONFIG.load(deps.storage)?;
+    if !state.is_owner(&info.sender) {
+        return Err(ContractError::Unauthorized {});
+    }
+    let recipient = state
+        .recovery_address
+        .ok_or(ContractError::NoRecoveryAddress {})?;
+    let pending = EmergencyWithdrawal {
+        recipient,
+        execute_after: env.block.height + EMERGENCY_DELAY,
+    };
+    PENDING_EMERGENCY.save(deps.storage, &pending)?;
+
+    Ok(Response::new().add_attributes(vec![
+        attr("action", "announce_emergency_withdraw"),
+        attr("sender", info.sender.as_str()),
+        attr("recipient", pending.recipient.as_str()),
+        attr("execute_after", pending.execute_after.to_string()),
+    ]))
+}
+
+// try_emergency_withdraw moves the whole native balance to the announced recipient and
+// pauses the contract, the bookkeeping of held, claimable or locked funds is left as is
+pub fn try_emergency_withdraw(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+) -> Result<Response, ContractError> {
+    let mut state = CONFIG.load(deps.storage)?;
+    if !state.is_owner(&info.sender) {
+        return Err(ContractError::Unauthorized {});
+    }
+    let pending = PENDING_EMERGENCY
+        .may_load(deps.storage)?
+        .ok_or(ContractError::NoEmergencyWithdrawal {})?;
+    if env.block.height < pending.execute_after {
+        return Err(ContractError::EmergencyNotReady {
+            execute_after: pending.execute_after,
+        });
+    }
+    PENDING_EMERGENCY.remove(deps.storage);
+    state.paused = true;
+    CONFIG.save(deps.storage, &state)?;
+
+    let balance = deps.querier.query_all_balances(&env.contract.address)?;
+    let mut taxes = vec![];
+    let mut attrs = vec![
+        attr("action", "emergency_withdraw"),
+        attr("sender", info.sender.as_str()),
+        attr("recipient", pending.recipient.as_str()),
+        attr("amount", coins_to_string(&balance)),
+    ];
+    let mut res = Response::new();
+    if !balance.is_empty() {
+        res = res.add_message(BankMsg::Send {
+            to_address: pending.recipient.to_string(),
+            amount: deduct_tax(&deps.querier, balance, &mut taxes)?,
+        });
+    }
+    if !taxes.is_empty() {
+        attrs.push(attr("tax", coins_to_string(&taxes)));
+    }
+    Ok(res.add_attributes(attrs))
+}
+
+pub fn try_cancel_emergency(deps: DepsMut, info: MessageInfo) -> Result<Response, ContractError> {
+    let state = CONFIG.load(deps.storage)?;
+    if !state.is_owner(&info.sender) {
+        return Err(ContractError::Unauthorized {});
+    }
+    if !PENDING_EMERGENCY.exists(deps.storage) {
+        return Err(ContractError::NoEmergencyWithdrawal {});
+    }
+    PENDING_EMERGENCY.remove(deps.storage);
+    Ok(config_response("cancel_emergency_withdraw", &info.sender))
+}
+
+// try_renounce_ownership removes the owner. The multisig, the owner pubkey and an announced
+// emergency withdrawal go with it, nothing could run them anymore
+pub fn try_renounce_ownership(deps: DepsMut, info: MessageInfo) -> Result<Response, ContractError> {
+    CONFIG.update(deps.storage, |mut state| {
+        if !state.is_owner(&info.sender) {
+            return Err(ContractError::Unauthorized {});
+        }
+        state.owner = None;
+        state.multisig = None;
+        state.owner_pubkey = None;
+        Ok(state)
+    })?;
+    PENDING_EMERGENCY.remove(deps.storage);
+    Ok(config_response("renounce_ownership", &info.sender))
+}
+
+// expire_ownership removes the owner, its multisig and an announced emergency withdrawal
+// once ownership expired
+fn expire_ownership(storage: &mut dyn Storage, block: &BlockInfo) -> StdResult<()> {
+    let mut state = CONFIG.load(storage)?;
+    match state.ownership_expires_at {
+        Some(expires) if expires.is_expired(block) && state.owner.is_some() => {
+            state.owner = None;
+            state.multisig = None;
+            state.owner_pubkey = None;
+            CONFIG.save(storage, &state)?;
+            PENDING_EMERGENCY.remove(storage);
+            Ok(())
+        }
+        _ => Ok(()),
+    }
+}
+
+pub fn try_set_ownership_expiry(
+    deps: DepsMut,
+    info: MessageInfo,
+    ownership_expires_at: Expiration,
+) -> Result<Response, ContractError> {
+    CONFIG.update(deps.storage, |mut state| {
+        if !state.is_owner(&info.sender) {
+            return Err(ContractError::Unauthorized {});
+        }
+        if state.ownership_expires_at.is_some() {
+            return Err(ContractError::OwnershipExpirySet {});
+        }
+        state.ownership_expires_at = Some(ownership_expires_at);
+        Ok(state)
+    })?;
+    Ok(config_response("set_ownership_expiry", &info.sender)
+        .add_attribute("expires_at", ready_at_string(&ownership_expires_at)))
+}
+
+// try_wind_down stops deposits and starts the claim period. Owner only
+pub fn try_wind_down(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+) -> Result<Response, ContractError> {
+    let state = CONFIG.load(deps.storage)?;
+    if !state.is_owner(&info.sender) {
+        return Err(ContractError::Unauthorized {});
+    }
+    if WIND_DOWN.exists(deps.storage) {
+        return Err(ContractError::WindingDown {});
+    }
+    let wind_down = WindDown {
+        claim_deadline: env.block.height + CLAIM_PERIOD,
+        terminated: false,
+    };
+    WIND_DOWN.save(deps.storage, &wind_down)?;
+    Ok(config_response("wind_down", &info.sender)
+        .add_attribute("claim_deadline", wind_down.claim_deadline.to_string()))
+}
+
+// try_terminate sends the whole native balance to the receiver once the claim period is
+// over and terminates the contract
+pub fn try_terminate(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+) -> Result<Response, ContractError> {
+    let mut wind_down = WIND_DOWN
+        .may_load(deps.storage)?
+        .ok_or(ContractError::NoWindDown {})?;
+    if env.block.height < wind_down.claim_deadline {
+        return Err(ContractError::ClaimPeriodNotOver {
+            claim_deadline: wind_down.claim_deadline,
+        });
+    }
+    wind_down.terminated = true;
+    WIND_DOWN.save(deps.storage, &wind_down)?;
+    let state = load_config(deps.storage, &env.block)?;
+
+    let balance = deps.querier.query_all_balances(&env.contract.address)?;
+    let mut taxes = vec![];
+    let mut attrs = vec![
+        attr("action", "terminate"),
+        attr("sender", info.sender.as_str()),
+        attr("recipient", state.receiver.as_str()),
+        attr("amount", coins_to_string(&balance)),
+    ];
+    let mut res = Response::new();
+    if !balance.is_empty() {
+        res = res.add_message(BankMsg::Send {
+            to_address: state.receiver.to_string(),
+            amount: deduct_tax(&deps.querier, balance, &mut taxes)?,
+        });
+    }
+    if !taxes.is_empty() {
+        attrs.push(attr("tax", coins_to_string(&taxes)));
+    }
+    Ok(res.add_attributes(attrs))
+}
+
+pub fn try_set_max_payouts(
+    deps: DepsMut,
+    info: MessageInfo,
+    max_payouts: Option<u32>,
+) -> Result<Response, ContractError> {
+    CONFIG.update(deps.storage, |mut state| {
+        if !state.is_owner(&info.sender) {
+            return Err(ContractError::Unauthorized {});
+        }
+        state.max_payouts = max_payouts;
+        Ok(state)
+    })?;
+    Ok(config_response("set_max_payouts", &info.sender))
+}
+
+pub fn try_set_claim_rebate(
+    deps: DepsMut,
+    info: MessageInfo,
+    claim_rebate: Option<Coin>,
+) -> Result<Response, ContractError> {
+    CONFIG.update(deps.storage, |mut state| {
+        if !state.is_owner(&info.sender) {
+            return Err(ContractError::Unauthorized {});
+        }
+        state.claim_rebate = claim_rebate;
+        Ok(state)
+    })?;
+    Ok(config_response("set_claim_rebate", &info.sender))
+}
+
+// try_top_up_rebates adds the sent funds to the rebate pool
+pub fn try_top_up_rebates(deps: DepsMut, info: MessageInfo) -> Result<Response, ContractError> {
+    let state = CONFIG.load(deps.storage)?;
+    if !state.is_owner(&info.sender) {
+        return Err(ContractError::Unauthorized {});
+    }
+    let funds: Vec<Coin> = info
+        .funds
+        .into_iter()
+        .filter(|coin| !coin.amount.is_zero())
+        .collect();
+    if funds.is_empty() {
+        return Err(ContractError::NoFundsSent {});
+    }
+    let mut pool = REBATE_POOL.may_load(deps.storage)?.unwrap_or_default();
+    add_coins(&mut pool, funds.clone());
+    REBATE_POOL.save(deps.storage, &pool)?;
+
+    Ok(Response::new().add_attributes(vec![
+        attr("action", "top_up_rebates"),
+        attr("sender", info.sender.as_str()),
+        attr("amount", coins_to_string(&funds)),
+        attr("balance", coins_to_string(&pool)),
+    ]))
+}
+
+pub fn try_set_retention(
+    deps: DepsMut,
+    info: MessageInfo,
+    retention: Option<Retention>,
+) -> Result<Response, ContractError> {
+    CONFIG.update(deps.storage, |mut state| {
+        if !state.is_owner(&info.sender) {
+            return Err(ContractError::Unauthorized {});
+        }
+        state.retention = retention;
+        Ok(state)
+    })?;
+    Ok(config_response("set_retention", &info.sender))
+}
+
+// try_prune removes the transfers up to up_to_id from the history. Ids keep counting from
+// the last transfer recorded
+pub fn try_prune(
+    deps: DepsMut,
+    info: MessageInfo,
+    up_to_id: u64,
+) -> Result<Response, ContractError> {
+    let state = CONFIG.load(deps.storage)?;
+    if !state.is_owner(&info.sender) {
+        return Err(ContractError::Unauthorized {});
+    }
+    let count = TRANSFER_COUNT.may_load(deps.storage)?.unwrap_or_default();
+    let pruned = prune_transfers(deps.storage, up_to_id.min(count), u64::MAX, u64::MAX)?;
+    Ok(config_response("prune", &info.sender).add_attribute("pruned", pruned.to_string()))
+}
+
+// try_distribute pays up to limit queued payouts, starting after the last one paid
+pub fn try_distribute(
+    mut deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    limit: Option<u32>,
+) -> Result<Response, ContractError> {
+    let mut taxes = vec![];
+    let (messages, pay_attrs, paid) = pay_queued(&mut deps, &env, limit, &mut taxes)?;
+    if paid == 0 {
+        return Err(ContractError::NothingToDistribute {});
+    }
+    let mut attrs = vec![
+        attr("action", "distribute"),
+        attr("sender", info.sender.as_str()),
+    ];
+    attrs.extend(pay_attrs);
+    if !taxes.is_empty() {
+        attrs.push(attr("tax", coins_to_string(&taxes)));
+    }
+
+    Ok(Response::new()
+        .add_submessages(messages)
+        .add_attributes(attrs))
+}
+
+// pay_queued pays up to limit queued payouts and returns how many it paid
+fn pay_queued(
+    deps: &mut DepsMut,
+    env: &Env,
+    limit: Option<u32>,
+    taxes: &mut Vec<Coin>,
+) -> Result<(Vec<SubMsg>, Vec<Attribute>, u32), ContractError> {
+    let state = load_config(deps.storage, &env.block)?;
+    let limit = limit.unwrap_or(DEFAULT_LIMIT) as usize;
+    let cursor = PAYOUT_QUEUE_CURSOR
+        .may_load(deps.storage)?
+        .unwrap_or_default();
+    let queued = PAYOUT_QUEUE
+        .range(
+            deps.storage,
+            Some(Bound::exclusive(cursor)),
+            None,
+            Order::Ascending,
+        )
+        .take(limit)
+        .collect::<StdResult<Vec<_>>>()?;
+    if queued.is_empty() {
+        return Ok((vec![], vec![], 0));
+    }
+
+    let mut messages = vec![];
+    let mut attrs = vec![];
+    let paid = queued.len() as u32;
+    for (id, (receiver, amount)) in queued {
+        PAYOUT_QUEUE.remove(deps.storage, id);
+        PAYOUT_QUEUE_CURSOR.save(deps.storage, &id)?;
+        attrs.push(attr("recipient", receiver.as_str()));
+        messages.extend(send_payout(
+            deps.storage,
+            &deps.querier,
+            env,
+            &state,
+            &receiver,
+            amount,
+            taxes,
+        )?);
+    }
+    let cursor = PAYOUT_QUEUE_CURSOR.load(deps.storage)?;
+    let count = PAYOUT_QUEUE_COUNT.load(deps.storage)?;
+    attrs.push(attr("remaining", (count - cursor).to_string()));
+
+    Ok((messages, attrs, paid))
+}
+
+// try_retry_failed sends failed payout id again, if it fails again it is held again
+pub fn try_retry_failed(deps: DepsMut, id: u64) -> Result<Response, ContractError> {
+    let mut payout = FAILED_PAYOUTS.load(deps.storage, id)?;
+    FAILED_PAYOUTS.remove(deps.storage, id);
+    payout.error = None;
+    PAYOUTS.save(deps.storage, id, &payout)?;
+
+    Ok(Response::new()
+        .add_submessage(SubMsg::reply_always(payout.msg, PAYOUT_REPLY_ID + id))
+        .add_attributes(vec![
+            attr("action", "retry_failed"),
+            attr("payout_id", id.to_string()),
+            attr("recipient", payout.recipient.as_str()),
+        ]))
+}
+
+pub fn try_set_refund_failed(
+    deps: DepsMut,
+    info: MessageInfo,
+    refund_failed: bool,
+) -> Result<Response, ContractError> {
+    CONFIG.update(deps.storage, |mut state| {
+        if !state.is_owner(&info.sender) {
+            return Err(ContractError::Unauthorized {});
+        }
+        state.refund_failed = refund_failed;
+        Ok(state)
+    })?;
+    Ok(config_response("set_refund_failed", &info.sender))
+}
+
+pub fn try_set_denom_rules(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    denom_rules: Vec<DenomRuleSettings>,
+) -> Result<Response, ContractError> {
+    let mut state = CONFIG.load(deps.storage)?;
+    if !state.is_owner(&info.sender) {
+        return Err(ContractError::Unauthorized {});
+    }
+    if state.governance.is_some() {
+        return Err(ContractError::Governed {});
+    }
+    state.denom_rules = validate_denom_rules(deps.api, &env, &state.fee_collector, denom_rules)?;
+    for receiver in state
+        .denom_rules
+        .iter()
+        .filter_map(|rule| rule.receiver.as_ref())
+    {
+        check_attestation(&deps.querier, &state, receiver)?;
+    }
+    CONFIG.save(deps.storage, &state)?;
+    Ok(config_response("set_denom_rules", &info.sender))
+}
+
+pub fn try_set_attestation(
+    deps: DepsMut,
+    info: MessageInfo,
+    attestation: Option<AttestationSettings>,
+) -> Result<Response, ContractError> {
+    let attestation = validate_attestation(deps.api, attestation)?;
+    CONFIG.update(deps.storage, |mut state| {
+        if !state.is_owner(&info.sender) {
+            return Err(ContractError::Unauthorized {});
+        }
+        state.attestation = attestation;
+        Ok(state)
+    })?;
+    Ok(config_response("set_attestation", &info.sender))
+}
+
+pub fn try_set_owner_pubkey(
+    deps: DepsMut,
+    info: MessageInfo,
+    owner_pubkey: Option<Binary>,
+) -> Result<Response, ContractError> {
+    CONFIG.update(deps.storage, |mut state| {
+        if !state.is_owner(&info.sender) {
+            return Err(ContractError::Unauthorized {});
+        }
+        state.owner_pubkey = owner_pubkey;
+        Ok(state)
+    })?;
+    Ok(config_response("set_owner_pubkey", &info.sender))
+}
+
+// try_exec_permit resets the receiver as the owner signed it. Each nonce can be used once, so
+// a permit cannot be replayed
+pub fn try_exec_permit(deps: DepsMut, env: Env, permit: Permit) -> Result<Response, ContractError> {
+    let state = load_config(deps.storage, &env.block)?;
+    let (owner, pubkey) = match (state.owner, state.owner_pubkey) {
+        (Some(owner), Some(pubkey)) => (owner, pubkey),
+        _ => return Err(ContractError::NoOwnerPubkey {}),
+    };
+    let params = permit.params;
+    if params.contract != env.contract.address.as_str() || params.chain_id != env.block.chain_id {
+        return Err(ContractError::PermitMismatch {});
+    }
+    if params.expires.is_expired(&env.block) {
+        return Err(ContractError::PermitExpired {});
+    }
+    let nonce = PERMIT_NONCE.may_load(deps.storage)?.unwrap_or_default();
+    if params.nonce != nonce {
+        return Err(ContractError::InvalidNonce { expected: nonce });
+    }
+    let hash = Sha256::digest(to_json_vec(&params)?);
+    let valid = deps
+        .api
+        .secp256k1_verify(&hash, &permit.signature, &pubkey)
+        .unwrap_or(false);
+    if !valid {
+        return Err(ContractError::InvalidSignature {});
+    }
+    PERMIT_NONCE.save(deps.storage, &(nonce + 1))?;
+
+    let info = MessageInfo {
+        sender: owner,
+        funds: vec![],
+    };
+    let res = try_reset(deps, env, info, params.receiver)?;
+    Ok(res.add_attribute("permit_nonce", nonce.to_string()))
+}
+
+pub fn try_set_claim_expiry(
+    deps: DepsMut,
+    info: MessageInfo,
+    claim_expiry: Option<u64>,
+) -> Result<Response, ContractError> {
+    CONFIG.update(deps.storage, |mut state| {
+        if !state.is_owner(&info.sender) {
+            return Err(ContractError::Unauthorized {});
+        }
+        state.claim_expiry = claim_expiry;
+        Ok(state)
+    })?;
+    Ok(config_response("set_claim_expiry", &info.sender))
+}
+
+// try_redirect_stale sends a pull mode balance that went unclaimed past the claim expiry to
+// the fallback receiver, so an abandoned receiver does not strand it
+pub fn try_redirect_stale(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    receiver: String,
+) -> Result<Response, ContractError> {
+    let state = load_config(deps.storage, &env.block)?;
+    let receiver = deps.api.addr_validate(&receiver)?;
+    let claimed_at = CLAIMED_AT.may_load(deps.storage, &receiver)?;
+    let expired = match (state.claim_expiry, claimed_at) {
+        (Some(expiry), Some(claimed_at)) => env.block.time.seconds() >= claimed_at + expiry,
+        _ => false,
+    };
+    if !expired {
+        return Err(ContractError::ClaimNotExpired {});
+    }
+    let fallback_receiver = state
+        .fallback_receiver
+        .ok_or(ContractError::NoFallbackReceiver {})?;
+    let balance = CLAIMABLE
+        .may_load(deps.storage, &receiver)?
+        .unwrap_or_default();
+    if balance.is_empty() {
+        return Err(ContractError::NothingToWithdraw {});
+    }
+    CLAIMABLE.remove(deps.storage, &receiver);
+    CLAIMED_AT.remove(deps.storage, &receiver);
+
+    let mut attrs = vec![
+        attr("action", "redirect_stale"),
+        attr("sender", info.sender.as_str()),
+        attr("receiver", receiver.as_str()),
+        attr("recipient", fallback_receiver.as_str()),
+        attr("amount", coins_to_string(&balance)),
+    ];
+    let mut taxes = vec![];
+    let amount = deduct_tax(&deps.querier, balance, &mut taxes)?;
+    if !taxes.is_empty() {
+        attrs.push(attr("tax", coins_to_string(&taxes)));
+    }
+
+    Ok(Response::new()
+        .add_message(BankMsg::Send {
+            to_address: fallback_receiver.to_string(),
+            amount,
+        })
+        .add_attributes(attrs))
+}
+
+// try_claim_refund pays the sender what its failed payouts owe it
+pub fn try_claim_refund(deps: DepsMut, info: MessageInfo) -> Result<Response, ContractError> {
+    let owed = REFUNDS
+        .may_load(deps.storage, &info.sender)?
+        .ok_or(ContractError::NothingToClaim {})?;
+    REFUNDS.remove(deps.storage, &info.sender);
+
+    let mut attrs = vec![
+        attr("action", "claim_refund"),
+        attr("sender", info.sender.as_str()),
+        attr("amount", coins_to_string(&owed)),
+    ];
+    let mut taxes = vec![];
+    let amount = deduct_tax(&deps.querier, owed, &mut taxes)?;
+    if !taxes.is_empty() {
+        attrs.push(attr("tax", coins_to_string(&taxes)));
+    }
+    Ok(Response::new()
+        .add_message(BankMsg::Send {
+            to_address: info.sender.to_string(),
+            amount,
+        })
+        .add_attributes(attrs))
+}
+
+pub fn try_set_stream_duration(
+    deps: DepsMut,
+    info: MessageInfo,
+    stream_duration: Option<u64>,
+) -> Result<Response, ContractError> {
+    CONFIG.update(deps.storage, |mut state| {
+        if !state.is_owner(&info.sender) {
+            return Err(ContractError::Unauthorized {});
+        }
+        state.stream_duration = stream_duration;
+        Ok(state)
+    })?;
+    Ok(config_response("set_stream_duration", &info.sender))
+}
+
+// try_claim forwards what vested in every stream since the last claim. Streams are removed
+// once they fully vested, so only the deposits of the last stream_duration are visited.
+pub fn try_claim(mut deps: DepsMut, env: Env) -> Result<Response, ContractError> {
+    let state = load_config(deps.storage, &env.block)?;
+    let now = env.block.time.seconds();
+    let streams: Vec<(u64, Stream)> = STREAMS
+        .range(deps.storage, None, None, Order::Ascending)
+        .collect::<StdResult<_>>()?;
+
+    let mut claimed = vec![];
+    for (id, mut stream) in streams {
+        let vested = stream.vested(now);
+        let claimable = remaining(&vested, &stream.claimed)
+            .into_iter()
+            .filter(|coin| !coin.amount.is_zero())
+            .collect();
+        add_coins(&mut claimed, claimable);
+        if now >= stream.end {
+            STREAMS.remove(deps.storage, id);
+        } else {
+            stream.claimed = vested;
+            STREAMS.save(deps.storage, id, &stream)?;
+        }
+    }
+    if claimed.is_empty() {
+        return Err(ContractError::NothingToClaim {});
+    }
+
+    let mut taxes = vec![];
+    let (messages, fwd_attrs, _, _) =
+        forward(&mut deps, &env, &state, claimed.clone(), &mut taxes)?;
+    let mut attrs = vec![
+        attr("action", "claim"),
+        attr("claimed", coins_to_string(&claimed)),
+    ];
+    attrs.extend(fwd_attrs);
+    if !taxes.is_empty() {
+        attrs.push(attr("tax", coins_to_string(&taxes)));
+    }
+
+    Ok(Response::new()
+        .add_submessages(messages)
+        .add_attributes(attrs))
+}
+
+// try_revoke ends a revocable stream. What vested stays claimable by the receivers, the rest
+// goes back to the sender of the stream
+pub fn try_revoke(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    id: u64,
+) -> Result<Response, ContractError> {
+    let state = CONFIG.load(deps.storage)?;
+    if !state.is_owner(&info.sender) {
+        return Err(ContractError::Unauthorized {});
+    }
+    let mut stream = STREAMS.load(deps.storage, id)?;
+    if !stream.revocable {
+        return Err(ContractError::Irrevocable {});
+    }
+
+    let now = env.block.time.seconds();
+    let vested = stream.vested(now);
+    let unvested: Vec<Coin> = remaining(&stream.amount, &vested)
+        .into_iter()
+        .filter(|coin| !coin.amount.is_zero())
+        .collect();
+    if unvested.is_empty() {
+        return Err(ContractError::Irrevocable {});
+    }
+    // the stream ends now with what has vested so far
+    stream.amount = vested;
+    stream.cliff = stream.cliff.min(now);
+    stream.end = now;
+    stream.revocable = false;
+    STREAMS.save(deps.storage, id, &stream)?;
+
+    let mut taxes = vec![];
+    let amount = deduct_tax(&deps.querier, unvested.clone(), &mut taxes)?;
+    let mut attrs = vec![
+        attr("action", "revoke"),
+        attr("sender", info.sender.as_str()),
+        attr("stream_id", id.to_string()),
+        attr("recipient", stream.sender.as_str()),
+        attr("amount", coins_to_string(&unvested)),
+    ];
+    if !taxes.is_empty() {
+        attrs.push(attr("tax", coins_to_string(&taxes)));
+    }
+
+    Ok(Response::new()
+        .add_message(BankMsg::Send {
+            to_address: stream.sender.to_string(),
+            amount,
+        })
+        .add_attributes(attrs))
+}
+
+// subscription_funds returns how much of denom was sent, other denoms are rejected
+fn subscription_funds(funds: &[Coin], denom: &str) -> Result<Uint128, ContractError> {
+    let mut total = Uint128::zero();
+    for coin in funds {
+        if coin.denom != denom {
+            return Err(ContractError::UnsupportedDenom {
+                denom: coin.denom.clone(),
+            });
+        }
+        total += coin.amount;
+    }
+    Ok(total)
+}
+
+pub fn try_create_invoice(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    payer: String,
+    amount: Coin,
+    expires: Expiration,
+    overpayment: Overpayment,
+) -> Result<Response, ContractError> {
+    let state = CONFIG.load(deps.storage)?;
+    if !state.is_owner(&info.sender) {
+        return Err(ContractError::Unauthorized {});
+    }
+    if amount.amount.is_zero() || expires.is_expired(&env.block) {
+        return Err(ContractError::InvalidInvoice {});
+    }
+    let payer = deps.api.addr_validate(&payer)?;
+
+    let id = INVOICE_COUNT.may_load(deps.storage)?.unwrap_or_default() + 1;
+    INVOICE_COUNT.save(deps.storage, &id)?;
+    let invoice = Invoice {
+        payer,
+        amount,
+        expires,
+        paid: None,
+        received: Uint128::zero(),
+        overpayment,
+    };
+    invoices().save(deps.storage, id, &invoice)?;
+
+    Ok(Response::new().add_attributes(vec![
+        attr("action", "create_invoice"),
+        attr("sender", info.sender.as_str()),
+        attr("invoice_id", id.to_string()),
+        attr("payer", invoice.payer.as_str()),
+        attr("amount", invoice.amount.to_string()),
+    ]))
+}
+
+// try_pay_invoice forwards the payment like a deposit of the payer, skipping hold, escrow
+// and the other modes. The payment that settles the invoice is recorded on it
+pub fn try_pay_invoice(
+    mut deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    id: u64,
+) -> Result<Response, ContractError> {
+    let state = load_config(deps.storage, &env.block)?;
+    if state.paused {
+        return Err(ContractError::Paused {});
+    }
+    let mut invoice = invoices().load(deps.storage, id)?;
+    if info.sender != invoice.payer {
+        return Err(ContractError::Unauthorized {});
+    }
+    if invoice.paid.is_some() {
+        return Err(ContractError::InvoicePaid { id });
+    }
+    if invoice.expires.is_expired(&env.block) {
+        return Err(ContractError::InvoiceExpired { id });
+    }
+    let sent = match info.funds.as_slice() {
+        [coin] if coin.denom == invoice.amount.denom && !coin.amount.is_zero() => coin.amount,
+        _ => {
+            return Err(ContractError::InvoiceDenomMismatch {
+                denom: invoice.amount.denom,
+            })
+        }
+    };
+    let remaining = invoice.remaining();
+    let excess = sent.saturating_sub(remaining);
+    let forwarded = match invoice.overpayment {
+        Overpayment::Reject if !excess.is_zero() => {
+            return Err(ContractError::InvoiceOverpaid {
+                remaining: Coin {
+                    denom: invoice.amount.denom,
+                    amount: remaining,
+                }
+                .to_string(),
+            })
+        }
+        Overpayment::RefundExcess => sent - excess,
+        _ => sent,
+    };
+    invoice.received += sent - excess;
+    let forwarded = vec![Coin {
+        denom: invoice.amount.denom.clone(),
+        amount: forwarded,
+    }];
+    record_contribution(deps.storage, &info.sender, &forwarded)?;
+
+    let mut taxes = vec![];
+    let (mut messages, fwd_attrs, recipients, _) =
+        forward(&mut deps, &env, &state, forwarded.clone(), &mut taxes)?;
+    let mut refund = vec![];
+    if invoice.overpayment == Overpayment::RefundExcess && !excess.is_zero() {
+        refund.push(Coin {
+            denom: invoice.amount.denom.clone(),
+            amount: excess,
+        });
+        messages.push(SubMsg::new(BankMsg::Send {
+            to_address: info.sender.to_string(),
+            amount: deduct_tax(&deps.querier, refund.clone(), &mut taxes)?,
+        }));
+    }
+    let transfer = Transfer {
+        sender: info.sender,
+        amount: forwarded,
+        recipients,
+        height: env.block.height,
+        memo: None,
+        status: ReceiptStatus::Forwarded,
+        minted: None,
+        category: None,
+        items: vec![],
+    };
+    let receipt_id = record_transfer(deps.storage, &transfer)?;
+    if invoice.remaining().is_zero() {
+        invoice.paid = Some(receipt_id);
+    }
+    invoices().save(deps.storage, id, &invoice)?;
+
+    let mut attrs = vec![
+        attr("action", "pay_invoice"),
+        attr("sender", transfer.sender.as_str()),
+        attr("invoice_id", id.to_string()),
+        attr("amount", coins_to_string(&transfer.amount)),
+        attr(
+            "remaining",
+            Coin {
+                denom: invoice.amount.denom.clone(),
+                amount: invoice.remaining(),
+            }
+            .to_string(),
+        ),
+    ];
+    attrs.extend(fwd_attrs);
+    if !refund.is_empty() {
+        attrs.push(attr("refund", coins_to_string(&refund)));
+    }
+    attrs.push(attr("receipt_id", receipt_id.to_string()));
+    if !taxes.is_empty() {
+        attrs.push(attr("tax", coins_to_string(&taxes)));
+    }
+
+    Ok(Response::new()
+        .add_submessages(messages)
+        .add_attributes(attrs)
+        .set_data(to_json_binary(&send_receipt(receipt_id, &transfer))?))
+}
+
+pub fn try_subscribe(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    amount: Coin,
+    interval: u64,
+) -> Result<Response, ContractError> {
+    let state = load_config(deps.storage, &env.block)?;
+    if state.paused {
+        return Err(ContractError::Paused {});
+    }
+    check_sender(deps.storage, &state, &info.sender)?;
+    if amount.amount.is_zero() || interval == 0 {
+        return Err(ContractError::InvalidSubscription {});
+    }
+    if !is_accepted(&state, &amount.denom) {
+        return Err(ContractError::UnsupportedDenom {
+            denom: amount.denom,
+        });
+    }
+    let balance = subscription_funds(&info.funds, &amount.denom)?;
+    if balance < amount.amount {
+        return Err(ContractError::SubscriptionUnderfunded {
+            denom: amount.denom,
+        });
+    }
+
+    let id = SUBSCRIPTION_COUNT
+        .may_load(deps.storage)?
+        .unwrap_or_default()
+        + 1;
+    SUBSCRIPTION_COUNT.save(deps.storage, &id)?;
+    let subscription = Subscription {
+        sender: info.sender,
+        amount,
+        interval,
+        next_charge: env.block.time.seconds(),
+        balance,
+        token: None,
+        delinquent: false,
+    };
+    subscriptions().save(deps.storage, id, &subscription)?;
+
+    Ok(Response::new().add_attributes(vec![
+        attr("action", "subscribe"),
+        attr("sender", subscription.sender.as_str()),
+        attr("subscription_id", id.to_string()),
+        attr("balance", balance),
+    ]))
+}
+
+pub fn try_subscribe_cw20(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    token: String,
+    amount: Uint128,
+    interval: u64,
+) -> Result<Response, ContractError> {
+    let state = load_config(deps.storage, &env.block)?;
+    if state.paused {
+        return Err(ContractError::Paused {});
+    }
+    check_sender(deps.storage, &state, &info.sender)?;
+    if amount.is_zero() || interval == 0 {
+        return Err(ContractError::InvalidSubscription {});
+    }
+    let token = deps.api.addr_validate(&token)?;
+    if !is_accepted(&state, token.as_str()) {
+        return Err(ContractError::UnsupportedDenom {
+            denom: token.to_string(),
+        });
+    }
+
+    let id = SUBSCRIPTION_COUNT
+        .may_load(deps.storage)?
+        .unwrap_or_default()
+        + 1;
+    SUBSCRIPTION_COUNT.save(deps.storage, &id)?;
+    let subscription = Subscription {
+        sender: info.sender,
+        amount: Coin {
+            denom: token.to_string(),
+            amount,
+        },
+        interval,
+        next_charge: env.block.time.seconds(),
+        balance: Uint128::zero(),
+        token: Some(token),
+        delinquent: false,
+    };
+    subscriptions().save(deps.storage, id, &subscription)?;
+
+    Ok(Response::new().add_attributes(vec![
+        attr("action", "subscribe_cw20"),
+        attr("sender", subscription.sender.as_str()),
+        attr("subscription_id", id.to_string()),
+        attr("denom", subscription.amount.denom),
+    ]))
+}
+
+// cw20_payable returns how much of token the contract can pull from owner right now, the
+// lower of the allowance and the balance. A token that cannot be queried pays nothing
+fn cw20_payable(deps: Deps, env: &Env, token: &Addr, owner: &Addr) -> Uint128 {
+    let allowance: StdResult<cw20::AllowanceResponse> = deps.querier.query_wasm_smart(
+        token,
+        &Cw20QueryMsg::Allowance {
+            owner: owner.to_string(),
+            spender: env.contract.address.to_string(),
+        },
+    );
+    let allowance = match allowance {
+        Ok(allowance) if !allowance.expires.is_expired(&env.block) => allowance.allowance,
+        _ => return Uint128::zero(),
+    };
+    let balance: StdResult<cw20::BalanceResponse> = deps.querier.query_wasm_smart(
+        token,
+        &Cw20QueryMsg::Balance {
+            address: owner.to_string(),
+        },
+    );
+    balance.map_or(Uint128::zero(), |balance| balance.balance.min(allowance))
+}
+
+pub fn try_fund_subscription(
+    deps: DepsMut,
+    info: MessageInfo,
+    id: u64,
+) -> Result<Response, ContractError> {
+    let mut subscription = subscriptions().load(deps.storage, id)?;
+    let amount = subscription_funds(&info.funds, &subscription.amount.denom)?;
+    if amount.is_zero() {
+        return Err(ContractError::NoFundsSent {});
+    }
+    subscription.balance += amount;
+    subscriptions().save(deps.storage, id, &subscription)?;
+
+    Ok(Response::new().add_attributes(vec![
+        attr("action", "fund_subscription"),
+        attr("sender", info.sender.as_str()),
+        attr("subscription_id", id.to_string()),
+        attr("amount", amount),
+        attr("balance", subscription.balance),
+    ]))
+}
+
+pub fn try_cancel_subscription(
+    deps: DepsMut,
+    info: MessageInfo,
+    id: u64,
+) -> Result<Response, ContractError> {
+    let subscription = subscriptions().load(deps.storage, id)?;
+    if info.sender != subscription.sender {
+        return Err(ContractError::Unauthorized {});
+    }
+    subscriptions().remove(deps.storage, id)?;
+    // a cw20 subscription holds no balance
+    if subscription.token.is_some() {
+        return Ok(Response::new().add_attributes(vec![
+            attr("action", "cancel_subscription"),
+            attr("sender", info.sender.as_str()),
+            attr("subscription_id", id.to_string()),
+        ]));
+    }
+
+    let refund = Coin {
+        denom: subscription.amount.denom,
+        amount: subscription.balance,
+    };
+    let mut attrs = vec![
+        attr("action", "cancel_subscription"),
+        attr("sender", info.sender.as_str()),
+        attr("subscription_id", id.to_string()),
+        attr("recipient", subscription.sender.as_str()),
+        attr("amount", refund.to_string()),
+    ];
+    let mut taxes = vec![];
+    let amount = deduct_tax(&deps.querier, vec![refund], &mut taxes)?;
+    if !taxes.is_empty() {
+        attrs.push(attr("tax", coins_to_string(&taxes)));
+    }
+
+    Ok(Response::new()
+        .add_message(BankMsg::Send {
+            to_address: subscription.sender.to_string(),
+            amount,
+        })
+        .add_attributes(attrs))
+}
+
+pub fn try_process_subscriptions(
+    mut deps: DepsMut,
+    env: Env,
+    limit: Option<u32>,
+) -> Result<Response, ContractError> {
+    let mut taxes = vec![];
+    let (messages, charge_attrs, _) = charge_subscriptions(&mut deps, &env, limit, &mut taxes)?;
+    let mut attrs = vec![attr("action", "process_subscriptions")];
+    attrs.extend(charge_attrs);
+    if !taxes.is_empty() {
+        attrs.push(attr("tax", coins_to_string(&taxes)));
+    }
+
+    Ok(Response::new()
+        .add_submessages(messages)
+        .add_attributes(attrs))
+}
+
+// charge_subscriptions charges every due subscription all installments it missed, as far
+// as its balance allows, and forwards them like a deposit of its sender. cw20 installments
+// are forwarded by reply_charge once they arrived. It returns how many subscriptions it
+// charged or marked delinquent, nothing is charged while the contract is paused
+fn charge_subscriptions(
+    deps: &mut DepsMut,
+    env: &Env,
+    limit: Option<u32>,
+    taxes: &mut Vec<Coin>,
+) -> Result<(Vec<SubMsg>, Vec<Attribute>, u32), ContractError> {
+    let state = load_config(deps.storage, &env.block)?;
+    if state.paused {
+        return Ok((vec![], vec![], 0));
+    }
+    let now = env.block.time.seconds();
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let due: Vec<(u64, Subscription)> = subscriptions()
+        .idx
+        .next_charge
+        .range(
+            deps.storage,
+            None,
+            Some(Bound::inclusive((now, u64::MAX))),
+            Order::Ascending,
+        )
+        .take(limit)
+        .collect::<StdResult<_>>()?;
+
+    let mut messages = vec![];
+    let mut attrs = vec![];
+    let mut processed = 0;
+    // what this batch already pulls of each token from each sender, the allowance and balance
+    // only go down once the charges ran
+    let mut pulled: Vec<(Addr, Addr, Uint128)> = vec![];
+    for (id, mut subscription) in due {
+        processed += 1;
+        let installment = subscription.amount.amount;
+        let missed = (now - subscription.next_charge) / subscription.interval + 1;
+        let payable = match &subscription.token {
+            Some(token) => {
+                let queued = pulled
+                    .iter()
+                    .filter(|(t, sender, _)| t == token && *sender == subscription.sender)
+                    .map(|(_, _, amount)| *amount)
+                    .sum::<Uint128>();
+                cw20_payable(deps.as_ref(), env, token, &subscription.sender).saturating_sub(queued)
+            }
+            None => subscription.balance,
+        };
+        let count = std::cmp::min(missed as u128, payable.u128() / installment.u128());
+        // only a cw20 subscription can come up short, its allowance or balance ran out. The
+        // installments it missed are skipped
+        if count == 0 {
+            subscription.delinquent = true;
+            subscription.next_charge += missed * subscription.interval;
+            subscriptions().save(deps.storage, id, &subscription)?;
+            attrs.push(attr("subscription_delinquent", id.to_string()));
+            continue;
+        }
+        let charged = Coin {
+            denom: subscription.amount.denom.clone(),
+            amount: installment.checked_mul(Uint128::new(count))?,
+        };
+        subscription.next_charge += count as u64 * subscription.interval;
+        subscription.delinquent = false;
+
+        // the tokens are forwarded once they arrived, a failed charge does not fail the batch
+        if let Some(token) = &subscription.token {
+            let msg = WasmMsg::Execute {
+                contract_addr: token.to_string(),
+                msg: to_json_binary(&Cw20ExecuteMsg::TransferFrom {
+                    owner: subscription.sender.to_string(),
+                    recipient: env.contract.address.to_string(),
+                    amount: charged.amount,
+                })?,
+                funds: vec![],
+            };
+            messages.push(SubMsg::reply_always(msg, CHARGE_REPLY_ID + id));
+            CHARGES.save(deps.storage, id, &charged.amount)?;
+            pulled.push((token.clone(), subscription.sender.clone(), charged.amount));
+            subscriptions().save(deps.storage, id, &subscription)?;
+            attrs.push(attr("subscription_id", id.to_string()));
+            attrs.push(attr("charged", charged.to_string()));
+            continue;
+        }
+        subscription.balance = subscription.balance.checked_sub(charged.amount)?;
+        let (fwd_messages, fwd_attrs, recipients, _) =
+            forward(deps, env, &state, vec![charged.clone()], taxes)?;
+        messages.extend(fwd_messages);
+        let transfer = Transfer {
+            sender: subscription.sender.clone(),
+            amount: vec![charged],
+            recipients,
+            height: env.block.height,
+            memo: None,
+            status: ReceiptStatus::Forwarded,
+            minted: None,
+            category: None,
+            items: vec![],
+        };
+        let transfer_id = record_transfer(deps.storage, &transfer)?;
+        attrs.push(attr("subscription_id", id.to_string()));
+        attrs.extend(fwd_attrs);
+        attrs.push(attr("receipt_id", transfer_id.to_string()));
+
+        if subscription.balance >= installment {
+            subscriptions().save(deps.storage, id, &subscription)?;
+            continue;
+        }
+        // the balance cannot pay another installment, the subscription ends here
+        subscriptions().remove(deps.storage, id)?;
+        attrs.push(attr("subscription_ended", id.to_string()));
+        if !subscription.balance.is_zero() {
+            let refund = Coin {
+                denom: subscription.amount.denom,
+                amount: subscription.balance,
+            };
+            attrs.push(attr(
+                "refund",
+                coins_to_string(std::slice::from_ref(&refund)),
+            ));
+            messages.push(SubMsg::new(BankMsg::Send {
+                to_address: subscription.sender.to_string(),
+                amount: deduct_tax(&deps.querier, vec![refund], taxes)?,
+            }));
+        }
+    }
+    Ok((messages, attrs, processed))
+}
+
+pub fn try_process_due(
+    mut deps: DepsMut,
+    env: Env,
+    limit: Option<u32>,
+) -> Result<Response, ContractError> {
+    let mut taxes = vec![];
+    let (mut messages, charge_attrs, subscriptions_charged) =
+        charge_subscriptions(&mut deps, &env, limit, &mut taxes)?;
+    let (pay_messages, pay_attrs, payouts_sent) = pay_queued(&mut deps, &env, limit, &mut taxes)?;
+    if subscriptions_charged == 0 && payouts_sent == 0 {
+        return Err(ContractError::NothingToDo {});
+    }
+    messages.extend(pay_messages);
+    let mut attrs = vec![attr("action", "process_due")];
+    attrs.extend(charge_attrs);
+    attrs.extend(pay_attrs);
+    if !taxes.is_empty() {
+        attrs.push(attr("tax", coins_to_string(&taxes)));
+    }
+    let work = WorkDone {
+        subscriptions_charged,
+        payouts_sent,
+        flushed: vec![],
+    };
+
+    Ok(Response::new()
+        .add_submessages(messages)
+        .add_attributes(attrs)
+        .set_data(to_json_binary(&work)?))
+}
+
+// try_flush_if_above_threshold forwards what hold would forward if a deposit came in now,
+// e.g. after the flush threshold was lowered
+pub fn try_flush_if_above_threshold(
+    mut deps: DepsMut,
+    env: Env,
+) -> Result<Response, ContractError> {
+    let state = load_config(deps.storage, &env.block)?;
+    let (flushed, held) = plan_hold(deps.storage, &state, vec![])?;
+    if flushed.is_empty() {
+        return Err(ContractError::NothingToDo {});
+    }
+    HELD.save(deps.storage, &held)?;
+
+    let mut taxes = vec![];
+    let (messages, fwd_attrs, recipients, _) =
+        forward(&mut deps, &env, &state, flushed.clone(), &mut taxes)?;
+    settle_held(deps.storage, &held, &recipients)?;
+    let mut attrs = vec![
+        attr("action", "flush_if_above_threshold"),
+        attr("amount", coins_to_string(&flushed)),
+    ];
+    attrs.extend(fwd_attrs);
+    if !taxes.is_empty() {
+        attrs.push(attr("tax", coins_to_string(&taxes)));
+    }
+    let work = WorkDone {
+        flushed,
+        ..Default::default()
+    };
+
+    Ok(Response::new()
+        .add_submessages(messages)
+        .add_attributes(attrs)
+        .set_data(to_json_binary(&work)?))
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> StdResult<Binary> {
+    match msg {
+        QueryMsg::GetReceiver {} => to_json_binary(&query_receiver(deps)?),
+        QueryMsg::ReceiverStatus {} => to_json_binary(&query_receiver_status(deps)?),
+        QueryMsg::ListAllowedSenders { start_after, limit } => {
+            to_json_binary(&query_allowed_senders(deps, start_after, limit)?)
+        }
+        QueryMsg::ResolveAlias { name } => to_json_binary(&query_alias(deps, name)?),
+        QueryMsg::ListAliases { start_after, limit } => {
+            to_json_binary(&query_aliases(deps, start_after, limit)?)
+        }
+        QueryMsg::ListDestinations { start_after, limit } => {
+            to_json_binary(&query_destinations(deps, start_after, limit)?)
+        }
+        QueryMsg::ListRoutes { start_after, limit } => {
+            to_json_binary(&query_routes(deps, start_after, limit)?)
+        }
+        QueryMsg::RouteStats { name } => to_json_binary(&query_route_stats(deps, name)?),
+        QueryMsg::ListCategories { start_after, limit } => {
+            to_json_binary(&query_categories(deps, start_after, limit)?)
+        }
+        QueryMsg::Referral { code } => to_json_binary(&query_referral(deps, code)?),
+        QueryMsg::ListReferrals { start_after, limit } => {
+            to_json_binary(&query_referrals(deps, start_after, limit)?)
+        }
+        QueryMsg::ListAllowedMethods { start_after, limit } => {
+            to_json_binary(&query_allowed_methods(deps, start_after, limit)?)
+        }
+        QueryMsg::ListBlockedSenders { start_after, limit } => {
+            to_json_binary(&query_blocked_senders(deps, start_after, limit)?)
+        }
+        QueryMsg::GetQuota { address } => to_json_binary(&query_quota(deps, address)?),
+        QueryMsg::EffectiveMinimum {} => to_json_binary(&query_effective_minimum(deps, env)?),
+        QueryMsg::Reconciliation {} => to_json_binary(&query_reconciliation(deps, env)?),
+        QueryMsg::Rotation {} => to_json_binary(&query_rotation(deps, env)?),
+        QueryMsg::GetPendingReceiver {} => to_json_binary(&query_pending_receiver(deps)?),
+        QueryMsg::Proposal { proposal_id } => {
+            to_json_binary(&query_proposal(deps, env, proposal_id)?)
+        }
+        QueryMsg::ListProposals { start_after, limit } => {
+            to_json_binary(&query_proposals(deps, env, start_after, limit)?)
+        }
+        QueryMsg::AdminProposal { id } => to_json_binary(&query_admin_proposal(deps, id)?),
+        QueryMsg::ListAdminProposals { start_after, limit } => {
+            to_json_binary(&query_admin_proposals(deps, start_after, limit)?)
+        }
+        QueryMsg::PendingEmergency {} => to_json_binary(&query_pending_emergency(deps)?),
+        QueryMsg::WindDown {} => {
+            let wind_down = WIND_DOWN.may_load(deps.storage)?;
+            to_json_binary(&WindDownResponse {
+                claim_deadline: wind_down.as_ref().map(|wind_down| wind_down.claim_deadline),
+                terminated: wind_down.is_some_and(|wind_down| wind_down.terminated),
+            })
+        }
+        QueryMsg::Ownership {} => to_json_binary(&query_ownership(deps, env)?),
+        QueryMsg::GetConfig {} => to_json_binary(&query_config(deps)?),
+        QueryMsg::Permissions { address } => to_json_binary(&query_permissions(deps, address)?),
+        QueryMsg::GetPaused {} => to_json_binary(&query_paused(deps)?),
+        QueryMsg::GetClaimable { address } => to_json_binary(&query_claimable(deps, address)?),
+        QueryMsg::TransferHistory { start_after, limit } => {
+            to_json_binary(&query_transfer_history(deps, start_after, limit)?)
+        }
+        QueryMsg::TransfersByMemo {
+            memo,
+            start_after,
+            limit,
+        } => to_json_binary(&query_transfers_by_memo(deps, memo, start_after, limit)?),
+        QueryMsg::ReceiptsBySender {
+            sender,
+            start_after,
+            limit,
+        } => to_json_binary(&query_receipts_by_sender(deps, sender, start_after, limit)?),
+        QueryMsg::TransfersBySender {
+            addr,
+            start_after,
+            limit,
+        } => {
+            let addr = deps.api.addr_validate(&addr)?;
+            to_json_binary(&query_indexed_transfers(
+                deps,
+                SENDER_TRANSFERS,
+                &addr,
+                start_after,
+                limit,
+            )?)
+        }
+        QueryMsg::TransfersByReceiver {
+            addr,
+            start_after,
+            limit,
+        } => {
+            let addr = deps.api.addr_validate(&addr)?;
+            to_json_binary(&query_indexed_transfers(
+                deps,
+                RECEIVER_TRANSFERS,
+                &addr,
+                start_after,
+                limit,
+            )?)
+        }
+        QueryMsg::ReceiverHistory { start_after, limit } => {
+            to_json_binary(&query_receiver_history(deps, start_after, limit)?)
+        }
+        QueryMsg::Receipt { id } => to_json_binary(&query_receipt(deps, id)?),
+        QueryMsg::Escrow { id } => to_json_binary(&query_escrow(deps, id)?),
+        QueryMsg::EscrowsBySender {
+            sender,
+            start_after,
+            limit,
+        } => to_json_binary(&query_escrows(
+            deps,
+            &escrows().idx.sender,
+            sender,
+            start_after,
+            limit,
+        )?),
+        QueryMsg::EscrowsByReceiver {
+            receiver,
+            start_after,
+            limit,
+        } => to_json_binary(&query_escrows(
+            deps,
+            &escrows().idx.receiver,
+            receiver,
+            start_after,
+            limit,
+        )?),
+        QueryMsg::Invoice { id } => to_json_binary(&query_invoice(deps, env, id)?),
+        QueryMsg::InvoicesByPayer {
+            payer,
+            status,
+            start_after,
+            limit,
+        } => to_json_binary(&query_invoices_by_payer(
+            deps,
+            env,
+            payer,
+            status,
+            start_after,
+            limit,
+        )?),
+        QueryMsg::GetStream {} => to_json_binary(&query_stream(deps, env)?),
+        QueryMsg::Stream { id } => to_json_binary(&query_stream_info(deps, env, id)?),
+        QueryMsg::Streams { start_after, limit } => {
+            to_json_binary(&query_streams(deps, env, start_after, limit)?)
+        }
+        QueryMsg::Subscription { id } => to_json_binary(&query_subscription(deps, id)?),
+        QueryMsg::UpcomingCharges { limit } => {
+            to_json_binary(&query_upcoming_charges(deps, limit)?)
+        }
+        QueryMsg::PendingForwards { start_after, limit } => {
+            to_json_binary(&query_pending_forwards(deps, start_after, limit)?)
+        }
+        QueryMsg::PayoutQueue { start_after, limit } => {
+            to_json_binary(&query_payout_queue(deps, start_after, limit)?)
+        }
+        QueryMsg::Refunds { start_after, limit } => {
+            to_json_binary(&query_refunds(deps, start_after, limit)?)
+        }
+        QueryMsg::FailedPayouts { start_after, limit } => {
+            to_json_binary(&query_failed_payouts(deps, start_after, limit)?)
+        }
+        QueryMsg::Stats {} => to_json_binary(&query_stats(deps)?),
+        QueryMsg::StatsAt { height } => to_json_binary(&query_stats_at(deps, height)?),
+        QueryMsg::Waterfall {} => to_json_binary(&query_waterfall(deps)?),
+        QueryMsg::Campaign {} => to_json_binary(&query_campaign(deps, env)?),
+        QueryMsg::CampaignDeposits { start_after, limit } => {
+            to_json_binary(&query_campaign_deposits(deps, start_after, limit)?)
+        }
+        QueryMsg::SimulateSend { amount, sender } => {
+            to_json_binary(&query_simulate_send(deps, env, amount, sender)?)
+        }
+        QueryMsg::PreviewSplit { amount } => to_json_binary(&query_preview_split(deps, amount)?),
+        QueryMsg::Dust {} => to_json_binary(&DustResponse {
+            dust: DUST.may_load(deps.storage)?.unwrap_or_default(),
+        }),
+        QueryMsg::RebatePool {} => to_json_binary(&RebatePoolResponse {
+            balance: REBATE_POOL.may_load(deps.storage)?.unwrap_or_default(),
+            claim_rebate: CONFIG.load(deps.storage)?.claim_rebate,
+        }),
+        QueryMsg::Contributions { sender } => to_json_binary(&query_contributions(deps, sender)?),
+        QueryMsg::TopContributors { denom, limit } => {
+            to_json_binary(&query_top_contributors(deps, denom, limit)?)
+        }
+        QueryMsg::Allowance { sender } => to_json_binary(&query_allowance(deps, sender)?),
+        QueryMsg::PermitNonce {} => to_json_binary(&PermitNonceResponse {
+            nonce: PERMIT_NONCE.may_load(deps.storage)?.unwrap_or_default(),
+        }),
+    }
+}
+
+fn query_permissions(deps: Deps, address: String) -> StdResult<PermissionsResponse> {
+    let addr = deps.api.addr_validate(&address)?;
+    let mask = PERMISSIONS
+        .may_load(deps.storage, &addr)?
+        .unwrap_or_default();
+    Ok(PermissionsResponse {
+        address,
+        permissions: Permission::ALL
+            .iter()
+            .copied()
+            .filter(|permission| mask & permission.bit() != 0)
+            .collect(),
+    })
+}
+
+fn query_receiver(deps: Deps) -> StdResult<ReceiverResponse> {
+    let state = CONFIG.load(deps.storage)?;
+    Ok(ReceiverResponse {
+        receiver: state.receiver.to_string(),
+    })
+}
+
+// query_rotation shows the receivers as they stand at the current height, the config only
+// catches up on the next execute
+fn query_rotation(deps: Deps, env: Env) -> StdResult<RotationResponse> {
+    let state = CONFIG.load(deps.storage)?;
+    let rotation = ROTATION.may_load(deps.storage)?.unwrap_or_default();
+    let (due, upcoming): (Vec<_>, Vec<_>) = rotation
+        .into_iter()
+        .partition(|entry| entry.active_from <= env.block.height);
+    let active = due.last().map_or(&state.receiver, |entry| &entry.receiver);
+    Ok(RotationResponse {
+        active: active.to_string(),
+        upcoming: upcoming
+            .into_iter()
+            .map(|entry| RotationEntry {
+                receiver: entry.receiver.to_string(),
+                active_from: entry.active_from,
+            })
+            .collect(),
+    })
+}
+
+fn query_receiver_status(deps: Deps) -> StdResult<ReceiverStatusResponse> {
+    let state = CONFIG.load(deps.storage)?;
+    let balances = deps.querier.query_all_balances(&state.receiver)?;
+    // the query fails for addresses without a contract
+    let contract = deps
+        .querier
+        .query_wasm_contract_info(state.receiver.as_str())
+        .ok()
+        .map(|info| ReceiverContractInfo {
+            code_id: info.code_id,
+            creator: info.creator,
+            admin: info.admin,
+        });
+    Ok(ReceiverStatusResponse {
+        receiver: state.receiver.to_string(),
+        balances,
+        contract,
+    })
+}
+
+fn query_quota(deps: Deps, address: String) -> StdResult<QuotaResponse> {
+    let state = CONFIG.load(deps.storage)?;
+    let limit = match state.rate_limit {
+        Some(limit) => limit,
+        None => {
+            return Ok(QuotaResponse {
+                sender_remaining: vec![],
+                sender_resets_at: None,
+                daily_remaining: vec![],
+                daily_resets_at: None,
+            })
+        }
+    };
+    let sender = deps.api.addr_validate(&address)?;
+    let usage = SENDER_USAGE
+        .may_load(deps.storage, &sender)?
+        .unwrap_or_default();
+    let daily = DAILY_USAGE.may_load(deps.storage)?.unwrap_or_default();
+    Ok(QuotaResponse {
+        sender_remaining: remaining(&limit.per_sender, &usage.used),
+        sender_resets_at: Some(usage.window_start + limit.window),
+        daily_remaining: remaining(&limit.daily_cap, &daily.used),
+        daily_resets_at: Some(daily.window_start + DAY),
+    })
+}
+
+fn query_effective_minimum(deps: Deps, env: Env) -> StdResult<EffectiveMinimumResponse> {
+    let state = CONFIG.load(deps.storage)?;
+    let time = env.block.time.seconds();
+    let (multiplier, forwards, resets_at) = match &state.congestion {
+        Some(congestion) => {
+            let traffic = current_traffic(deps.storage, congestion, time)?;
+            (
+                congestion.multiplier(traffic.forwards),
+                traffic.forwards,
+                Some(traffic.window_start + congestion.window),
+            )
+        }
+        None => (Decimal::one(), 0, None),
+    };
+    Ok(EffectiveMinimumResponse {
+        min_amount: state
+            .min_amount
+            .into_iter()
+            .map(|min| Coin {
+                denom: min.denom,
+                amount: min.amount * multiplier,
+            })
+            .collect(),
+        multiplier,
+        forwards,
+        resets_at,
+    })
+}
+
+fn query_reconciliation(deps: Deps, env: Env) -> StdResult<ReconciliationResponse> {
+    let balances = deps.querier.query_all_balances(&env.contract.address)?;
+    let ledgers = ledgers(deps.storage, env.block.time.seconds())?;
+    let mut liabilities = vec![];
+    for (_, amount) in ledgers.iter() {
+        add_coins(&mut liabilities, amount.clone());
+    }
+    let amount_of = |coins: &[Coin], denom: &str| {
+        coins
+            .iter()
+            .find(|coin| coin.denom == denom)
+            .map(|coin| coin.amount)
+            .unwrap_or_default()
+    };
+    let mut denoms: Vec<String> = balances
+        .iter()
+        .chain(liabilities.iter())
+        .map(|coin| coin.denom.clone())
+        .collect();
+    denoms.sort();
+    denoms.dedup();
+    Ok(ReconciliationResponse {
+        ledgers: ledgers
+            .into_iter()
+            .map(|(name, total)| LedgerInfo {
+                name: name.to_string(),
+                total,
+            })
+            .collect(),
+        denoms: denoms
+            .into_iter()
+            .map(|denom| {
+                let balance = amount_of(&balances, &denom);
+                let owed = amount_of(&liabilities, &denom);
+                DenomReconciliation {
+                    surplus: balance.saturating_sub(owed),
+                    deficit: owed.saturating_sub(balance),
+                    denom,
+                    balance,
+                    liabilities: owed,
+                }
+            })
+            .collect(),
+    })
+}
+
+fn query_pending_receiver(deps: Deps) -> StdResult<PendingReceiverResponse> {
+    match PENDING_RECEIVER.may_load(deps.storage)? {
+        Some(pending) => Ok(PendingReceiverResponse {
+            receiver: Some(pending.receiver.to_string()),
+            ready_at: Some(pending.ready_at),
+            awaiting_acceptance: pending.awaiting_acceptance,
+        }),
+        None => Ok(PendingReceiverResponse {
+            receiver: None,
+            ready_at: None,
+            awaiting_acceptance: false,
+        }),
+    }
+}
+
+fn query_waterfall(deps: Deps) -> StdResult<WaterfallResponse> {
+    let state = CONFIG.load(deps.storage)?;
+    let tiers = state
+        .waterfall
+        .into_iter()
+        .map(|(receiver, cap)| {
+            let filled = cap
+                .iter()
+                .map(|cap| {
+                    let amount = WATERFALL_FILLED
+                        .may_load(deps.storage, (&receiver, cap.denom.as_str()))?
+                        .unwrap_or_default();
+                    Ok(Coin {
+                        denom: cap.denom.clone(),
+                        amount,
+                    })
+                })
+                .collect::<StdResult<_>>()?;
+            Ok(WaterfallTier {
+                receiver: receiver.into_string(),
+                cap,
+                filled,
+            })
+        })
+        .collect::<StdResult<_>>()?;
+    Ok(WaterfallResponse { tiers })
+}
+
+fn query_proposal(deps: Deps, env: Env, proposal_id: u64) -> StdResult<ProposalInfo> {
+    let state = CONFIG.load(deps.storage)?;
+    let proposal = PROPOSALS.load(deps.storage, proposal_id)?;
+    Ok(proposal_info(
+        proposal_id,
+        proposal,
+        state.governance.as_ref(),
+        &env.block,
+    ))
+}
+
+fn query_proposals(
+    deps: Deps,
+    env: Env,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> StdResult<ProposalsResponse> {
+    let state = CONFIG.load(deps.storage)?;
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.map(Bound::exclusive);
+
+    let proposals = PROPOSALS
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|item| {
+            let (id, proposal) = item?;
+            Ok(proposal_info(
+                id,
+                proposal,
+                state.governance.as_ref(),
+                &env.block,
+            ))
+        })
+        .collect::<StdResult<_>>()?;
+    Ok(ProposalsResponse { proposals })
+}
+
+fn proposal_info(
+    id: u64,
+    proposal: Proposal,
+    governance: Option<&GovernanceConfig>,
+    block: &BlockInfo,
+) -> ProposalInfo {
+    ProposalInfo {
+        id,
+        status: proposal_status(&proposal, governance, block),
+        receiver: proposal.receiver.map(String::from),
+        governance: proposal.governance,
+        proposer: proposal.proposer.to_string(),
+        yes: proposal.yes,
+        no: proposal.no,
+        ends: proposal.ends,
+    }
+}
+
+fn query_admin_proposal(deps: Deps, id: u64) -> StdResult<AdminProposalInfo> {
+    let proposal = ADMIN_PROPOSALS.load(deps.storage, id)?;
+    Ok(admin_proposal_info(id, proposal))
+}
+
+fn query_admin_proposals(
+    deps: Deps,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> StdResult<AdminProposalsResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.map(Bound::exclusive);
+
+    let proposals = ADMIN_PROPOSALS
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|item| {
+            let (id, proposal) = item?;
+            Ok(admin_proposal_info(id, proposal))
+        })
+        .collect::<StdResult<_>>()?;
+    Ok(AdminProposalsResponse { proposals })
+}
+
+fn admin_proposal_info(id: u64, proposal: AdminProposal) -> AdminProposalInfo {
+    AdminProposalInfo {
+        id,
+        action: proposal.action,
+        proposer: proposal.proposer.to_string(),
+        approvals: proposal
+            .approvals
+            .into_iter()
+            .map(|addr| addr.to_string())
+            .collect(),
+        executed: proposal.executed,
+    }
+}
+
+fn query_campaign(deps: Deps, env: Env) -> StdResult<CampaignResponse> {
+    let state = CONFIG.load(deps.storage)?;
+    let progress = CAMPAIGN_PROGRESS
+        .may_load(deps.storage)?
+        .unwrap_or_default();
+    let status = state
+        .campaign
+        .as_ref()
+        .map(|campaign| campaign_status(campaign, &progress, &env.block));
+    Ok(CampaignResponse {
+        campaign: state.campaign,
+        raised: progress.raised,
+        status,
+    })
+}
+
+fn query_campaign_deposits(
+    deps: Deps,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<CampaignDepositsResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start_after = match start_after {
+        Some(addr) => Some(deps.api.addr_validate(&addr)?),
+        None => None,
+    };
+    let start = start_after.as_ref().map(Bound::exclusive);
+
+    let deposits = CAMPAIGN_DEPOSITS
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|item| {
+            let (sender, amount) = item?;
+            Ok(CampaignDeposit {
+                sender: sender.to_string(),
+                amount,
+            })
+        })
+        .collect::<StdResult<_>>()?;
+    Ok(CampaignDepositsResponse { deposits })
+}
+
+fn query_simulate_send(
+    deps: Deps,
+    env: Env,
+    amount: Vec<Coin>,
+    sender: String,
+) -> StdResult<SimulateSendResponse> {
+    let sender = deps.api.addr_validate(&sender)?;
+    Ok(
+        simulate_send(deps, &env, &sender, amount).unwrap_or_else(|err| SimulateSendResponse {
+            error: Some(err.to_string()),
+            ..Default::default()
+        }),
+    )
+}
+
+// simulate_send runs the checks of a TokenSend of funds by sender and works out its payout
+// the way try_tokensend and forward would, without changing anything
+fn simulate_send(
+    deps: Deps,
+    env: &Env,
+    sender: &Addr,
+    funds: Vec<Coin>,
+) -> Result<SimulateSendResponse, ContractError> {
+    let mut state = CONFIG.load(deps.storage)?;
+    if let Some(pending) = PENDING_RECEIVER.may_load(deps.storage)? {
+        if pending.ready_at.is_expired(&env.block) && !pending.awaiting_acceptance {
+            state.receiver = pending.receiver;
+        }
+    }
+    if state.paused {
+        return Err(ContractError::Paused {});
+    }
+    check_sender(deps.storage, &state, sender)?;
+    if let Some(campaign) = &state.campaign {
+        if !campaign.is_open(&env.block) {
+            return Err(ContractError::CampaignNotOpen {});
+        }
+    }
+    let (funds, refund) = apply_funds_policy(&state, funds)?;
+    let multiplier = min_multiplier(deps.storage, &state, env.block.time.seconds())?;
+    for coin in funds.iter() {
+        check_amount(&state, &coin.denom, coin.amount, multiplier)?;
+    }
+    let usd_value = check_usd_floor(&deps.querier, &state, &funds)?;
+    check_quota(
+        deps.storage,
+        &state,
+        sender,
+        &funds,
+        env.block.time.seconds(),
+    )?;
+    check_contribution(deps.storage, sender, &funds)?;
+
+    let action = if state.campaign.is_some() {
+        "campaign"
+    } else if needs_approval(&state, &funds) {
+        "pending"
+    } else if state.escrow.is_some() {
+        "escrow"
+    } else if state.stream_duration.is_some() {
+        "stream"
+    } else if state.pull {
+        "deposit"
+    } else {
+        "send"
+    };
+    let mut response = SimulateSendResponse {
+        accepted: true,
+        action: action.to_string(),
+        usd_value,
+        ..Default::default()
+    };
+    let mut taxes = vec![];
+    if matches!(action, "send" | "deposit") {
+        let (staked, unstaked) = match &state.validator {
+            Some(_) => {
+                let bonded = deps.querier.query_bonded_denom()?;
+                funds.into_iter().partition(|coin| coin.denom == bonded)
+            }
+            None => (vec![], funds),
+        };
+        response.delegated = staked;
+        let (forwarded, held) = if state.hold {
+            plan_hold(deps.storage, &state, unstaked)?
+        } else {
+            (unstaked, vec![])
+        };
+        response.held = held;
+        let mut forwarded = forwarded;
+        if let Some(swap) = &state.swap {
+            let (swapped, rest) = forwarded
+                .into_iter()
+                .partition(|coin| swap.pairs.iter().any(|(denom, _)| *denom == coin.denom));
+            response.swapped = swapped;
+            forwarded = rest;
+        }
+
+        let distribution = distribute(deps.storage, &state, &forwarded)?;
+        response.dust = distribution.dust;
+        if state.fee_collector.is_some() && !distribution.fees.is_empty() {
+            deduct_tax(&deps.querier, distribution.fees.clone(), &mut taxes)?;
+            response.fee = distribution.fees;
+        }
+        for (receiver, amount) in distribution.payouts {
+            let amount = if state.pull {
+                amount
+            } else {
+                deduct_tax(&deps.querier, amount, &mut taxes)?
+            };
+            add_coins(&mut response.net, amount.clone());
+            response.shares.push(Share {
+                receiver: receiver.to_string(),
+                amount,
+            });
+        }
+    }
+    if !refund.is_empty() {
+        deduct_tax(&deps.querier, refund.clone(), &mut taxes)?;
+        response.refund = refund;
+    }
+    response.tax = taxes;
+    Ok(response)
+}
+
+fn query_payout_queue(
+    deps: Deps,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> StdResult<PayoutQueueResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.map(Bound::exclusive);
+
+    let payouts = PAYOUT_QUEUE
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|item| {
+            let (id, (receiver, amount)) = item?;
+            Ok(QueuedPayoutInfo {
+                id,
+                receiver: receiver.into_string(),
+                amount,
+            })
+        })
+        .collect::<StdResult<_>>()?;
+    Ok(PayoutQueueResponse {
+        cursor: PAYOUT_QUEUE_CURSOR
+            .may_load(deps.storage)?
+            .unwrap_or_default(),
+        payouts,
+    })
+}
+
+fn query_pending_emergency(deps: Deps) -> StdResult<PendingEmergencyResponse> {
+    let pending = PENDING_EMERGENCY.may_load(deps.storage)?;
+    Ok(PendingEmergencyResponse {
+        recipient: pending
+            .as_ref()
+            .map(|pending| pending.recipient.to_string()),
+        execute_after: pending.map(|pending| pending.execute_after),
+    })
+}
+
+// query_ownership reports the owner as gone once ownership expired, even before the next
+// message removed it
+fn query_ownership(deps: Deps, env: Env) -> StdResult<OwnershipResponse> {
+    let state = CONFIG.load(deps.storage)?;
+    let expired = state
+        .ownership_expires_at
+        .is_some_and(|expires| expires.is_expired(&env.block));
+    Ok(OwnershipResponse {
+        owner: state
+            .owner
+            .filter(|_| !expired)
+            .map(|addr| addr.to_string()),
+        expires_at: state.ownership_expires_at,
+        expired,
+    })
+}
+
+// query_preview_split shares amount out the way a forward would once the fee is taken
+fn query_preview_split(deps: Deps, amount: Coin) -> StdResult<SplitPreviewResponse> {
+    let state = CONFIG.load(deps.storage)?;
+    let (_, net) = deduct_fee(&state, &amount.denom, amount.amount)?;
+    let (parts, _, left_over) = plan_route(deps.storage, &state, &amount.denom, net)?;
+    let shares = parts
+        .into_iter()
+        .map(|(receiver, part)| Share {
+            receiver: receiver.to_string(),
+            amount: vec![Coin {
+                denom: amount.denom.clone(),
+                amount: part,
+            }],
+        })
+        .collect();
+    Ok(SplitPreviewResponse { shares, left_over })
+}
+
+fn query_config(deps: Deps) -> StdResult<ConfigResponse> {
+    let state = CONFIG.load(deps.storage)?;
+    Ok(ConfigResponse {
+        owner: state.owner.map(|addr| addr.to_string()),
+        receiver: state.receiver.to_string(),
+        receivers: state
+            .receivers
+            .iter()
+            .map(|(addr, weight)| (addr.to_string(), *weight))
+            .collect(),
+        fee_rate: state.fee_rate,
+        fee_collector: state.fee_collector.map(|addr| addr.to_string()),
+        fee_cap: state.fee_cap,
+        paused: state.paused,
+        pull: state.pull,
+        min_amount: state.min_amount,
+        accepted_denoms: state.accepted_denoms,
+        funds_policy: state.funds_policy,
+        allowlist: state
+            .allowlist
+            .iter()
+            .map(|addr| addr.to_string())
+            .collect(),
+        hold: state.hold,
+        flush_threshold: state.flush_threshold,
+        receiver_delay: state.receiver_delay,
+        restrict_senders: state.restrict_senders,
+        rate_limit: state.rate_limit,
+        escrow: state.escrow,
+        stream_duration: state.stream_duration,
+        approval_threshold: state.approval_threshold,
+        callbacks: state
+            .callbacks
+            .into_iter()
+            .map(|(addr, msg)| (addr.to_string(), msg))
+            .collect(),
+        validator: state.validator,
+        swap: state.swap.map(|swap| SwapSettings {
+            target_denom: swap.target_denom,
+            pairs: swap
+                .pairs
+                .into_iter()
+                .map(|(denom, pair)| (denom, pair.to_string()))
+                .collect(),
+            max_slippage: swap.max_slippage,
+        }),
+        hold_failed: state.hold_failed,
+        fallback_receiver: state.fallback_receiver.map(|addr| addr.to_string()),
+        require_acceptance: state.require_acceptance,
+        recovery_address: state.recovery_address.map(|addr| addr.to_string()),
+        dust_threshold: state.dust_threshold,
+        max_amount: state.max_amount,
+        waterfall: state
+            .waterfall
+            .into_iter()
+            .map(|(addr, cap)| (addr.to_string(), cap))
+            .collect(),
+        governance: state.governance.map(|governance| GovernanceSettings {
+            voters: governance
+                .voters
+                .into_iter()
+                .map(|(addr, weight)| (addr.to_string(), weight))
+                .collect(),
+            quorum: governance.quorum,
+            voting_period: governance.voting_period,
+        }),
+        multisig: state.multisig.map(|multisig| MultisigSettings {
+            admins: multisig
+                .admins
+                .into_iter()
+                .map(|addr| addr.to_string())
+                .collect(),
+            threshold: multisig.threshold,
+        }),
+        campaign: state.campaign,
+        receipt_nft: state.receipt_nft.map(|nft| ReceiptNftSettings {
+            contract: nft.contract.to_string(),
+            threshold: nft.threshold,
+        }),
+        mint_receipts: state.mint_receipts,
+        referral_rate: state.referral_rate,
+        usd_floor: state.usd_floor.map(|floor| UsdFloorSettings {
+            oracle: floor.oracle.to_string(),
+            min_value: floor.min_value,
+        }),
+        max_payouts: state.max_payouts,
+        claim_rebate: state.claim_rebate,
+        retention: state.retention,
+        ownership_expires_at: state.ownership_expires_at,
+        rounding: state.rounding,
+        refund_failed: state.refund_failed,
+        anchor: state.anchor.map(|anchor| AnchorSettings {
+            money_market: anchor.money_market.to_string(),
+            aterra: anchor.aterra.to_string(),
+            denom: anchor.denom,
+        }),
+        claim_expiry: state.claim_expiry,
+        denom_rules: state
+            .denom_rules
+            .into_iter()
+            .map(|rule| DenomRuleSettings {
+                denom: rule.denom,
+                fee_rate: rule.fee_rate,
+                receiver: rule.receiver.map(|addr| addr.to_string()),
+            })
+            .collect(),
+        attestation: state.attestation.map(|attestation| AttestationSettings {
+            registry: attestation.registry.to_string(),
+            credential: attestation.credential,
+        }),
+        owner_pubkey: state.owner_pubkey,
+        congestion: state.congestion,
+        ibc: state.ibc,
+    })
+}
+
+fn query_paused(deps: Deps) -> StdResult<PausedResponse> {
+    let state = CONFIG.load(deps.storage)?;
+    Ok(PausedResponse {
+        paused: state.paused,
+    })
+}
+
+fn query_claimable(deps: Deps, address: String) -> StdResult<ClaimableResponse> {
+    let receiver = deps.api.addr_validate(&address)?;
+    let claimable = CLAIMABLE
+        .may_load(deps.storage, &receiver)?
+        .unwrap_or_default();
+    let claimed_at = CLAIMED_AT.may_load(deps.storage, &receiver)?;
+    let expires_at = match (CONFIG.load(deps.storage)?.claim_expiry, claimed_at) {
+        (Some(expiry), Some(claimed_at)) => Some(claimed_at + expiry),
+        _ => None,
+    };
+    let unbonding = UNBONDING
+        .may_load(deps.storage, &receiver)?
+        .unwrap_or_default();
+    Ok(ClaimableResponse {
+        claimable,
+        expires_at,
+        unbonding,
+    })
+}
+
+// settings for pagination
+const MAX_LIMIT: u32 = 30;
+const DEFAULT_LIMIT: u32 = 10;
+
+fn query_transfer_history(
+    deps: Deps,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> StdResult<TransferHistoryResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.map(Bound::exclusive);
+
+    let transfers = TRANSFERS
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|item| {
+            let (id, transfer) = item?;
+            Ok(transfer_info(id, transfer))
+        })
+        .collect::<StdResult<_>>()?;
+    Ok(TransferHistoryResponse { transfers })
+}
+
+fn query_receiver_history(
+    deps: Deps,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> StdResult<ReceiverHistoryResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.map(Bound::exclusive);
+
+    let changes = RECEIVER_CHANGES
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|item| {
+            let (id, change) = item?;
+            Ok(receiver_change_info(id, change))
+        })
+        .collect::<StdResult<_>>()?;
+    Ok(ReceiverHistoryResponse { changes })
+}
+
+fn query_receipt(deps: Deps, id: u64) -> StdResult<SendReceipt> {
+    let transfer = TRANSFERS.load(deps.storage, id)?;
+    Ok(send_receipt(id, &transfer))
+}
+
+fn query_transfers_by_memo(
+    deps: Deps,
+    memo: String,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> StdResult<TransferHistoryResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.map(Bound::exclusive);
+
+    let transfers = MEMO_TRANSFERS
+        .prefix(&memo)
+        .keys(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|id| {
+            let id = id?;
+            let transfer = TRANSFERS.load(deps.storage, id)?;
+            Ok(transfer_info(id, transfer))
+        })
+        .collect::<StdResult<_>>()?;
+    Ok(TransferHistoryResponse { transfers })
+}
+
+fn query_receipts_by_sender(
+    deps: Deps,
+    sender: String,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> StdResult<ReceiptsResponse> {
+    let sender = deps.api.addr_validate(&sender)?;
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.map(Bound::exclusive);
+
+    let receipts = SENDER_TRANSFERS
+        .prefix(&sender)
+        .keys(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|id| {
+            let id = id?;
+            let transfer = TRANSFERS.load(deps.storage, id)?;
+            Ok(ReceiptInfo {
+                id,
+                amount: transfer.amount,
+                status: transfer.status,
+                height: transfer.height,
+            })
+        })
+        .collect::<StdResult<_>>()?;
+    Ok(ReceiptsResponse { receipts })
+}
+
+// query_indexed_transfers pages through the transfers index holds for addr
+fn query_indexed_transfers(
+    deps: Deps,
+    index: Map<(&Addr, u64), Empty>,
+    addr: &Addr,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> StdResult<TransferHistoryResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.map(Bound::exclusive);
+
+    let transfers = index
+        .prefix(addr)
+        .keys(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|id| {
+            let id = id?;
+            let transfer = TRANSFERS.load(deps.storage, id)?;
+            Ok(transfer_info(id, transfer))
+        })
+        .collect::<StdResult<_>>()?;
+    Ok(TransferHistoryResponse { transfers })
+}
+
+fn query_allowed_senders(
+    deps: Deps,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<AllowedSendersResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start_after = match start_after {
+        Some(addr) => Some(deps.api.addr_validate(&addr)?),
+        None => None,
+    };
+    let start = start_after.as_ref().map(Bound::exclusive);
+
+    let senders = ALLOWED_SENDERS
+        .keys(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|sender| Ok(sender?.to_string()))
+        .collect::<StdResult<_>>()?;
+    Ok(AllowedSendersResponse { senders })
+}
+
+fn query_alias(deps: Deps, name: String) -> StdResult<AliasInfo> {
+    let address = ALIASES.load(deps.storage, &name)?;
+    Ok(AliasInfo {
+        name,
+        address: address.into_string(),
+    })
+}
+
+fn query_aliases(
+    deps: Deps,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<AliasesResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.as_deref().map(Bound::exclusive);
+
+    let aliases = ALIASES
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|item| {
+            let (name, address) = item?;
+            Ok(AliasInfo {
+                name,
+                address: address.into_string(),
+            })
+        })
+        .collect::<StdResult<_>>()?;
+    Ok(AliasesResponse { aliases })
+}
+
+fn query_destinations(
+    deps: Deps,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<DestinationsResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.as_deref().map(Bound::exclusive);
+
+    let destinations = DESTINATIONS
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|item| {
+            let (name, destination) = item?;
+            Ok(DestinationInfo { name, destination })
+        })
+        .collect::<StdResult<_>>()?;
+    Ok(DestinationsResponse { destinations })
+}
+
+fn query_refunds(
+    deps: Deps,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<RefundsResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start_after = match start_after {
+        Some(addr) => Some(deps.api.addr_validate(&addr)?),
+        None => None,
+    };
+    let start = start_after.as_ref().map(Bound::exclusive);
+
+    let refunds = REFUNDS
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|item| {
+            let (sender, amount) = item?;
+            Ok(RefundInfo {
+                sender: sender.to_string(),
+                amount,
+            })
+        })
+        .collect::<StdResult<_>>()?;
+    Ok(RefundsResponse { refunds })
+}
+
+fn query_routes(
+    deps: Deps,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<RoutesResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.as_deref().map(Bound::exclusive);
+
+    let routes = ROUTES
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|item| {
+            let (name, route) = item?;
+            let route = RouteSettings {
+                receiver: route.receiver.to_string(),
+                receivers: route
+                    .receivers
+                    .into_iter()
+                    .map(|(addr, weight)| (addr.to_string(), weight))
+                    .collect(),
+                fee_rate: route.fee_rate,
+                fee_collector: route.fee_collector.map(|addr| addr.to_string()),
+                fee_cap: route.fee_cap,
+                accepted_denoms: route.accepted_denoms,
+                funds_policy: route.funds_policy,
+            };
+            let paused = PAUSED_ROUTES.has(deps.storage, &name);
+            Ok(RouteInfo {
+                name,
+                route,
+                paused,
+            })
+        })
+        .collect::<StdResult<_>>()?;
+    Ok(RoutesResponse { routes })
+}
+
+fn query_categories(
+    deps: Deps,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<CategoriesResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.as_deref().map(Bound::exclusive);
+
+    let categories = CATEGORIES
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|item| {
+            let (name, category) = item?;
+            let stats = CATEGORY_STATS
+                .may_load(deps.storage, &name)?
+                .unwrap_or_default();
+            Ok(CategoryInfo {
+                name,
+                receiver: category.receiver.map(|addr| addr.to_string()),
+                total: stats.forwarded,
+                deposits: stats.forwards,
+                last_deposit_height: stats.last_height,
+            })
+        })
+        .collect::<StdResult<_>>()?;
+    Ok(CategoriesResponse { categories })
+}
+
+fn query_route_stats(deps: Deps, name: String) -> StdResult<RouteStatsResponse> {
+    let stats = ROUTE_STATS
+        .may_load(deps.storage, &name)?
+        .unwrap_or_default();
+    Ok(RouteStatsResponse {
+        paused: PAUSED_ROUTES.has(deps.storage, &name),
+        name,
+        forwarded: stats.forwarded,
+        forwards: stats.forwards,
+        last_forward_height: stats.last_height,
+    })
+}
+
+fn query_referral(deps: Deps, code: String) -> StdResult<ReferralInfo> {
+    let referral = REFERRALS.load(deps.storage, &code)?;
+    Ok(referral_info(code, referral))
+}
+
+fn query_referrals(
+    deps: Deps,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<ReferralsResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.as_deref().map(Bound::exclusive);
+
+    let referrals = REFERRALS
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|item| {
+            let (code, referral) = item?;
+            Ok(referral_info(code, referral))
+        })
+        .collect::<StdResult<_>>()?;
+    Ok(ReferralsResponse { referrals })
+}
+
+fn referral_info(code: String, referral: Referral) -> ReferralInfo {
+    ReferralInfo {
+        code,
+        referrer: referral.referrer.into_string(),
+        deposits: referral.deposits,
+        volume: referral.volume,
+        commission: referral.commission,
+    }
+}
+
+fn query_blocked_senders(
+    deps: Deps,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<BlockedSendersResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start_after = match start_after {
+        Some(addr) => Some(deps.api.addr_validate(&addr)?),
+        None => None,
+    };
+    let start = start_after.as_ref().map(Bound::exclusive);
+
+    let senders = BLOCKED_SENDERS
+        .keys(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|sender| Ok(sender?.to_string()))
+        .collect::<StdResult<_>>()?;
+    Ok(BlockedSendersResponse { senders })
+}
+
+fn query_allowed_methods(
+    deps: Deps,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<AllowedMethodsResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.as_deref().map(Bound::exclusive);
+
+    let methods = ALLOWED_METHODS
+        .keys(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .collect::<StdResult<_>>()?;
+    Ok(AllowedMethodsResponse { methods })
+}
+
+fn query_escrow(deps: Deps, id: u64) -> StdResult<EscrowInfo> {
+    let escrow = escrows().load(deps.storage, id)?;
+    Ok(escrow_info(id, escrow))
+}
+
+// query_escrows pages through the escrows of address in index
+fn query_escrows(
+    deps: Deps,
+    index: &MultiIndex<Addr, Escrow, u64>,
+    address: String,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> StdResult<EscrowsResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let address = deps.api.addr_validate(&address)?;
+    let start = start_after.map(Bound::exclusive);
+
+    let escrows = index
+        .prefix(address)
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|item| {
+            let (id, escrow) = item?;
+            Ok(escrow_info(id, escrow))
+        })
+        .collect::<StdResult<_>>()?;
+    Ok(EscrowsResponse { escrows })
+}
+
+fn escrow_info(id: u64, escrow: Escrow) -> EscrowInfo {
+    EscrowInfo {
+        id,
+        sender: escrow.sender.to_string(),
+        receiver: escrow.receiver.to_string(),
+        amount: escrow.amount,
+        expires: escrow.expires,
+        hashlock: escrow.hashlock,
+        arbiter: escrow.arbiter.map(|addr| addr.to_string()),
+        disputed: escrow.disputed,
+    }
+}
+
+fn query_invoice(deps: Deps, env: Env, id: u64) -> StdResult<InvoiceInfo> {
+    let invoice = invoices().load(deps.storage, id)?;
+    Ok(invoice_info(&env.block, id, invoice))
+}
+
+fn query_invoices_by_payer(
+    deps: Deps,
+    env: Env,
+    payer: String,
+    status: Option<InvoiceStatus>,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> StdResult<InvoicesResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let payer = deps.api.addr_validate(&payer)?;
+    let start = start_after.map(Bound::exclusive);
+
+    let invoices = invoices()
+        .idx
+        .payer
+        .prefix(payer)
+        .range(deps.storage, start, None, Order::Ascending)
+        .map(|item| {
+            let (id, invoice) = item?;
+            Ok(invoice_info(&env.block, id, invoice))
+        })
+        .filter(|info: &StdResult<InvoiceInfo>| match (info, status) {
+            (Ok(info), Some(status)) => info.status == status,
+            _ => true,
+        })
+        .take(limit)
+        .collect::<StdResult<_>>()?;
+    Ok(InvoicesResponse { invoices })
+}
+
+fn invoice_info(block: &BlockInfo, id: u64, invoice: Invoice) -> InvoiceInfo {
+    let status = if invoice.paid.is_some() {
+        InvoiceStatus::Paid
+    } else if invoice.expires.is_expired(block) {
+        InvoiceStatus::Expired
+    } else if !invoice.received.is_zero() {
+        InvoiceStatus::PartiallyPaid
+    } else {
+        InvoiceStatus::Open
+    };
+    InvoiceInfo {
+        id,
+        payer: invoice.payer.to_string(),
+        remaining: invoice.remaining(),
+        amount: invoice.amount,
+        expires: invoice.expires,
+        status,
+        receipt_id: invoice.paid,
+        received: invoice.received,
+        overpayment: invoice.overpayment,
+    }
+}
+
+fn query_stream(deps: Deps, env: Env) -> StdResult<StreamResponse> {
+    let now = env.block.time.seconds();
+    let mut total = vec![];
+    let mut vested = vec![];
+    let mut claimed = vec![];
+    let mut ends_at = None;
+    for item in STREAMS.range(deps.storage, None, None, Order::Ascending) {
+        let (_, stream) = item?;
+        add_coins(&mut vested, stream.vested(now));
+        add_coins(&mut total, stream.amount);
+        add_coins(&mut claimed, stream.claimed);
+        ends_at = ends_at.max(Some(stream.end));
+    }
+    Ok(StreamResponse {
+        outstanding: remaining(&total, &vested),
+        total,
+        vested,
+        claimed,
+        ends_at,
+    })
+}
+
+fn query_stream_info(deps: Deps, env: Env, id: u64) -> StdResult<StreamInfo> {
+    let stream = STREAMS.load(deps.storage, id)?;
+    Ok(stream_info(id, stream, env.block.time.seconds()))
+}
+
+fn query_streams(
+    deps: Deps,
+    env: Env,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> StdResult<StreamsResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.map(Bound::exclusive);
+    let now = env.block.time.seconds();
+
+    let streams = STREAMS
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|item| {
+            let (id, stream) = item?;
+            Ok(stream_info(id, stream, now))
+        })
+        .collect::<StdResult<_>>()?;
+    Ok(StreamsResponse { streams })
+}
+
+fn stream_info(id: u64, stream: Stream, time: u64) -> StreamInfo {
+    StreamInfo {
+        id,
+        sender: stream.sender.to_string(),
+        vested: stream.vested(time),
+        next_unlock: stream.next_unlock(time),
+        amount: stream.amount,
+        claimed: stream.claimed,
+        start: stream.start,
+        cliff: stream.cliff,
+        end: stream.end,
+        revocable: stream.revocable,
+    }
+}
+
+fn query_subscription(deps: Deps, id: u64) -> StdResult<SubscriptionInfo> {
+    let subscription = subscriptions().load(deps.storage, id)?;
+    Ok(SubscriptionInfo {
+        id,
+        sender: subscription.sender.to_string(),
+        amount: subscription.amount,
+        interval: subscription.interval,
+        next_charge: subscription.next_charge,
+        balance: subscription.balance,
+        token: subscription.token.map(|token| token.to_string()),
+        delinquent: subscription.delinquent,
+    })
+}
+
+fn query_upcoming_charges(deps: Deps, limit: Option<u32>) -> StdResult<UpcomingChargesResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let charges = subscriptions()
+        .idx
+        .next_charge
+        .range(deps.storage, None, None, Order::Ascending)
+        .take(limit)
+        .map(|item| {
+            let (id, subscription) = item?;
+            Ok(Charge {
+                subscription_id: id,
+                sender: subscription.sender.to_string(),
+                amount: subscription.amount,
+                due: subscription.next_charge,
+            })
+        })
+        .collect::<StdResult<_>>()?;
+    Ok(UpcomingChargesResponse { charges })
+}
+
+fn query_pending_forwards(
+    deps: Deps,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> StdResult<PendingForwardsResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.map(Bound::exclusive);
+
+    let pending = PENDING_FORWARDS
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|item| {
+            let (id, pending) = item?;
+            Ok(PendingForwardInfo {
+                id,
+                sender: pending.sender.to_string(),
+                recipient: pending.recipient.map(|addr| addr.to_string()),
+                amount: pending.amount,
+            })
+        })
+        .collect::<StdResult<_>>()?;
+    Ok(PendingForwardsResponse { pending })
+}
+
+fn query_failed_payouts(
+    deps: Deps,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> StdResult<FailedPayoutsResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.map(Bound::exclusive);
+
+    let payouts = FAILED_PAYOUTS
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|item| {
+            let (id, payout) = item?;
+            Ok(FailedPayoutInfo {
+                id,
+                recipient: payout.recipient.to_string(),
+                msg: payout.msg,
+                error: payout.error.unwrap_or_default(),
+            })
+        })
+        .collect::<StdResult<_>>()?;
+    Ok(FailedPayoutsResponse { payouts })
+}
+
+fn query_stats(deps: Deps) -> StdResult<StatsResponse> {
+    let stats = STATS.may_load(deps.storage)?.unwrap_or_default();
+    Ok(stats_response(stats))
+}
+
+// query_stats_at reads the totals back from the changelog, a snapshot at a height holds
+// them as they were before the block
+fn query_stats_at(deps: Deps, height: u64) -> StdResult<StatsResponse> {
+    let stats = STATS
+        .may_load_at_height(deps.storage, height + 1)?
+        .unwrap_or_default();
+    Ok(stats_response(stats))
+}
+
+fn stats_response(stats: Stats) -> StatsResponse {
+    StatsResponse {
+        forwarded: stats.forwarded,
+        forwards: stats.forwards,
+        last_forward_height: stats.last_height,
+    }
+}
+
+fn query_contributions(deps: Deps, sender: String) -> StdResult<ContributionsResponse> {
+    let address = deps.api.addr_validate(&sender)?;
+    let contributions = contributions()
+        .prefix(&address)
+        .range(deps.storage, None, None, Order::Ascending)
+        .map(|item| {
+            let (denom, contribution) = item?;
+            Ok(Coin {
+                denom,
+                amount: contribution.amount,
+            })
+        })
+        .collect::<StdResult<_>>()?;
+    Ok(ContributionsResponse {
+        sender,
+        contributions,
+    })
+}
+
+fn query_allowance(deps: Deps, sender: String) -> StdResult<AllowanceResponse> {
+    let address = deps.api.addr_validate(&sender)?;
+    let cap = LIFETIME_CAPS
+        .may_load(deps.storage, &address)?
+        .unwrap_or_default();
+    let contributed = cap
+        .iter()
+        .map(|cap| {
+            let amount = contributions()
+                .may_load(deps.storage, (&address, cap.denom.as_str()))?
+                .map(|contribution| contribution.amount)
+                .unwrap_or_default();
+            Ok(Coin {
+                denom: cap.denom.clone(),
+                amount,
+            })
+        })
+        .collect::<StdResult<Vec<_>>>()?;
+    Ok(AllowanceResponse {
+        sender,
+        remaining: remaining(&cap, &contributed),
+        cap,
+    })
+}
+
+fn query_top_contributors(
+    deps: Deps,
+    denom: String,
+    limit: Option<u32>,
+) -> StdResult<TopContributorsResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let contributors = contributions()
+        .idx
+        .amount
+        .sub_prefix(denom)
+        .range(deps.storage, None, None, Order::Descending)
+        .take(limit)
+        .map(|item| {
+            let (_, contribution) = item?;
+            Ok(Contributor {
+                sender: contribution.sender.to_string(),
+                amount: contribution.amount,
+            })
+        })
+        .collect::<StdResult<_>>()?;
+    Ok(TopContributorsResponse { contributors })
+}
+
+// transfer_info converts a stored transfer for queries
+fn transfer_info(id: u64, transfer: Transfer) -> TransferInfo {
+    TransferInfo {
+        id,
+        sender: transfer.sender.to_string(),
+        amount: transfer.amount,
+        recipients: transfer
+            .recipients
+            .iter()
+            .map(|recipient| recipient.to_string())
+            .collect(),
+        height: transfer.height,
+        memo: transfer.memo,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::anchor::MarketExecuteMsg;
+    use crate::mock_querier::{
+        mock_contract, mock_dependencies, mock_dependencies_with_tax, mock_oracle, mock_registry,
+    };
+    use crate::msg::PermitParams;
+    use crate::nft::{Cw721ExecuteMsg, Metadata, Trait};
+    use crate::state::{length_prefixed, LegacyState};
+    use crate::swap::{Asset, AssetInfo, PairExecuteMsg};
+    use cosmwasm_std::testing::{mock_env, mock_info, MOCK_CONTRACT_ADDR};
+    use cosmwasm_std::{
+        coin, coins, from_json, ContractResult, Event, FullDelegation, OwnedDeps, StdError,
+        SubMsgResponse, SystemResult, Timestamp, WasmQuery,
+    };
+
+    #[test]
+    fn proper_initialization() {
+        let mut deps = mock_dependencies(&[]);
+
+        let msg = InstantiateMsg {
+            receiver: "terra1j40dd3k6f3wmlx8h00eg5avasjygvsh3pg3g5p".to_string(),
+            ..Default::default()
+        };
+        let info = mock_info("creator", &coins(1000, "uusd"));
+
+        // we can just call .unwrap() to assert this was a success
+        let res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+        assert_eq!(0, res.messages.len());
+
+        // it worked, let's query the state
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetReceiver {}).unwrap();
+        let value: ReceiverResponse = from_json(&res).unwrap();
+        assert_eq!(
+            "terra1j40dd3k6f3wmlx8h00eg5avasjygvsh3pg3g5p",
+            value.receiver.to_string()
+        );
+    }
+
+    #[test]
+    fn failed_tokensend() {
+        let mut deps = mock_dependencies(&coins(2, "token"));
+
+        let msg = InstantiateMsg {
+            receiver: "terra1w548z72h5mgf6cgdkrx5h7fqk3e5wdejkv22d5".to_string(),
+            ..Default::default()
+        };
+        let info = mock_info("creator", &coins(1000, "token"));
+
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let info = mock_info("anyone", &[]);
+        let msg = ExecuteMsg::TokenSend {
+            recipient: None,
+            route: None,
+            memo: None,
+            on_behalf_of: None,
+            category: None,
+        };
+        let res = execute(deps.as_mut(), mock_env(), info, msg);
+        match res {
+            Ok(_) => panic!("expected error"),
+            Err(ContractError::NoFundsSent {}) => {}
+            Err(e) => panic!("unexpected error: {:?}", e),
+        }
+    }
+
+    #[test]
+    fn tokensend() {
+        let mut deps = mock_dependencies(&coins(2, "uusd"));
+
+        let msg = InstantiateMsg {
+            receiver: "terra1w548z72h5mgf6cgdkrx5h7fqk3e5wdejkv22d5".to_string(),
+            ..Default::default()
+        };
+        let info = mock_info("creator", &coins(1000, "uusd"));
+
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let balance = coins(100, "uusd");
+        let info = mock_info("anyone", &balance);
+        let msg = ExecuteMsg::TokenSend {
+            recipient: None,
+            route: None,
+            memo: None,
+            on_behalf_of: None,
+            category: None,
+        };
+
+        //deps.querier.update_balance("anyone", coins(200, "token"));
+        //let query_balance = deps.querier.query_all_balances("anyone");
+        //println!("Balance {:#?}", query_balance);
+
+        let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        let msg = res.messages.first().expect("no message");
+        assert_eq!(
+            msg,
+            &SubMsg::new(BankMsg::Send {
+                to_address: "terra1w548z72h5mgf6cgdkrx5h7fqk3e5wdejkv22d5".to_string(),
+                amount: coins(100, "uusd"),
+            })
+        );
+        assert_eq!(
+            res.attributes,
+            vec![
+                attr("action", "send"),
+                attr("sender", "anyone"),
+                attr("amount", "100uusd"),
+                attr("recipient", "terra1w548z72h5mgf6cgdkrx5h7fqk3e5wdejkv22d5"),
+                attr("receipt_id", "1"),
+            ]
+        );
+    }
+
+    #[test]
+    fn receive_cw20() {
+        let mut deps = mock_dependencies(&[]);
+
+        let msg = InstantiateMsg {
+            receiver: "terra1w548z72h5mgf6cgdkrx5h7fqk3e5wdejkv22d5".to_string(),
+            ..Default::default()
+        };
+        let info = mock_info("creator", &[]);
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        // an empty transfer is rejected
+        let info = mock_info("token", &[]);
+        let msg = ExecuteMsg::Receive(Cw20ReceiveMsg {
+            sender: "anyone".to_string(),
+            amount: Uint128::new(0),
+            msg: Binary::default(),
+        });
+        let res = execute(deps.as_mut(), mock_env(), info, msg);
+        match res {
+            Err(ContractError::NoFundsSent {}) => {}
+            _ => panic!("Must return NoFundsSent error"),
+        }
+
+        // the token contract is told to move the tokens on to the receiver
+        let info = mock_info("token", &[]);
+        let msg = ExecuteMsg::Receive(Cw20ReceiveMsg {
+            sender: "anyone".to_string(),
+            amount: Uint128::new(100),
+            msg: Binary::default(),
+        });
+        let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        assert_eq!(1, res.messages.len());
+        assert_eq!(
+            res.messages[0],
+            SubMsg::new(WasmMsg::Execute {
+                contract_addr: "token".to_string(),
+                msg: to_json_binary(&Cw20ExecuteMsg::Transfer {
+                    recipient: "terra1w548z72h5mgf6cgdkrx5h7fqk3e5wdejkv22d5".to_string(),
+                    amount: Uint128::new(100),
+                })
+                .unwrap(),
+                funds: vec![],
+            })
+        );
+        assert_eq!(
+            res.attributes,
+            vec![
+                attr("action", "send"),
+                attr("sender", "anyone"),
+                attr("amount", "100"),
+                attr("denom", "token"),
+                attr("recipient", "terra1w548z72h5mgf6cgdkrx5h7fqk3e5wdejkv22d5"),
+                attr("receipt_id", "1"),
+            ]
+        );
+    }
+
+    #[test]
+    fn reset() {
+        let mut deps = mock_dependencies(&coins(2, "token"));
+
+        let msg = InstantiateMsg {
+            receiver: "terra1j40dd3k6f3wmlx8h00eg5avasjygvsh3pg3g5p".to_string(),
+            ..Default::default()
+        };
+        let info = mock_info("creator", &coins(2, "token"));
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        // beneficiary can release it
+        let unauth_info = mock_info("anyone", &coins(2, "token"));
+        let msg = ExecuteMsg::ResetReceiver {
+            receiver: "terra1w548z72h5mgf6cgdkrx5h7fqk3e5wdejkv22d5".to_string(),
+        };
+        let res = execute(deps.as_mut(), mock_env(), unauth_info, msg);
+        match res {
+            Err(ContractError::Unauthorized {}) => {}
+            _ => panic!("Must return unauthorized error"),
+        }
+
+        // only the original creator can reset the receiver
+        let auth_info = mock_info("creator", &coins(2, "token"));
+        let msg = ExecuteMsg::ResetReceiver {
+            receiver: "terra1w548z72h5mgf6cgdkrx5h7fqk3e5wdejkv22d5".to_string(),
+        };
+        let res = execute(deps.as_mut(), mock_env(), auth_info, msg).unwrap();
+        assert_eq!(
+            res.attributes,
+            vec![
+                attr("action", "reset_receiver"),
+                attr("sender", "creator"),
+                attr("recipient", "terra1w548z72h5mgf6cgdkrx5h7fqk3e5wdejkv22d5"),
+                attr("old", "terra1j40dd3k6f3wmlx8h00eg5avasjygvsh3pg3g5p"),
+                attr("new", "terra1w548z72h5mgf6cgdkrx5h7fqk3e5wdejkv22d5"),
+                attr("actor", "creator"),
+            ]
+        );
+        // the change record is set as data
+        let change: ReceiverChangeInfo = from_json(res.data.unwrap()).unwrap();
+        assert_eq!(
+            change,
+            ReceiverChangeInfo {
+                id: 1,
+                old_receiver: "terra1j40dd3k6f3wmlx8h00eg5avasjygvsh3pg3g5p".to_string(),
+                new_receiver: "terra1w548z72h5mgf6cgdkrx5h7fqk3e5wdejkv22d5".to_string(),
+                actor: "creator".to_string(),
+                height: mock_env().block.height,
+                time: mock_env().block.time.seconds(),
+            }
+        );
+
+        // should now be 5
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetReceiver {}).unwrap();
+        let value: ReceiverResponse = from_json(&res).unwrap();
+        assert_eq!(
+            "terra1w548z72h5mgf6cgdkrx5h7fqk3e5wdejkv22d5",
+            value.receiver.to_string()
+        );
+    }
+
+    #[test]
+    fn split_tokensend() {
+        let mut deps = mock_dependencies(&[]);
+
+        let msg = InstantiateMsg {
+            receiver: "treasury".to_string(),
+            receivers: vec![
+                ("alice".to_string(), Decimal::percent(50)),
+                ("bob".to_string(), Decimal::percent(25)),
+                ("carol".to_string(), Decimal::percent(25)),
+            ],
+            ..Default::default()
+        };
+        let info = mock_info("creator", &[]);
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        // 101 uusd splits into 50/25/25 with the remainder going to the first receiver,
+        // 2 token only gives alice a share
+        let info = mock_info("anyone", &[coin(101, "uusd"), coin(2, "token")]);
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            info,
+            ExecuteMsg::TokenSend {
+                recipient: None,
+                route: None,
+                memo: None,
+                on_behalf_of: None,
+                category: None,
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages,
+            vec![
+                SubMsg::new(BankMsg::Send {
+                    to_address: "alice".to_string(),
+                    amount: vec![coin(51, "uusd"), coin(2, "token")],
+                }),
+                SubMsg::new(BankMsg::Send {
+                    to_address: "bob".to_string(),
+                    amount: coins(25, "uusd"),
+                }),
+                SubMsg::new(BankMsg::Send {
+                    to_address: "carol".to_string(),
+                    amount: coins(25, "uusd"),
+                }),
+            ]
+        );
+        assert_eq!(
+            res.attributes,
+            vec![
+                attr("action", "send"),
+                attr("sender", "anyone"),
+                attr("amount", "101uusd,2token"),
+                attr("recipient", "alice"),
+                attr("recipient", "bob"),
+                attr("recipient", "carol"),
+                attr("receipt_id", "1"),
+            ]
+        );
+    }
+
+    #[test]
+    fn set_receivers() {
+        let mut deps = mock_dependencies(&[]);
+
+        let msg = InstantiateMsg {
+            receiver: "treasury".to_string(),
+            ..Default::default()
+        };
+        let info = mock_info("creator", &[]);
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        // only the owner can change the split
+        let msg = ExecuteMsg::SetReceivers {
+            receivers: vec![
+                ("alice".to_string(), Decimal::percent(60)),
+                ("bob".to_string(), Decimal::percent(40)),
+            ],
+        };
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("anyone", &[]),
+            msg.clone(),
+        );
+        match res {
+            Err(ContractError::Unauthorized {}) => {}
+            _ => panic!("Must return unauthorized error"),
+        }
+
+        // weights have to add up to 1
+        let bad = ExecuteMsg::SetReceivers {
+            receivers: vec![
+                ("alice".to_string(), Decimal::percent(60)),
+                ("bob".to_string(), Decimal::percent(60)),
+            ],
+        };
+        let res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), bad);
+        match res {
+            Err(ContractError::InvalidWeights {}) => {}
+            _ => panic!("Must return InvalidWeights error"),
+        }
+
+        let _res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+        let state = CONFIG.load(&deps.storage).unwrap();
+        let alice = Addr::unchecked("alice");
+        let bob = Addr::unchecked("bob");
+        assert_eq!(
+            state.receivers,
+            vec![(alice, Decimal::percent(60)), (bob, Decimal::percent(40))]
+        );
+
+        // cw20 transfers are split the same way
+        let msg = ExecuteMsg::Receive(Cw20ReceiveMsg {
+            sender: "anyone".to_string(),
+            amount: Uint128::new(10),
+            msg: Binary::default(),
+        });
+        let res = execute(deps.as_mut(), mock_env(), mock_info("token", &[]), msg).unwrap();
+        assert_eq!(2, res.messages.len());
+        assert_eq!(
+            res.messages[1],
+            SubMsg::new(WasmMsg::Execute {
+                contract_addr: "token".to_string(),
+                msg: to_json_binary(&Cw20ExecuteMsg::Transfer {
+                    recipient: "bob".to_string(),
+                    amount: Uint128::new(4),
+                })
+                .unwrap(),
+                funds: vec![],
+            })
+        );
+    }
+
+    #[test]
+    fn fee_tokensend() {
+        let mut deps = mock_dependencies(&[]);
+
+        let msg = InstantiateMsg {
+            receiver: "treasury".to_string(),
+            fee_rate: Decimal::percent(2),
+            fee_collector: Some("collector".to_string()),
+            fee_cap: coins(3, "token"),
+            ..Default::default()
+        };
+        let info = mock_info("creator", &[]);
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        // 2% of 500 token would be 10 but is capped at 3
+        let info = mock_info("anyone", &[coin(100, "uusd"), coin(500, "token")]);
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            info,
+            ExecuteMsg::TokenSend {
+                recipient: None,
+                route: None,
+                memo: None,
+                on_behalf_of: None,
+                category: None,
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages,
+            vec![
+                SubMsg::new(BankMsg::Send {
+                    to_address: "collector".to_string(),
+                    amount: vec![coin(2, "uusd"), coin(3, "token")],
+                }),
+                SubMsg::new(BankMsg::Send {
+                    to_address: "treasury".to_string(),
+                    amount: vec![coin(98, "uusd"), coin(497, "token")],
+                }),
+            ]
+        );
+        assert_eq!(
+            res.attributes,
+            vec![
+                attr("action", "send"),
+                attr("sender", "anyone"),
+                attr("amount", "100uusd,500token"),
+                attr("fee", "2uusd,3token"),
+                attr("fee_collector", "collector"),
+                attr("recipient", "treasury"),
+                attr("receipt_id", "1"),
+            ]
+        );
+    }
+
+    #[test]
+    fn set_fee() {
+        let mut deps = mock_dependencies(&[]);
+
+        let msg = InstantiateMsg {
+            receiver: "treasury".to_string(),
+            ..Default::default()
+        };
+        let info = mock_info("creator", &[]);
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        // a fee needs somebody to collect it
+        let msg = ExecuteMsg::SetFee {
+            fee_rate: Decimal::percent(1),
+            fee_collector: None,
+            fee_cap: vec![],
+        };
+        let res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg);
+        match res {
+            Err(ContractError::NoFeeCollector {}) => {}
+            _ => panic!("Must return NoFeeCollector error"),
+        }
+
+        let msg = ExecuteMsg::SetFee {
+            fee_rate: Decimal::percent(10),
+            fee_collector: Some("collector".to_string()),
+            fee_cap: vec![],
+        };
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("anyone", &[]),
+            msg.clone(),
+        );
+        match res {
+            Err(ContractError::Unauthorized {}) => {}
+            _ => panic!("Must return unauthorized error"),
+        }
+        let _res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+
+        // cw20 forwards pay the fee in the token itself
+        let msg = ExecuteMsg::Receive(Cw20ReceiveMsg {
+            sender: "anyone".to_string(),
+            amount: Uint128::new(50),
+            msg: Binary::default(),
+        });
+        let res = execute(deps.as_mut(), mock_env(), mock_info("token", &[]), msg).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![
+                SubMsg::new(WasmMsg::Execute {
+                    contract_addr: "token".to_string(),
+                    msg: to_json_binary(&Cw20ExecuteMsg::Transfer {
+                        recipient: "collector".to_string(),
+                        amount: Uint128::new(5),
+                    })
+                    .unwrap(),
+                    funds: vec![],
+                }),
+                SubMsg::new(WasmMsg::Execute {
+                    contract_addr: "token".to_string(),
+                    msg: to_json_binary(&Cw20ExecuteMsg::Transfer {
+                        recipient: "treasury".to_string(),
+                        amount: Uint128::new(45),
+                    })
+                    .unwrap(),
+                    funds: vec![],
+                }),
+            ]
+        );
+    }
+
+    #[test]
+    fn pause() {
+        let mut deps = mock_dependencies(&[]);
+
+        let msg = InstantiateMsg {
+            receiver: "treasury".to_string(),
+            ..Default::default()
+        };
+        let info = mock_info("creator", &[]);
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        // only the owner can pause
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("anyone", &[]),
+            ExecuteMsg::Pause {},
+        );
+        match res {
+            Err(ContractError::Unauthorized {}) => {}
+            _ => panic!("Must return unauthorized error"),
+        }
+        let _res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            ExecuteMsg::Pause {},
+        )
+        .unwrap();
+
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetPaused {}).unwrap();
+        let value: PausedResponse = from_json(&res).unwrap();
+        assert!(value.paused);
+
+        // deposits are refused while paused
+        let info = mock_info("anyone", &coins(100, "uusd"));
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            info,
+            ExecuteMsg::TokenSend {
+                recipient: None,
+                route: None,
+                memo: None,
+                on_behalf_of: None,
+                category: None,
+            },
+        );
+        match res {
+            Err(ContractError::Paused {}) => {}
+            _ => panic!("Must return Paused error"),
+        }
+        let msg = ExecuteMsg::Receive(Cw20ReceiveMsg {
+            sender: "anyone".to_string(),
+            amount: Uint128::new(100),
+            msg: Binary::default(),
+        });
+        let res = execute(deps.as_mut(), mock_env(), mock_info("token", &[]), msg);
+        match res {
+            Err(ContractError::Paused {}) => {}
+            _ => panic!("Must return Paused error"),
+        }
+
+        // and flow again once unpaused
+        let _res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            ExecuteMsg::Unpause {},
+        )
+        .unwrap();
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetPaused {}).unwrap();
+        let value: PausedResponse = from_json(&res).unwrap();
+        assert!(!value.paused);
+        let info = mock_info("anyone", &coins(100, "uusd"));
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            info,
+            ExecuteMsg::TokenSend {
+                recipient: None,
+                route: None,
+                memo: None,
+                on_behalf_of: None,
+                category: None,
+            },
+        )
+        .unwrap();
+        assert_eq!(1, res.messages.len());
+    }
+
+    #[test]
+    fn migrate_legacy_state() {
+        let mut deps = mock_dependencies(&[]);
+
+        // an instance deployed before version tracking only stored the receiver and owner
+        let legacy = LegacyState {
+            receiver: deps.api.addr_canonicalize("treasury").unwrap(),
+            owner: deps.api.addr_canonicalize("creator").unwrap(),
+        };
+        deps.storage
+            .set(&length_prefixed("config"), &to_json_vec(&legacy).unwrap());
+        assert!(CONFIG.load(&deps.storage).is_err());
+
+        let _res = migrate(deps.as_mut(), mock_env(), MigrateMsg {}).unwrap();
+
+        let state = CONFIG.load(&deps.storage).unwrap();
+        assert_eq!(state.receiver, "treasury");
+        assert_eq!(state.owner.unwrap(), "creator");
+        assert!(state.receivers.is_empty());
+        assert!(!state.paused);
+        let version = get_contract_version(&deps.storage).unwrap();
+        assert_eq!(CONTRACT_NAME, version.contract);
+        assert_eq!(CONTRACT_VERSION, version.version);
+
+        // deposits keep flowing to the same receiver
+        let info = mock_info("anyone", &coins(100, "uusd"));
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            info,
+            ExecuteMsg::TokenSend {
+                recipient: None,
+                route: None,
+                memo: None,
+                on_behalf_of: None,
+                category: None,
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: "treasury".to_string(),
+                amount: coins(100, "uusd"),
+            })]
+        );
+    }
+
+    #[test]
+    fn migrate_singletons() {
+        let mut deps = mock_dependencies(&[]);
+
+        let msg = InstantiateMsg {
+            receiver: "treasury".to_string(),
+            ..Default::default()
+        };
+        let _res = instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+        let msg = ExecuteMsg::TokenSend {
+            recipient: None,
+            route: None,
+            memo: Some("invoice-1".to_string()),
+            on_behalf_of: None,
+            category: None,
+        };
+        let info = mock_info("anyone", &coins(100, "uusd"));
+        let _res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        // earlier versions kept the config and transfer count in cosmwasm-storage singletons
+        for key in ["config", "transfer_count"] {
+            let value = deps.storage.get(key.as_bytes()).unwrap();
+            deps.storage.remove(key.as_bytes());
+            deps.storage.set(&length_prefixed(key), &value);
+        }
+        assert!(CONFIG.load(&deps.storage).is_err());
+
+        let _res = migrate(deps.as_mut(), mock_env(), MigrateMsg {}).unwrap();
+        let state = CONFIG.load(&deps.storage).unwrap();
+        assert_eq!(state.receiver, "treasury");
+
+        // the history is kept and new transfers continue its ids
+        let msg = ExecuteMsg::TokenSend {
+            recipient: None,
+            route: None,
+            memo: Some("invoice-1".to_string()),
+            on_behalf_of: None,
+            category: None,
+        };
+        let info = mock_info("anyone", &coins(50, "uusd"));
+        let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        assert!(res.attributes.contains(&attr("receipt_id", "2")));
+
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::TransfersByMemo {
+                memo: "invoice-1".to_string(),
+                start_after: None,
+                limit: None,
+            },
+        )
+        .unwrap();
+        let value: TransferHistoryResponse = from_json(&res).unwrap();
+        let ids: Vec<u64> = value.transfers.iter().map(|t| t.id).collect();
+        assert_eq!(ids, vec![1, 2]);
+    }
+
+    #[test]
+    fn migrate_other_contract() {
+        let mut deps = mock_dependencies(&[]);
+
+        let msg = InstantiateMsg {
+            receiver: "treasury".to_string(),
+            ..Default::default()
+        };
+        let _res = instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+
+        // migrating an up to date instance just bumps the version
+        let _res = migrate(deps.as_mut(), mock_env(), MigrateMsg {}).unwrap();
+
+        set_contract_version(&mut deps.storage, "crates.io:cw20-base", "0.2.3").unwrap();
+        let res = migrate(deps.as_mut(), mock_env(), MigrateMsg {});
+        match res {
+            Err(ContractError::CannotMigrate { contract }) => {
+                assert_eq!(contract, "crates.io:cw20-base")
+            }
+            _ => panic!("Must return CannotMigrate error"),
+        }
+    }
+
+    #[test]
+    fn pull_mode() {
+        let mut deps = mock_dependencies(&[]);
+
+        let msg = InstantiateMsg {
+            receiver: "treasury".to_string(),
+            receivers: vec![
+                ("alice".to_string(), Decimal::percent(50)),
+                ("bob".to_string(), Decimal::percent(50)),
+            ],
+            pull: true,
+            ..Default::default()
+        };
+        let info = mock_info("creator", &[]);
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        // deposits accrue instead of being sent
+        let info = mock_info("anyone", &[coin(100, "uusd"), coin(10, "token")]);
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            info,
+            ExecuteMsg::TokenSend {
+                recipient: None,
+                route: None,
+                memo: None,
+                on_behalf_of: None,
+                category: None,
+            },
+        )
+        .unwrap();
+        assert_eq!(0, res.messages.len());
+        assert_eq!(res.attributes[0], attr("action", "deposit"));
+        let info = mock_info("anyone", &coins(20, "uusd"));
+        let _res = execute(
+            deps.as_mut(),
+            mock_env(),
+            info,
+            ExecuteMsg::TokenSend {
+                recipient: None,
+                route: None,
+                memo: None,
+                on_behalf_of: None,
+                category: None,
+            },
+        )
+        .unwrap();
+
+        let msg = QueryMsg::GetClaimable {
+            address: "alice".to_string(),
+        };
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let value: ClaimableResponse = from_json(&res).unwrap();
+        assert_eq!(value.claimable, vec![coin(60, "uusd"), coin(5, "token")]);
+
+        // an amount can only be withdrawn for a specific denom
+        let msg = ExecuteMsg::Withdraw {
+            denom: None,
+            amount: Some(Uint128::new(10)),
+        };
+        let res = execute(deps.as_mut(), mock_env(), mock_info("alice", &[]), msg);
+        match res {
+            Err(ContractError::WithdrawDenomRequired {}) => {}
+            _ => panic!("Must return WithdrawDenomRequired error"),
+        }
+
+        // and not more than what has accrued
+        let msg = ExecuteMsg::Withdraw {
+            denom: Some("uusd".to_string()),
+            amount: Some(Uint128::new(61)),
+        };
+        let res = execute(deps.as_mut(), mock_env(), mock_info("alice", &[]), msg);
+        match res {
+            Err(ContractError::InsufficientClaimable {}) => {}
+            _ => panic!("Must return InsufficientClaimable error"),
+        }
+
+        let msg = ExecuteMsg::Withdraw {
+            denom: Some("uusd".to_string()),
+            amount: Some(Uint128::new(40)),
+        };
+        let res = execute(deps.as_mut(), mock_env(), mock_info("alice", &[]), msg).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: "alice".to_string(),
+                amount: coins(40, "uusd"),
+            })]
+        );
+
+        // withdrawing without a denom takes the rest
+        let msg = ExecuteMsg::Withdraw {
+            denom: None,
+            amount: None,
+        };
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("alice", &[]),
+            msg.clone(),
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: "alice".to_string(),
+                amount: vec![coin(20, "uusd"), coin(5, "token")],
+            })]
+        );
+        let res = execute(deps.as_mut(), mock_env(), mock_info("alice", &[]), msg);
+        match res {
+            Err(ContractError::NothingToWithdraw {}) => {}
+            _ => panic!("Must return NothingToWithdraw error"),
+        }
+
+        // bob's share is untouched
+        let msg = QueryMsg::GetClaimable {
+            address: "bob".to_string(),
+        };
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let value: ClaimableResponse = from_json(&res).unwrap();
+        assert_eq!(value.claimable, vec![coin(60, "uusd"), coin(5, "token")]);
+    }
+
+    #[test]
+    fn transfer_history() {
+        let mut deps = mock_dependencies(&[]);
+
+        let msg = InstantiateMsg {
+            receiver: "treasury".to_string(),
+            ..Default::default()
+        };
+        let info = mock_info("creator", &[]);
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let info = mock_info("alice", &coins(100, "uusd"));
+        let mut env = mock_env();
+        env.block.height = 100;
+        let res = execute(
+            deps.as_mut(),
+            env,
+            info,
+            ExecuteMsg::TokenSend {
+                recipient: None,
+                route: None,
+                memo: None,
+                on_behalf_of: None,
+                category: None,
+            },
+        )
+        .unwrap();
+        assert_eq!(res.attributes.last(), Some(&attr("receipt_id", "1")));
+        let info = mock_info("token", &[]);
+        let mut env = mock_env();
+        env.block.height = 101;
+        let msg = ExecuteMsg::Receive(Cw20ReceiveMsg {
+            sender: "bob".to_string(),
+            amount: Uint128::new(7),
+            msg: Binary::default(),
+        });
+        let _res = execute(deps.as_mut(), env, info, msg).unwrap();
+        for _ in 0..3 {
+            let info = mock_info("carol", &coins(1, "uusd"));
+            let _res = execute(
+                deps.as_mut(),
+                mock_env(),
+                info,
+                ExecuteMsg::TokenSend {
+                    recipient: None,
+                    route: None,
+                    memo: None,
+                    on_behalf_of: None,
+                    category: None,
+                },
+            )
+            .unwrap();
+        }
+
+        let msg = QueryMsg::TransferHistory {
+            start_after: None,
+            limit: Some(2),
+        };
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let value: TransferHistoryResponse = from_json(&res).unwrap();
+        assert_eq!(
+            value.transfers,
+            vec![
+                TransferInfo {
+                    id: 1,
+                    sender: "alice".to_string(),
+                    amount: coins(100, "uusd"),
+                    recipients: vec!["treasury".to_string()],
+                    height: 100,
+                    memo: None,
+                },
+                TransferInfo {
+                    id: 2,
+                    sender: "bob".to_string(),
+                    amount: coins(7, "token"),
+                    recipients: vec!["treasury".to_string()],
+                    height: 101,
+                    memo: None,
+                },
+            ]
+        );
+
+        // continue after the last page
+        let msg = QueryMsg::TransferHistory {
+            start_after: Some(2),
+            limit: None,
+        };
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let value: TransferHistoryResponse = from_json(&res).unwrap();
+        let ids: Vec<u64> = value.transfers.iter().map(|t| t.id).collect();
+        assert_eq!(ids, vec![3, 4, 5]);
+    }
+
+    #[test]
+    fn get_config() {
+        let mut deps = mock_dependencies(&[]);
+        mock_contract(&mut deps.querier, "vault");
+
+        let msg = InstantiateMsg {
+            receiver: "treasury".to_string(),
+            receivers: vec![
+                ("bob".to_string(), Decimal::percent(40)),
+                ("carol".to_string(), Decimal::percent(60)),
+            ],
+            fee_rate: Decimal::percent(2),
+            fee_collector: Some("collector".to_string()),
+            fee_cap: coins(10, "uusd"),
+            pull: true,
+            min_amount: coins(5, "uusd"),
+            accepted_denoms: vec!["uusd".to_string(), "ukrw".to_string()],
+            funds_policy: FundsPolicy::RejectMixed,
+            allowlist: vec!["router".to_string()],
+            hold: true,
+            flush_threshold: coins(1000, "uusd"),
+            receiver_delay: Some(Duration::Height(100)),
+            restrict_senders: true,
+            rate_limit: Some(RateLimit {
+                per_sender: coins(100, "uusd"),
+                window: 3600,
+                daily_cap: coins(1000, "uusd"),
+            }),
+            escrow: Some(Duration::Time(86400)),
+            stream_duration: Some(3600),
+            approval_threshold: coins(500, "uusd"),
+            callbacks: vec![("vault".to_string(), Binary::from(br#"{"deposit":{}}"#))],
+            validator: Some("terravaloper1".to_string()),
+            swap: Some(SwapSettings {
+                target_denom: "uusd".to_string(),
+                pairs: vec![("uluna".to_string(), "pair".to_string())],
+                max_slippage: Decimal::percent(1),
+            }),
+            hold_failed: true,
+            fallback_receiver: Some("reserve".to_string()),
+            require_acceptance: true,
+            recovery_address: Some("recovery".to_string()),
+            dust_threshold: coins(10, "uusd"),
+            max_amount: coins(100_000, "uusd"),
+            waterfall: vec![("alice".to_string(), coins(1000, "uusd"))],
+            governance: Some(GovernanceSettings {
+                voters: vec![("dao".to_string(), 1)],
+                quorum: Decimal::percent(50),
+                voting_period: Duration::Height(100),
+            }),
+            multisig: Some(MultisigSettings {
+                admins: vec!["alice".to_string(), "bob".to_string()],
+                threshold: 2,
+            }),
+            campaign: Some(Campaign {
+                start: Expiration::AtHeight(100),
+                end: Expiration::AtHeight(200),
+                goal: coin(1000, "uusd"),
+            }),
+            receipt_nft: Some(ReceiptNftSettings {
+                contract: "receipts".to_string(),
+                threshold: coins(1000, "uusd"),
+            }),
+            mint_receipts: true,
+            referral_rate: Decimal::percent(5),
+            usd_floor: Some(UsdFloorSettings {
+                oracle: "oracle".to_string(),
+                min_value: Uint128::new(10),
+            }),
+            max_payouts: Some(50),
+            claim_rebate: Some(coin(1, "uusd")),
+            retention: Some(Retention::MaxEntries { count: 1000 }),
+            ownership_expires_at: Some(Expiration::AtHeight(1_000_000)),
+            rounding: Rounding::LargestRemainder,
+            refund_failed: false,
+            anchor: None,
+            claim_expiry: None,
+            denom_rules: vec![],
+            attestation: None,
+            owner_pubkey: None,
+            congestion: None,
+            ibc: None,
+        };
+        let info = mock_info("creator", &[]);
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+        let _res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            ExecuteMsg::Pause {},
+        )
+        .unwrap();
+
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetConfig {}).unwrap();
+        let value: ConfigResponse = from_json(&res).unwrap();
+        assert_eq!(
+            value,
+            ConfigResponse {
+                owner: Some("creator".to_string()),
+                receiver: "treasury".to_string(),
+                receivers: vec![
+                    ("bob".to_string(), Decimal::percent(40)),
+                    ("carol".to_string(), Decimal::percent(60)),
+                ],
+                fee_rate: Decimal::percent(2),
+                fee_collector: Some("collector".to_string()),
+                fee_cap: coins(10, "uusd"),
+                paused: true,
+                pull: true,
+                min_amount: coins(5, "uusd"),
+                accepted_denoms: vec!["uusd".to_string(), "ukrw".to_string()],
+                funds_policy: FundsPolicy::RejectMixed,
+                allowlist: vec!["router".to_string()],
+                hold: true,
+                flush_threshold: coins(1000, "uusd"),
+                receiver_delay: Some(Duration::Height(100)),
+                restrict_senders: true,
+                rate_limit: Some(RateLimit {
+                    per_sender: coins(100, "uusd"),
+                    window: 3600,
+                    daily_cap: coins(1000, "uusd"),
+                }),
+                escrow: Some(Duration::Time(86400)),
+                stream_duration: Some(3600),
+                approval_threshold: coins(500, "uusd"),
+                callbacks: vec![("vault".to_string(), Binary::from(br#"{"deposit":{}}"#))],
+                validator: Some("terravaloper1".to_string()),
+                swap: Some(SwapSettings {
+                    target_denom: "uusd".to_string(),
+                    pairs: vec![("uluna".to_string(), "pair".to_string())],
+                    max_slippage: Decimal::percent(1),
+                }),
+                hold_failed: true,
+                fallback_receiver: Some("reserve".to_string()),
+                require_acceptance: true,
+                recovery_address: Some("recovery".to_string()),
+                dust_threshold: coins(10, "uusd"),
+                max_amount: coins(100_000, "uusd"),
+                waterfall: vec![("alice".to_string(), coins(1000, "uusd"))],
+                governance: Some(GovernanceSettings {
+                    voters: vec![("dao".to_string(), 1)],
+                    quorum: Decimal::percent(50),
+                    voting_period: Duration::Height(100),
+                }),
+                multisig: Some(MultisigSettings {
+                    admins: vec!["alice".to_string(), "bob".to_string()],
+                    threshold: 2,
+                }),
+                campaign: Some(Campaign {
+                    start: Expiration::AtHeight(100),
+                    end: Expiration::AtHeight(200),
+                    goal: coin(1000, "uusd"),
+                }),
+                receipt_nft: Some(ReceiptNftSettings {
+                    contract: "receipts".to_string(),
+                    threshold: coins(1000, "uusd"),
+                }),
+                mint_receipts: true,
+                referral_rate: Decimal::percent(5),
+                usd_floor: Some(UsdFloorSettings {
+                    oracle: "oracle".to_string(),
+                    min_value: Uint128::new(10),
+                }),
+                max_payouts: Some(50),
+                claim_rebate: Some(coin(1, "uusd")),
+                retention: Some(Retention::MaxEntries { count: 1000 }),
+                ownership_expires_at: Some(Expiration::AtHeight(1_000_000)),
+                rounding: Rounding::LargestRemainder,
+                refund_failed: false,
+                anchor: None,
+                claim_expiry: None,
+                denom_rules: vec![],
+                attestation: None,
+                owner_pubkey: None,
+                congestion: None,
+                ibc: None,
+            }
+        );
+    }
+
+    #[test]
+    fn min_amount() {
+        let mut deps = mock_dependencies(&[]);
+
+        let msg = InstantiateMsg {
+            receiver: "treasury".to_string(),
+            min_amount: coins(10, "uusd"),
+            ..Default::default()
+        };
+        let info = mock_info("creator", &[]);
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let info = mock_info("anyone", &coins(9, "uusd"));
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            info,
+            ExecuteMsg::TokenSend {
+                recipient: None,
+                route: None,
+                memo: None,
+                on_behalf_of: None,
+                category: None,
+            },
+        );
+        match res {
+            Err(ContractError::BelowMinimum { denom, min, got }) => {
+                assert_eq!(denom, "uusd");
+                assert_eq!(min, Uint128::new(10));
+                assert_eq!(got, Uint128::new(9));
+            }
+            _ => panic!("Must return BelowMinimum error"),
+        }
+        let info = mock_info("anyone", &coins(10, "uusd"));
+        let _res = execute(
+            deps.as_mut(),
+            mock_env(),
+            info,
+            ExecuteMsg::TokenSend {
+                recipient: None,
+                route: None,
+                memo: None,
+                on_behalf_of: None,
+                category: None,
+            },
+        )
+        .unwrap();
+
+        // only the owner can change the minimums, cw20 tokens use their contract address
+        let msg = ExecuteMsg::SetMinAmount {
+            min_amount: coins(100, "token"),
+        };
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("anyone", &[]),
+            msg.clone(),
+        );
+        match res {
+            Err(ContractError::Unauthorized {}) => {}
+            _ => panic!("Must return unauthorized error"),
+        }
+        let _res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+
+        let info = mock_info("anyone", &coins(1, "uusd"));
+        let _res = execute(
+            deps.as_mut(),
+            mock_env(),
+            info,
+            ExecuteMsg::TokenSend {
+                recipient: None,
+                route: None,
+                memo: None,
+                on_behalf_of: None,
+                category: None,
+            },
+        )
+        .unwrap();
+        let msg = ExecuteMsg::Receive(Cw20ReceiveMsg {
+            sender: "anyone".to_string(),
+            amount: Uint128::new(99),
+            msg: Binary::default(),
+        });
+        let res = execute(deps.as_mut(), mock_env(), mock_info("token", &[]), msg);
+        match res {
+            Err(ContractError::BelowMinimum { denom, min, got }) => {
+                assert_eq!(denom, "token");
+                assert_eq!(min, Uint128::new(100));
+                assert_eq!(got, Uint128::new(99));
+            }
+            _ => panic!("Must return BelowMinimum error"),
+        }
+    }
+
+    #[test]
+    fn max_amount() {
+        let mut deps = mock_dependencies(&[]);
+
+        let msg = InstantiateMsg {
+            receiver: "treasury".to_string(),
+            max_amount: coins(1000, "uusd"),
+            ..Default::default()
+        };
+        let _res = instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+
+        let send = ExecuteMsg::TokenSend {
+            recipient: None,
+            route: None,
+            memo: None,
+            on_behalf_of: None,
+            category: None,
+        };
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("anyone", &coins(1001, "uusd")),
+            send.clone(),
+        );
+        match res {
+            Err(ContractError::AmountTooLarge { denom, max }) => {
+                assert_eq!(denom, "uusd");
+                assert_eq!(max, Uint128::new(1000));
+            }
+            _ => panic!("Must return AmountTooLarge error"),
+        }
+        let _res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("anyone", &[coin(1000, "uusd"), coin(5000, "uluna")]),
+            send,
+        )
+        .unwrap();
+
+        // cw20 tokens use their contract address
+        let msg = ExecuteMsg::SetMaxAmount {
+            max_amount: coins(50, "token"),
+        };
+        let _res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+        let msg = ExecuteMsg::Receive(Cw20ReceiveMsg {
+            sender: "anyone".to_string(),
+            amount: Uint128::new(51),
+            msg: Binary::default(),
+        });
+        let res = execute(deps.as_mut(), mock_env(), mock_info("token", &[]), msg);
+        match res {
+            Err(ContractError::AmountTooLarge { denom, max }) => {
+                assert_eq!(denom, "token");
+                assert_eq!(max, Uint128::new(50));
+            }
+            _ => panic!("Must return AmountTooLarge error"),
+        }
+    }
+
+    #[test]
+    fn refund_denoms() {
+        let mut deps = mock_dependencies(&[]);
+
+        let msg = InstantiateMsg {
+            receiver: "treasury".to_string(),
+            accepted_denoms: vec!["uusd".to_string(), "ukrw".to_string()],
+            ..Default::default()
+        };
+        let info = mock_info("creator", &[]);
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let info = mock_info(
+            "anyone",
+            &[
+                coin(100, "uusd"),
+                coin(5, "uluna"),
+                coin(7, "ukrw"),
+                coin(3, "umnt"),
+            ],
+        );
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            info,
+            ExecuteMsg::TokenSend {
+                recipient: None,
+                route: None,
+                memo: None,
+                on_behalf_of: None,
+                category: None,
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages,
+            vec![
+                SubMsg::new(BankMsg::Send {
+                    to_address: "treasury".to_string(),
+                    amount: vec![coin(100, "uusd"), coin(7, "ukrw")],
+                }),
+                SubMsg::new(BankMsg::Send {
+                    to_address: "anyone".to_string(),
+                    amount: vec![coin(5, "uluna"), coin(3, "umnt")],
+                }),
+            ]
+        );
+        assert_eq!(
+            res.attributes,
+            vec![
+                attr("action", "send"),
+                attr("sender", "anyone"),
+                attr("amount", "100uusd,7ukrw"),
+                attr("recipient", "treasury"),
+                attr("refund", "5uluna,3umnt"),
+                attr("receipt_id", "1"),
+            ]
+        );
+
+        // the history only holds what was forwarded
+        let msg = QueryMsg::TransferHistory {
+            start_after: None,
+            limit: None,
+        };
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let value: TransferHistoryResponse = from_json(&res).unwrap();
+        assert_eq!(
+            value.transfers[0].amount,
+            vec![coin(100, "uusd"), coin(7, "ukrw")]
+        );
+
+        // accepting everything again forwards all denoms
+        let msg = ExecuteMsg::SetAcceptedDenoms {
+            accepted_denoms: vec![],
+        };
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("anyone", &[]),
+            msg.clone(),
+        );
+        match res {
+            Err(ContractError::Unauthorized {}) => {}
+            _ => panic!("Must return unauthorized error"),
+        }
+        let _res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+        let info = mock_info("anyone", &[coin(100, "uusd"), coin(5, "uluna")]);
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            info,
+            ExecuteMsg::TokenSend {
+                recipient: None,
+                route: None,
+                memo: None,
+                on_behalf_of: None,
+                category: None,
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: "treasury".to_string(),
+                amount: vec![coin(100, "uusd"), coin(5, "uluna")],
+            })]
+        );
+    }
+
+    #[test]
+    fn funds_policy() {
+        let mut deps = mock_dependencies(&[]);
+
+        let msg = InstantiateMsg {
+            receiver: "treasury".to_string(),
+            accepted_denoms: vec!["ukrw".to_string()],
+            funds_policy: FundsPolicy::ForwardAll,
+            ..Default::default()
+        };
+        let info = mock_info("creator", &[]);
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let send = ExecuteMsg::TokenSend {
+            recipient: None,
+            route: None,
+            memo: None,
+            on_behalf_of: None,
+            category: None,
+        };
+
+        // a deposit needs an accepted denom, uusd is not special
+        let info = mock_info("anyone", &[coin(100, "uusd"), coin(5, "uluna")]);
+        let res = execute(deps.as_mut(), mock_env(), info, send.clone());
+        match res {
+            Err(ContractError::UnsupportedDenom { denom }) => assert_eq!(denom, "uusd"),
+            _ => panic!("Must return UnsupportedDenom error"),
+        }
+
+        // everything attached is forwarded, nothing is refunded
+        let info = mock_info("anyone", &[coin(7, "ukrw"), coin(5, "uluna")]);
+        let res = execute(deps.as_mut(), mock_env(), info, send.clone()).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: "treasury".to_string(),
+                amount: vec![coin(7, "ukrw"), coin(5, "uluna")],
+            })]
+        );
+        assert_eq!(
+            res.attributes,
+            vec![
+                attr("action", "send"),
+                attr("sender", "anyone"),
+                attr("amount", "7ukrw,5uluna"),
+                attr("recipient", "treasury"),
+                attr("receipt_id", "1"),
+            ]
+        );
+
+        // only the owner can change the policy
+        let msg = ExecuteMsg::SetFundsPolicy {
+            funds_policy: FundsPolicy::RejectMixed,
+        };
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("anyone", &[]),
+            msg.clone(),
+        );
+        match res {
+            Err(ContractError::Unauthorized {}) => {}
+            _ => panic!("Must return unauthorized error"),
+        }
+        let _res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+
+        // mixed deposits are rejected as a whole
+        let info = mock_info("anyone", &[coin(7, "ukrw"), coin(5, "uluna")]);
+        let res = execute(deps.as_mut(), mock_env(), info, send.clone());
+        match res {
+            Err(ContractError::MixedDenomsNotAllowed {}) => {}
+            _ => panic!("Must return MixedDenomsNotAllowed error"),
+        }
+        let info = mock_info("anyone", &[coin(7, "ukrw"), coin(0, "uluna")]);
+        let res = execute(deps.as_mut(), mock_env(), info, send).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: "treasury".to_string(),
+                amount: coins(7, "ukrw"),
+            })]
+        );
+
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetConfig {}).unwrap();
+        let value: ConfigResponse = from_json(&res).unwrap();
+        assert_eq!(value.funds_policy, FundsPolicy::RejectMixed);
+    }
+
+    #[test]
+    fn tax_tokensend() {
+        let mut deps =
+            mock_dependencies_with_tax(&[], Decimal::percent(1), &[("uusd", 1000000), ("ukrw", 2)]);
+
+        let msg = InstantiateMsg {
+            receiver: "treasury".to_string(),
+            fee_rate: Decimal::percent(10),
+            fee_collector: Some("collector".to_string()),
+            ..Default::default()
+        };
+        let info = mock_info("creator", &[]);
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        // luna is exempt and krw hits its cap
+        let info = mock_info(
+            "anyone",
+            &[coin(1010, "uusd"), coin(1000, "uluna"), coin(1000, "ukrw")],
+        );
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            info,
+            ExecuteMsg::TokenSend {
+                recipient: None,
+                route: None,
+                memo: None,
+                on_behalf_of: None,
+                category: None,
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages,
+            vec![
+                SubMsg::new(BankMsg::Send {
+                    to_address: "collector".to_string(),
+                    amount: vec![coin(100, "uusd"), coin(100, "uluna"), coin(99, "ukrw")],
+                }),
+                SubMsg::new(BankMsg::Send {
+                    to_address: "treasury".to_string(),
+                    amount: vec![coin(900, "uusd"), coin(900, "uluna"), coin(898, "ukrw")],
+                }),
+            ]
+        );
+        assert_eq!(
+            res.attributes,
+            vec![
+                attr("action", "send"),
+                attr("sender", "anyone"),
+                attr("amount", "1010uusd,1000uluna,1000ukrw"),
+                attr("fee", "101uusd,100uluna,100ukrw"),
+                attr("fee_collector", "collector"),
+                attr("recipient", "treasury"),
+                attr("tax", "10uusd,3ukrw"),
+                attr("receipt_id", "1"),
+            ]
+        );
+    }
+
+    #[test]
+    fn recipient_override() {
+        let mut deps = mock_dependencies(&[]);
+
+        let msg = InstantiateMsg {
+            receiver: "treasury".to_string(),
+            receivers: vec![
+                ("alice".to_string(), Decimal::percent(50)),
+                ("bob".to_string(), Decimal::percent(50)),
+            ],
+            ..Default::default()
+        };
+        let info = mock_info("creator", &[]);
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        // the owner routes the whole deposit to the recipient
+        let msg = ExecuteMsg::TokenSend {
+            recipient: Some("carol".to_string()),
+            route: None,
+            memo: None,
+            on_behalf_of: None,
+            category: None,
+        };
+        let info = mock_info("creator", &coins(100, "uusd"));
+        let res = execute(deps.as_mut(), mock_env(), info, msg.clone()).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: "carol".to_string(),
+                amount: coins(100, "uusd"),
+            })]
+        );
+
+        // anyone else still pays the configured receivers
+        let info = mock_info("router", &coins(100, "uusd"));
+        let res = execute(deps.as_mut(), mock_env(), info, msg.clone()).unwrap();
+        assert_eq!(res.attributes[3], attr("recipient", "alice"));
+        assert_eq!(res.attributes[4], attr("recipient", "bob"));
+
+        let allow = ExecuteMsg::SetAllowlist {
+            allowlist: vec!["router".to_string()],
+        };
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("router", &[]),
+            allow.clone(),
+        );
+        match res {
+            Err(ContractError::Unauthorized {}) => {}
+            _ => panic!("Must return unauthorized error"),
+        }
+        let _res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), allow).unwrap();
+
+        let info = mock_info("router", &coins(100, "uusd"));
+        let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: "carol".to_string(),
+                amount: coins(100, "uusd"),
+            })]
+        );
+    }
+
+    #[test]
+    fn receipt() {
+        let mut deps = mock_dependencies(&[]);
+
+        let msg = InstantiateMsg {
+            receiver: "treasury".to_string(),
+            ..Default::default()
+        };
+        let info = mock_info("creator", &[]);
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let info = mock_info("anyone", &[coin(100, "uusd"), coin(5, "uluna")]);
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            info,
+            ExecuteMsg::TokenSend {
+                recipient: None,
+                route: None,
+                memo: None,
+                on_behalf_of: None,
+                category: None,
+            },
+        )
+        .unwrap();
+        let receipt: SendReceipt = from_json(res.data.unwrap()).unwrap();
+        assert_eq!(
+            receipt,
+            SendReceipt {
+                id: 1,
+                sender: "anyone".to_string(),
+                recipients: vec!["treasury".to_string()],
+                amount: vec![coin(100, "uusd"), coin(5, "uluna")],
+                height: 12_345,
+                minted: None,
+                category: None,
+                items: vec![
+                    DenomRouting {
+                        denom: "uusd".to_string(),
+                        amount: Uint128::new(100),
+                        fee: Uint128::zero(),
+                        payouts: vec![(Addr::unchecked("treasury"), Uint128::new(100))],
+                    },
+                    DenomRouting {
+                        denom: "uluna".to_string(),
+                        amount: Uint128::new(5),
+                        fee: Uint128::zero(),
+                        payouts: vec![(Addr::unchecked("treasury"), Uint128::new(5))],
+                    },
+                ],
+            }
+        );
+
+        let msg = ExecuteMsg::Receive(Cw20ReceiveMsg {
+            sender: "anyone".to_string(),
+            amount: Uint128::new(50),
+            msg: Binary::default(),
+        });
+        let res = execute(deps.as_mut(), mock_env(), mock_info("token", &[]), msg).unwrap();
+        let receipt: SendReceipt = from_json(res.data.unwrap()).unwrap();
+        assert_eq!(receipt.id, 2);
+        assert_eq!(receipt.amount, coins(50, "token"));
+
+        // the same receipt can be queried later
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::Receipt { id: 2 }).unwrap();
+        let value: SendReceipt = from_json(&res).unwrap();
+        assert_eq!(value, receipt);
+
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::Receipt { id: 3 });
+        match res {
+            Err(StdError::NotFound { .. }) => {}
+            _ => panic!("Must return not found error"),
+        }
+    }
+
+    #[test]
+    fn memo() {
+        let mut deps = mock_dependencies(&[]);
+
+        let msg = InstantiateMsg {
+            receiver: "treasury".to_string(),
+            ..Default::default()
+        };
+        let info = mock_info("creator", &[]);
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        for memo in &["order-1", "order-2", "order-1"] {
+            let msg = ExecuteMsg::TokenSend {
+                recipient: None,
+                route: None,
+                memo: Some(memo.to_string()),
+                on_behalf_of: None,
+                category: None,
+            };
+            let info = mock_info("alice", &coins(100, "uusd"));
+            let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+            assert_eq!(res.attributes.last(), Some(&attr("memo", *memo)));
+        }
+        let msg = ExecuteMsg::TokenSend {
+            recipient: None,
+            route: None,
+            memo: None,
+            on_behalf_of: None,
+            category: None,
+        };
+        let info = mock_info("alice", &coins(100, "uusd"));
+        let _res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let msg = QueryMsg::TransfersByMemo {
+            memo: "order-1".to_string(),
+            start_after: None,
+            limit: None,
+        };
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let value: TransferHistoryResponse = from_json(&res).unwrap();
+        let ids: Vec<u64> = value.transfers.iter().map(|t| t.id).collect();
+        assert_eq!(ids, vec![1, 3]);
+        assert_eq!(value.transfers[1].memo, Some("order-1".to_string()));
+
+        let msg = QueryMsg::TransfersByMemo {
+            memo: "order-1".to_string(),
+            start_after: Some(1),
+            limit: None,
+        };
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let value: TransferHistoryResponse = from_json(&res).unwrap();
+        let ids: Vec<u64> = value.transfers.iter().map(|t| t.id).collect();
+        assert_eq!(ids, vec![3]);
+    }
+
+    #[test]
+    fn contract_error_to_std_error() {
+        let err: StdError = ContractError::Unauthorized {}.into();
+        match err {
+            StdError::GenericErr { msg, .. } => assert_eq!(msg, "Unauthorized"),
+            _ => panic!("Must return generic error"),
+        }
+
+        // wasm clients keep seeing the same messages as before
+        let err: StdError = ContractError::NoFundsSent {}.into();
+        match err {
+            StdError::GenericErr { msg, .. } => assert_eq!(msg, "No funds were sent"),
+            _ => panic!("Must return generic error"),
+        }
+        let err: StdError = ContractError::BelowMinimum {
+            denom: "uusd".to_string(),
+            min: Uint128::new(10),
+            got: Uint128::new(9),
+        }
+        .into();
+        match err {
+            StdError::GenericErr { msg, .. } => {
+                assert_eq!(msg, "Sent 9uusd, the minimum is 10uusd")
+            }
+            _ => panic!("Must return generic error"),
+        }
+    }
+
+    #[test]
+    fn hold_and_flush() {
+        let mut deps = mock_dependencies(&[]);
+
+        let msg = InstantiateMsg {
+            receiver: "treasury".to_string(),
+            hold: true,
+            flush_threshold: coins(100, "uusd"),
+            ..Default::default()
+        };
+        let info = mock_info("creator", &[]);
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let msg = ExecuteMsg::TokenSend {
+            recipient: None,
+            route: None,
+            memo: None,
+            on_behalf_of: None,
+            category: None,
+        };
+        let info = mock_info("alice", &[coin(60, "uusd"), coin(5, "uluna")]);
+        let res = execute(deps.as_mut(), mock_env(), info, msg.clone()).unwrap();
+        assert_eq!(0, res.messages.len());
+        assert_eq!(res.attributes[3], attr("held", "60uusd,5uluna"));
+
+        // crossing the threshold forwards the uusd, luna has none and waits for a flush
+        let info = mock_info("bob", &coins(40, "uusd"));
+        let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: "treasury".to_string(),
+                amount: coins(100, "uusd"),
+            })]
+        );
+        assert_eq!(res.attributes[4], attr("held", "5uluna"));
+
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("anyone", &[]),
+            ExecuteMsg::Flush {},
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: "treasury".to_string(),
+                amount: coins(5, "uluna"),
+            })]
+        );
+        assert_eq!(res.attributes[0], attr("action", "flush"));
+
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("anyone", &[]),
+            ExecuteMsg::Flush {},
+        );
+        match res {
+            Err(ContractError::NothingToFlush {}) => {}
+            _ => panic!("Must return NothingToFlush error"),
+        }
+
+        let msg = ExecuteMsg::SetHold {
+            hold: false,
+            flush_threshold: vec![],
+        };
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("anyone", &[]),
+            msg.clone(),
+        );
+        match res {
+            Err(ContractError::Unauthorized {}) => {}
+            _ => panic!("Must return unauthorized error"),
+        }
+        let _res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+    }
+
+    #[test]
+    fn timelocked_reset() {
+        let mut deps = mock_dependencies(&[]);
+
+        let msg = InstantiateMsg {
+            receiver: "treasury".to_string(),
+            receiver_delay: Some(Duration::Height(100)),
+            ..Default::default()
+        };
+        let info = mock_info("creator", &[]);
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let msg = ExecuteMsg::ResetReceiver {
+            receiver: "mallory".to_string(),
+        };
+        let info = mock_info("creator", &[]);
+        let mut env = mock_env();
+        env.block.height = 1000;
+        let res = execute(deps.as_mut(), env, info, msg).unwrap();
+        assert_eq!(res.attributes[3], attr("ready_at", "height 1100"));
+
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetPendingReceiver {}).unwrap();
+        let value: PendingReceiverResponse = from_json(&res).unwrap();
+        assert_eq!(
+            value,
+            PendingReceiverResponse {
+                receiver: Some("mallory".to_string()),
+                ready_at: Some(Expiration::AtHeight(1100)),
+                awaiting_acceptance: false,
+            }
+        );
+
+        // deposits keep going to the current receiver until the delay passed
+        let msg = ExecuteMsg::TokenSend {
+            recipient: None,
+            route: None,
+            memo: None,
+            on_behalf_of: None,
+            category: None,
+        };
+        let info = mock_info("anyone", &coins(10, "uusd"));
+        let mut env = mock_env();
+        env.block.height = 1099;
+        let res = execute(deps.as_mut(), env, info, msg.clone()).unwrap();
+        assert_eq!(res.attributes[3], attr("recipient", "treasury"));
+
+        let info = mock_info("anyone", &coins(10, "uusd"));
+        let mut env = mock_env();
+        env.block.height = 1100;
+        let res = execute(deps.as_mut(), env, info, msg).unwrap();
+        assert_eq!(res.attributes[3], attr("recipient", "mallory"));
+
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetReceiver {}).unwrap();
+        let value: ReceiverResponse = from_json(&res).unwrap();
+        assert_eq!("mallory", value.receiver);
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetPendingReceiver {}).unwrap();
+        let value: PendingReceiverResponse = from_json(&res).unwrap();
+        assert_eq!(value.receiver, None);
+
+        // the delay can be measured in seconds as well
+        let msg = ExecuteMsg::SetReceiverDelay {
+            receiver_delay: Some(Duration::Time(3600)),
+        };
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("anyone", &[]),
+            msg.clone(),
+        );
+        match res {
+            Err(ContractError::Unauthorized {}) => {}
+            _ => panic!("Must return unauthorized error"),
+        }
+        let _res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+        let msg = ExecuteMsg::ResetReceiver {
+            receiver: "treasury".to_string(),
+        };
+        let info = mock_info("creator", &[]);
+        let ready_at = mock_env().block.time.seconds() + 3600;
+        let _res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetPendingReceiver {}).unwrap();
+        let value: PendingReceiverResponse = from_json(&res).unwrap();
+        assert_eq!(value.ready_at, Some(Expiration::AtTime(ready_at)));
+    }
+
+    #[test]
+    fn restrict_senders() {
+        let mut deps = mock_dependencies(&[]);
+
+        let msg = InstantiateMsg {
+            receiver: "treasury".to_string(),
+            restrict_senders: true,
+            ..Default::default()
+        };
+        let info = mock_info("creator", &[]);
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let msg = ExecuteMsg::TokenSend {
+            recipient: None,
+            route: None,
+            memo: None,
+            on_behalf_of: None,
+            category: None,
+        };
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("alice", &coins(10, "uusd")),
+            msg.clone(),
+        );
+        match res {
+            Err(ContractError::SenderNotAllowed {}) => {}
+            _ => panic!("Must return SenderNotAllowed error"),
+        }
+
+        let add = ExecuteMsg::AddSender {
+            address: "alice".to_string(),
+        };
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("alice", &[]),
+            add.clone(),
+        );
+        match res {
+            Err(ContractError::Unauthorized {}) => {}
+            _ => panic!("Must return unauthorized error"),
+        }
+        for sender in &["alice", "bob", "carol"] {
+            let add = ExecuteMsg::AddSender {
+                address: sender.to_string(),
+            };
+            let _res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), add).unwrap();
+        }
+        let _res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("alice", &coins(10, "uusd")),
+            msg.clone(),
+        )
+        .unwrap();
+
+        // cw20 deposits are checked against the original sender
+        let receive = ExecuteMsg::Receive(Cw20ReceiveMsg {
+            sender: "dave".to_string(),
+            amount: Uint128::new(10),
+            msg: Binary::default(),
+        });
+        let res = execute(deps.as_mut(), mock_env(), mock_info("token", &[]), receive);
+        match res {
+            Err(ContractError::SenderNotAllowed {}) => {}
+            _ => panic!("Must return SenderNotAllowed error"),
+        }
+
+        let list = QueryMsg::ListAllowedSenders {
+            start_after: None,
+            limit: Some(2),
+        };
+        let res = query(deps.as_ref(), mock_env(), list).unwrap();
+        let value: AllowedSendersResponse = from_json(&res).unwrap();
+        assert_eq!(value.senders, vec!["alice", "bob"]);
+        let list = QueryMsg::ListAllowedSenders {
+            start_after: Some("bob".to_string()),
+            limit: None,
+        };
+        let res = query(deps.as_ref(), mock_env(), list).unwrap();
+        let value: AllowedSendersResponse = from_json(&res).unwrap();
+        assert_eq!(value.senders, vec!["carol"]);
+
+        let remove = ExecuteMsg::RemoveSender {
+            address: "alice".to_string(),
+        };
+        let _res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), remove).unwrap();
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("alice", &coins(10, "uusd")),
+            msg.clone(),
+        );
+        assert!(res.is_err());
+
+        // lifting the restriction lets everyone deposit again
+        let lift = ExecuteMsg::SetRestrictSenders {
+            restrict_senders: false,
+        };
+        let _res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), lift).unwrap();
+        let _res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("alice", &coins(10, "uusd")),
+            msg,
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn rate_limit() {
+        let mut deps = mock_dependencies(&[]);
+
+        let msg = InstantiateMsg {
+            receiver: "treasury".to_string(),
+            rate_limit: Some(RateLimit {
+                per_sender: coins(100, "uusd"),
+                window: 3600,
+                daily_cap: vec![coin(150, "uusd"), coin(10, "token")],
+            }),
+            ..Default::default()
+        };
+        let info = mock_info("creator", &[]);
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let msg = ExecuteMsg::TokenSend {
+            recipient: None,
+            route: None,
+            memo: None,
+            on_behalf_of: None,
+            category: None,
+        };
+        let day_start = 20000 * DAY;
+        let info = mock_info("alice", &coins(70, "uusd"));
+        let mut env = mock_env();
+        env.block.time = Timestamp::from_seconds(day_start + 100);
+        let _res = execute(deps.as_mut(), env, info, msg.clone()).unwrap();
+
+        let info = mock_info("alice", &coins(40, "uusd"));
+        let mut env = mock_env();
+        env.block.time = Timestamp::from_seconds(day_start + 200);
+        let res = execute(deps.as_mut(), env, info, msg.clone());
+        match res {
+            Err(ContractError::RateLimitExceeded { denom, remaining }) => {
+                assert_eq!(denom, "uusd");
+                assert_eq!(remaining, Uint128::new(30));
+            }
+            _ => panic!("Must return RateLimitExceeded error"),
+        }
+
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::GetQuota {
+                address: "alice".to_string(),
+            },
+        )
+        .unwrap();
+        let value: QuotaResponse = from_json(&res).unwrap();
+        assert_eq!(
+            value,
+            QuotaResponse {
+                sender_remaining: coins(30, "uusd"),
+                sender_resets_at: Some(day_start + 3700),
+                daily_remaining: vec![coin(80, "uusd"), coin(10, "token")],
+                daily_resets_at: Some(day_start + DAY),
+            }
+        );
+
+        // the window of a sender starts over, the daily cap only the next day
+        let info = mock_info("alice", &coins(80, "uusd"));
+        let mut env = mock_env();
+        env.block.time = Timestamp::from_seconds(day_start + 3700);
+        let _res = execute(deps.as_mut(), env, info, msg.clone()).unwrap();
+        let info = mock_info("bob", &coins(1, "uusd"));
+        let mut env = mock_env();
+        env.block.time = Timestamp::from_seconds(day_start + 3800);
+        let res = execute(deps.as_mut(), env, info, msg.clone());
+        match res {
+            Err(ContractError::DailyCapExceeded { denom, remaining }) => {
+                assert_eq!(denom, "uusd");
+                assert_eq!(remaining, Uint128::new(0));
+            }
+            _ => panic!("Must return DailyCapExceeded error"),
+        }
+        let info = mock_info("bob", &coins(1, "uusd"));
+        let mut env = mock_env();
+        env.block.time = Timestamp::from_seconds(day_start + DAY);
+        let _res = execute(deps.as_mut(), env, info, msg).unwrap();
+
+        // cw20 tokens are limited by their contract address
+        let receive = ExecuteMsg::Receive(Cw20ReceiveMsg {
+            sender: "bob".to_string(),
+            amount: Uint128::new(11),
+            msg: Binary::default(),
+        });
+        let res = execute(deps.as_mut(), mock_env(), mock_info("token", &[]), receive);
+        match res {
+            Err(ContractError::DailyCapExceeded { denom, .. }) => assert_eq!(denom, "token"),
+            _ => panic!("Must return DailyCapExceeded error"),
+        }
+
+        let msg = ExecuteMsg::SetRateLimit { rate_limit: None };
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("anyone", &[]),
+            msg.clone(),
+        );
+        match res {
+            Err(ContractError::Unauthorized {}) => {}
+            _ => panic!("Must return unauthorized error"),
+        }
+        let _res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+    }
+
+    #[test]
+    fn escrow() {
+        let mut deps = mock_dependencies(&[]);
+
+        let msg = InstantiateMsg {
+            receiver: "treasury".to_string(),
+            fee_rate: Decimal::percent(10),
+            fee_collector: Some("collector".to_string()),
+            escrow: Some(Duration::Time(3600)),
+            ..Default::default()
+        };
+        let _res = instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+
+        // deposits stay in the contract, each in its own escrow
+        let msg = ExecuteMsg::TokenSend {
+            recipient: None,
+            route: None,
+            memo: None,
+            on_behalf_of: None,
+            category: None,
+        };
+        let expires = mock_env().block.time.seconds() + 3600;
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("alice", &coins(100, "uusd")),
+            msg.clone(),
+        )
+        .unwrap();
+        assert!(res.messages.is_empty());
+        assert_eq!(
+            res.attributes,
+            vec![
+                attr("action", "escrow"),
+                attr("sender", "alice"),
+                attr("amount", "100uusd"),
+                attr("receipt_id", "1"),
+                attr("receiver", "treasury"),
+                attr("expires", format!("time {}", expires)),
+            ]
+        );
+        let _res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("bob", &coins(50, "uusd")),
+            msg,
+        )
+        .unwrap();
+
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::EscrowsByReceiver {
+                receiver: "treasury".to_string(),
+                start_after: None,
+                limit: None,
+            },
+        )
+        .unwrap();
+        let value: EscrowsResponse = from_json(&res).unwrap();
+        let ids: Vec<u64> = value.escrows.iter().map(|escrow| escrow.id).collect();
+        assert_eq!(ids, vec![1, 2]);
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::EscrowsBySender {
+                sender: "bob".to_string(),
+                start_after: None,
+                limit: None,
+            },
+        )
+        .unwrap();
+        let value: EscrowsResponse = from_json(&res).unwrap();
+        assert_eq!(
+            value.escrows,
+            vec![EscrowInfo {
+                id: 2,
+                sender: "bob".to_string(),
+                receiver: "treasury".to_string(),
+                amount: coins(50, "uusd"),
+                expires: Expiration::AtTime(expires),
+                hashlock: None,
+                arbiter: None,
+                disputed: false,
+            }]
+        );
+
+        // only the receiver can release, the fee is charged on release
+        let release = ExecuteMsg::Release {
+            id: 1,
+            preimage: None,
+        };
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("alice", &[]),
+            release.clone(),
+        );
+        match res {
+            Err(ContractError::Unauthorized {}) => {}
+            _ => panic!("Must return unauthorized error"),
+        }
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("treasury", &[]),
+            release.clone(),
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages,
+            vec![
+                SubMsg::new(BankMsg::Send {
+                    to_address: "collector".to_string(),
+                    amount: coins(10, "uusd"),
+                }),
+                SubMsg::new(BankMsg::Send {
+                    to_address: "treasury".to_string(),
+                    amount: coins(90, "uusd"),
+                }),
+            ]
+        );
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("treasury", &[]),
+            release,
+        );
+        assert!(res.is_err());
+
+        // the sender can only refund once the escrow expired
+        let refund = ExecuteMsg::Refund { id: 2 };
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("bob", &[]),
+            refund.clone(),
+        );
+        match res {
+            Err(ContractError::EscrowNotExpired {}) => {}
+            _ => panic!("Must return EscrowNotExpired error"),
+        }
+        let mut env = mock_env();
+        env.block.time = Timestamp::from_seconds(expires);
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("alice", &[]),
+            refund.clone(),
+        );
+        match res {
+            Err(ContractError::Unauthorized {}) => {}
+            _ => panic!("Must return unauthorized error"),
+        }
+        let res = execute(deps.as_mut(), env, mock_info("bob", &[]), refund).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: "bob".to_string(),
+                amount: coins(50, "uusd"),
+            })]
+        );
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::Escrow { id: 2 });
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn hashlock_send() {
+        let mut deps = mock_dependencies(&[]);
+
+        let msg = InstantiateMsg {
+            receiver: "treasury".to_string(),
+            ..Default::default()
+        };
+        let _res = instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+
+        // sha256("secret"), the preimage is submitted hex encoded
+        let hash = "2BB80D537B1DA3E38BD30361AA855686BDE0EACD7162FEF6A25FE97BF527A25B";
+        let preimage = hex::encode("secret");
+        let msg = ExecuteMsg::HashlockSend {
+            hash: "not a hash".to_string(),
+            timeout: Duration::Time(3600),
+            memo: None,
+        };
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("alice", &coins(100, "uusd")),
+            msg,
+        );
+        match res {
+            Err(ContractError::InvalidHash {}) => {}
+            _ => panic!("Must return InvalidHash error"),
+        }
+        let msg = ExecuteMsg::HashlockSend {
+            hash: hash.to_string(),
+            timeout: Duration::Time(3600),
+            memo: None,
+        };
+        let expires = mock_env().block.time.seconds() + 3600;
+        for sender in ["alice", "bob"] {
+            let res = execute(
+                deps.as_mut(),
+                mock_env(),
+                mock_info(sender, &coins(100, "uusd")),
+                msg.clone(),
+            )
+            .unwrap();
+            assert!(res.messages.is_empty());
+        }
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::Escrow { id: 1 }).unwrap();
+        let value: EscrowInfo = from_json(&res).unwrap();
+        assert_eq!(value.hashlock, Some(hash.to_lowercase()));
+        assert_eq!(value.expires, Expiration::AtTime(expires));
+
+        // the receiver needs the preimage of the hash
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("treasury", &[]),
+            ExecuteMsg::Release {
+                id: 1,
+                preimage: None,
+            },
+        );
+        match res {
+            Err(ContractError::WrongPreimage {}) => {}
+            _ => panic!("Must return WrongPreimage error"),
+        }
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("treasury", &[]),
+            ExecuteMsg::Release {
+                id: 1,
+                preimage: Some(hex::encode("guess")),
+            },
+        );
+        match res {
+            Err(ContractError::WrongPreimage {}) => {}
+            _ => panic!("Must return WrongPreimage error"),
+        }
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("treasury", &[]),
+            ExecuteMsg::Release {
+                id: 1,
+                preimage: Some(preimage.clone()),
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: "treasury".to_string(),
+                amount: coins(100, "uusd"),
+            })]
+        );
+        assert!(res.attributes.contains(&attr("preimage", preimage.clone())));
+
+        // after the timeout the receiver cannot claim anymore, the sender gets the refund
+        let mut env = mock_env();
+        env.block.time = Timestamp::from_seconds(expires);
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("treasury", &[]),
+            ExecuteMsg::Release {
+                id: 2,
+                preimage: Some(preimage),
+            },
+        );
+        match res {
+            Err(ContractError::HashlockExpired {}) => {}
+            _ => panic!("Must return HashlockExpired error"),
+        }
+        let res = execute(
+            deps.as_mut(),
+            env,
+            mock_info("bob", &[]),
+            ExecuteMsg::Refund { id: 2 },
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: "bob".to_string(),
+                amount: coins(100, "uusd"),
+            })]
+        );
+    }
+
+    #[test]
+    fn approval_gate() {
+        let mut deps = mock_dependencies(&[]);
+
+        let msg = InstantiateMsg {
+            receiver: "treasury".to_string(),
+            approval_threshold: coins(1000, "uusd"),
+            ..Default::default()
+        };
+        let _res = instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+
+        // deposits below the threshold are forwarded right away
+        let msg = ExecuteMsg::TokenSend {
+            recipient: None,
+            route: None,
+            memo: None,
+            on_behalf_of: None,
+            category: None,
+        };
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("alice", &coins(999, "uusd")),
+            msg.clone(),
+        )
+        .unwrap();
+        assert_eq!(res.messages.len(), 1);
+        for sender in ["alice", "bob"] {
+            let res = execute(
+                deps.as_mut(),
+                mock_env(),
+                mock_info(sender, &coins(1000, "uusd")),
+                msg.clone(),
+            )
+            .unwrap();
+            assert!(res.messages.is_empty());
+            assert_eq!(res.attributes[0], attr("action", "pending"));
+        }
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &coins(5000, "uusd")),
+            ExecuteMsg::TokenSend {
+                recipient: Some("vendor".to_string()),
+                route: None,
+                memo: None,
+                on_behalf_of: None,
+                category: None,
+            },
+        )
+        .unwrap();
+        assert!(res.messages.is_empty());
+
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::PendingForwards {
+                start_after: Some(2),
+                limit: None,
+            },
+        )
+        .unwrap();
+        let value: PendingForwardsResponse = from_json(&res).unwrap();
+        assert_eq!(
+            value.pending,
+            vec![
+                PendingForwardInfo {
+                    id: 3,
+                    sender: "bob".to_string(),
+                    recipient: None,
+                    amount: coins(1000, "uusd"),
+                },
+                PendingForwardInfo {
+                    id: 4,
+                    sender: "creator".to_string(),
+                    recipient: Some("vendor".to_string()),
+                    amount: coins(5000, "uusd"),
+                },
+            ]
+        );
+
+        // only the owner can approve or reject
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("alice", &[]),
+            ExecuteMsg::Approve { id: 2 },
+        );
+        match res {
+            Err(ContractError::Unauthorized {}) => {}
+            _ => panic!("Must return unauthorized error"),
+        }
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            ExecuteMsg::Approve { id: 2 },
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: "treasury".to_string(),
+                amount: coins(1000, "uusd"),
+            })]
+        );
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            ExecuteMsg::Approve { id: 4 },
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: "vendor".to_string(),
+                amount: coins(5000, "uusd"),
+            })]
+        );
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            ExecuteMsg::Reject { id: 3 },
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: "bob".to_string(),
+                amount: coins(1000, "uusd"),
+            })]
+        );
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            ExecuteMsg::Reject { id: 3 },
+        );
+        assert!(res.is_err());
+
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::PendingForwards {
+                start_after: None,
+                limit: None,
+            },
+        )
+        .unwrap();
+        let value: PendingForwardsResponse = from_json(&res).unwrap();
+        assert!(value.pending.is_empty());
+    }
+
+    #[test]
+    fn receiver_callbacks() {
+        let mut deps = mock_dependencies(&[]);
+        mock_contract(&mut deps.querier, "vault");
+
+        let deposit = Binary::from(br#"{"deposit":{}}"#);
+        let msg = InstantiateMsg {
+            receiver: "treasury".to_string(),
+            receivers: vec![
+                ("treasury".to_string(), Decimal::percent(50)),
+                ("vault".to_string(), Decimal::percent(50)),
+            ],
+            ..Default::default()
+        };
+        let _res = instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+        let msg = ExecuteMsg::SetCallbacks {
+            callbacks: vec![("vault".to_string(), deposit.clone())],
+        };
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("anyone", &[]),
+            msg.clone(),
+        );
+        match res {
+            Err(ContractError::Unauthorized {}) => {}
+            _ => panic!("Must return unauthorized error"),
+        }
+        let _res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+
+        // the vault is executed with its share attached, the treasury gets a bank send
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("alice", &coins(100, "uusd")),
+            ExecuteMsg::TokenSend {
+                recipient: None,
+                route: None,
+                memo: None,
+                on_behalf_of: None,
+                category: None,
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages,
+            vec![
+                SubMsg::new(BankMsg::Send {
+                    to_address: "treasury".to_string(),
+                    amount: coins(50, "uusd"),
+                }),
+                SubMsg::new(WasmMsg::Execute {
+                    contract_addr: "vault".to_string(),
+                    msg: deposit.clone(),
+                    funds: coins(50, "uusd"),
+                }),
+            ]
+        );
+
+        // cw20 tokens are sent to the vault along with the callback
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("token", &[]),
+            ExecuteMsg::Receive(Cw20ReceiveMsg {
+                sender: "alice".to_string(),
+                amount: Uint128::new(100),
+                msg: Binary::default(),
+            }),
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages[1],
+            SubMsg::new(WasmMsg::Execute {
+                contract_addr: "token".to_string(),
+                msg: to_json_binary(&Cw20ExecuteMsg::Send {
+                    contract: "vault".to_string(),
+                    amount: Uint128::new(50),
+                    msg: deposit,
+                })
+                .unwrap(),
+                funds: vec![],
+            })
+        );
+    }
+
+    #[test]
+    fn staking_mode() {
+        let mut deps = mock_dependencies(&[]);
+        deps.querier.update_staking(
+            "uluna",
+            &[],
+            &[FullDelegation {
+                delegator: Addr::unchecked(MOCK_CONTRACT_ADDR),
+                validator: "terravaloper1".to_string(),
+                amount: coin(500, "uluna"),
+                can_redelegate: coin(500, "uluna"),
+                accumulated_rewards: coins(7, "uluna"),
+            }],
+        );
+
+        let msg = InstantiateMsg {
+            receiver: "treasury".to_string(),
+            validator: Some("terravaloper1".to_string()),
+            ..Default::default()
+        };
+        let _res = instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+
+        // the bonded denom is delegated, everything else is forwarded
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("alice", &[coin(100, "uluna"), coin(50, "uusd")]),
+            ExecuteMsg::TokenSend {
+                recipient: None,
+                route: None,
+                memo: None,
+                on_behalf_of: None,
+                category: None,
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages,
+            vec![
+                SubMsg::new(BankMsg::Send {
+                    to_address: "treasury".to_string(),
+                    amount: coins(50, "uusd"),
+                }),
+                SubMsg::new(StakingMsg::Delegate {
+                    validator: "terravaloper1".to_string(),
+                    amount: coin(100, "uluna"),
+                }),
+            ]
+        );
+
+        // the owner withdraws the rewards and unbonds to the receiver
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("alice", &[]),
+            ExecuteMsg::WithdrawRewards {},
+        );
+        match res {
+            Err(ContractError::Unauthorized {}) => {}
+            _ => panic!("Must return unauthorized error"),
+        }
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            ExecuteMsg::WithdrawRewards {},
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages,
+            vec![
+                SubMsg::new(DistributionMsg::SetWithdrawAddress {
+                    address: "treasury".to_string(),
+                }),
+                SubMsg::new(DistributionMsg::WithdrawDelegatorReward {
+                    validator: "terravaloper1".to_string(),
+                }),
+            ]
+        );
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            ExecuteMsg::Undelegate {
+                amount: coin(200, "uluna"),
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(StakingMsg::Undelegate {
+                validator: "terravaloper1".to_string(),
+                amount: coin(200, "uluna"),
+            })]
+        );
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::GetClaimable {
+                address: "treasury".to_string(),
+            },
+        )
+        .unwrap();
+        let value: ClaimableResponse = from_json(&res).unwrap();
+        assert_eq!(value.claimable, vec![]);
+        assert_eq!(
+            value.unbonding,
+            vec![Unbonding {
+                amount: coin(200, "uluna"),
+                completes_at: mock_env().block.time.seconds() + UNBONDING_PERIOD,
+            }]
+        );
+
+        // the contract keeps funds for others, the receiver cannot withdraw them before the
+        // undelegation completed
+        deps.querier
+            .update_balance(MOCK_CONTRACT_ADDR, coins(300, "uluna"));
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("treasury", &[]),
+            ExecuteMsg::Withdraw {
+                denom: None,
+                amount: None,
+            },
+        );
+        match res {
+            Err(ContractError::NothingToWithdraw {}) => {}
+            _ => panic!("Must return NothingToWithdraw error"),
+        }
+        let mut env = mock_env();
+        env.block.time = env.block.time.plus_seconds(UNBONDING_PERIOD);
+        let res = execute(
+            deps.as_mut(),
+            env,
+            mock_info("treasury", &[]),
+            ExecuteMsg::Withdraw {
+                denom: None,
+                amount: None,
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: "treasury".to_string(),
+                amount: coins(200, "uluna"),
+            })]
+        );
+
+        // a new validator takes over the delegation, without one staking mode ends
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            ExecuteMsg::SetValidator {
+                validator: Some("terravaloper2".to_string()),
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(StakingMsg::Redelegate {
+                src_validator: "terravaloper1".to_string(),
+                dst_validator: "terravaloper2".to_string(),
+                amount: coin(500, "uluna"),
+            })]
+        );
+        let _res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            ExecuteMsg::SetValidator { validator: None },
+        )
+        .unwrap();
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            ExecuteMsg::WithdrawRewards {},
+        );
+        match res {
+            Err(ContractError::NoValidator {}) => {}
+            _ => panic!("Must return NoValidator error"),
+        }
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("alice", &coins(100, "uluna")),
+            ExecuteMsg::TokenSend {
+                recipient: None,
+                route: None,
+                memo: None,
+                on_behalf_of: None,
+                category: None,
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: "treasury".to_string(),
+                amount: coins(100, "uluna"),
+            })]
+        );
+    }
+
+    #[test]
+    fn auto_swap() {
+        let mut deps = mock_dependencies(&coins(30, "uusd"));
+
+        let msg = InstantiateMsg {
+            receiver: "treasury".to_string(),
+            swap: Some(SwapSettings {
+                target_denom: "uusd".to_string(),
+                pairs: vec![("uluna".to_string(), "pair".to_string())],
+                max_slippage: Decimal::percent(1),
+            }),
+            ..Default::default()
+        };
+        let _res = instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+
+        // luna is sold on the pair first, uusd is forwarded right away
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("alice", &[coin(100, "uluna"), coin(20, "uusd")]),
+            ExecuteMsg::TokenSend {
+                recipient: None,
+                route: None,
+                memo: None,
+                on_behalf_of: None,
+                category: None,
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages,
+            vec![
+                SubMsg::reply_on_success(
+                    WasmMsg::Execute {
+                        contract_addr: "pair".to_string(),
+                        msg: to_json_binary(&PairExecuteMsg::Swap {
+                            offer_asset: Asset {
+                                info: AssetInfo::NativeToken {
+                                    denom: "uluna".to_string(),
+                                },
+                                amount: Uint128::new(100),
+                            },
+                            belief_price: None,
+                            max_spread: Some(Decimal::percent(1)),
+                            to: None,
+                        })
+                        .unwrap(),
+                        funds: coins(100, "uluna"),
+                    },
+                    SWAP_REPLY_ID,
+                ),
+                SubMsg::new(BankMsg::Send {
+                    to_address: "treasury".to_string(),
+                    amount: coins(20, "uusd"),
+                }),
+            ]
+        );
+        assert!(res.attributes.contains(&attr("swapped", "100uluna")));
+
+        // the reply forwards what the uusd balance grew by
+        deps.querier
+            .update_balance(MOCK_CONTRACT_ADDR, vec![coin(30 + 9500, "uusd")]);
+        let reply_msg = Reply {
+            id: SWAP_REPLY_ID,
+            result: SubMsgResult::Ok(SubMsgResponse {
+                events: vec![],
+                data: None,
+            }),
+        };
+        let res = reply(deps.as_mut(), mock_env(), reply_msg).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: "treasury".to_string(),
+                amount: coins(9500, "uusd"),
+            })]
+        );
+        assert_eq!(res.attributes[1], attr("proceeds", "9500uusd"));
+
+        // one-off recipients get the coins as they are
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &coins(100, "uluna")),
+            ExecuteMsg::TokenSend {
+                recipient: Some("vendor".to_string()),
+                route: None,
+                memo: None,
+                on_behalf_of: None,
+                category: None,
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: "vendor".to_string(),
+                amount: coins(100, "uluna"),
+            })]
+        );
+    }
+
+    #[test]
+    fn auto_swap_several_denoms() {
+        let mut deps = mock_dependencies(&[]);
+
+        let msg = InstantiateMsg {
+            receiver: "treasury".to_string(),
+            pull: true,
+            swap: Some(SwapSettings {
+                target_denom: "uusd".to_string(),
+                pairs: vec![
+                    ("uluna".to_string(), "luna_pair".to_string()),
+                    ("ukrw".to_string(), "krw_pair".to_string()),
+                ],
+                max_slippage: Decimal::percent(1),
+            }),
+            ..Default::default()
+        };
+        let _res = instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("alice", &[coin(100, "uluna"), coin(5000, "ukrw")]),
+            ExecuteMsg::TokenSend {
+                recipient: None,
+                route: None,
+                memo: None,
+                on_behalf_of: None,
+                category: None,
+            },
+        )
+        .unwrap();
+        assert_eq!(res.messages.len(), 2);
+
+        // the proceeds of the first swap stay claimable in the contract, the second swap is
+        // measured from there
+        let reply_msg = Reply {
+            id: SWAP_REPLY_ID,
+            result: SubMsgResult::Ok(SubMsgResponse {
+                events: vec![],
+                data: None,
+            }),
+        };
+        deps.querier
+            .update_balance(MOCK_CONTRACT_ADDR, coins(9500, "uusd"));
+        let res = reply(deps.as_mut(), mock_env(), reply_msg.clone()).unwrap();
+        assert_eq!(res.attributes[1], attr("proceeds", "9500uusd"));
+        deps.querier
+            .update_balance(MOCK_CONTRACT_ADDR, coins(9500 + 400, "uusd"));
+        let res = reply(deps.as_mut(), mock_env(), reply_msg).unwrap();
+        assert_eq!(res.attributes[1], attr("proceeds", "400uusd"));
+
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::GetClaimable {
+                address: "treasury".to_string(),
+            },
+        )
+        .unwrap();
+        let value: ClaimableResponse = from_json(&res).unwrap();
+        assert_eq!(value.claimable, coins(9900, "uusd"));
+    }
+
+    #[test]
+    fn hold_failed_payouts() {
+        let mut deps = mock_dependencies(&[]);
+
+        let msg = InstantiateMsg {
+            receiver: "treasury".to_string(),
+            fee_rate: Decimal::percent(10),
+            fee_collector: Some("collector".to_string()),
+            hold_failed: true,
+            ..Default::default()
+        };
+        let _res = instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+
+        // the receiver is paid with a submessage, the fee is not
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("alice", &coins(100, "uusd")),
+            ExecuteMsg::TokenSend {
+                recipient: None,
+                route: None,
+                memo: None,
+                on_behalf_of: None,
+                category: None,
+            },
+        )
+        .unwrap();
+        let send = BankMsg::Send {
+            to_address: "treasury".to_string(),
+            amount: coins(90, "uusd"),
+        };
+        assert_eq!(
+            res.messages,
+            vec![
+                SubMsg::new(BankMsg::Send {
+                    to_address: "collector".to_string(),
+                    amount: coins(10, "uusd"),
+                }),
+                SubMsg::reply_always(send.clone(), PAYOUT_REPLY_ID + 1),
+            ]
+        );
+
+        // a failed payout is held instead of reverting the deposit
+        let failed = Reply {
+            id: PAYOUT_REPLY_ID + 1,
+            result: SubMsgResult::Err("receiver is blocked".to_string()),
+        };
+        let res = reply(deps.as_mut(), mock_env(), failed.clone()).unwrap();
+        assert!(res.messages.is_empty());
+        assert_eq!(res.attributes[0], attr("action", "payout_failed"));
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::FailedPayouts {
+                start_after: None,
+                limit: None,
+            },
+        )
+        .unwrap();
+        let value: FailedPayoutsResponse = from_json(&res).unwrap();
+        assert_eq!(
+            value.payouts,
+            vec![FailedPayoutInfo {
+                id: 1,
+                recipient: "treasury".to_string(),
+                msg: send.clone().into(),
+                error: "receiver is blocked".to_string(),
+            }]
+        );
+
+        // anyone can retry it, once it goes through the record is dropped
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("anyone", &[]),
+            ExecuteMsg::RetryFailed { id: 1 },
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::reply_always(send, PAYOUT_REPLY_ID + 1)]
+        );
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("anyone", &[]),
+            ExecuteMsg::RetryFailed { id: 1 },
+        );
+        assert!(res.is_err());
+        let succeeded = Reply {
+            id: PAYOUT_REPLY_ID + 1,
+            result: SubMsgResult::Ok(SubMsgResponse {
+                events: vec![],
+                data: None,
+            }),
+        };
+        let _res = reply(deps.as_mut(), mock_env(), succeeded).unwrap();
+        assert!(!PAYOUTS.has(deps.as_ref().storage, 1));
+        assert!(!FAILED_PAYOUTS.has(deps.as_ref().storage, 1));
+        let res = reply(deps.as_mut(), mock_env(), failed);
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn forward_stats() {
+        let mut deps = mock_dependencies(&[]);
+
+        let msg = InstantiateMsg {
+            receiver: "treasury".to_string(),
+            ..Default::default()
+        };
+        let _res = instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::Stats {}).unwrap();
+        let value: StatsResponse = from_json(&res).unwrap();
+        assert_eq!(value.forwards, 0);
+        assert_eq!(value.last_forward_height, None);
+
+        let msg = ExecuteMsg::TokenSend {
+            recipient: None,
+            route: None,
+            memo: None,
+            on_behalf_of: None,
+            category: None,
+        };
+        let _res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("alice", &[coin(100, "uusd"), coin(5, "uluna")]),
+            msg.clone(),
+        )
+        .unwrap();
+        let mut env = mock_env();
+        env.block.height += 10;
+        let _res = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("bob", &coins(50, "uusd")),
+            msg,
+        )
+        .unwrap();
+        let _res = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("token", &[]),
+            ExecuteMsg::Receive(Cw20ReceiveMsg {
+                sender: "alice".to_string(),
+                amount: Uint128::new(7),
+                msg: Binary::default(),
+            }),
+        )
+        .unwrap();
+
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::Stats {}).unwrap();
+        let value: StatsResponse = from_json(&res).unwrap();
+        assert_eq!(
+            value,
+            StatsResponse {
+                forwarded: vec![coin(150, "uusd"), coin(5, "uluna"), coin(7, "token")],
+                forwards: 3,
+                last_forward_height: Some(env.block.height),
+            }
+        );
+    }
+
+    #[test]
+    fn contributor_leaderboard() {
+        let mut deps = mock_dependencies(&[]);
+
+        let msg = InstantiateMsg {
+            receiver: "charity".to_string(),
+            ..Default::default()
+        };
+        let _res = instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+
+        let msg = ExecuteMsg::TokenSend {
+            recipient: None,
+            route: None,
+            memo: None,
+            on_behalf_of: None,
+            category: None,
+        };
+        for (sender, funds) in [
+            ("alice", vec![coin(100, "uusd"), coin(3, "uluna")]),
+            ("bob", coins(250, "uusd")),
+            ("carol", coins(40, "uusd")),
+            ("alice", coins(200, "uusd")),
+        ] {
+            let _res = execute(
+                deps.as_mut(),
+                mock_env(),
+                mock_info(sender, &funds),
+                msg.clone(),
+            )
+            .unwrap();
+        }
+
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::Contributions {
+                sender: "alice".to_string(),
+            },
+        )
+        .unwrap();
+        let value: ContributionsResponse = from_json(&res).unwrap();
+        assert_eq!(
+            value.contributions,
+            vec![coin(3, "uluna"), coin(300, "uusd")]
+        );
+
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::TopContributors {
+                denom: "uusd".to_string(),
+                limit: Some(2),
+            },
+        )
+        .unwrap();
+        let value: TopContributorsResponse = from_json(&res).unwrap();
+        assert_eq!(
+            value.contributors,
+            vec![
+                Contributor {
+                    sender: "alice".to_string(),
+                    amount: Uint128::new(300),
+                },
+                Contributor {
+                    sender: "bob".to_string(),
+                    amount: Uint128::new(250),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn fallback_receiver() {
+        let mut deps = mock_dependencies(&[]);
+        mock_contract(&mut deps.querier, "vault");
+
+        let deposit = Binary::from(br#"{"deposit":{}}"#);
+        let msg = InstantiateMsg {
+            receiver: "vault".to_string(),
+            callbacks: vec![("vault".to_string(), deposit.clone())],
+            fallback_receiver: Some("reserve".to_string()),
+            ..Default::default()
+        };
+        let _res = instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("alice", &coins(100, "uusd")),
+            ExecuteMsg::TokenSend {
+                recipient: None,
+                route: None,
+                memo: None,
+                on_behalf_of: None,
+                category: None,
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::reply_always(
+                WasmMsg::Execute {
+                    contract_addr: "vault".to_string(),
+                    msg: deposit,
+                    funds: coins(100, "uusd"),
+                },
+                PAYOUT_REPLY_ID + 1,
+            )]
+        );
+
+        // the vault rejects the deposit, the funds go to the fallback receiver instead
+        let failed = |id| Reply {
+            id: PAYOUT_REPLY_ID + id,
+            result: SubMsgResult::Err("vault is closed".to_string()),
+        };
+        let res = reply(deps.as_mut(), mock_env(), failed(1)).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::reply_always(
+                BankMsg::Send {
+                    to_address: "reserve".to_string(),
+                    amount: coins(100, "uusd"),
+                },
+                PAYOUT_REPLY_ID + 2,
+            )]
+        );
+        assert_eq!(
+            res.attributes[..4],
+            [
+                attr("action", "fallback"),
+                attr("payout_id", "1"),
+                attr("failed_recipient", "vault"),
+                attr("recipient", "reserve"),
+            ]
+        );
+
+        // without hold_failed a failing fallback reverts the deposit
+        let res = reply(deps.as_mut(), mock_env(), failed(2));
+        match res {
+            Err(ContractError::PayoutFailed { error }) => assert_eq!(error, "vault is closed"),
+            _ => panic!("Must return PayoutFailed error"),
+        }
+
+        // cw20 tokens are transferred to the fallback receiver
+        let _res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("token", &[]),
+            ExecuteMsg::Receive(Cw20ReceiveMsg {
+                sender: "alice".to_string(),
+                amount: Uint128::new(7),
+                msg: Binary::default(),
+            }),
+        )
+        .unwrap();
+        let res = reply(deps.as_mut(), mock_env(), failed(3)).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::reply_always(
+                WasmMsg::Execute {
+                    contract_addr: "token".to_string(),
+                    msg: to_json_binary(&Cw20ExecuteMsg::Transfer {
+                        recipient: "reserve".to_string(),
+                        amount: Uint128::new(7),
+                    })
+                    .unwrap(),
+                    funds: vec![],
+                },
+                PAYOUT_REPLY_ID + 4,
+            )]
+        );
+    }
+
+    #[test]
+    fn sweep_stuck_funds() {
+        let mut deps = mock_dependencies(&[coin(1000, "uusd"), coin(50, "ukrw")]);
+
+        let msg = InstantiateMsg {
+            receiver: "treasury".to_string(),
+            hold: true,
+            ..Default::default()
+        };
+        let _res = instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+        // 200uusd of the balance are held for the receiver
+        let _res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("alice", &coins(200, "uusd")),
+            ExecuteMsg::TokenSend {
+                recipient: None,
+                route: None,
+                memo: None,
+                on_behalf_of: None,
+                category: None,
+            },
+        )
+        .unwrap();
+
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("alice", &[]),
+            ExecuteMsg::Sweep { denom: None },
+        );
+        match res {
+            Err(ContractError::Unauthorized {}) => {}
+            _ => panic!("Must return unauthorized error"),
+        }
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            ExecuteMsg::Sweep {
+                denom: Some("uusd".to_string()),
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: "treasury".to_string(),
+                amount: coins(800, "uusd"),
+            })]
+        );
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            ExecuteMsg::Sweep { denom: None },
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: "treasury".to_string(),
+                amount: vec![coin(800, "uusd"), coin(50, "ukrw")],
+            })]
+        );
+
+        // once only tracked funds are left there is nothing to sweep
+        deps.querier
+            .update_balance(MOCK_CONTRACT_ADDR, coins(200, "uusd"));
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            ExecuteMsg::Sweep { denom: None },
+        );
+        match res {
+            Err(ContractError::NothingToSweep {}) => {}
+            _ => panic!("Must return NothingToSweep error"),
+        }
+    }
+
+    #[test]
+    fn cw20_sweep_and_pull() {
+        let mut deps = mock_dependencies(&[]);
+        deps.querier.update_wasm(|query| match query {
+            WasmQuery::Smart { contract_addr, .. } if contract_addr == "token" => {
+                let balance = cw20::BalanceResponse {
+                    balance: Uint128::new(70),
+                };
+                SystemResult::Ok(ContractResult::Ok(to_json_binary(&balance).unwrap()))
+            }
+            _ => panic!("unexpected query"),
+        });
+
+        let msg = InstantiateMsg {
+            receiver: "treasury".to_string(),
+            ..Default::default()
+        };
+        let _res = instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+
+        // tokens transferred to the contract are swept to the receiver
+        let msg = ExecuteMsg::SweepCw20 {
+            token: "token".to_string(),
+        };
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("alice", &[]),
+            msg.clone(),
+        );
+        match res {
+            Err(ContractError::Unauthorized {}) => {}
+            _ => panic!("Must return unauthorized error"),
+        }
+        let res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(WasmMsg::Execute {
+                contract_addr: "token".to_string(),
+                msg: to_json_binary(&Cw20ExecuteMsg::Transfer {
+                    recipient: "treasury".to_string(),
+                    amount: Uint128::new(70),
+                })
+                .unwrap(),
+                funds: vec![],
+            })]
+        );
+
+        // a pull moves the tokens in with the allowance before forwarding them
+        let msg = ExecuteMsg::PullCw20 {
+            token: "token".to_string(),
+            amount: Uint128::new(40),
+        };
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("alice", &[]),
+            msg.clone(),
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages,
+            vec![
+                SubMsg::new(WasmMsg::Execute {
+                    contract_addr: "token".to_string(),
+                    msg: to_json_binary(&Cw20ExecuteMsg::TransferFrom {
+                        owner: "alice".to_string(),
+                        recipient: MOCK_CONTRACT_ADDR.to_string(),
+                        amount: Uint128::new(40),
+                    })
+                    .unwrap(),
+                    funds: vec![],
+                }),
+                SubMsg::new(WasmMsg::Execute {
+                    contract_addr: "token".to_string(),
+                    msg: to_json_binary(&Cw20ExecuteMsg::Transfer {
+                        recipient: "treasury".to_string(),
+                        amount: Uint128::new(40),
+                    })
+                    .unwrap(),
+                    funds: vec![],
+                }),
+            ]
+        );
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::Receipt { id: 1 }).unwrap();
+        let value: SendReceipt = from_json(&res).unwrap();
+        assert_eq!(value.amount, coins(40, "token"));
+
+        // tokens that are not accepted are neither pulled nor received
+        let update = ConfigUpdate {
+            accepted_denoms: Some(vec!["uusd".to_string()]),
+            ..Default::default()
+        };
+        let _res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            ExecuteMsg::UpdateConfig(update),
+        )
+        .unwrap();
+        let receive = ExecuteMsg::Receive(Cw20ReceiveMsg {
+            sender: "alice".to_string(),
+            amount: Uint128::new(40),
+            msg: Binary::default(),
+        });
+        for (sender, msg) in [("alice", msg), ("token", receive)] {
+            let res = execute(deps.as_mut(), mock_env(), mock_info(sender, &[]), msg);
+            match res {
+                Err(ContractError::UnsupportedDenom { denom }) => assert_eq!(denom, "token"),
+                _ => panic!("Must return UnsupportedDenom error"),
+            }
+        }
+    }
+
+    #[test]
+    fn receiver_validation() {
+        let mut deps = mock_dependencies(&[]);
+        mock_contract(&mut deps.querier, "vault");
+
+        // the contract cannot forward to itself
+        let msg = InstantiateMsg {
+            receiver: MOCK_CONTRACT_ADDR.to_string(),
+            ..Default::default()
+        };
+        let res = instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg);
+        match res {
+            Err(ContractError::SelfReceiver {}) => {}
+            _ => panic!("Must return SelfReceiver error"),
+        }
+
+        let msg = InstantiateMsg {
+            receiver: "treasury".to_string(),
+            ..Default::default()
+        };
+        let _res = instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+
+        // malformed and self-referential receivers are rejected on reset
+        let msg = ExecuteMsg::ResetReceiver {
+            receiver: "x".to_string(),
+        };
+        let res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg);
+        assert!(res.is_err());
+        let msg = ExecuteMsg::ResetReceiver {
+            receiver: MOCK_CONTRACT_ADDR.to_string(),
+        };
+        let res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg);
+        match res {
+            Err(ContractError::SelfReceiver {}) => {}
+            _ => panic!("Must return SelfReceiver error"),
+        }
+        // and as wallet or contract destinations
+        let destinations = vec![
+            Destination::Wallet(Addr::unchecked(MOCK_CONTRACT_ADDR)),
+            Destination::Contract {
+                addr: Addr::unchecked(MOCK_CONTRACT_ADDR),
+                msg: Binary::from(br#"{"deposit":{}}"#.to_vec()),
+            },
+        ];
+        for destination in destinations {
+            let msg = ExecuteMsg::SetDestination {
+                name: "loop".to_string(),
+                destination,
+            };
+            let res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg);
+            match res {
+                Err(ContractError::SelfReceiver {}) => {}
+                _ => panic!("Must return SelfReceiver error"),
+            }
+        }
+
+        // callbacks need a contract to execute
+        let msg = ExecuteMsg::SetCallbacks {
+            callbacks: vec![("treasury".to_string(), Binary::from(br#"{"deposit":{}}"#))],
+        };
+        let res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg);
+        match res {
+            Err(ContractError::NotAContract { address }) => assert_eq!(address, "treasury"),
+            _ => panic!("Must return NotAContract error"),
+        }
+        let msg = ExecuteMsg::SetCallbacks {
+            callbacks: vec![("vault".to_string(), Binary::from(br#"{"deposit":{}}"#))],
+        };
+        let _res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+    }
+
+    #[test]
+    fn blocked_senders() {
+        let mut deps = mock_dependencies(&[]);
+
+        let msg = InstantiateMsg {
+            receiver: "treasury".to_string(),
+            ..Default::default()
+        };
+        let _res = instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+
+        let block = ExecuteMsg::BlockSender {
+            address: "mallory".to_string(),
+        };
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("mallory", &[]),
+            block.clone(),
+        );
+        match res {
+            Err(ContractError::Unauthorized {}) => {}
+            _ => panic!("Must return unauthorized error"),
+        }
+        for sender in &["mallory", "oscar"] {
+            let block = ExecuteMsg::BlockSender {
+                address: sender.to_string(),
+            };
+            let _res =
+                execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), block).unwrap();
+        }
+
+        // blocked senders cannot deposit natively or with cw20 tokens
+        let msg = ExecuteMsg::TokenSend {
+            recipient: None,
+            route: None,
+            memo: None,
+            on_behalf_of: None,
+            category: None,
+        };
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("mallory", &coins(10, "uusd")),
+            msg.clone(),
+        );
+        match res {
+            Err(ContractError::SenderBlocked {}) => {}
+            _ => panic!("Must return SenderBlocked error"),
+        }
+        let receive = ExecuteMsg::Receive(Cw20ReceiveMsg {
+            sender: "oscar".to_string(),
+            amount: Uint128::new(10),
+            msg: Binary::default(),
+        });
+        let res = execute(deps.as_mut(), mock_env(), mock_info("token", &[]), receive);
+        match res {
+            Err(ContractError::SenderBlocked {}) => {}
+            _ => panic!("Must return SenderBlocked error"),
+        }
+        let _res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("alice", &coins(10, "uusd")),
+            msg.clone(),
+        )
+        .unwrap();
+
+        let list = QueryMsg::ListBlockedSenders {
+            start_after: None,
+            limit: Some(1),
+        };
+        let res = query(deps.as_ref(), mock_env(), list).unwrap();
+        let value: BlockedSendersResponse = from_json(&res).unwrap();
+        assert_eq!(value.senders, vec!["mallory"]);
+        let list = QueryMsg::ListBlockedSenders {
+            start_after: Some("mallory".to_string()),
+            limit: None,
+        };
+        let res = query(deps.as_ref(), mock_env(), list).unwrap();
+        let value: BlockedSendersResponse = from_json(&res).unwrap();
+        assert_eq!(value.senders, vec!["oscar"]);
+
+        let unblock = ExecuteMsg::UnblockSender {
+            address: "mallory".to_string(),
+        };
+        let _res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            unblock,
+        )
+        .unwrap();
+        let _res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("mallory", &coins(10, "uusd")),
+            msg,
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn lifetime_caps() {
+        let mut deps = mock_dependencies(&[]);
+
+        let msg = InstantiateMsg {
+            receiver: "treasury".to_string(),
+            ..Default::default()
+        };
+        let _res = instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+
+        let cap = ExecuteMsg::SetLifetimeCap {
+            address: "alice".to_string(),
+            cap: coins(100, "uusd"),
+        };
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("alice", &[]),
+            cap.clone(),
+        );
+        match res {
+            Err(ContractError::Unauthorized {}) => {}
+            _ => panic!("Must return unauthorized error"),
+        }
+        let _res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), cap).unwrap();
+
+        let msg = ExecuteMsg::TokenSend {
+            recipient: None,
+            route: None,
+            memo: None,
+            on_behalf_of: None,
+            category: None,
+        };
+        let _res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("alice", &[coin(60, "uusd"), coin(500, "uluna")]),
+            msg.clone(),
+        )
+        .unwrap();
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("alice", &coins(50, "uusd")),
+            msg.clone(),
+        );
+        match res {
+            Err(ContractError::LifetimeCapExceeded { denom, remaining }) => {
+                assert_eq!(denom, "uusd");
+                assert_eq!(remaining, Uint128::new(40));
+            }
+            _ => panic!("Must return LifetimeCapExceeded error"),
+        }
+        // other senders are not limited
+        let _res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("bob", &coins(500, "uusd")),
+            msg,
+        )
+        .unwrap();
+
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::Allowance {
+                sender: "alice".to_string(),
+            },
+        )
+        .unwrap();
+        let value: AllowanceResponse = from_json(&res).unwrap();
+        assert_eq!(
+            value,
+            AllowanceResponse {
+                sender: "alice".to_string(),
+                cap: coins(100, "uusd"),
+                remaining: coins(40, "uusd"),
+            }
+        );
+
+        // an empty cap removes the limit
+        let cap = ExecuteMsg::SetLifetimeCap {
+            address: "alice".to_string(),
+            cap: vec![],
+        };
+        let _res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), cap).unwrap();
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::Allowance {
+                sender: "alice".to_string(),
+            },
+        )
+        .unwrap();
+        let value: AllowanceResponse = from_json(&res).unwrap();
+        assert!(value.cap.is_empty());
+        assert!(value.remaining.is_empty());
+    }
+
+    #[test]
+    fn batch_send() {
+        let mut deps = mock_dependencies(&[]);
+
+        let msg = InstantiateMsg {
+            receiver: "treasury".to_string(),
+            ..Default::default()
+        };
+        let _res = instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+
+        let payment = |recipient: &str, amount: u128, denom: &str| Payment {
+            recipient: recipient.to_string(),
+            amount: Uint128::new(amount),
+            denom: denom.to_string(),
+        };
+        let msg = ExecuteMsg::BatchSend {
+            payments: vec![
+                payment("alice", 100, "uusd"),
+                payment("bob", 50, "uusd"),
+                payment("alice", 7, "uluna"),
+            ],
+            memo: Some("payroll".to_string()),
+        };
+
+        // the funds must add up to the payments
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("payer", &[coin(150, "uusd")]),
+            msg.clone(),
+        );
+        match res {
+            Err(ContractError::BatchMismatch {}) => {}
+            _ => panic!("Must return BatchMismatch error"),
+        }
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("payer", &[coin(7, "uluna"), coin(151, "uusd")]),
+            msg.clone(),
+        );
+        match res {
+            Err(ContractError::BatchMismatch {}) => {}
+            _ => panic!("Must return BatchMismatch error"),
+        }
+
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("payer", &[coin(7, "uluna"), coin(150, "uusd")]),
+            msg,
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages,
+            vec![
+                SubMsg::new(BankMsg::Send {
+                    to_address: "alice".to_string(),
+                    amount: vec![coin(100, "uusd"), coin(7, "uluna")],
+                }),
+                SubMsg::new(BankMsg::Send {
+                    to_address: "bob".to_string(),
+                    amount: coins(50, "uusd"),
+                }),
+            ]
+        );
+        let receipt: SendReceipt = from_json(res.data.unwrap()).unwrap();
+        assert_eq!(
+            receipt,
+            SendReceipt {
+                id: 1,
+                sender: "payer".to_string(),
+                recipients: vec!["alice".to_string(), "bob".to_string()],
+                amount: vec![coin(7, "uluna"), coin(150, "uusd")],
+                height: mock_env().block.height,
+                minted: None,
+                category: None,
+                items: vec![],
+            }
+        );
+        assert!(res.attributes.contains(&attr("memo", "payroll")));
+    }
+
+    #[test]
+    fn receiver_aliases() {
+        let mut deps = mock_dependencies(&[]);
+
+        let msg = InstantiateMsg {
+            receiver: "treasury".to_string(),
+            ..Default::default()
+        };
+        let _res = instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+
+        let alias = ExecuteMsg::SetAlias {
+            name: "ops".to_string(),
+            address: "opswallet".to_string(),
+        };
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("mallory", &[]),
+            alias.clone(),
+        );
+        match res {
+            Err(ContractError::Unauthorized {}) => {}
+            _ => panic!("Must return unauthorized error"),
+        }
+        let _res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), alias).unwrap();
+        let alias = ExecuteMsg::SetAlias {
+            name: "charity".to_string(),
+            address: "charitywallet".to_string(),
+        };
+        let _res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), alias).unwrap();
+
+        // anyone can route a deposit to a registered alias
+        let msg = ExecuteMsg::TokenSend {
+            recipient: None,
+            route: Some("ops".to_string()),
+            memo: None,
+            on_behalf_of: None,
+            category: None,
+        };
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("alice", &coins(10, "uusd")),
+            msg,
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: "opswallet".to_string(),
+                amount: coins(10, "uusd"),
+            })]
+        );
+        let msg = ExecuteMsg::TokenSend {
+            recipient: None,
+            route: Some("dev".to_string()),
+            memo: None,
+            on_behalf_of: None,
+            category: None,
+        };
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("alice", &coins(10, "uusd")),
+            msg,
+        );
+        match res {
+            Err(ContractError::UnknownAlias { name }) => assert_eq!(name, "dev"),
+            _ => panic!("Must return UnknownAlias error"),
+        }
+
+        // the receiver can be reset by name
+        let msg = ExecuteMsg::ResetReceiver {
+            receiver: "charity".to_string(),
+        };
+        let _res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetReceiver {}).unwrap();
+        let value: ReceiverResponse = from_json(&res).unwrap();
+        assert_eq!(value.receiver, "charitywallet");
+
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::ResolveAlias {
+                name: "ops".to_string(),
+            },
+        )
+        .unwrap();
+        let value: AliasInfo = from_json(&res).unwrap();
+        assert_eq!(value.address, "opswallet");
+
+        let msg = ExecuteMsg::RemoveAlias {
+            name: "ops".to_string(),
+        };
+        let _res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+        let list = QueryMsg::ListAliases {
+            start_after: None,
+            limit: None,
+        };
+        let res = query(deps.as_ref(), mock_env(), list).unwrap();
+        let value: AliasesResponse = from_json(&res).unwrap();
+        assert_eq!(
+            value.aliases,
+            vec![AliasInfo {
+                name: "charity".to_string(),
+                address: "charitywallet".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn receiver_acceptance() {
+        let mut deps = mock_dependencies(&[]);
+
+        let msg = InstantiateMsg {
+            receiver: "treasury".to_string(),
+            require_acceptance: true,
+            ..Default::default()
+        };
+        let _res = instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+
+        let msg = ExecuteMsg::ResetReceiver {
+            receiver: "mallory".to_string(),
+        };
+        let _res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetPendingReceiver {}).unwrap();
+        let value: PendingReceiverResponse = from_json(&res).unwrap();
+        assert!(value.awaiting_acceptance);
+
+        // deposits keep going to the current receiver until the new one accepted
+        let send = ExecuteMsg::TokenSend {
+            recipient: None,
+            route: None,
+            memo: None,
+            on_behalf_of: None,
+            category: None,
+        };
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("alice", &coins(10, "uusd")),
+            send.clone(),
+        )
+        .unwrap();
+        assert_eq!(res.attributes[3], attr("recipient", "treasury"));
+
+        let accept = ExecuteMsg::AcceptReceivership {};
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("alice", &[]),
+            accept.clone(),
+        );
+        match res {
+            Err(ContractError::Unauthorized {}) => {}
+            _ => panic!("Must return unauthorized error"),
+        }
+        let res = execute(deps.as_mut(), mock_env(), mock_info("mallory", &[]), accept).unwrap();
+        assert_eq!(res.attributes[2], attr("active", "true"));
+
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("alice", &coins(10, "uusd")),
+            send,
+        )
+        .unwrap();
+        assert_eq!(res.attributes[3], attr("recipient", "mallory"));
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetPendingReceiver {}).unwrap();
+        let value: PendingReceiverResponse = from_json(&res).unwrap();
+        assert_eq!(value.receiver, None);
+    }
+
+    #[test]
+    fn emergency_withdraw() {
+        let mut deps = mock_dependencies(&[coin(500, "uusd"), coin(20, "uluna")]);
+
+        let msg = InstantiateMsg {
+            receiver: "treasury".to_string(),
+            ..Default::default()
+        };
+        let _res = instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+
+        let announce = ExecuteMsg::AnnounceEmergencyWithdraw {};
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            announce.clone(),
+        );
+        match res {
+            Err(ContractError::NoRecoveryAddress {}) => {}
+            _ => panic!("Must return NoRecoveryAddress error"),
+        }
+        let msg = ExecuteMsg::SetRecoveryAddress {
+            recovery_address: Some("recovery".to_string()),
+        };
+        let _res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("mallory", &[]),
+            announce.clone(),
+        );
+        match res {
+            Err(ContractError::Unauthorized {}) => {}
+            _ => panic!("Must return unauthorized error"),
+        }
+        let _res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            announce,
+        )
+        .unwrap();
+
+        let execute_after = mock_env().block.height + EMERGENCY_DELAY;
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::PendingEmergency {}).unwrap();
+        let value: PendingEmergencyResponse = from_json(&res).unwrap();
+        assert_eq!(
+            value,
+            PendingEmergencyResponse {
+                recipient: Some("recovery".to_string()),
+                execute_after: Some(execute_after),
+            }
+        );
+
+        // nothing moves before the delay passed
+        let withdraw = ExecuteMsg::EmergencyWithdraw {};
+        let mut env = mock_env();
+        env.block.height = execute_after - 1;
+        let res = execute(
+            deps.as_mut(),
+            env,
+            mock_info("creator", &[]),
+            withdraw.clone(),
+        );
+        match res {
+            Err(ContractError::EmergencyNotReady { execute_after: at }) => {
+                assert_eq!(at, execute_after)
+            }
+            _ => panic!("Must return EmergencyNotReady error"),
+        }
+
+        let mut env = mock_env();
+        env.block.height = execute_after;
+        let res = execute(deps.as_mut(), env, mock_info("creator", &[]), withdraw).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: "recovery".to_string(),
+                amount: vec![coin(500, "uusd"), coin(20, "uluna")],
+            })]
+        );
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetPaused {}).unwrap();
+        let value: PausedResponse = from_json(&res).unwrap();
+        assert!(value.paused);
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::PendingEmergency {}).unwrap();
+        let value: PendingEmergencyResponse = from_json(&res).unwrap();
+        assert_eq!(value.execute_after, None);
+    }
+
+    #[test]
+    fn dust_bucket() {
+        let mut deps = mock_dependencies(&[]);
+
+        let msg = InstantiateMsg {
+            receiver: "alice".to_string(),
+            receivers: vec![
+                ("alice".to_string(), Decimal::percent(98)),
+                ("bob".to_string(), Decimal::percent(2)),
+            ],
+            fee_rate: Decimal::percent(1),
+            fee_collector: Some("collector".to_string()),
+            dust_threshold: coins(5, "uusd"),
+            ..Default::default()
+        };
+        let _res = instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+
+        // the 1uusd fee and the 1uusd share of bob are too small to send
+        let msg = ExecuteMsg::TokenSend {
+            recipient: None,
+            route: None,
+            memo: None,
+            on_behalf_of: None,
+            category: None,
+        };
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("anyone", &coins(100, "uusd")),
+            msg,
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: "alice".to_string(),
+                amount: coins(98, "uusd"),
+            })]
+        );
+        assert!(res.attributes.contains(&attr("dust", "2uusd")));
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::Dust {}).unwrap();
+        let value: DustResponse = from_json(&res).unwrap();
+        assert_eq!(value.dust, coins(2, "uusd"));
+
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("anyone", &[]),
+            ExecuteMsg::FlushDust {},
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: "alice".to_string(),
+                amount: coins(2, "uusd"),
+            })]
+        );
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("anyone", &[]),
+            ExecuteMsg::FlushDust {},
+        );
+        match res {
+            Err(ContractError::NothingToFlush {}) => {}
+            _ => panic!("Must return NothingToFlush error"),
+        }
+    }
+
+    #[test]
+    fn dust_payout_modes() {
+        let ibc = IbcForwarding {
+            channel: "channel-7".to_string(),
+            remote_receiver: "osmo1treasury".to_string(),
+            timeout: 600,
+        };
+        for pull in [false, true] {
+            let mut deps = mock_dependencies(&[]);
+            let msg = InstantiateMsg {
+                receiver: "treasury".to_string(),
+                fee_rate: Decimal::percent(1),
+                fee_collector: Some("collector".to_string()),
+                dust_threshold: coins(5, "uusd"),
+                pull,
+                ibc: if pull { None } else { Some(ibc.clone()) },
+                ..Default::default()
+            };
+            let _res =
+                instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+            // the 1uusd fee is dust
+            let msg = ExecuteMsg::TokenSend {
+                recipient: None,
+                route: None,
+                memo: None,
+                on_behalf_of: None,
+                category: None,
+            };
+            let info = mock_info("alice", &coins(100, "uusd"));
+            let _res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+            let res = execute(
+                deps.as_mut(),
+                mock_env(),
+                mock_info("anyone", &[]),
+                ExecuteMsg::FlushDust {},
+            )
+            .unwrap();
+            if pull {
+                // the dust waits for the receiver with its other funds
+                assert!(res.messages.is_empty());
+                let res = query(
+                    deps.as_ref(),
+                    mock_env(),
+                    QueryMsg::GetClaimable {
+                        address: "treasury".to_string(),
+                    },
+                )
+                .unwrap();
+                let value: ClaimableResponse = from_json(&res).unwrap();
+                assert_eq!(value.claimable, coins(100, "uusd"));
+            } else {
+                // the dust follows the receiver's share to the remote receiver
+                assert_eq!(
+                    res.messages,
+                    vec![SubMsg::reply_on_success(
+                        transfer_msg(
+                            &Addr::unchecked(MOCK_CONTRACT_ADDR),
+                            "channel-7",
+                            "osmo1treasury",
+                            &coin(1, "uusd"),
+                            mock_env().block.time.plus_seconds(600),
+                        ),
+                        IBC_REPLY_ID + 2,
+                    )]
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn waterfall() {
+        let mut deps = mock_dependencies(&[]);
+
+        let msg = InstantiateMsg {
+            receiver: "treasury".to_string(),
+            waterfall: vec![
+                ("alice".to_string(), coins(100, "uusd")),
+                ("bob".to_string(), coins(50, "uusd")),
+            ],
+            ..Default::default()
+        };
+        let _res = instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+
+        let send = ExecuteMsg::TokenSend {
+            recipient: None,
+            route: None,
+            memo: None,
+            on_behalf_of: None,
+            category: None,
+        };
+        // alice fills up first and bob gets the overflow, uluna has no cap and goes to the receiver
+        let info = mock_info("anyone", &[coin(120, "uusd"), coin(7, "uluna")]);
+        let res = execute(deps.as_mut(), mock_env(), info, send.clone()).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![
+                SubMsg::new(BankMsg::Send {
+                    to_address: "alice".to_string(),
+                    amount: coins(100, "uusd"),
+                }),
+                SubMsg::new(BankMsg::Send {
+                    to_address: "bob".to_string(),
+                    amount: coins(20, "uusd"),
+                }),
+                SubMsg::new(BankMsg::Send {
+                    to_address: "treasury".to_string(),
+                    amount: coins(7, "uluna"),
+                }),
+            ]
+        );
+
+        // bob fills up and the rest goes to the receiver
+        let info = mock_info("anyone", &coins(50, "uusd"));
+        let res = execute(deps.as_mut(), mock_env(), info, send.clone()).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![
+                SubMsg::new(BankMsg::Send {
+                    to_address: "bob".to_string(),
+                    amount: coins(30, "uusd"),
+                }),
+                SubMsg::new(BankMsg::Send {
+                    to_address: "treasury".to_string(),
+                    amount: coins(20, "uusd"),
+                }),
+            ]
+        );
+
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::Waterfall {}).unwrap();
+        let value: WaterfallResponse = from_json(&res).unwrap();
+        assert_eq!(
+            value.tiers,
+            vec![
+                WaterfallTier {
+                    receiver: "alice".to_string(),
+                    cap: coins(100, "uusd"),
+                    filled: coins(100, "uusd"),
+                },
+                WaterfallTier {
+                    receiver: "bob".to_string(),
+                    cap: coins(50, "uusd"),
+                    filled: coins(50, "uusd"),
+                },
+            ]
+        );
+
+        // only the owner can change the tiers
+        let msg = ExecuteMsg::SetWaterfall {
+            tiers: vec![
+                ("bob".to_string(), coins(50, "uusd")),
+                ("alice".to_string(), coins(200, "uusd")),
+            ],
+        };
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("anyone", &[]),
+            msg.clone(),
+        );
+        match res {
+            Err(ContractError::Unauthorized {}) => {}
+            _ => panic!("Must return unauthorized error"),
+        }
+        let _res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+
+        // reordering keeps what was filled, so bob is skipped and alice has room again
+        let info = mock_info("anyone", &coins(10, "uusd"));
+        let res = execute(deps.as_mut(), mock_env(), info, send.clone()).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: "alice".to_string(),
+                amount: coins(10, "uusd"),
+            })]
+        );
+
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("anyone", &[]),
+            ExecuteMsg::ResetWaterfall {},
+        );
+        match res {
+            Err(ContractError::Unauthorized {}) => {}
+            _ => panic!("Must return unauthorized error"),
+        }
+        let _res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            ExecuteMsg::ResetWaterfall {},
+        )
+        .unwrap();
+
+        // after a reset bob is first in line again
+        let info = mock_info("anyone", &coins(10, "uusd"));
+        let res = execute(deps.as_mut(), mock_env(), info, send).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: "bob".to_string(),
+                amount: coins(10, "uusd"),
+            })]
+        );
+    }
+
+    #[test]
+    fn governance() {
+        let mut deps = mock_dependencies(&[]);
+
+        let msg = InstantiateMsg {
+            receiver: "treasury".to_string(),
+            governance: Some(GovernanceSettings {
+                voters: vec![
+                    ("alice".to_string(), 40),
+                    ("bob".to_string(), 35),
+                    ("carol".to_string(), 25),
+                ],
+                quorum: Decimal::percent(60),
+                voting_period: Duration::Height(100),
+            }),
+            ..Default::default()
+        };
+        let _res = instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+
+        // the owner cannot change the receiver on their own
+        let msg = ExecuteMsg::ResetReceiver {
+            receiver: "vault".to_string(),
+        };
+        let res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg);
+        match res {
+            Err(ContractError::Governed {}) => {}
+            _ => panic!("Must return Governed error"),
+        }
+        // nor anything else deciding who is paid
+        let msgs = vec![
+            ExecuteMsg::SetReceivers {
+                receivers: vec![("vault".to_string(), Decimal::one())],
+            },
+            ExecuteMsg::SetWaterfall {
+                tiers: vec![("vault".to_string(), coins(100, "uusd"))],
+            },
+            ExecuteMsg::SetDenomRules {
+                denom_rules: vec![DenomRuleSettings {
+                    denom: "uluna".to_string(),
+                    fee_rate: None,
+                    receiver: Some("vault".to_string()),
+                }],
+            },
+            ExecuteMsg::SetAlias {
+                name: "vault".to_string(),
+                address: "vault".to_string(),
+            },
+            ExecuteMsg::RegisterReferral {
+                code: "vault".to_string(),
+                referrer: "vault".to_string(),
+            },
+            ExecuteMsg::SetCallbacks { callbacks: vec![] },
+            // and governance cannot be switched off to get around the vote
+            ExecuteMsg::SetGovernance { governance: None },
+        ];
+        for msg in msgs {
+            let res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg);
+            match res {
+                Err(ContractError::Governed {}) => {}
+                _ => panic!("Must return Governed error"),
+            }
+        }
+
+        // only voters can propose
+        let propose = ExecuteMsg::ProposeReceiver {
+            receiver: "vault".to_string(),
+        };
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            propose.clone(),
+        );
+        match res {
+            Err(ContractError::Unauthorized {}) => {}
+            _ => panic!("Must return unauthorized error"),
+        }
+        let _res = execute(deps.as_mut(), mock_env(), mock_info("alice", &[]), propose).unwrap();
+
+        let vote = |approve| ExecuteMsg::Vote {
+            proposal_id: 1,
+            approve,
+        };
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("alice", &[]),
+            vote(true),
+        );
+        match res {
+            Err(ContractError::AlreadyVoted { id }) => assert_eq!(id, 1),
+            _ => panic!("Must return AlreadyVoted error"),
+        }
+        let _res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("bob", &[]),
+            vote(false),
+        )
+        .unwrap();
+
+        // 40 yes against 35 no with 75% turnout, but voting is still open
+        let execute_proposal = ExecuteMsg::ExecuteProposal { proposal_id: 1 };
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("anyone", &[]),
+            execute_proposal.clone(),
+        );
+        match res {
+            Err(ContractError::ProposalNotPassed { id }) => assert_eq!(id, 1),
+            _ => panic!("Must return ProposalNotPassed error"),
+        }
+
+        let mut env = mock_env();
+        env.block.height += 100;
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("carol", &[]),
+            vote(true),
+        );
+        match res {
+            Err(ContractError::VotingClosed { id }) => assert_eq!(id, 1),
+            _ => panic!("Must return VotingClosed error"),
+        }
+        let res = query(
+            deps.as_ref(),
+            env.clone(),
+            QueryMsg::Proposal { proposal_id: 1 },
+        )
+        .unwrap();
+        let value: ProposalInfo = from_json(&res).unwrap();
+        assert_eq!(
+            value,
+            ProposalInfo {
+                id: 1,
+                receiver: Some("vault".to_string()),
+                governance: None,
+                proposer: "alice".to_string(),
+                yes: 40,
+                no: 35,
+                ends: Expiration::AtHeight(mock_env().block.height + 100),
+                status: ProposalStatus::Passed,
+            }
+        );
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("anyone", &[]),
+            execute_proposal.clone(),
+        )
+        .unwrap();
+        assert_eq!(res.attributes[0], attr("action", "reset_receiver"));
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::GetReceiver {}).unwrap();
+        let value: ReceiverResponse = from_json(&res).unwrap();
+        assert_eq!(value.receiver, "vault");
+
+        // a proposal is executed once
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("anyone", &[]),
+            execute_proposal,
+        );
+        match res {
+            Err(ContractError::ProposalNotPassed { id }) => assert_eq!(id, 1),
+            _ => panic!("Must return ProposalNotPassed error"),
+        }
+
+        // carol alone does not reach the quorum
+        let propose = ExecuteMsg::ProposeReceiver {
+            receiver: "treasury".to_string(),
+        };
+        let _res = execute(deps.as_mut(), env.clone(), mock_info("carol", &[]), propose).unwrap();
+        env.block.height += 100;
+        let res = query(
+            deps.as_ref(),
+            env.clone(),
+            QueryMsg::ListProposals {
+                start_after: None,
+                limit: None,
+            },
+        )
+        .unwrap();
+        let value: ProposalsResponse = from_json(&res).unwrap();
+        let statuses: Vec<ProposalStatus> = value.proposals.iter().map(|p| p.status).collect();
+        assert_eq!(
+            statuses,
+            vec![ProposalStatus::Executed, ProposalStatus::Rejected]
+        );
+
+        // the voters can hand receiver changes back to the owner
+        let msg = ExecuteMsg::ProposeGovernance { governance: None };
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("creator", &[]),
+            msg.clone(),
+        );
+        match res {
+            Err(ContractError::Unauthorized {}) => {}
+            _ => panic!("Must return unauthorized error"),
+        }
+        let _res = execute(deps.as_mut(), env.clone(), mock_info("alice", &[]), msg).unwrap();
+        let msg = ExecuteMsg::Vote {
+            proposal_id: 3,
+            approve: true,
+        };
+        let _res = execute(deps.as_mut(), env.clone(), mock_info("bob", &[]), msg).unwrap();
+        env.block.height += 100;
+        let msg = ExecuteMsg::ExecuteProposal { proposal_id: 3 };
+        let res = execute(deps.as_mut(), env.clone(), mock_info("anyone", &[]), msg).unwrap();
+        assert_eq!(res.attributes[0], attr("action", "set_governance"));
+        let msg = ExecuteMsg::ResetReceiver {
+            receiver: "treasury".to_string(),
+        };
+        let _res = execute(deps.as_mut(), env.clone(), mock_info("creator", &[]), msg).unwrap();
+        let res = query(deps.as_ref(), env, QueryMsg::GetReceiver {}).unwrap();
+        let value: ReceiverResponse = from_json(&res).unwrap();
+        assert_eq!(value.receiver, "treasury");
+    }
+
+    #[test]
+    fn multisig() {
+        let mut deps = mock_dependencies(&coins(1000, "uusd"));
+
+        let msg = InstantiateMsg {
+            receiver: "treasury".to_string(),
+            recovery_address: Some("recovery".to_string()),
+            multisig: Some(MultisigSettings {
+                admins: vec!["alice".to_string(), "bob".to_string(), "carol".to_string()],
+                threshold: 2,
+            }),
+            ..Default::default()
+        };
+        let _res = instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+
+        // the owner alone cannot run sensitive actions anymore
+        let msg = ExecuteMsg::SetFee {
+            fee_rate: Decimal::percent(1),
+            fee_collector: None,
+            fee_cap: vec![],
+        };
+        let res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg);
+        match res {
+            Err(ContractError::ApprovalRequired {}) => {}
+            _ => panic!("Must return ApprovalRequired error"),
+        }
+        let msg = ExecuteMsg::SetMultisig { multisig: None };
+        let res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg);
+        match res {
+            Err(ContractError::ApprovalRequired {}) => {}
+            _ => panic!("Must return ApprovalRequired error"),
+        }
+        let msg = ExecuteMsg::SetReceivers {
+            receivers: vec![("vault".to_string(), Decimal::one())],
+        };
+        let res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg);
+        match res {
+            Err(ContractError::ApprovalRequired {}) => {}
+            _ => panic!("Must return ApprovalRequired error"),
+        }
+
+        // only admins can propose and approve
+        let propose = ExecuteMsg::ProposeAction {
+            action: AdminAction::ResetReceiver {
+                receiver: "vault".to_string(),
+            },
+        };
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            propose.clone(),
+        );
+        match res {
+            Err(ContractError::Unauthorized {}) => {}
+            _ => panic!("Must return unauthorized error"),
+        }
+        let res = execute(deps.as_mut(), mock_env(), mock_info("alice", &[]), propose).unwrap();
+        assert_eq!(
+            res.attributes,
+            vec![
+                attr("action", "approve_action"),
+                attr("sender", "alice"),
+                attr("admin_proposal_id", "1"),
+                attr("approvals", "1"),
+            ]
+        );
+
+        let approve = ExecuteMsg::ApproveAction { id: 1 };
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("alice", &[]),
+            approve.clone(),
+        );
+        match res {
+            Err(ContractError::AlreadyApproved { id }) => assert_eq!(id, 1),
+            _ => panic!("Must return AlreadyApproved error"),
+        }
+
+        // the second approval runs the action as the owner
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("carol", &[]),
+            approve.clone(),
+        )
+        .unwrap();
+        assert_eq!(
+            res.attributes,
+            vec![
+                attr("action", "reset_receiver"),
+                attr("sender", "creator"),
+                attr("recipient", "vault"),
+                attr("old", "treasury"),
+                attr("new", "vault"),
+                attr("actor", "creator"),
+                attr("admin_proposal_id", "1"),
+            ]
+        );
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetReceiver {}).unwrap();
+        let value: ReceiverResponse = from_json(&res).unwrap();
+        assert_eq!(value.receiver, "vault");
+
+        let res = execute(deps.as_mut(), mock_env(), mock_info("bob", &[]), approve);
+        match res {
+            Err(ContractError::AlreadyExecuted { id }) => assert_eq!(id, 1),
+            _ => panic!("Must return AlreadyExecuted error"),
+        }
+
+        // an emergency withdrawal still waits for its delay after approval
+        let propose = ExecuteMsg::ProposeAction {
+            action: AdminAction::AnnounceEmergencyWithdraw {},
+        };
+        let _res = execute(deps.as_mut(), mock_env(), mock_info("bob", &[]), propose).unwrap();
+        let msg = ExecuteMsg::EmergencyWithdraw {};
+        let res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg);
+        match res {
+            Err(ContractError::ApprovalRequired {}) => {}
+            _ => panic!("Must return ApprovalRequired error"),
+        }
+
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::ListAdminProposals {
+                start_after: None,
+                limit: None,
+            },
+        )
+        .unwrap();
+        let value: AdminProposalsResponse = from_json(&res).unwrap();
+        assert_eq!(
+            value.proposals,
+            vec![
+                AdminProposalInfo {
+                    id: 1,
+                    action: AdminAction::ResetReceiver {
+                        receiver: "vault".to_string(),
+                    },
+                    proposer: "alice".to_string(),
+                    approvals: vec!["alice".to_string(), "carol".to_string()],
+                    executed: true,
+                },
+                AdminProposalInfo {
+                    id: 2,
+                    action: AdminAction::AnnounceEmergencyWithdraw {},
+                    proposer: "bob".to_string(),
+                    approvals: vec!["bob".to_string()],
+                    executed: false,
+                },
+            ]
+        );
+
+        let _res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("alice", &[]),
+            ExecuteMsg::ApproveAction { id: 2 },
+        )
+        .unwrap();
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::PendingEmergency {}).unwrap();
+        let value: PendingEmergencyResponse = from_json(&res).unwrap();
+        assert_eq!(value.recipient, Some("recovery".to_string()));
+
+        // changing the split takes approvals as well
+        let propose = ExecuteMsg::ProposeAction {
+            action: AdminAction::SetReceivers {
+                receivers: vec![
+                    ("vault".to_string(), Decimal::percent(50)),
+                    ("treasury".to_string(), Decimal::percent(50)),
+                ],
+            },
+        };
+        let _res = execute(deps.as_mut(), mock_env(), mock_info("bob", &[]), propose).unwrap();
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("carol", &[]),
+            ExecuteMsg::ApproveAction { id: 3 },
+        )
+        .unwrap();
+        assert_eq!(res.attributes[0], attr("action", "set_receivers"));
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("anyone", &coins(100, "uusd")),
+            ExecuteMsg::TokenSend {
+                recipient: None,
+                route: None,
+                memo: None,
+                on_behalf_of: None,
+                category: None,
+            },
+        )
+        .unwrap();
+        assert_eq!(res.messages.len(), 2);
+    }
+
+    #[test]
+    fn multisig_payees() {
+        let mut deps = mock_dependencies(&[]);
+        let msg = InstantiateMsg {
+            receiver: "treasury".to_string(),
+            multisig: Some(MultisigSettings {
+                admins: vec!["alice".to_string(), "bob".to_string()],
+                threshold: 2,
+            }),
+            ..Default::default()
+        };
+        let _res = instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+
+        // nothing deciding who is paid can be changed by the owner alone
+        let msgs = vec![
+            ExecuteMsg::SetRotation {
+                rotation: vec![RotationEntry {
+                    receiver: "vault".to_string(),
+                    active_from: mock_env().block.height + 10,
+                }],
+            },
+            ExecuteMsg::SetWaterfall {
+                tiers: vec![("vault".to_string(), coins(100, "uusd"))],
+            },
+            ExecuteMsg::SetRoute {
+                name: "grants".to_string(),
+                route: RouteSettings {
+                    receiver: "vault".to_string(),
+                    receivers: vec![],
+                    fee_rate: Decimal::zero(),
+                    fee_collector: None,
+                    fee_cap: vec![],
+                    accepted_denoms: vec![],
+                    funds_policy: FundsPolicy::default(),
+                },
+            },
+            ExecuteMsg::SetCategory {
+                name: "grant".to_string(),
+                receiver: Some("vault".to_string()),
+            },
+            ExecuteMsg::SetAlias {
+                name: "vault".to_string(),
+                address: "vault".to_string(),
+            },
+            ExecuteMsg::SetDestination {
+                name: "vault".to_string(),
+                destination: Destination::Wallet(Addr::unchecked("vault")),
+            },
+            ExecuteMsg::SetDenomRules {
+                denom_rules: vec![DenomRuleSettings {
+                    denom: "uluna".to_string(),
+                    fee_rate: None,
+                    receiver: Some("vault".to_string()),
+                }],
+            },
+            ExecuteMsg::SetFallbackReceiver {
+                fallback_receiver: Some("vault".to_string()),
+            },
+            ExecuteMsg::SetIbc {
+                ibc: Some(IbcForwarding {
+                    channel: "channel-7".to_string(),
+                    remote_receiver: "osmo1vault".to_string(),
+                    timeout: 600,
+                }),
+            },
+            ExecuteMsg::UpdateConfig(ConfigUpdate {
+                fallback_receiver: Some("vault".to_string()),
+                ..Default::default()
+            }),
+        ];
+        for msg in msgs {
+            let res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg);
+            match res {
+                Err(ContractError::ApprovalRequired {}) => {}
+                _ => panic!("Must return ApprovalRequired error"),
+            }
+        }
+
+        // with the approval of both admins the alias is set
+        let propose = ExecuteMsg::ProposeAction {
+            action: AdminAction::SetAlias {
+                name: "vault".to_string(),
+                address: "vault".to_string(),
+            },
+        };
+        let _res = execute(deps.as_mut(), mock_env(), mock_info("alice", &[]), propose).unwrap();
+        let msg = ExecuteMsg::ApproveAction { id: 1 };
+        let res = execute(deps.as_mut(), mock_env(), mock_info("bob", &[]), msg).unwrap();
+        assert_eq!(res.attributes[0], attr("action", "set_alias"));
+        let msg = ExecuteMsg::TokenSend {
+            recipient: None,
+            route: Some("vault".to_string()),
+            memo: None,
+            on_behalf_of: None,
+            category: None,
+        };
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("carol", &coins(10, "uusd")),
+            msg,
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: "vault".to_string(),
+                amount: coins(10, "uusd"),
+            })]
+        );
+    }
+
+    #[test]
+    fn renounce_ownership() {
+        let mut deps = mock_dependencies(&[]);
+
+        let msg = InstantiateMsg {
+            receiver: "treasury".to_string(),
+            ..Default::default()
+        };
+        let _res = instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("anyone", &[]),
+            ExecuteMsg::RenounceOwnership {},
+        );
+        match res {
+            Err(ContractError::Unauthorized {}) => {}
+            _ => panic!("Must return unauthorized error"),
+        }
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            ExecuteMsg::RenounceOwnership {},
+        )
+        .unwrap();
+        assert_eq!(
+            res.attributes,
+            vec![
+                attr("action", "renounce_ownership"),
+                attr("sender", "creator")
+            ]
+        );
+
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetConfig {}).unwrap();
+        let value: ConfigResponse = from_json(&res).unwrap();
+        assert_eq!(value.owner, None);
+
+        // the former owner cannot change anything anymore
+        let msgs = vec![
+            ExecuteMsg::ResetReceiver {
+                receiver: "mallory".to_string(),
+            },
+            ExecuteMsg::Pause {},
+            ExecuteMsg::SetFee {
+                fee_rate: Decimal::percent(10),
+                fee_collector: Some("mallory".to_string()),
+                fee_cap: vec![],
+            },
+            ExecuteMsg::RenounceOwnership {},
+        ];
+        for msg in msgs {
+            let res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg);
+            match res {
+                Err(ContractError::Unauthorized {}) => {}
+                _ => panic!("Must return unauthorized error"),
+            }
+        }
+
+        // deposits are still forwarded
+        let info = mock_info("anyone", &coins(100, "uusd"));
+        let msg = ExecuteMsg::TokenSend {
+            recipient: Some("mallory".to_string()),
+            route: None,
+            memo: None,
+            on_behalf_of: None,
+            category: None,
+        };
+        let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: "treasury".to_string(),
+                amount: coins(100, "uusd"),
+            })]
+        );
+    }
+
+    #[test]
+    fn campaign() {
+        let mut deps = mock_dependencies(&[]);
+
+        let start = mock_env().block.height + 10;
+        let msg = InstantiateMsg {
+            receiver: "treasury".to_string(),
+            campaign: Some(Campaign {
+                start: Expiration::AtHeight(start),
+                end: Expiration::AtHeight(start + 100),
+                goal: coin(1000, "uusd"),
+            }),
+            ..Default::default()
+        };
+        let _res = instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+
+        let send = ExecuteMsg::TokenSend {
+            recipient: None,
+            route: None,
+            memo: None,
+            on_behalf_of: None,
+            category: None,
+        };
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("alice", &coins(100, "uusd")),
+            send.clone(),
+        );
+        match res {
+            Err(ContractError::CampaignNotOpen {}) => {}
+            _ => panic!("Must return CampaignNotOpen error"),
+        }
+
+        // deposits are kept during the campaign
+        let mut env = mock_env();
+        env.block.height = start;
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("alice", &coins(600, "uusd")),
+            send.clone(),
+        )
+        .unwrap();
+        assert_eq!(res.messages, vec![]);
+        assert_eq!(res.attributes[0], attr("action", "campaign"));
+        let _res = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("bob", &coins(300, "uusd")),
+            send.clone(),
+        )
+        .unwrap();
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("anyone", &[]),
+            ExecuteMsg::RefundCampaign {},
+        );
+        match res {
+            Err(ContractError::CampaignNotEnded {}) => {}
+            _ => panic!("Must return CampaignNotEnded error"),
+        }
+
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::Campaign {}).unwrap();
+        let value: CampaignResponse = from_json(&res).unwrap();
+        assert_eq!(value.raised, coins(900, "uusd"));
+        assert_eq!(value.status, Some(CampaignStatus::Open));
+        let res = query(
+            deps.as_ref(),
+            env.clone(),
+            QueryMsg::CampaignDeposits {
+                start_after: None,
+                limit: None,
+            },
+        )
+        .unwrap();
+        let value: CampaignDepositsResponse = from_json(&res).unwrap();
+        assert_eq!(
+            value.deposits,
+            vec![
+                CampaignDeposit {
+                    sender: "alice".to_string(),
+                    amount: coins(600, "uusd"),
+                },
+                CampaignDeposit {
+                    sender: "bob".to_string(),
+                    amount: coins(300, "uusd"),
+                },
+            ]
+        );
+
+        // the goal was missed, so the senders take their deposits back
+        env.block.height = start + 100;
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("carol", &coins(100, "uusd")),
+            send,
+        );
+        match res {
+            Err(ContractError::CampaignNotOpen {}) => {}
+            _ => panic!("Must return CampaignNotOpen error"),
+        }
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("alice", &[]),
+            ExecuteMsg::ClaimCampaign {},
+        );
+        match res {
+            Err(ContractError::Unauthorized {}) => {}
+            _ => panic!("Must return unauthorized error"),
+        }
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("treasury", &[]),
+            ExecuteMsg::ClaimCampaign {},
+        );
+        match res {
+            Err(ContractError::GoalNotMet {}) => {}
+            _ => panic!("Must return GoalNotMet error"),
+        }
+        let msg = ExecuteMsg::SetCampaign { campaign: None };
+        let res = execute(deps.as_mut(), env.clone(), mock_info("creator", &[]), msg);
+        match res {
+            Err(ContractError::CampaignActive {}) => {}
+            _ => panic!("Must return CampaignActive error"),
+        }
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("alice", &[]),
+            ExecuteMsg::RefundCampaign {},
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: "alice".to_string(),
+                amount: coins(600, "uusd"),
+            })]
+        );
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("alice", &[]),
+            ExecuteMsg::RefundCampaign {},
+        );
+        match res {
+            Err(ContractError::NothingToWithdraw {}) => {}
+            _ => panic!("Must return NothingToWithdraw error"),
+        }
+
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::Campaign {}).unwrap();
+        let value: CampaignResponse = from_json(&res).unwrap();
+        assert_eq!(value.raised, coins(300, "uusd"));
+        assert_eq!(value.status, Some(CampaignStatus::Failed));
+
+        // every sender only gets their own deposit back
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("carol", &[]),
+            ExecuteMsg::RefundCampaign {},
+        );
+        match res {
+            Err(ContractError::NothingToWithdraw {}) => {}
+            _ => panic!("Must return NothingToWithdraw error"),
+        }
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("bob", &[]),
+            ExecuteMsg::RefundCampaign {},
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: "bob".to_string(),
+                amount: coins(300, "uusd"),
+            })]
+        );
+        let res = query(deps.as_ref(), env, QueryMsg::Campaign {}).unwrap();
+        let value: CampaignResponse = from_json(&res).unwrap();
+        assert_eq!(value.raised, vec![]);
+    }
+
+    #[test]
+    fn campaign_goal_met() {
+        let mut deps = mock_dependencies(&[]);
+
+        let end = mock_env().block.height + 100;
+        let msg = InstantiateMsg {
+            receiver: "treasury".to_string(),
+            campaign: Some(Campaign {
+                start: Expiration::AtHeight(0),
+                end: Expiration::AtHeight(end),
+                goal: coin(1000, "uusd"),
+            }),
+            ..Default::default()
+        };
+        let _res = instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+
+        let send = ExecuteMsg::TokenSend {
+            recipient: None,
+            route: None,
+            memo: None,
+            on_behalf_of: None,
+            category: None,
+        };
+        let info = mock_info("alice", &[coin(1200, "uusd"), coin(5, "uluna")]);
+        let _res = execute(deps.as_mut(), mock_env(), info, send).unwrap();
+
+        let mut env = mock_env();
+        env.block.height = end;
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::Campaign {}).unwrap();
+        let value: CampaignResponse = from_json(&res).unwrap();
+        assert_eq!(value.status, Some(CampaignStatus::Succeeded));
+
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("alice", &[]),
+            ExecuteMsg::RefundCampaign {},
+        );
+        match res {
+            Err(ContractError::GoalMet {}) => {}
+            _ => panic!("Must return GoalMet error"),
+        }
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("anyone", &[]),
+            ExecuteMsg::ClaimCampaign {},
+        );
+        match res {
+            Err(ContractError::Unauthorized {}) => {}
+            _ => panic!("Must return unauthorized error"),
+        }
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("treasury", &[]),
+            ExecuteMsg::ClaimCampaign {},
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: "treasury".to_string(),
+                amount: vec![coin(1200, "uusd"), coin(5, "uluna")],
+            })]
+        );
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("treasury", &[]),
+            ExecuteMsg::ClaimCampaign {},
+        );
+        match res {
+            Err(ContractError::NothingToClaim {}) => {}
+            _ => panic!("Must return NothingToClaim error"),
+        }
+
+        // a new campaign can start once the last one was paid out
+        let msg = ExecuteMsg::SetCampaign { campaign: None };
+        let _res = execute(deps.as_mut(), env, mock_info("creator", &[]), msg).unwrap();
+    }
+
+    #[test]
+    fn receipt_nft() {
+        let mut deps = mock_dependencies(&[]);
+
+        let msg = InstantiateMsg {
+            receiver: "treasury".to_string(),
+            receipt_nft: Some(ReceiptNftSettings {
+                contract: "receipts".to_string(),
+                threshold: coins(1000, "uusd"),
+            }),
+            mint_receipts: true,
+            ..Default::default()
+        };
+        let _res = instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+
+        let send = ExecuteMsg::TokenSend {
+            recipient: None,
+            route: None,
+            memo: None,
+            on_behalf_of: None,
+            category: None,
+        };
+        // small deposits get no receipt
+        let info = mock_info("alice", &coins(999, "uusd"));
+        let res = execute(deps.as_mut(), mock_env(), info, send.clone()).unwrap();
+        assert_eq!(res.messages.len(), 1);
+
+        let info = mock_info("alice", &[coin(1000, "uusd"), coin(5, "uluna")]);
+        let res = execute(deps.as_mut(), mock_env(), info, send.clone()).unwrap();
+        let mint = Cw721ExecuteMsg::Mint {
+            token_id: "2".to_string(),
+            owner: "alice".to_string(),
+            token_uri: None,
+            extension: Metadata {
+                attributes: vec![
+                    Trait {
+                        trait_type: "amount".to_string(),
+                        value: "1000".to_string(),
+                    },
+                    Trait {
+                        trait_type: "denom".to_string(),
+                        value: "uusd".to_string(),
+                    },
+                    Trait {
+                        trait_type: "amount".to_string(),
+                        value: "5".to_string(),
+                    },
+                    Trait {
+                        trait_type: "denom".to_string(),
+                        value: "uluna".to_string(),
+                    },
+                ],
+            },
+        };
+        assert_eq!(
+            res.messages[1],
+            SubMsg::reply_on_error(
+                WasmMsg::Execute {
+                    contract_addr: "receipts".to_string(),
+                    msg: to_json_binary(&mint).unwrap(),
+                    funds: vec![],
+                },
+                MINT_REPLY_ID
+            )
+        );
+
+        // a failed mint does not fail the deposit
+        let res = reply(
+            deps.as_mut(),
+            mock_env(),
+            Reply {
+                id: MINT_REPLY_ID,
+                result: SubMsgResult::Err("not the minter".to_string()),
+            },
+        )
+        .unwrap();
+        assert_eq!(res.attributes, vec![attr("mint_failed", "not the minter")]);
+
+        let msg = ExecuteMsg::SetMintReceipts {
+            mint_receipts: false,
+        };
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("anyone", &[]),
+            msg.clone(),
+        );
+        match res {
+            Err(ContractError::Unauthorized {}) => {}
+            _ => panic!("Must return unauthorized error"),
+        }
+        let _res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+        let info = mock_info("alice", &coins(1000, "uusd"));
+        let res = execute(deps.as_mut(), mock_env(), info, send).unwrap();
+        assert_eq!(res.messages.len(), 1);
+    }
+
+    #[test]
+    fn referral() {
+        let mut deps = mock_dependencies(&[]);
+
+        let msg = InstantiateMsg {
+            receiver: "treasury".to_string(),
+            referral_rate: Decimal::percent(10),
+            ..Default::default()
+        };
+        let _res = instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+
+        let msg = ExecuteMsg::RegisterReferral {
+            code: "ALICE10".to_string(),
+            referrer: "alice".to_string(),
+        };
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("anyone", &[]),
+            msg.clone(),
+        );
+        match res {
+            Err(ContractError::Unauthorized {}) => {}
+            _ => panic!("Must return unauthorized error"),
+        }
+        let _res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+
+        // a memo naming the code pays alice 10%
+        let send = |memo: &str| ExecuteMsg::TokenSend {
+            recipient: None,
+            route: None,
+            memo: Some(memo.to_string()),
+            on_behalf_of: None,
+            category: None,
+        };
+        let info = mock_info("bob", &coins(1000, "uusd"));
+        let res = execute(deps.as_mut(), mock_env(), info, send("ALICE10")).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![
+                SubMsg::new(BankMsg::Send {
+                    to_address: "treasury".to_string(),
+                    amount: coins(900, "uusd"),
+                }),
+                SubMsg::new(BankMsg::Send {
+                    to_address: "alice".to_string(),
+                    amount: coins(100, "uusd"),
+                }),
+            ]
+        );
+        assert_eq!(res.attributes[4], attr("referrer", "alice"));
+        assert_eq!(res.attributes[5], attr("commission", "100uusd"));
+
+        // any other memo is just a memo
+        let info = mock_info("bob", &coins(1000, "uusd"));
+        let res = execute(deps.as_mut(), mock_env(), info, send("invoice 7")).unwrap();
+        assert_eq!(res.messages.len(), 1);
+
+        let info = mock_info("carol", &coins(500, "uusd"));
+        let _res = execute(deps.as_mut(), mock_env(), info, send("ALICE10")).unwrap();
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::Referral {
+                code: "ALICE10".to_string(),
+            },
+        )
+        .unwrap();
+        let value: ReferralInfo = from_json(&res).unwrap();
+        assert_eq!(
+            value,
+            ReferralInfo {
+                code: "ALICE10".to_string(),
+                referrer: "alice".to_string(),
+                deposits: 2,
+                volume: coins(1500, "uusd"),
+                commission: coins(150, "uusd"),
+            }
+        );
+
+        let msg = ExecuteMsg::SetReferralRate {
+            referral_rate: Decimal::percent(101),
+        };
+        let res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg);
+        match res {
+            Err(ContractError::ReferralRateTooHigh {}) => {}
+            _ => panic!("Must return ReferralRateTooHigh error"),
+        }
+
+        let msg = ExecuteMsg::RemoveReferral {
+            code: "ALICE10".to_string(),
+        };
+        let _res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            msg.clone(),
+        )
+        .unwrap();
+        let res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg);
+        match res {
+            Err(ContractError::UnknownReferral { code }) => assert_eq!(code, "ALICE10"),
+            _ => panic!("Must return UnknownReferral error"),
+        }
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::ListReferrals {
+                start_after: None,
+                limit: None,
+            },
+        )
+        .unwrap();
+        let value: ReferralsResponse = from_json(&res).unwrap();
+        assert_eq!(value.referrals, vec![]);
+    }
+
+    #[test]
+    fn referral_commission_base() {
+        let mut deps = mock_dependencies(&[]);
+
+        let msg = InstantiateMsg {
+            receiver: "treasury".to_string(),
+            referral_rate: Decimal::percent(10),
+            hold: true,
+            flush_threshold: coins(1000, "uusd"),
+            ..Default::default()
+        };
+        let _res = instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+        let msg = ExecuteMsg::RegisterReferral {
+            code: "ALICE10".to_string(),
+            referrer: "alice".to_string(),
+        };
+        let _res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+
+        let send = |memo: Option<&str>| ExecuteMsg::TokenSend {
+            recipient: None,
+            route: None,
+            memo: memo.map(|memo| memo.to_string()),
+            on_behalf_of: None,
+            category: None,
+        };
+        let info = mock_info("carol", &coins(900, "uusd"));
+        let res = execute(deps.as_mut(), mock_env(), info, send(None)).unwrap();
+        assert_eq!(res.messages, vec![]);
+
+        // the commission comes out of bob's deposit, not out of the pool it flushes
+        let info = mock_info("bob", &coins(200, "uusd"));
+        let res = execute(deps.as_mut(), mock_env(), info, send(Some("ALICE10"))).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![
+                SubMsg::new(BankMsg::Send {
+                    to_address: "treasury".to_string(),
+                    amount: coins(1080, "uusd"),
+                }),
+                SubMsg::new(BankMsg::Send {
+                    to_address: "alice".to_string(),
+                    amount: coins(20, "uusd"),
+                }),
+            ]
+        );
+
+        // an escrowed deposit may go back to bob, it pays nothing and does not count
+        let msg = ExecuteMsg::SetEscrow {
+            escrow: Some(Duration::Time(3600)),
+        };
+        let _res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+        let info = mock_info("bob", &coins(500, "uusd"));
+        let res = execute(deps.as_mut(), mock_env(), info, send(Some("ALICE10"))).unwrap();
+        assert_eq!(res.attributes[0], attr("action", "escrow"));
+        assert_eq!(res.messages, vec![]);
+
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::Referral {
+                code: "ALICE10".to_string(),
+            },
+        )
+        .unwrap();
+        let value: ReferralInfo = from_json(&res).unwrap();
+        assert_eq!(value.deposits, 1);
+        assert_eq!(value.volume, coins(200, "uusd"));
+        assert_eq!(value.commission, coins(20, "uusd"));
+    }
+
+    #[test]
+    fn usd_floor() {
+        let mut deps = mock_dependencies(&[]);
+        mock_oracle(
+            &mut deps.querier,
+            "oracle",
+            &[("uluna", Decimal::from_ratio(50u128, 1u128))],
+        );
+
+        let msg = InstantiateMsg {
+            receiver: "treasury".to_string(),
+            usd_floor: Some(UsdFloorSettings {
+                oracle: "oracle".to_string(),
+                min_value: Uint128::new(1000),
+            }),
+            ..Default::default()
+        };
+        let _res = instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+
+        let send = ExecuteMsg::TokenSend {
+            recipient: None,
+            route: None,
+            memo: None,
+            on_behalf_of: None,
+            category: None,
+        };
+        let info = mock_info("anyone", &coins(999, "uusd"));
+        let res = execute(deps.as_mut(), mock_env(), info, send.clone());
+        match res {
+            Err(ContractError::BelowUsdFloor { value, min }) => {
+                assert_eq!(value, Uint128::new(999));
+                assert_eq!(min, Uint128::new(1000));
+            }
+            _ => panic!("Must return BelowUsdFloor error"),
+        }
+        let info = mock_info("anyone", &coins(19, "uluna"));
+        let res = execute(deps.as_mut(), mock_env(), info, send.clone());
+        match res {
+            Err(ContractError::BelowUsdFloor { value, .. }) => {
+                assert_eq!(value, Uint128::new(950))
+            }
+            _ => panic!("Must return BelowUsdFloor error"),
+        }
+
+        // the value is logged with the deposit
+        let info = mock_info("anyone", &[coin(500, "uusd"), coin(10, "uluna")]);
+        let res = execute(deps.as_mut(), mock_env(), info, send.clone()).unwrap();
+        assert_eq!(res.attributes[3], attr("usd_value", "1000"));
+
+        // a denom the oracle cannot price is rejected
+        let info = mock_info("anyone", &coins(1_000_000, "ukrw"));
+        let res = execute(deps.as_mut(), mock_env(), info, send);
+        assert!(res.is_err());
+
+        let msg = ExecuteMsg::SetUsdFloor { usd_floor: None };
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("anyone", &[]),
+            msg.clone(),
+        );
+        match res {
+            Err(ContractError::Unauthorized {}) => {}
+            _ => panic!("Must return unauthorized error"),
+        }
+        let _res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+    }
+
+    #[test]
+    fn simulate_send() {
+        let mut deps =
+            mock_dependencies_with_tax(&[], Decimal::percent(1), &[("uusd", 1000000), ("ukrw", 2)]);
+
+        let msg = InstantiateMsg {
+            receiver: "treasury".to_string(),
+            fee_rate: Decimal::percent(10),
+            fee_collector: Some("collector".to_string()),
+            ..Default::default()
+        };
+        let info = mock_info("creator", &[]);
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        // the preview matches what tax_tokensend pays
+        let simulate = QueryMsg::SimulateSend {
+            amount: vec![coin(1010, "uusd"), coin(1000, "uluna"), coin(1000, "ukrw")],
+            sender: "anyone".to_string(),
+        };
+        let res = query(deps.as_ref(), mock_env(), simulate.clone()).unwrap();
+        let value: SimulateSendResponse = from_json(&res).unwrap();
+        let net = vec![coin(900, "uusd"), coin(900, "uluna"), coin(898, "ukrw")];
+        assert_eq!(
+            value,
+            SimulateSendResponse {
+                accepted: true,
+                action: "send".to_string(),
+                fee: vec![coin(101, "uusd"), coin(100, "uluna"), coin(100, "ukrw")],
+                tax: vec![coin(10, "uusd"), coin(3, "ukrw")],
+                net: net.clone(),
+                shares: vec![Share {
+                    receiver: "treasury".to_string(),
+                    amount: net,
+                }],
+                ..Default::default()
+            }
+        );
+        assert_eq!(TRANSFER_COUNT.may_load(&deps.storage).unwrap(), None);
+
+        let msg = ExecuteMsg::SetMaxAmount {
+            max_amount: coins(500, "uluna"),
+        };
+        let _res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+        let res = query(deps.as_ref(), mock_env(), simulate).unwrap();
+        let value: SimulateSendResponse = from_json(&res).unwrap();
+        assert!(!value.accepted);
+        assert_eq!(
+            value.error,
+            Some(
+                ContractError::AmountTooLarge {
+                    denom: "uluna".to_string(),
+                    max: Uint128::new(500),
+                }
+                .to_string()
+            )
+        );
+        assert!(value.shares.is_empty());
+    }
+
+    #[test]
+    fn receiver_history() {
+        let mut deps = mock_dependencies(&[]);
+
+        let msg = InstantiateMsg {
+            receiver: "treasury".to_string(),
+            ..Default::default()
+        };
+        let info = mock_info("creator", &[]);
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let msg = ExecuteMsg::ResetReceiver {
+            receiver: "charitywallet".to_string(),
+        };
+        let _res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+
+        // a delayed change is recorded once it takes over
+        let msg = ExecuteMsg::SetReceiverDelay {
+            receiver_delay: Some(Duration::Height(100)),
+        };
+        let _res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+        let msg = ExecuteMsg::ResetReceiver {
+            receiver: "treasury".to_string(),
+        };
+        let mut env = mock_env();
+        env.block.height = 1000;
+        let _res = execute(deps.as_mut(), env, mock_info("creator", &[]), msg).unwrap();
+        let msg = QueryMsg::ReceiverHistory {
+            start_after: None,
+            limit: None,
+        };
+        let res = query(deps.as_ref(), mock_env(), msg.clone()).unwrap();
+        let value: ReceiverHistoryResponse = from_json(&res).unwrap();
+        assert_eq!(value.changes.len(), 1);
+
+        let send = ExecuteMsg::TokenSend {
+            recipient: None,
+            route: None,
+            memo: None,
+            on_behalf_of: None,
+            category: None,
+        };
+        let mut env = mock_env();
+        env.block.height = 1100;
+        let info = mock_info("anyone", &coins(10, "uusd"));
+        let _res = execute(deps.as_mut(), env, info, send).unwrap();
+
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let value: ReceiverHistoryResponse = from_json(&res).unwrap();
+        let time = mock_env().block.time.seconds();
+        assert_eq!(
+            value.changes,
+            vec![
+                ReceiverChangeInfo {
+                    id: 1,
+                    old_receiver: "treasury".to_string(),
+                    new_receiver: "charitywallet".to_string(),
+                    actor: "creator".to_string(),
+                    height: mock_env().block.height,
+                    time,
+                },
+                ReceiverChangeInfo {
+                    id: 2,
+                    old_receiver: "charitywallet".to_string(),
+                    new_receiver: "treasury".to_string(),
+                    actor: "creator".to_string(),
+                    height: 1100,
+                    time,
+                },
+            ]
+        );
+
+        let msg = QueryMsg::ReceiverHistory {
+            start_after: Some(1),
+            limit: Some(1),
+        };
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let value: ReceiverHistoryResponse = from_json(&res).unwrap();
+        assert_eq!(value.changes.len(), 1);
+        assert_eq!(value.changes[0].id, 2);
+    }
+
+    #[test]
+    fn delegated_permissions() {
+        let mut deps = mock_dependencies(&[]);
+
+        let msg = InstantiateMsg {
+            receiver: "treasury".to_string(),
+            ..Default::default()
+        };
+        let info = mock_info("creator", &[]);
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let grant = ExecuteMsg::GrantPermissions {
+            address: "operator".to_string(),
+            permissions: vec![Permission::Pause, Permission::UpdateDenoms],
+        };
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("operator", &[]),
+            grant.clone(),
+        );
+        match res {
+            Err(ContractError::Unauthorized {}) => {}
+            _ => panic!("Must return unauthorized error"),
+        }
+        let _res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), grant).unwrap();
+        let msg = QueryMsg::Permissions {
+            address: "operator".to_string(),
+        };
+        let res = query(deps.as_ref(), mock_env(), msg.clone()).unwrap();
+        let value: PermissionsResponse = from_json(&res).unwrap();
+        assert_eq!(
+            value.permissions,
+            vec![Permission::Pause, Permission::UpdateDenoms]
+        );
+
+        let operator = mock_info("operator", &[]);
+        let _res = execute(
+            deps.as_mut(),
+            mock_env(),
+            operator.clone(),
+            ExecuteMsg::Pause {},
+        )
+        .unwrap();
+        let denoms = ExecuteMsg::SetAcceptedDenoms {
+            accepted_denoms: vec!["uusd".to_string()],
+        };
+        let _res = execute(deps.as_mut(), mock_env(), operator.clone(), denoms.clone()).unwrap();
+
+        // what was not granted stays with the owner
+        let msgs = vec![
+            ExecuteMsg::SetMinAmount {
+                min_amount: coins(10, "uusd"),
+            },
+            ExecuteMsg::ResetReceiver {
+                receiver: "operator".to_string(),
+            },
+        ];
+        for msg in msgs {
+            let res = execute(deps.as_mut(), mock_env(), operator.clone(), msg);
+            match res {
+                Err(ContractError::Unauthorized {}) => {}
+                _ => panic!("Must return unauthorized error"),
+            }
+        }
+
+        let revoke = ExecuteMsg::RevokePermissions {
+            address: "operator".to_string(),
+            permissions: vec![Permission::Pause],
+        };
+        let _res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), revoke).unwrap();
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            operator.clone(),
+            ExecuteMsg::Unpause {},
+        );
+        match res {
+            Err(ContractError::Unauthorized {}) => {}
+            _ => panic!("Must return unauthorized error"),
+        }
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let value: PermissionsResponse = from_json(&res).unwrap();
+        assert_eq!(value.permissions, vec![Permission::UpdateDenoms]);
+
+        // granted permissions lapse with the owner
+        let msg = ExecuteMsg::RenounceOwnership {};
+        let _res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+        let res = execute(deps.as_mut(), mock_env(), operator, denoms);
+        match res {
+            Err(ContractError::Unauthorized {}) => {}
+            _ => panic!("Must return unauthorized error"),
+        }
+    }
+
+    #[test]
+    fn special_destinations() {
+        let mut deps = mock_dependencies(&[]);
+
+        let msg = InstantiateMsg {
+            receiver: "treasury".to_string(),
+            fee_rate: Decimal::percent(10),
+            fee_collector: Some("collector".to_string()),
+            ..Default::default()
+        };
+        let info = mock_info("creator", &[]);
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let msg = ExecuteMsg::SetDestination {
+            name: "burn".to_string(),
+            destination: Destination::Burn,
+        };
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("anyone", &[]),
+            msg.clone(),
+        );
+        match res {
+            Err(ContractError::Unauthorized {}) => {}
+            _ => panic!("Must return unauthorized error"),
+        }
+        let _res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+        let msg = ExecuteMsg::SetDestination {
+            name: "pool".to_string(),
+            destination: Destination::CommunityPool,
+        };
+        let _res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+
+        let msg = QueryMsg::ListDestinations {
+            start_after: None,
+            limit: None,
+        };
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let value: DestinationsResponse = from_json(&res).unwrap();
+        assert_eq!(
+            value.destinations,
+            vec![
+                DestinationInfo {
+                    name: "burn".to_string(),
+                    destination: Destination::Burn,
+                },
+                DestinationInfo {
+                    name: "pool".to_string(),
+                    destination: Destination::CommunityPool,
+                },
+            ]
+        );
+
+        // anyone can route a deposit to a destination, the fee is still collected
+        let send = |route: &str| ExecuteMsg::TokenSend {
+            recipient: None,
+            route: Some(route.to_string()),
+            memo: None,
+            on_behalf_of: None,
+            category: None,
+        };
+        let info = mock_info("anyone", &coins(100, "uusd"));
+        let res = execute(deps.as_mut(), mock_env(), info, send("burn")).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![
+                SubMsg::new(BankMsg::Send {
+                    to_address: "collector".to_string(),
+                    amount: coins(10, "uusd"),
+                }),
+                SubMsg::new(BankMsg::Burn {
+                    amount: coins(90, "uusd"),
+                }),
+            ]
+        );
+        assert_eq!(res.attributes[5], attr("burned", "90uusd"));
+
+        let info = mock_info("anyone", &coins(100, "uluna"));
+        let res = execute(deps.as_mut(), mock_env(), info, send("pool")).unwrap();
+        assert_eq!(
+            res.messages[1],
+            SubMsg::new(DistributionMsg::FundCommunityPool {
+                amount: coins(90, "uluna"),
+            })
+        );
+        assert_eq!(res.attributes[5], attr("community_pool", "90uluna"));
+
+        // an alias of the same name replaces the destination
+        let msg = ExecuteMsg::SetAlias {
+            name: "pool".to_string(),
+            address: "charitywallet".to_string(),
+        };
+        let _res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+        let info = mock_info("anyone", &coins(100, "uluna"));
+        let res = execute(deps.as_mut(), mock_env(), info, send("pool")).unwrap();
+        assert_eq!(res.attributes[5], attr("recipient", "charitywallet"));
+
+        let msg = ExecuteMsg::RemoveAlias {
+            name: "burn".to_string(),
+        };
+        let _res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+        let info = mock_info("anyone", &coins(100, "uusd"));
+        let res = execute(deps.as_mut(), mock_env(), info, send("burn"));
+        match res {
+            Err(ContractError::UnknownAlias { name }) => assert_eq!(name, "burn"),
+            _ => panic!("Must return UnknownAlias error"),
+        }
+    }
+
+    #[test]
+    fn wind_down() {
+        let mut deps = mock_dependencies(&[coin(300, "uusd")]);
+
+        let msg = InstantiateMsg {
+            receiver: "treasury".to_string(),
+            ..Default::default()
+        };
+        let _res = instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+        let msg = ExecuteMsg::HashlockSend {
+            hash: "2bb80d537b1da3e38bd30361aa855686bde0eacd7162fef6a25fe97bf527a25b".to_string(),
+            timeout: Duration::Time(3600),
+            memo: None,
+        };
+        let info = mock_info("alice", &coins(100, "uusd"));
+        let _res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("anyone", &[]),
+            ExecuteMsg::WindDown {},
+        );
+        match res {
+            Err(ContractError::Unauthorized {}) => {}
+            _ => panic!("Must return unauthorized error"),
+        }
+        let _res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            ExecuteMsg::WindDown {},
+        )
+        .unwrap();
+        let claim_deadline = mock_env().block.height + CLAIM_PERIOD;
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::WindDown {}).unwrap();
+        let value: WindDownResponse = from_json(&res).unwrap();
+        assert_eq!(
+            value,
+            WindDownResponse {
+                claim_deadline: Some(claim_deadline),
+                terminated: false,
+            }
+        );
+
+        // deposits stop, escrows can be refunded before they expire
+        let send = ExecuteMsg::TokenSend {
+            recipient: None,
+            route: None,
+            memo: None,
+            on_behalf_of: None,
+            category: None,
+        };
+        let info = mock_info("anyone", &coins(10, "uusd"));
+        let res = execute(deps.as_mut(), mock_env(), info, send.clone());
+        match res {
+            Err(ContractError::WindingDown {}) => {}
+            _ => panic!("Must return WindingDown error"),
+        }
+        let msg = ExecuteMsg::Refund { id: 1 };
+        let res = execute(deps.as_mut(), mock_env(), mock_info("alice", &[]), msg).unwrap();
+        assert_eq!(res.attributes[0], attr("action", "refund"));
+
+        let mut env = mock_env();
+        env.block.height = claim_deadline - 1;
+        let res = execute(
+            deps.as_mut(),
+            env,
+            mock_info("anyone", &[]),
+            ExecuteMsg::Terminate {},
+        );
+        match res {
+            Err(ContractError::ClaimPeriodNotOver { claim_deadline: at }) => {
+                assert_eq!(at, claim_deadline)
+            }
+            _ => panic!("Must return ClaimPeriodNotOver error"),
+        }
+        let mut env = mock_env();
+        env.block.height = claim_deadline;
+        let res = execute(
+            deps.as_mut(),
+            env,
+            mock_info("anyone", &[]),
+            ExecuteMsg::Terminate {},
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: "treasury".to_string(),
+                amount: coins(300, "uusd"),
+            })]
+        );
+
+        // nothing runs anymore
+        let msgs = vec![
+            ExecuteMsg::Terminate {},
+            ExecuteMsg::Pause {},
+            ExecuteMsg::Claim {},
+        ];
+        for msg in msgs {
+            let res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg);
+            match res {
+                Err(ContractError::Terminated {}) => {}
+                _ => panic!("Must return Terminated error"),
+            }
+        }
+    }
+
+    #[test]
+    fn update_config() {
+        let mut deps = mock_dependencies(&[]);
+
+        let msg = InstantiateMsg {
+            receiver: "treasury".to_string(),
+            ..Default::default()
+        };
+        let _res = instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+
+        let update = ConfigUpdate {
+            fee_rate: Some(Decimal::percent(5)),
+            fee_collector: Some("collector".to_string()),
+            min_amount: Some(coins(10, "uusd")),
+            max_amount: Some(coins(1000, "uusd")),
+            paused: Some(true),
+            ..Default::default()
+        };
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("anyone", &[]),
+            ExecuteMsg::UpdateConfig(update.clone()),
+        );
+        match res {
+            Err(ContractError::Unauthorized {}) => {}
+            _ => panic!("Must return unauthorized error"),
+        }
+        let _res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            ExecuteMsg::UpdateConfig(update),
+        )
+        .unwrap();
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetConfig {}).unwrap();
+        let value: ConfigResponse = from_json(&res).unwrap();
+        assert_eq!(value.fee_rate, Decimal::percent(5));
+        assert_eq!(value.fee_collector, Some("collector".to_string()));
+        assert_eq!(value.min_amount, coins(10, "uusd"));
+        assert!(value.paused);
+        assert_eq!(value.funds_policy, FundsPolicy::ForwardAccepted);
+
+        // an invalid result changes nothing
+        let updates = vec![
+            ConfigUpdate {
+                fee_rate: Some(Decimal::percent(150)),
+                paused: Some(false),
+                ..Default::default()
+            },
+            ConfigUpdate {
+                min_amount: Some(coins(2000, "uusd")),
+                paused: Some(false),
+                ..Default::default()
+            },
+        ];
+        for update in updates {
+            let res = execute(
+                deps.as_mut(),
+                mock_env(),
+                mock_info("creator", &[]),
+                ExecuteMsg::UpdateConfig(update),
+            );
+            assert!(res.is_err());
+        }
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetConfig {}).unwrap();
+        let value: ConfigResponse = from_json(&res).unwrap();
+        assert_eq!(value.fee_rate, Decimal::percent(5));
+        assert_eq!(value.min_amount, coins(10, "uusd"));
+        assert!(value.paused);
+
+        let update = ConfigUpdate {
+            max_amount: Some(coins(5, "uusd")),
+            ..Default::default()
+        };
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            ExecuteMsg::UpdateConfig(update),
+        );
+        match res {
+            Err(ContractError::InvalidLimits { denom }) => assert_eq!(denom, "uusd"),
+            _ => panic!("Must return InvalidLimits error"),
+        }
+    }
+
+    #[test]
+    fn batched_distribution() {
+        let mut deps = mock_dependencies(&[]);
+
+        let msg = InstantiateMsg {
+            receiver: "treasury".to_string(),
+            receivers: vec![
+                ("alice".to_string(), Decimal::percent(40)),
+                ("bob".to_string(), Decimal::percent(30)),
+                ("carol".to_string(), Decimal::percent(30)),
+            ],
+            max_payouts: Some(2),
+            ..Default::default()
+        };
+        let _res = instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+
+        // three receivers are more than max_payouts, so nothing is paid right away
+        let send = ExecuteMsg::TokenSend {
+            recipient: None,
+            route: None,
+            memo: None,
+            on_behalf_of: None,
+            category: None,
+        };
+        let info = mock_info("anyone", &coins(100, "uusd"));
+        let res = execute(deps.as_mut(), mock_env(), info, send).unwrap();
+        assert!(res.messages.is_empty());
+        assert_eq!(res.attributes[3], attr("queued", "3"));
+
+        let msg = QueryMsg::PayoutQueue {
+            start_after: None,
+            limit: None,
+        };
+        let res = query(deps.as_ref(), mock_env(), msg.clone()).unwrap();
+        let value: PayoutQueueResponse = from_json(&res).unwrap();
+        assert_eq!(value.cursor, 0);
+        assert_eq!(
+            value.payouts[2],
+            QueuedPayoutInfo {
+                id: 3,
+                receiver: "carol".to_string(),
+                amount: coins(30, "uusd"),
+            }
+        );
+
+        // anyone can pay the queue in batches
+        let distribute = ExecuteMsg::Distribute { limit: Some(2) };
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("keeper", &[]),
+            distribute.clone(),
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages,
+            vec![
+                SubMsg::new(BankMsg::Send {
+                    to_address: "alice".to_string(),
+                    amount: coins(40, "uusd"),
+                }),
+                SubMsg::new(BankMsg::Send {
+                    to_address: "bob".to_string(),
+                    amount: coins(30, "uusd"),
+                }),
+            ]
+        );
+        assert_eq!(res.attributes[4], attr("remaining", "1"));
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let value: PayoutQueueResponse = from_json(&res).unwrap();
+        assert_eq!(value.cursor, 2);
+        assert_eq!(value.payouts.len(), 1);
+
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("keeper", &[]),
+            distribute.clone(),
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: "carol".to_string(),
+                amount: coins(30, "uusd"),
+            })]
+        );
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("keeper", &[]),
+            distribute,
+        );
+        match res {
+            Err(ContractError::NothingToDistribute {}) => {}
+            _ => panic!("Must return NothingToDistribute error"),
+        }
+
+        let msg = ExecuteMsg::SetMaxPayouts { max_payouts: None };
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("anyone", &[]),
+            msg.clone(),
+        );
+        match res {
+            Err(ContractError::Unauthorized {}) => {}
+            _ => panic!("Must return unauthorized error"),
+        }
+        let _res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+    }
+
+    #[test]
+    fn settled_receipts() {
+        let mut deps = mock_dependencies(&[]);
+
+        let msg = InstantiateMsg {
+            receiver: "treasury".to_string(),
+            escrow: Some(Duration::Height(100)),
+            ..Default::default()
+        };
+        let _res = instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+
+        let send = ExecuteMsg::TokenSend {
+            recipient: None,
+            route: None,
+            memo: None,
+            on_behalf_of: None,
+            category: None,
+        };
+        let info = mock_info("anyone", &coins(100, "uusd"));
+        let res = execute(deps.as_mut(), mock_env(), info, send.clone()).unwrap();
+        let receipt: SendReceipt = from_json(res.data.unwrap()).unwrap();
+        assert_eq!(receipt.id, 1);
+        assert!(receipt.recipients.is_empty());
+
+        // the release keeps the receipt id and records who was paid
+        let msg = ExecuteMsg::Release {
+            id: 1,
+            preimage: None,
+        };
+        let res = execute(deps.as_mut(), mock_env(), mock_info("treasury", &[]), msg).unwrap();
+        assert!(res.attributes.contains(&attr("receipt_id", "1")));
+        let receipt: SendReceipt = from_json(res.data.unwrap()).unwrap();
+        assert_eq!(
+            receipt,
+            SendReceipt {
+                id: 1,
+                sender: "anyone".to_string(),
+                recipients: vec!["treasury".to_string()],
+                amount: coins(100, "uusd"),
+                height: mock_env().block.height,
+                minted: None,
+                category: None,
+                items: vec![],
+            }
+        );
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::Receipt { id: 1 }).unwrap();
+        let value: SendReceipt = from_json(&res).unwrap();
+        assert_eq!(value, receipt);
+
+        // a held deposit is settled once the flush paid it out
+        let mut deps = mock_dependencies(&[]);
+        let msg = InstantiateMsg {
+            receiver: "treasury".to_string(),
+            hold: true,
+            flush_threshold: coins(1000, "uusd"),
+            ..Default::default()
+        };
+        let _res = instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+        let statuses = |deps: &OwnedDeps<_, _, _, _>| {
+            let msg = QueryMsg::ReceiptsBySender {
+                sender: "anyone".to_string(),
+                start_after: None,
+                limit: None,
+            };
+            let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+            let value: ReceiptsResponse = from_json(&res).unwrap();
+            value
+                .receipts
+                .into_iter()
+                .map(|receipt| receipt.status)
+                .collect::<Vec<_>>()
+        };
+        let info = mock_info("anyone", &coins(100, "uusd"));
+        let _res = execute(deps.as_mut(), mock_env(), info, send.clone()).unwrap();
+        assert_eq!(statuses(&deps), vec![ReceiptStatus::Held]);
+
+        let info = mock_info("anyone", &coins(900, "uusd"));
+        let res = execute(deps.as_mut(), mock_env(), info, send).unwrap();
+        assert_eq!(res.messages.len(), 1);
+        assert_eq!(
+            statuses(&deps),
+            vec![ReceiptStatus::Forwarded, ReceiptStatus::Forwarded]
+        );
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::Receipt { id: 1 }).unwrap();
+        let value: SendReceipt = from_json(&res).unwrap();
+        assert_eq!(value.recipients, vec!["treasury".to_string()]);
+    }
+
+    #[test]
+    fn receiver_status() {
+        let mut deps = mock_dependencies(&[]);
+        mock_contract(&mut deps.querier, "vault");
+        deps.querier
+            .update_balance("treasury", vec![coin(100, "uusd"), coin(5, "ukrw")]);
+
+        let msg = InstantiateMsg {
+            receiver: "treasury".to_string(),
+            ..Default::default()
+        };
+        let info = mock_info("creator", &[]);
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        // a plain account has balances but no contract info
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::ReceiverStatus {}).unwrap();
+        let value: ReceiverStatusResponse = from_json(&res).unwrap();
+        assert_eq!(value.receiver, "treasury");
+        assert_eq!(value.balances, vec![coin(100, "uusd"), coin(5, "ukrw")]);
+        assert_eq!(value.contract, None);
+
+        let msg = ExecuteMsg::ResetReceiver {
+            receiver: "vault".to_string(),
+        };
+        let _res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::ReceiverStatus {}).unwrap();
+        let value: ReceiverStatusResponse = from_json(&res).unwrap();
+        assert_eq!(value.receiver, "vault");
+        assert!(value.balances.is_empty());
+        assert_eq!(
+            value.contract,
+            Some(ReceiverContractInfo {
+                code_id: 0,
+                creator: "".to_string(),
+                admin: None,
+            })
+        );
+    }
+
+    #[test]
+    fn forward_exec() {
+        let mut deps = mock_dependencies(&[]);
+        mock_contract(&mut deps.querier, "vault");
+
+        let msg = InstantiateMsg {
+            receiver: "vault".to_string(),
+            pull: true,
+            ..Default::default()
+        };
+        let _res = instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+
+        let deposit = Binary::from(br#"{"deposit":{"memo":"x"}}"#);
+        let send = ExecuteMsg::ForwardExec {
+            msg: deposit.clone(),
+        };
+        let info = mock_info("alice", &coins(100, "uusd"));
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), send.clone());
+        match res {
+            Err(ContractError::MethodNotAllowed { method }) => assert_eq!(method, "deposit"),
+            _ => panic!("Must return MethodNotAllowed error"),
+        }
+
+        let allow = ExecuteMsg::AllowMethod {
+            method: "deposit".to_string(),
+        };
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("alice", &[]),
+            allow.clone(),
+        );
+        match res {
+            Err(ContractError::Unauthorized {}) => {}
+            _ => panic!("Must return unauthorized error"),
+        }
+        let _res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), allow).unwrap();
+        let msg = QueryMsg::ListAllowedMethods {
+            start_after: None,
+            limit: None,
+        };
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let value: AllowedMethodsResponse = from_json(&res).unwrap();
+        assert_eq!(value.methods, vec!["deposit".to_string()]);
+
+        // the receiver is executed right away, even in pull mode
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), send.clone()).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(WasmMsg::Execute {
+                contract_addr: "vault".to_string(),
+                msg: deposit,
+                funds: coins(100, "uusd"),
+            })]
+        );
+
+        for msg in [br#"{"deposit":{},"withdraw":{}}"#.to_vec(), b"[1]".to_vec()] {
+            let send = ExecuteMsg::ForwardExec {
+                msg: Binary::from(msg),
+            };
+            let res = execute(deps.as_mut(), mock_env(), info.clone(), send);
+            match res {
+                Err(ContractError::InvalidExecMsg {}) => {}
+                _ => panic!("Must return InvalidExecMsg error"),
+            }
+        }
+
+        let msg = ExecuteMsg::ResetReceiver {
+            receiver: "treasury".to_string(),
+        };
+        let _res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), send.clone());
+        match res {
+            Err(ContractError::NotAContract { address }) => assert_eq!(address, "treasury"),
+            _ => panic!("Must return NotAContract error"),
+        }
+
+        let msg = ExecuteMsg::DisallowMethod {
+            method: "deposit".to_string(),
+        };
+        let _res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+        let res = execute(deps.as_mut(), mock_env(), info, send);
+        match res {
+            Err(ContractError::MethodNotAllowed { .. }) => {}
+            _ => panic!("Must return MethodNotAllowed error"),
+        }
+    }
+
+    #[test]
+    fn claim_rebates() {
+        let mut deps = mock_dependencies(&[]);
+
+        let msg = InstantiateMsg {
+            receiver: "treasury".to_string(),
+            pull: true,
+            claim_rebate: Some(coin(2, "uluna")),
+            ..Default::default()
+        };
+        let _res = instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+
+        let send = ExecuteMsg::TokenSend {
+            recipient: None,
+            route: None,
+            memo: None,
+            on_behalf_of: None,
+            category: None,
+        };
+        let withdraw = ExecuteMsg::Withdraw {
+            denom: None,
+            amount: None,
+        };
+        let info = mock_info("alice", &coins(100, "uusd"));
+        let _res = execute(deps.as_mut(), mock_env(), info.clone(), send.clone()).unwrap();
+
+        // no rebate is paid while the pool is empty
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("treasury", &[]),
+            withdraw.clone(),
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: "treasury".to_string(),
+                amount: coins(100, "uusd"),
+            })]
+        );
+
+        let top_up = ExecuteMsg::TopUpRebates {};
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("alice", &coins(3, "uluna")),
+            top_up.clone(),
+        );
+        match res {
+            Err(ContractError::Unauthorized {}) => {}
+            _ => panic!("Must return unauthorized error"),
+        }
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            top_up.clone(),
+        );
+        match res {
+            Err(ContractError::NoFundsSent {}) => {}
+            _ => panic!("Must return NoFundsSent error"),
+        }
+        let _res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &coins(3, "uluna")),
+            top_up,
+        )
+        .unwrap();
+
+        // each claim gets up to the rebate, until the pool runs dry
+        for rebate in [2, 1] {
+            let _res = execute(deps.as_mut(), mock_env(), info.clone(), send.clone()).unwrap();
+            let res = execute(
+                deps.as_mut(),
+                mock_env(),
+                mock_info("treasury", &[]),
+                withdraw.clone(),
+            )
+            .unwrap();
+            assert_eq!(
+                res.messages,
+                vec![SubMsg::new(BankMsg::Send {
+                    to_address: "treasury".to_string(),
+                    amount: vec![coin(100, "uusd"), coin(rebate, "uluna")],
+                })]
+            );
+            assert!(res
+                .attributes
+                .contains(&attr("rebate", format!("{}uluna", rebate))));
+        }
+
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::RebatePool {}).unwrap();
+        let value: RebatePoolResponse = from_json(&res).unwrap();
+        assert!(value.balance.is_empty());
+        assert_eq!(value.claim_rebate, Some(coin(2, "uluna")));
+    }
+
+    #[test]
+    fn stats_at_height() {
+        let mut deps = mock_dependencies(&[]);
+
+        let msg = InstantiateMsg {
+            receiver: "treasury".to_string(),
+            ..Default::default()
+        };
+        let _res = instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+
+        let msg = ExecuteMsg::TokenSend {
+            recipient: None,
+            route: None,
+            memo: None,
+            on_behalf_of: None,
+            category: None,
+        };
+        let start = mock_env().block.height;
+        for (offset, sender) in [(0, "alice"), (10, "bob"), (10, "carol")] {
+            let mut env = mock_env();
+            env.block.height += offset;
+            let info = mock_info(sender, &coins(100, "uusd"));
+            let _res = execute(deps.as_mut(), env, info, msg.clone()).unwrap();
+        }
+
+        // a block counts every forward made in it
+        for (height, forwards, forwarded) in [
+            (start - 1, 0, 0),
+            (start, 1, 100),
+            (start + 5, 1, 100),
+            (start + 10, 3, 300),
+            (start + 100, 3, 300),
+        ] {
+            let msg = QueryMsg::StatsAt { height };
+            let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+            let value: StatsResponse = from_json(&res).unwrap();
+            assert_eq!(value.forwards, forwards);
+            assert_eq!(
+                value
+                    .forwarded
+                    .iter()
+                    .map(|coin| coin.amount.u128())
+                    .sum::<u128>(),
+                forwarded
+            );
+        }
+    }
+
+    #[test]
+    fn transfers_by_address() {
+        let mut deps = mock_dependencies(&[]);
+
+        let msg = InstantiateMsg {
+            receiver: "treasury".to_string(),
+            allowlist: vec!["router".to_string()],
+            ..Default::default()
+        };
+        let _res = instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+
+        let send = ExecuteMsg::TokenSend {
+            recipient: None,
+            route: None,
+            memo: None,
+            on_behalf_of: None,
+            category: None,
+        };
+        for sender in ["alice", "bob", "alice"] {
+            let info = mock_info(sender, &coins(100, "uusd"));
+            let _res = execute(deps.as_mut(), mock_env(), info, send.clone()).unwrap();
+        }
+        let msg = ExecuteMsg::TokenSend {
+            recipient: Some("vendor".to_string()),
+            route: None,
+            memo: None,
+            on_behalf_of: None,
+            category: None,
+        };
+        let info = mock_info("router", &coins(100, "uusd"));
+        let _res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        // a held deposit is indexed under the receiver once it is released
+        let msg = ExecuteMsg::SetEscrow {
+            escrow: Some(Duration::Height(100)),
+        };
+        let _res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+        let info = mock_info("alice", &coins(100, "uusd"));
+        let _res = execute(deps.as_mut(), mock_env(), info, send).unwrap();
+
+        let ids = |deps: Deps<_>, msg: QueryMsg| -> Vec<u64> {
+            let res = query(deps, mock_env(), msg).unwrap();
+            let value: TransferHistoryResponse = from_json(&res).unwrap();
+            value.transfers.iter().map(|t| t.id).collect()
+        };
+        let by_sender = |addr: &str, start_after| QueryMsg::TransfersBySender {
+            addr: addr.to_string(),
+            start_after,
+            limit: None,
+        };
+        let by_receiver = |addr: &str| QueryMsg::TransfersByReceiver {
+            addr: addr.to_string(),
+            start_after: None,
+            limit: None,
+        };
+        assert_eq!(ids(deps.as_ref(), by_sender("alice", None)), vec![1, 3, 5]);
+        assert_eq!(ids(deps.as_ref(), by_sender("alice", Some(1))), vec![3, 5]);
+        assert_eq!(ids(deps.as_ref(), by_sender("bob", None)), vec![2]);
+        assert_eq!(ids(deps.as_ref(), by_receiver("treasury")), vec![1, 2, 3]);
+        assert_eq!(ids(deps.as_ref(), by_receiver("vendor")), vec![4]);
+
+        let msg = ExecuteMsg::Release {
+            id: 5,
+            preimage: None,
+        };
+        let _res = execute(deps.as_mut(), mock_env(), mock_info("treasury", &[]), msg).unwrap();
+        assert_eq!(
+            ids(deps.as_ref(), by_receiver("treasury")),
+            vec![1, 2, 3, 5]
+        );
+    }
+
+    #[test]
+    fn history_retention() {
+        let mut deps = mock_dependencies(&[]);
+
+        let msg = InstantiateMsg {
+            receiver: "treasury".to_string(),
+            retention: Some(Retention::MaxEntries { count: 2 }),
+            ..Default::default()
+        };
+        let _res = instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+
+        let send = ExecuteMsg::TokenSend {
+            recipient: None,
+            route: None,
+            memo: Some("invoice".to_string()),
+            on_behalf_of: None,
+            category: None,
+        };
+        let info = mock_info("alice", &coins(100, "uusd"));
+        for _ in 0..4 {
+            let _res = execute(deps.as_mut(), mock_env(), info.clone(), send.clone()).unwrap();
+        }
+        let ids = |deps: Deps<_>| -> Vec<u64> {
+            let msg = QueryMsg::TransferHistory {
+                start_after: None,
+                limit: None,
+            };
+            let res = query(deps, mock_env(), msg).unwrap();
+            let value: TransferHistoryResponse = from_json(&res).unwrap();
+            value.transfers.iter().map(|t| t.id).collect()
+        };
+        assert_eq!(ids(deps.as_ref()), vec![3, 4]);
+        let msg = QueryMsg::TransfersBySender {
+            addr: "alice".to_string(),
+            start_after: None,
+            limit: None,
+        };
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let value: TransferHistoryResponse = from_json(&res).unwrap();
+        assert_eq!(value.transfers.len(), 2);
+
+        let msg = ExecuteMsg::Prune { up_to_id: 10 };
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("alice", &[]),
+            msg.clone(),
+        );
+        match res {
+            Err(ContractError::Unauthorized {}) => {}
+            _ => panic!("Must return unauthorized error"),
+        }
+        let res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+        assert!(res.attributes.contains(&attr("pruned", "2")));
+        assert!(ids(deps.as_ref()).is_empty());
+
+        // ids keep counting after a prune, old transfers age out
+        let msg = ExecuteMsg::SetRetention {
+            retention: Some(Retention::MaxAge { blocks: 10 }),
+        };
+        let _res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), send.clone()).unwrap();
+        assert!(res.attributes.contains(&attr("receipt_id", "5")));
+        let mut env = mock_env();
+        env.block.height += 10;
+        let _res = execute(deps.as_mut(), env.clone(), info.clone(), send.clone()).unwrap();
+        assert_eq!(ids(deps.as_ref()), vec![5, 6]);
+        env.block.height += 1;
+        let _res = execute(deps.as_mut(), env, info, send).unwrap();
+        assert_eq!(ids(deps.as_ref()), vec![6, 7]);
+    }
+
+    #[test]
+    fn deposit_on_behalf_of() {
+        let mut deps = mock_dependencies(&[]);
+
+        let msg = InstantiateMsg {
+            receiver: "treasury".to_string(),
+            allowlist: vec!["router".to_string()],
+            ..Default::default()
+        };
+        let _res = instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+
+        let send = ExecuteMsg::TokenSend {
+            recipient: None,
+            route: None,
+            memo: None,
+            on_behalf_of: Some("alice".to_string()),
+            category: None,
+        };
+        let info = mock_info("proxy", &coins(100, "uusd"));
+        let res = execute(deps.as_mut(), mock_env(), info, send.clone());
+        match res {
+            Err(ContractError::Unauthorized {}) => {}
+            _ => panic!("Must return unauthorized error"),
+        }
+
+        // an allowlisted contract attributes the deposit to the user
+        let info = mock_info("router", &coins(100, "uusd"));
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), send.clone()).unwrap();
+        assert!(res.attributes.contains(&attr("sender", "alice")));
+        assert!(res.attributes.contains(&attr("caller", "router")));
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::Receipt { id: 1 }).unwrap();
+        let value: SendReceipt = from_json(&res).unwrap();
+        assert_eq!(value.sender, "alice");
+        let msg = QueryMsg::Contributions {
+            sender: "alice".to_string(),
+        };
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let value: ContributionsResponse = from_json(&res).unwrap();
+        assert_eq!(value.contributions, coins(100, "uusd"));
+        let msg = QueryMsg::Contributions {
+            sender: "router".to_string(),
+        };
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let value: ContributionsResponse = from_json(&res).unwrap();
+        assert!(value.contributions.is_empty());
+
+        // a blocked user cannot deposit through a contract either
+        let msg = ExecuteMsg::BlockSender {
+            address: "alice".to_string(),
+        };
+        let _res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+        let res = execute(deps.as_mut(), mock_env(), info, send);
+        match res {
+            Err(ContractError::SenderBlocked {}) => {}
+            _ => panic!("Must return SenderBlocked error"),
+        }
+    }
+
+    #[test]
+    fn sudo_control() {
+        let mut deps = mock_dependencies(&[]);
+
+        let msg = InstantiateMsg {
+            receiver: "treasury".to_string(),
+            receiver_delay: Some(Duration::Height(100)),
+            ..Default::default()
+        };
+        let _res = instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+        let msg = ExecuteMsg::RenounceOwnership {};
+        let _res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+
+        // the chain can still administer the contract without an owner
+        let _res = sudo(deps.as_mut(), mock_env(), SudoMsg::Pause {}).unwrap();
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetPaused {}).unwrap();
+        let value: PausedResponse = from_json(&res).unwrap();
+        assert!(value.paused);
+        let _res = sudo(deps.as_mut(), mock_env(), SudoMsg::Unpause {}).unwrap();
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetPaused {}).unwrap();
+        let value: PausedResponse = from_json(&res).unwrap();
+        assert!(!value.paused);
+
+        // the receiver changes right away, whatever the delay
+        let msg = SudoMsg::SetReceiver {
+            receiver: "charity".to_string(),
+        };
+        let res = sudo(deps.as_mut(), mock_env(), msg).unwrap();
+        assert_eq!(res.attributes[1], attr("recipient", "charity"));
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetReceiver {}).unwrap();
+        let value: ReceiverResponse = from_json(&res).unwrap();
+        assert_eq!(value.receiver, "charity");
+        let msg = QueryMsg::ReceiverHistory {
+            start_after: None,
+            limit: None,
+        };
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let value: ReceiverHistoryResponse = from_json(&res).unwrap();
+        assert_eq!(value.changes[0].actor, MOCK_CONTRACT_ADDR);
+    }
+
+    #[test]
+    fn ownership_expiry() {
+        let mut deps = mock_dependencies(&[]);
+
+        let msg = InstantiateMsg {
+            receiver: "treasury".to_string(),
+            ..Default::default()
+        };
+        let _res = instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+
+        let expires = Expiration::AtHeight(mock_env().block.height + 100);
+        let msg = ExecuteMsg::SetOwnershipExpiry {
+            ownership_expires_at: expires,
+        };
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("alice", &[]),
+            msg.clone(),
+        );
+        match res {
+            Err(ContractError::Unauthorized {}) => {}
+            _ => panic!("Must return unauthorized error"),
+        }
+        let _res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            msg.clone(),
+        )
+        .unwrap();
+        // the expiry cannot be pushed back
+        let res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg);
+        match res {
+            Err(ContractError::OwnershipExpirySet {}) => {}
+            _ => panic!("Must return OwnershipExpirySet error"),
+        }
+
+        let pause = ExecuteMsg::Pause {};
+        let _res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            pause.clone(),
+        )
+        .unwrap();
+
+        let mut env = mock_env();
+        env.block.height += 100;
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::Ownership {}).unwrap();
+        let value: OwnershipResponse = from_json(&res).unwrap();
+        assert_eq!(
+            value,
+            OwnershipResponse {
+                owner: None,
+                expires_at: Some(expires),
+                expired: true,
+            }
+        );
+        let res = execute(deps.as_mut(), env.clone(), mock_info("creator", &[]), pause);
+        match res {
+            Err(ContractError::Unauthorized {}) => {}
+            _ => panic!("Must return unauthorized error"),
+        }
+        let res = query(deps.as_ref(), env, QueryMsg::GetConfig {}).unwrap();
+        let value: ConfigResponse = from_json(&res).unwrap();
+        assert_eq!(value.owner, None);
+    }
+
+    #[test]
+    fn split_sums_to_input() {
+        // a fixed linear congruential generator stands in for random inputs
+        let mut seed: u64 = 42;
+        let mut next = move || {
+            seed = seed
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            seed >> 33
+        };
+        for _ in 0..500 {
+            let amount = Uint128::new(match next() % 3 {
+                0 => (next() % 10) as u128,
+                1 => next() as u128,
+                _ => (next() as u128) << 64 | next() as u128,
+            });
+            let count = (next() % 6 + 1) as usize;
+            let raw: Vec<u64> = (0..count).map(|_| next() % 1000 + 1).collect();
+            let total: u64 = raw.iter().sum();
+            let mut weights: Vec<Decimal> = raw[1..]
+                .iter()
+                .map(|w| Decimal::from_ratio(*w, total))
+                .collect();
+            let rest = weights
+                .iter()
+                .fold(Decimal::one(), |rest, weight| rest - *weight);
+            weights.insert(0, rest);
+
+            for rounding in [
+                Rounding::FirstReceiver,
+                Rounding::LargestRemainder,
+                Rounding::Floor,
+            ] {
+                let (shares, left_over) = split_amount(amount, &weights, rounding).unwrap();
+                assert_eq!(shares.len(), count);
+                let sum = shares.iter().fold(left_over, |total, share| total + *share);
+                assert_eq!(sum, amount);
+                for (share, weight) in shares.iter().zip(weights.iter()) {
+                    let floor = amount * *weight;
+                    assert!(*share >= floor);
+                    if rounding == Rounding::LargestRemainder {
+                        assert!(*share <= floor + Uint128::one());
+                    }
+                }
+                if rounding == Rounding::Floor {
+                    assert!(left_over < Uint128::new(count as u128));
+                } else {
+                    assert!(left_over.is_zero());
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn rounding_policies() {
+        let receivers = vec![
+            ("alice".to_string(), Decimal::percent(20)),
+            ("bob".to_string(), Decimal::percent(30)),
+            ("carol".to_string(), Decimal::percent(50)),
+        ];
+        // 7 is split into 1.4, 2.1 and 3.5
+        for (rounding, expected, left_over) in [
+            (Rounding::FirstReceiver, [2, 2, 3], 0),
+            (Rounding::LargestRemainder, [1, 2, 4], 0),
+            (Rounding::Floor, [1, 2, 3], 1),
+        ] {
+            let mut deps = mock_dependencies(&[]);
+            let msg = InstantiateMsg {
+                receiver: "treasury".to_string(),
+                receivers: receivers.clone(),
+                rounding,
+                ..Default::default()
+            };
+            let info = mock_info("creator", &[]);
+            let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+            let msg = QueryMsg::PreviewSplit {
+                amount: coin(7, "uusd"),
+            };
+            let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+            let value: SplitPreviewResponse = from_json(&res).unwrap();
+            let amounts: Vec<u128> = value
+                .shares
+                .iter()
+                .map(|share| share.amount[0].amount.u128())
+                .collect();
+            assert_eq!(amounts, expected);
+            assert_eq!(value.left_over, Uint128::new(left_over));
+
+            // the preview matches the forward, what is left over is kept as dust
+            let send = ExecuteMsg::TokenSend {
+                recipient: None,
+                route: None,
+                memo: None,
+                on_behalf_of: None,
+                category: None,
+            };
+            let info = mock_info("anyone", &coins(7, "uusd"));
+            let res = execute(deps.as_mut(), mock_env(), info, send).unwrap();
+            let sent: Vec<u128> = res
+                .messages
+                .iter()
+                .map(|msg| match &msg.msg {
+                    CosmosMsg::Bank(BankMsg::Send { amount, .. }) => amount[0].amount.u128(),
+                    _ => panic!("unexpected message"),
+                })
+                .collect();
+            assert_eq!(sent, expected);
+            let res = query(deps.as_ref(), mock_env(), QueryMsg::Dust {}).unwrap();
+            let value: DustResponse = from_json(&res).unwrap();
+            let dust: u128 = value.dust.iter().map(|coin| coin.amount.u128()).sum();
+            assert_eq!(dust, left_over);
+        }
+    }
+
+    #[test]
+    fn named_routes() {
+        let mut deps = mock_dependencies(&[]);
+
+        let msg = InstantiateMsg {
+            receiver: "treasury".to_string(),
+            ..Default::default()
+        };
+        let _res = instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+
+        let grants = RouteSettings {
+            receiver: "grantsmultisig".to_string(),
+            receivers: vec![
+                ("granteeone".to_string(), Decimal::percent(50)),
+                ("granteetwo".to_string(), Decimal::percent(50)),
+            ],
+            fee_rate: Decimal::percent(10),
+            fee_collector: Some("feepot".to_string()),
+            fee_cap: vec![],
+            accepted_denoms: vec!["uusd".to_string()],
+            funds_policy: FundsPolicy::RejectMixed,
+        };
+        let msg = ExecuteMsg::SetRoute {
+            name: "grants".to_string(),
+            route: grants.clone(),
+        };
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("mallory", &[]),
+            msg.clone(),
+        );
+        match res {
+            Err(ContractError::Unauthorized {}) => {}
+            _ => panic!("Must return unauthorized error"),
+        }
+        let _res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+
+        // a route name cannot be reused as an alias
+        let msg = ExecuteMsg::SetAlias {
+            name: "grants".to_string(),
+            address: "opswallet".to_string(),
+        };
+        let res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg);
+        match res {
+            Err(ContractError::NameTaken { name }) => assert_eq!(name, "grants"),
+            _ => panic!("Must return NameTaken error"),
+        }
+
+        // the route splits with its own receivers and fee
+        let msg = ExecuteMsg::TokenSend {
+            recipient: None,
+            route: Some("grants".to_string()),
+            memo: None,
+            on_behalf_of: None,
+            category: None,
+        };
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("alice", &coins(100, "uusd")),
+            msg.clone(),
+        )
+        .unwrap();
+        let mut sent: Vec<(String, Vec<Coin>)> = res
+            .messages
+            .iter()
+            .map(|msg| match &msg.msg {
+                CosmosMsg::Bank(BankMsg::Send { to_address, amount }) => {
+                    (to_address.clone(), amount.clone())
+                }
+                _ => panic!("Unexpected message"),
+            })
+            .collect();
+        sent.sort_by(|a, b| a.0.cmp(&b.0));
+        assert_eq!(
+            sent,
+            vec![
+                ("feepot".to_string(), coins(10, "uusd")),
+                ("granteeone".to_string(), coins(45, "uusd")),
+                ("granteetwo".to_string(), coins(45, "uusd")),
+            ]
+        );
+
+        // and its own denom policy
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("alice", &[coin(100, "uusd"), coin(5, "uluna")]),
+            msg,
+        );
+        match res {
+            Err(ContractError::MixedDenomsNotAllowed {}) => {}
+            _ => panic!("Must return MixedDenomsNotAllowed error"),
+        }
+
+        // a plain deposit still goes to the contract's receiver
+        let msg = ExecuteMsg::TokenSend {
+            recipient: None,
+            route: None,
+            memo: None,
+            on_behalf_of: None,
+            category: None,
+        };
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("alice", &coins(10, "uusd")),
+            msg,
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: "treasury".to_string(),
+                amount: coins(10, "uusd"),
+            })]
+        );
+
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::ListRoutes {
+                start_after: None,
+                limit: None,
+            },
+        )
+        .unwrap();
+        let value: RoutesResponse = from_json(&res).unwrap();
+        assert_eq!(
+            value.routes,
+            vec![RouteInfo {
+                name: "grants".to_string(),
+                route: grants,
+                paused: false,
+            }]
+        );
+
+        let msg = ExecuteMsg::RemoveRoute {
+            name: "grants".to_string(),
+        };
+        let _res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+        let msg = ExecuteMsg::TokenSend {
+            recipient: None,
+            route: Some("grants".to_string()),
+            memo: None,
+            on_behalf_of: None,
+            category: None,
+        };
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("alice", &coins(10, "uusd")),
+            msg,
+        );
+        match res {
+            Err(ContractError::UnknownAlias { name }) => assert_eq!(name, "grants"),
+            _ => panic!("Must return UnknownAlias error"),
+        }
+    }
+
+    #[test]
+    fn route_pause_and_stats() {
+        let mut deps = mock_dependencies(&[]);
+
+        let msg = InstantiateMsg {
+            receiver: "treasury".to_string(),
+            ..Default::default()
+        };
+        let _res = instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+        for name in ["grants", "bounties"] {
+            let msg = ExecuteMsg::SetRoute {
+                name: name.to_string(),
+                route: RouteSettings {
+                    receiver: format!("{}wallet", name),
+                    receivers: vec![],
+                    fee_rate: Decimal::zero(),
+                    fee_collector: None,
+                    fee_cap: vec![],
+                    accepted_denoms: vec![],
+                    funds_policy: FundsPolicy::default(),
+                },
+            };
+            let _res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+        }
+        let send = |route: &str| ExecuteMsg::TokenSend {
+            recipient: None,
+            route: Some(route.to_string()),
+            memo: None,
+            on_behalf_of: None,
+            category: None,
+        };
+
+        let _res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("alice", &coins(10, "uusd")),
+            send("grants"),
+        )
+        .unwrap();
+        let _res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("bob", &coins(15, "uusd")),
+            send("grants"),
+        )
+        .unwrap();
+
+        let msg = ExecuteMsg::PauseRoute {
+            name: "grants".to_string(),
+        };
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("mallory", &[]),
+            msg.clone(),
+        );
+        match res {
+            Err(ContractError::Unauthorized {}) => {}
+            _ => panic!("Must return unauthorized error"),
+        }
+        let _res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+
+        // only the paused route stops taking deposits
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("alice", &coins(10, "uusd")),
+            send("grants"),
+        );
+        match res {
+            Err(ContractError::RoutePaused { name }) => assert_eq!(name, "grants"),
+            _ => panic!("Must return RoutePaused error"),
+        }
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("alice", &coins(7, "uusd")),
+            send("bounties"),
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: "bountieswallet".to_string(),
+                amount: coins(7, "uusd"),
+            })]
+        );
+
+        let stats = |deps: Deps<_>, name: &str| -> RouteStatsResponse {
+            let msg = QueryMsg::RouteStats {
+                name: name.to_string(),
+            };
+            from_json(query(deps, mock_env(), msg).unwrap()).unwrap()
+        };
+        assert_eq!(
+            stats(deps.as_ref(), "grants"),
+            RouteStatsResponse {
+                name: "grants".to_string(),
+                paused: true,
+                forwarded: coins(25, "uusd"),
+                forwards: 2,
+                last_forward_height: Some(mock_env().block.height),
+            }
+        );
+        let value = stats(deps.as_ref(), "bounties");
+        assert!(!value.paused);
+        assert_eq!(value.forwarded, coins(7, "uusd"));
+        assert_eq!(value.forwards, 1);
+
+        let msg = ExecuteMsg::UnpauseRoute {
+            name: "grants".to_string(),
+        };
+        let _res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+        let _res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("alice", &coins(10, "uusd")),
+            send("grants"),
+        )
+        .unwrap();
+        let value = stats(deps.as_ref(), "grants");
+        assert!(!value.paused);
+        assert_eq!(value.forwarded, coins(35, "uusd"));
+    }
+
+    #[test]
+    fn typed_destinations() {
+        let mut deps = mock_dependencies(&[]);
+        deps.querier.update_staking("uluna", &[], &[]);
+
+        let msg = InstantiateMsg {
+            receiver: "treasury".to_string(),
+            ..Default::default()
+        };
+        let _res = instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+
+        let destinations = vec![
+            ("ops", Destination::Wallet(Addr::unchecked("opswallet"))),
+            (
+                "vault",
+                Destination::Contract {
+                    addr: Addr::unchecked("vault"),
+                    msg: Binary::from(br#"{"deposit":{}}"#.to_vec()),
+                },
+            ),
+            ("stake", Destination::Validator("terravaloper1".to_string())),
+        ];
+        for (name, destination) in destinations {
+            let msg = ExecuteMsg::SetDestination {
+                name: name.to_string(),
+                destination,
+            };
+            let _res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+        }
+        let send = |route: &str| ExecuteMsg::TokenSend {
+            recipient: None,
+            route: Some(route.to_string()),
+            memo: None,
+            on_behalf_of: None,
+            category: None,
+        };
+
+        let info = mock_info("alice", &coins(10, "uusd"));
+        let res = execute(deps.as_mut(), mock_env(), info, send("ops")).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: "opswallet".to_string(),
+                amount: coins(10, "uusd"),
+            })]
+        );
+
+        let info = mock_info("alice", &coins(10, "uusd"));
+        let res = execute(deps.as_mut(), mock_env(), info, send("vault")).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(WasmMsg::Execute {
+                contract_addr: "vault".to_string(),
+                msg: Binary::from(br#"{"deposit":{}}"#.to_vec()),
+                funds: coins(10, "uusd"),
+            })]
+        );
+
+        // only the bonded denom is delegated, the rest goes to the receiver
+        let info = mock_info("alice", &[coin(100, "uluna"), coin(10, "uusd")]);
+        let res = execute(deps.as_mut(), mock_env(), info, send("stake")).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![
+                SubMsg::new(StakingMsg::Delegate {
+                    validator: "terravaloper1".to_string(),
+                    amount: coin(100, "uluna"),
+                }),
+                SubMsg::new(BankMsg::Send {
+                    to_address: "treasury".to_string(),
+                    amount: coins(10, "uusd"),
+                }),
+            ]
+        );
+    }
+
+    #[test]
+    fn receipts_by_sender() {
+        let mut deps = mock_dependencies(&[]);
+
+        let msg = InstantiateMsg {
+            receiver: "treasury".to_string(),
+            approval_threshold: coins(1000, "uusd"),
+            ..Default::default()
+        };
+        let _res = instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+
+        // alice forwards one deposit, then makes two that wait for approval
+        for (sender, amount) in [("alice", 10), ("alice", 1000), ("bob", 20), ("alice", 2000)] {
+            let msg = ExecuteMsg::TokenSend {
+                recipient: None,
+                route: None,
+                memo: None,
+                on_behalf_of: None,
+                category: None,
+            };
+            let info = mock_info(sender, &coins(amount, "uusd"));
+            let _res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        }
+        let _res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            ExecuteMsg::Reject { id: 2 },
+        )
+        .unwrap();
+
+        let msg = QueryMsg::ReceiptsBySender {
+            sender: "alice".to_string(),
+            start_after: None,
+            limit: Some(2),
+        };
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let value: ReceiptsResponse = from_json(&res).unwrap();
+        let height = mock_env().block.height;
+        assert_eq!(
+            value.receipts,
+            vec![
+                ReceiptInfo {
+                    id: 1,
+                    amount: coins(10, "uusd"),
+                    status: ReceiptStatus::Forwarded,
+                    height,
+                },
+                ReceiptInfo {
+                    id: 2,
+                    amount: coins(1000, "uusd"),
+                    status: ReceiptStatus::Refunded,
+                    height,
+                },
+            ]
+        );
+
+        let msg = QueryMsg::ReceiptsBySender {
+            sender: "alice".to_string(),
+            start_after: Some(2),
+            limit: None,
+        };
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let value: ReceiptsResponse = from_json(&res).unwrap();
+        assert_eq!(
+            value.receipts,
+            vec![ReceiptInfo {
+                id: 4,
+                amount: coins(2000, "uusd"),
+                status: ReceiptStatus::Pending,
+                height,
+            }]
+        );
+
+        // approving forwards it
+        let _res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            ExecuteMsg::Approve { id: 4 },
+        )
+        .unwrap();
+        let msg = QueryMsg::ReceiptsBySender {
+            sender: "alice".to_string(),
+            start_after: Some(2),
+            limit: None,
+        };
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let value: ReceiptsResponse = from_json(&res).unwrap();
+        assert_eq!(value.receipts[0].status, ReceiptStatus::Forwarded);
+    }
+
+    #[test]
+    fn receiver_rotation() {
+        let mut deps = mock_dependencies(&[]);
+
+        let msg = InstantiateMsg {
+            receiver: "treasury".to_string(),
+            ..Default::default()
+        };
+        let _res = instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+
+        let height = mock_env().block.height;
+        let entry = |receiver: &str, active_from: u64| RotationEntry {
+            receiver: receiver.to_string(),
+            active_from,
+        };
+        let msg = ExecuteMsg::SetRotation {
+            rotation: vec![entry("qtwo", height + 200), entry("qone", height + 100)],
+        };
+        let res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg);
+        match res {
+            Err(ContractError::InvalidRotation {}) => {}
+            _ => panic!("Must return InvalidRotation error"),
+        }
+        let msg = ExecuteMsg::SetRotation {
+            rotation: vec![entry("qone", height + 100), entry("qtwo", height + 200)],
+        };
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("mallory", &[]),
+            msg.clone(),
+        );
+        match res {
+            Err(ContractError::Unauthorized {}) => {}
+            _ => panic!("Must return unauthorized error"),
+        }
+        let _res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::Rotation {}).unwrap();
+        let value: RotationResponse = from_json(&res).unwrap();
+        assert_eq!(
+            value,
+            RotationResponse {
+                active: "treasury".to_string(),
+                upcoming: vec![entry("qone", height + 100), entry("qtwo", height + 200)],
+            }
+        );
+
+        // the next receiver takes over at its height without anyone acting
+        let mut env = mock_env();
+        env.block.height += 100;
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::Rotation {}).unwrap();
+        let value: RotationResponse = from_json(&res).unwrap();
+        assert_eq!(value.active, "qone");
+        assert_eq!(value.upcoming, vec![entry("qtwo", height + 200)]);
+
+        let msg = ExecuteMsg::TokenSend {
+            recipient: None,
+            route: None,
+            memo: None,
+            on_behalf_of: None,
+            category: None,
+        };
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("alice", &coins(10, "uusd")),
+            msg,
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: "qone".to_string(),
+                amount: coins(10, "uusd"),
+            })]
+        );
+        let msg = QueryMsg::ReceiverHistory {
+            start_after: None,
+            limit: None,
+        };
+        let res = query(deps.as_ref(), env, msg).unwrap();
+        let value: ReceiverHistoryResponse = from_json(&res).unwrap();
+        assert_eq!(value.changes.len(), 1);
+        assert_eq!(value.changes[0].new_receiver, "qone");
+        assert_eq!(value.changes[0].actor, "creator");
+    }
+
+    #[test]
+    fn refund_failed_payouts() {
+        let mut deps = mock_dependencies(&[]);
+
+        let msg = InstantiateMsg {
+            receiver: "treasury".to_string(),
+            refund_failed: true,
+            ..Default::default()
+        };
+        let _res = instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+
+        let msg = ExecuteMsg::TokenSend {
+            recipient: None,
+            route: None,
+            memo: None,
+            on_behalf_of: None,
+            category: None,
+        };
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("alice", &coins(100, "uusd")),
+            msg,
+        )
+        .unwrap();
+        let send = BankMsg::Send {
+            to_address: "treasury".to_string(),
+            amount: coins(100, "uusd"),
+        };
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::reply_always(send, PAYOUT_REPLY_ID + 1)]
+        );
+
+        // the failed payout is owed back to alice instead of reverting the deposit
+        let failed = Reply {
+            id: PAYOUT_REPLY_ID + 1,
+            result: SubMsgResult::Err("receiver is blocked".to_string()),
+        };
+        let res = reply(deps.as_mut(), mock_env(), failed).unwrap();
+        assert!(res.messages.is_empty());
+        assert_eq!(res.attributes[0], attr("action", "payout_refundable"));
+        let msg = QueryMsg::Refunds {
+            start_after: None,
+            limit: None,
+        };
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let value: RefundsResponse = from_json(&res).unwrap();
+        assert_eq!(
+            value.refunds,
+            vec![RefundInfo {
+                sender: "alice".to_string(),
+                amount: coins(100, "uusd"),
+            }]
+        );
+
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("bob", &[]),
+            ExecuteMsg::ClaimRefund {},
+        );
+        match res {
+            Err(ContractError::NothingToClaim {}) => {}
+            _ => panic!("Must return NothingToClaim error"),
+        }
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("alice", &[]),
+            ExecuteMsg::ClaimRefund {},
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: "alice".to_string(),
+                amount: coins(100, "uusd"),
+            })]
+        );
+        let msg = QueryMsg::Refunds {
+            start_after: None,
+            limit: None,
+        };
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let value: RefundsResponse = from_json(&res).unwrap();
+        assert!(value.refunds.is_empty());
+    }
+
+    #[test]
+    fn cw20_subscriptions() {
+        let mut deps = mock_dependencies(&[]);
+        let mock_token = |allowance: u128| {
+            move |query: &WasmQuery| match query {
+                WasmQuery::Smart { contract_addr, msg } if contract_addr == "token" => {
+                    let res = match from_json(msg).unwrap() {
+                        Cw20QueryMsg::Allowance { .. } => {
+                            to_json_binary(&cw20::AllowanceResponse {
+                                allowance: Uint128::new(allowance),
+                                expires: cw20::Expiration::Never {},
+                            })
+                        }
+                        Cw20QueryMsg::Balance { .. } => to_json_binary(&cw20::BalanceResponse {
+                            balance: Uint128::new(100),
+                        }),
+                        _ => panic!("unexpected query"),
+                    };
+                    SystemResult::Ok(ContractResult::Ok(res.unwrap()))
+                }
+                _ => panic!("unexpected query"),
+            }
+        };
+        deps.querier.update_wasm(mock_token(30));
+
+        let msg = InstantiateMsg {
+            receiver: "treasury".to_string(),
+            ..Default::default()
+        };
+        let _res = instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+        let msg = ExecuteMsg::SubscribeCw20 {
+            token: "token".to_string(),
+            amount: Uint128::new(10),
+            interval: 100,
+        };
+        let _res = execute(deps.as_mut(), mock_env(), mock_info("alice", &[]), msg).unwrap();
+
+        // the installment is pulled with the allowance, the reply forwards it
+        let process = ExecuteMsg::ProcessSubscriptions { limit: None };
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("anyone", &[]),
+            process.clone(),
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::reply_always(
+                WasmMsg::Execute {
+                    contract_addr: "token".to_string(),
+                    msg: to_json_binary(&Cw20ExecuteMsg::TransferFrom {
+                        owner: "alice".to_string(),
+                        recipient: MOCK_CONTRACT_ADDR.to_string(),
+                        amount: Uint128::new(10),
+                    })
+                    .unwrap(),
+                    funds: vec![],
+                },
+                CHARGE_REPLY_ID + 1,
+            )]
+        );
+        let charged = Reply {
+            id: CHARGE_REPLY_ID + 1,
+            result: SubMsgResult::Ok(SubMsgResponse {
+                events: vec![],
+                data: None,
+            }),
+        };
+        let res = reply(deps.as_mut(), mock_env(), charged.clone()).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(WasmMsg::Execute {
+                contract_addr: "token".to_string(),
+                msg: to_json_binary(&Cw20ExecuteMsg::Transfer {
+                    recipient: "treasury".to_string(),
+                    amount: Uint128::new(10),
+                })
+                .unwrap(),
+                funds: vec![],
+            })]
+        );
+
+        // once the allowance runs out the subscription is delinquent and skips the installment
+        deps.querier.update_wasm(mock_token(5));
+        let mut env = mock_env();
+        env.block.time = env.block.time.plus_seconds(100);
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("anyone", &[]),
+            process.clone(),
+        )
+        .unwrap();
+        assert!(res.messages.is_empty());
+        assert!(res
+            .attributes
+            .contains(&attr("subscription_delinquent", "1")));
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::Subscription { id: 1 }).unwrap();
+        let value: SubscriptionInfo = from_json(&res).unwrap();
+        assert!(value.delinquent);
+        assert_eq!(value.token, Some("token".to_string()));
+        assert_eq!(value.next_charge, mock_env().block.time.seconds() + 200);
+
+        // a new allowance brings it back
+        deps.querier.update_wasm(mock_token(10));
+        env.block.time = env.block.time.plus_seconds(100);
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("anyone", &[]),
+            process.clone(),
+        )
+        .unwrap();
+        assert_eq!(res.messages.len(), 1);
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::Subscription { id: 1 }).unwrap();
+        let value: SubscriptionInfo = from_json(&res).unwrap();
+        assert!(!value.delinquent);
+
+        // a charge the token rejects marks it delinquent without failing the batch
+        let failed = Reply {
+            id: CHARGE_REPLY_ID + 1,
+            result: SubMsgResult::Err("insufficient allowance".to_string()),
+        };
+        let res = reply(deps.as_mut(), env.clone(), failed).unwrap();
+        assert!(res.messages.is_empty());
+        assert!(res
+            .attributes
+            .contains(&attr("subscription_delinquent", "1")));
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::Subscription { id: 1 }).unwrap();
+        let value: SubscriptionInfo = from_json(&res).unwrap();
+        assert!(value.delinquent);
+
+        // two subscriptions of alice share her allowance, the batch only pulls what it covers
+        deps.querier.update_wasm(mock_token(15));
+        let msg = ExecuteMsg::SubscribeCw20 {
+            token: "token".to_string(),
+            amount: Uint128::new(10),
+            interval: 100,
+        };
+        let _res = execute(deps.as_mut(), env.clone(), mock_info("alice", &[]), msg).unwrap();
+        env.block.time = env.block.time.plus_seconds(100);
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("anyone", &[]),
+            process,
+        )
+        .unwrap();
+        assert_eq!(res.messages.len(), 1);
+        let delinquent = res
+            .attributes
+            .iter()
+            .filter(|attr| attr.key == "subscription_delinquent")
+            .count();
+        assert_eq!(delinquent, 1);
+
+        // cancelling it refunds nothing, the sender kept the tokens
+        let msg = ExecuteMsg::CancelSubscription { id: 1 };
+        let res = execute(deps.as_mut(), env, mock_info("alice", &[]), msg).unwrap();
+        assert!(res.messages.is_empty());
+    }
+
+    #[test]
+    fn cw20_subscription_allowance() {
+        let mut deps = mock_dependencies(&[]);
+        deps.querier.update_wasm(|query| match query {
+            WasmQuery::Smart { contract_addr, msg } if contract_addr == "token" => {
+                let res = match from_json(msg).unwrap() {
+                    Cw20QueryMsg::Allowance { .. } => to_json_binary(&cw20::AllowanceResponse {
+                        allowance: Uint128::new(25),
+                        expires: cw20::Expiration::Never {},
+                    }),
+                    Cw20QueryMsg::Balance { .. } => to_json_binary(&cw20::BalanceResponse {
+                        balance: Uint128::new(100),
+                    }),
+                    _ => panic!("unexpected query"),
+                };
+                SystemResult::Ok(ContractResult::Ok(res.unwrap()))
+            }
+            _ => panic!("unexpected query"),
+        });
+
+        let msg = InstantiateMsg {
+            receiver: "treasury".to_string(),
+            accepted_denoms: vec!["token".to_string()],
+            ..Default::default()
+        };
+        let _res = instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+        let subscribe = |token: &str| ExecuteMsg::SubscribeCw20 {
+            token: token.to_string(),
+            amount: Uint128::new(10),
+            interval: 100,
+        };
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("alice", &[]),
+            subscribe("other"),
+        );
+        match res {
+            Err(ContractError::UnsupportedDenom { denom }) => assert_eq!(denom, "other"),
+            _ => panic!("Must return UnsupportedDenom error"),
+        }
+        for _ in 0..3 {
+            let msg = subscribe("token");
+            let _res = execute(deps.as_mut(), mock_env(), mock_info("alice", &[]), msg).unwrap();
+        }
+
+        // nothing is pulled while the contract is paused
+        let msg = ExecuteMsg::Pause {};
+        let _res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+        let process = ExecuteMsg::ProcessSubscriptions { limit: None };
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("anyone", &[]),
+            process.clone(),
+        )
+        .unwrap();
+        assert!(res.messages.is_empty());
+        let msg = ExecuteMsg::Unpause {};
+        let _res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+
+        // the allowance of 25 covers the first two installments, not the third
+        let res = execute(deps.as_mut(), mock_env(), mock_info("anyone", &[]), process).unwrap();
+        let pull = |id: u64| {
+            SubMsg::reply_always(
+                WasmMsg::Execute {
+                    contract_addr: "token".to_string(),
+                    msg: to_json_binary(&Cw20ExecuteMsg::TransferFrom {
+                        owner: "alice".to_string(),
+                        recipient: MOCK_CONTRACT_ADDR.to_string(),
+                        amount: Uint128::new(10),
+                    })
+                    .unwrap(),
+                    funds: vec![],
+                },
+                CHARGE_REPLY_ID + id,
+            )
+        };
+        assert_eq!(res.messages, vec![pull(1), pull(2)]);
+        assert!(res
+            .attributes
+            .contains(&attr("subscription_delinquent", "3")));
+    }
+
+    #[test]
+    fn anchor_mode() {
+        let mut deps = mock_dependencies(&[]);
+        let mock_aust = |balance: u128| {
+            move |query: &WasmQuery| match query {
+                WasmQuery::Smart { contract_addr, .. } if contract_addr == "aust" => {
+                    let res = cw20::BalanceResponse {
+                        balance: Uint128::new(balance),
+                    };
+                    SystemResult::Ok(ContractResult::Ok(to_json_binary(&res).unwrap()))
+                }
+                _ => panic!("unexpected query"),
+            }
+        };
+        deps.querier.update_wasm(mock_aust(5));
+
+        let msg = InstantiateMsg {
+            receiver: "treasury".to_string(),
+            anchor: Some(AnchorSettings {
+                money_market: "market".to_string(),
+                aterra: "aust".to_string(),
+                denom: "uusd".to_string(),
+            }),
+            ..Default::default()
+        };
+        let _res = instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+
+        // the uusd is deposited into the money market, other coins are forwarded as usual
+        let msg = ExecuteMsg::TokenSend {
+            recipient: None,
+            route: None,
+            memo: None,
+            on_behalf_of: None,
+            category: None,
+        };
+        let info = mock_info("alice", &[coin(100, "uusd"), coin(7, "uluna")]);
+        let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![
+                SubMsg::reply_on_success(
+                    WasmMsg::Execute {
+                        contract_addr: "market".to_string(),
+                        msg: to_json_binary(&MarketExecuteMsg::DepositStable {}).unwrap(),
+                        funds: coins(100, "uusd"),
+                    },
+                    ANCHOR_REPLY_ID,
+                ),
+                SubMsg::new(BankMsg::Send {
+                    to_address: "treasury".to_string(),
+                    amount: coins(7, "uluna"),
+                }),
+            ]
+        );
+
+        // the reply forwards what the aUST balance grew by and adds it to the receipt
+        deps.querier.update_wasm(mock_aust(95));
+        let msg = Reply {
+            id: ANCHOR_REPLY_ID,
+            result: SubMsgResult::Ok(SubMsgResponse {
+                events: vec![],
+                data: None,
+            }),
+        };
+        let res = reply(deps.as_mut(), mock_env(), msg).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(WasmMsg::Execute {
+                contract_addr: "aust".to_string(),
+                msg: to_json_binary(&Cw20ExecuteMsg::Transfer {
+                    recipient: "treasury".to_string(),
+                    amount: Uint128::new(90),
+                })
+                .unwrap(),
+                funds: vec![],
+            })]
+        );
+        let receipt: SendReceipt = from_json(res.data.unwrap()).unwrap();
+        assert_eq!(receipt.id, 1);
+        assert_eq!(receipt.minted, Some(coin(90, "aust")));
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::Receipt { id: 1 }).unwrap();
+        let receipt: SendReceipt = from_json(&res).unwrap();
+        assert_eq!(receipt.minted, Some(coin(90, "aust")));
+        assert_eq!(receipt.recipients, vec!["treasury", "treasury"]);
+    }
+
+    #[test]
+    fn forward_rewards() {
+        let mut deps = mock_dependencies(&[]);
+        deps.querier.update_staking(
+            "uluna",
+            &[],
+            &[
+                FullDelegation {
+                    delegator: Addr::unchecked(MOCK_CONTRACT_ADDR),
+                    validator: "terravaloper1".to_string(),
+                    amount: coin(500, "uluna"),
+                    can_redelegate: coin(500, "uluna"),
+                    accumulated_rewards: vec![coin(7, "uluna"), coin(3, "uusd")],
+                },
+                FullDelegation {
+                    delegator: Addr::unchecked(MOCK_CONTRACT_ADDR),
+                    validator: "terravaloper2".to_string(),
+                    amount: coin(100, "uluna"),
+                    can_redelegate: coin(100, "uluna"),
+                    accumulated_rewards: vec![],
+                },
+            ],
+        );
+
+        let msg = InstantiateMsg {
+            receiver: "treasury".to_string(),
+            receivers: vec![
+                ("treasury".to_string(), Decimal::percent(50)),
+                ("ops".to_string(), Decimal::percent(50)),
+            ],
+            ..Default::default()
+        };
+        let _res = instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("alice", &[]),
+            ExecuteMsg::ForwardRewards {},
+        );
+        match res {
+            Err(ContractError::Unauthorized {}) => {}
+            _ => panic!("Must return unauthorized error"),
+        }
+
+        // only delegations with rewards are withdrawn, then the rewards are split
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            ExecuteMsg::ForwardRewards {},
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages[..2],
+            [
+                SubMsg::new(DistributionMsg::SetWithdrawAddress {
+                    address: MOCK_CONTRACT_ADDR.to_string(),
+                }),
+                SubMsg::new(DistributionMsg::WithdrawDelegatorReward {
+                    validator: "terravaloper1".to_string(),
+                }),
+            ]
+        );
+        let paid: Vec<_> = res.messages[2..]
+            .iter()
+            .map(|msg| match &msg.msg {
+                CosmosMsg::Bank(BankMsg::Send { to_address, amount }) => {
+                    (to_address.as_str(), amount.clone())
+                }
+                _ => panic!("Must be a bank send"),
+            })
+            .collect();
+        assert_eq!(
+            paid,
+            vec![
+                ("treasury", vec![coin(4, "uluna"), coin(2, "uusd")]),
+                ("ops", vec![coin(3, "uluna"), coin(1, "uusd")]),
+            ]
+        );
+
+        // without rewards there is nothing to forward
+        deps.querier.update_staking("uluna", &[], &[]);
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            ExecuteMsg::ForwardRewards {},
+        );
+        match res {
+            Err(ContractError::NoRewards {}) => {}
+            _ => panic!("Must return NoRewards error"),
+        }
+    }
+
+    #[test]
+    fn keeper_operators() {
+        let mut deps = mock_dependencies(&coins(300, "uusd"));
+        let msg = InstantiateMsg {
+            receiver: "treasury".to_string(),
+            ..Default::default()
+        };
+        let _res = instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+        let sweep = ExecuteMsg::Sweep { denom: None };
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("keeper", &[]),
+            sweep.clone(),
+        );
+        match res {
+            Err(ContractError::Unauthorized {}) => {}
+            _ => panic!("Must return unauthorized error"),
+        }
+
+        // an operator runs the maintenance, but cannot change the config
+        let grant = ExecuteMsg::GrantPermissions {
+            address: "keeper".to_string(),
+            permissions: vec![Permission::Operate],
+        };
+        let _res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), grant).unwrap();
+        let res = execute(deps.as_mut(), mock_env(), mock_info("keeper", &[]), sweep).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: "treasury".to_string(),
+                amount: coins(300, "uusd"),
+            })]
+        );
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("keeper", &[]),
+            ExecuteMsg::ForwardRewards {},
+        );
+        match res {
+            Err(ContractError::NoRewards {}) => {}
+            _ => panic!("Must return NoRewards error"),
+        }
+        let _res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("keeper", &[]),
+            ExecuteMsg::ProcessSubscriptions { limit: None },
+        )
+        .unwrap();
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("keeper", &[]),
+            ExecuteMsg::Pause {},
+        );
+        match res {
+            Err(ContractError::Unauthorized {}) => {}
+            _ => panic!("Must return unauthorized error"),
+        }
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("keeper", &[]),
+            ExecuteMsg::SetHoldFailed { hold_failed: true },
+        );
+        match res {
+            Err(ContractError::Unauthorized {}) => {}
+            _ => panic!("Must return unauthorized error"),
+        }
+    }
+
+    #[test]
+    fn keeper_jobs() {
+        let mut deps = mock_dependencies(&[]);
+        let msg = InstantiateMsg {
+            receiver: "treasury".to_string(),
+            hold: true,
+            flush_threshold: coins(1000, "uusd"),
+            ..Default::default()
+        };
+        let _res = instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+        let send = ExecuteMsg::TokenSend {
+            recipient: None,
+            route: None,
+            memo: None,
+            on_behalf_of: None,
+            category: None,
+        };
+        let _res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("alice", &coins(600, "uusd")),
+            send,
+        )
+        .unwrap();
+
+        // nothing reached its threshold and nothing is due yet
+        let flush = ExecuteMsg::FlushIfAboveThreshold {};
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("keeper", &[]),
+            flush.clone(),
+        );
+        match res {
+            Err(ContractError::NothingToDo {}) => {}
+            _ => panic!("Must return NothingToDo error"),
+        }
+        let process = ExecuteMsg::ProcessDue { limit: None };
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("keeper", &[]),
+            process.clone(),
+        );
+        match res {
+            Err(ContractError::NothingToDo {}) => {}
+            _ => panic!("Must return NothingToDo error"),
+        }
+
+        // after the threshold is lowered the held funds are flushed
+        let msg = ExecuteMsg::SetHold {
+            hold: true,
+            flush_threshold: coins(500, "uusd"),
+        };
+        let _res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("keeper", &[]),
+            flush.clone(),
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: "treasury".to_string(),
+                amount: coins(600, "uusd"),
+            })]
+        );
+        let work: WorkDone = from_json(res.data.unwrap()).unwrap();
+        assert_eq!(
+            work,
+            WorkDone {
+                flushed: coins(600, "uusd"),
+                ..Default::default()
+            }
+        );
+        let res = execute(deps.as_mut(), mock_env(), mock_info("keeper", &[]), flush);
+        match res {
+            Err(ContractError::NothingToDo {}) => {}
+            _ => panic!("Must return NothingToDo error"),
+        }
+
+        // a due subscription is charged
+        let msg = ExecuteMsg::Subscribe {
+            amount: coin(10, "uusd"),
+            interval: 100,
+        };
+        let _res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("bob", &coins(30, "uusd")),
+            msg,
+        )
+        .unwrap();
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("keeper", &[]),
+            process.clone(),
+        )
+        .unwrap();
+        let work: WorkDone = from_json(res.data.unwrap()).unwrap();
+        assert_eq!(
+            work,
+            WorkDone {
+                subscriptions_charged: 1,
+                ..Default::default()
+            }
+        );
+        let res = execute(deps.as_mut(), mock_env(), mock_info("keeper", &[]), process);
+        match res {
+            Err(ContractError::NothingToDo {}) => {}
+            _ => panic!("Must return NothingToDo error"),
+        }
+    }
+
+    #[test]
+    fn deposit_categories() {
+        let mut deps = mock_dependencies(&[]);
+        let msg = InstantiateMsg {
+            receiver: "treasury".to_string(),
+            ..Default::default()
+        };
+        let _res = instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+
+        let msg = ExecuteMsg::SetCategory {
+            name: "grant".to_string(),
+            receiver: Some("grants".to_string()),
+        };
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("alice", &[]),
+            msg.clone(),
+        );
+        match res {
+            Err(ContractError::Unauthorized {}) => {}
+            _ => panic!("Must return unauthorized error"),
+        }
+        let _res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+        let msg = ExecuteMsg::SetCategory {
+            name: "tip".to_string(),
+            receiver: None,
+        };
+        let _res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+        let msg = ExecuteMsg::SetCategory {
+            name: "loop".to_string(),
+            receiver: Some(MOCK_CONTRACT_ADDR.to_string()),
+        };
+        let res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg);
+        match res {
+            Err(ContractError::SelfReceiver {}) => {}
+            _ => panic!("Must return SelfReceiver error"),
+        }
+
+        let send = |category: &str| ExecuteMsg::TokenSend {
+            recipient: None,
+            route: None,
+            memo: None,
+            on_behalf_of: None,
+            category: Some(category.to_string()),
+        };
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("alice", &coins(100, "uusd")),
+            send("invoice"),
+        );
+        match res {
+            Err(ContractError::UnknownCategory { name }) => assert_eq!(name, "invoice"),
+            _ => panic!("Must return UnknownCategory error"),
+        }
+
+        // a category with a receiver overrides where the deposit goes
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("alice", &coins(100, "uusd")),
+            send("grant"),
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: "grants".to_string(),
+                amount: coins(100, "uusd"),
+            })]
+        );
+        assert!(res.attributes.contains(&attr("category", "grant")));
+        let receipt: SendReceipt = from_json(res.data.unwrap()).unwrap();
+        assert_eq!(receipt.category, Some("grant".to_string()));
+        for amount in [5, 7] {
+            let res = execute(
+                deps.as_mut(),
+                mock_env(),
+                mock_info("bob", &coins(amount, "uusd")),
+                send("tip"),
+            )
+            .unwrap();
+            assert_eq!(
+                res.messages,
+                vec![SubMsg::new(BankMsg::Send {
+                    to_address: "treasury".to_string(),
+                    amount: coins(amount, "uusd"),
+                })]
+            );
+        }
+
+        let msg = QueryMsg::ListCategories {
+            start_after: None,
+            limit: None,
+        };
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let value: CategoriesResponse = from_json(res).unwrap();
+        let height = mock_env().block.height;
+        assert_eq!(
+            value.categories,
+            vec![
+                CategoryInfo {
+                    name: "grant".to_string(),
+                    receiver: Some("grants".to_string()),
+                    total: coins(100, "uusd"),
+                    deposits: 1,
+                    last_deposit_height: Some(height),
+                },
+                CategoryInfo {
+                    name: "tip".to_string(),
+                    receiver: None,
+                    total: coins(12, "uusd"),
+                    deposits: 2,
+                    last_deposit_height: Some(height),
+                },
+            ]
+        );
+
+        let msg = ExecuteMsg::RemoveCategory {
+            name: "tip".to_string(),
+        };
+        let _res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            msg.clone(),
+        )
+        .unwrap();
+        let res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg);
+        match res {
+            Err(ContractError::UnknownCategory { name }) => assert_eq!(name, "tip"),
+            _ => panic!("Must return UnknownCategory error"),
+        }
+    }
+
+    #[test]
+    fn invoice_workflow() {
+        let mut deps = mock_dependencies(&[]);
+        let msg = InstantiateMsg {
+            receiver: "treasury".to_string(),
+            ..Default::default()
+        };
+        let _res = instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+
+        let now = mock_env().block.time.seconds();
+        let create = |amount: u128, expires: u64| ExecuteMsg::CreateInvoice {
+            payer: "alice".to_string(),
+            amount: coin(amount, "uusd"),
+            expires: Expiration::AtTime(expires),
+            overpayment: Overpayment::Reject,
+        };
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("alice", &[]),
+            create(100, now + 100),
+        );
+        match res {
+            Err(ContractError::Unauthorized {}) => {}
+            _ => panic!("Must return unauthorized error"),
+        }
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            create(100, now),
+        );
+        match res {
+            Err(ContractError::InvalidInvoice {}) => {}
+            _ => panic!("Must return InvalidInvoice error"),
+        }
+        for (amount, expires) in [(100, now + 100), (50, now + 10), (70, now + 100)] {
+            let _res = execute(
+                deps.as_mut(),
+                mock_env(),
+                mock_info("creator", &[]),
+                create(amount, expires),
+            )
+            .unwrap();
+        }
+
+        // only the payer settles an invoice, in its denom
+        let pay = ExecuteMsg::PayInvoice { id: 1 };
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("bob", &coins(100, "uusd")),
+            pay.clone(),
+        );
+        match res {
+            Err(ContractError::Unauthorized {}) => {}
+            _ => panic!("Must return unauthorized error"),
+        }
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("alice", &coins(100, "ukrw")),
+            pay.clone(),
+        );
+        match res {
+            Err(ContractError::InvoiceDenomMismatch { denom }) => assert_eq!(denom, "uusd"),
+            _ => panic!("Must return InvoiceDenomMismatch error"),
+        }
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("alice", &coins(100, "uusd")),
+            pay.clone(),
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: "treasury".to_string(),
+                amount: coins(100, "uusd"),
+            })]
+        );
+        let receipt: SendReceipt = from_json(res.data.unwrap()).unwrap();
+        assert_eq!(receipt.id, 1);
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("alice", &coins(100, "uusd")),
+            pay,
+        );
+        match res {
+            Err(ContractError::InvoicePaid { id }) => assert_eq!(id, 1),
+            _ => panic!("Must return InvoicePaid error"),
+        }
+
+        let mut env = mock_env();
+        env.block.time = env.block.time.plus_seconds(10);
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("alice", &coins(50, "uusd")),
+            ExecuteMsg::PayInvoice { id: 2 },
+        );
+        match res {
+            Err(ContractError::InvoiceExpired { id }) => assert_eq!(id, 2),
+            _ => panic!("Must return InvoiceExpired error"),
+        }
+
+        let by_status = |status: Option<InvoiceStatus>| QueryMsg::InvoicesByPayer {
+            payer: "alice".to_string(),
+            status,
+            start_after: None,
+            limit: None,
+        };
+        let res = query(deps.as_ref(), env.clone(), by_status(None)).unwrap();
+        let value: InvoicesResponse = from_json(res).unwrap();
+        let statuses: Vec<_> = value
+            .invoices
+            .iter()
+            .map(|invoice| (invoice.id, invoice.status, invoice.receipt_id))
+            .collect();
+        assert_eq!(
+            statuses,
+            vec![
+                (1, InvoiceStatus::Paid, Some(1)),
+                (2, InvoiceStatus::Expired, None),
+                (3, InvoiceStatus::Open, None),
+            ]
+        );
+        let res = query(deps.as_ref(), env, by_status(Some(InvoiceStatus::Open))).unwrap();
+        let value: InvoicesResponse = from_json(res).unwrap();
+        assert_eq!(
+            value.invoices,
+            vec![InvoiceInfo {
+                id: 3,
+                payer: "alice".to_string(),
+                amount: coin(70, "uusd"),
+                expires: Expiration::AtTime(now + 100),
+                status: InvoiceStatus::Open,
+                receipt_id: None,
+                received: Uint128::zero(),
+                remaining: Uint128::new(70),
+                overpayment: Overpayment::Reject,
+            }]
+        );
+    }
+
+    #[test]
+    fn partial_invoice_payments() {
+        let mut deps = mock_dependencies(&[]);
+        let msg = InstantiateMsg {
+            receiver: "treasury".to_string(),
+            ..Default::default()
+        };
+        let _res = instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+        let expires = Expiration::AtTime(mock_env().block.time.seconds() + 100);
+        for overpayment in [
+            Overpayment::Reject,
+            Overpayment::ForwardExcess,
+            Overpayment::RefundExcess,
+        ] {
+            let msg = ExecuteMsg::CreateInvoice {
+                payer: "alice".to_string(),
+                amount: coin(100, "uusd"),
+                expires,
+                overpayment,
+            };
+            let _res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+        }
+        let mut pay = |id: u64, amount: u128| {
+            execute(
+                deps.as_mut(),
+                mock_env(),
+                mock_info("alice", &coins(amount, "uusd")),
+                ExecuteMsg::PayInvoice { id },
+            )
+        };
+
+        // payments add up until the invoice is paid in full
+        for id in 1..=3 {
+            let res = pay(id, 60).unwrap();
+            assert_eq!(
+                res.messages,
+                vec![SubMsg::new(BankMsg::Send {
+                    to_address: "treasury".to_string(),
+                    amount: coins(60, "uusd"),
+                })]
+            );
+            assert!(res.attributes.contains(&attr("remaining", "40uusd")));
+        }
+
+        // what is paid above the remaining balance is rejected, forwarded or refunded
+        match pay(1, 50) {
+            Err(ContractError::InvoiceOverpaid { remaining }) => assert_eq!(remaining, "40uusd"),
+            _ => panic!("Must return InvoiceOverpaid error"),
+        }
+        let res = pay(1, 40).unwrap();
+        assert!(res.attributes.contains(&attr("remaining", "0uusd")));
+        let res = pay(2, 50).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: "treasury".to_string(),
+                amount: coins(50, "uusd"),
+            })]
+        );
+        let res = pay(3, 50).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![
+                SubMsg::new(BankMsg::Send {
+                    to_address: "treasury".to_string(),
+                    amount: coins(40, "uusd"),
+                }),
+                SubMsg::new(BankMsg::Send {
+                    to_address: "alice".to_string(),
+                    amount: coins(10, "uusd"),
+                }),
+            ]
+        );
+        match pay(3, 10) {
+            Err(ContractError::InvoicePaid { id }) => assert_eq!(id, 3),
+            _ => panic!("Must return InvoicePaid error"),
+        }
+
+        let msg = QueryMsg::InvoicesByPayer {
+            payer: "alice".to_string(),
+            status: None,
+            start_after: None,
+            limit: None,
+        };
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let value: InvoicesResponse = from_json(res).unwrap();
+        let balances: Vec<_> = value
+            .invoices
+            .iter()
+            .map(|invoice| {
+                (
+                    invoice.status,
+                    invoice.received.u128(),
+                    invoice.remaining.u128(),
+                    invoice.receipt_id,
+                )
+            })
+            .collect();
+        assert_eq!(
+            balances,
+            vec![
+                (InvoiceStatus::Paid, 100, 0, Some(4)),
+                (InvoiceStatus::Paid, 100, 0, Some(5)),
+                (InvoiceStatus::Paid, 100, 0, Some(6)),
+            ]
+        );
+    }
+
+    #[test]
+    fn escrow_arbiter() {
+        let mut deps = mock_dependencies(&[]);
+        let msg = InstantiateMsg {
+            receiver: "treasury".to_string(),
+            fee_rate: Decimal::percent(10),
+            fee_collector: Some("collector".to_string()),
+            ..Default::default()
+        };
+        let _res = instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+        let send = ExecuteMsg::ArbitratedSend {
+            arbiter: "judge".to_string(),
+            timeout: Duration::Time(100),
+            memo: None,
+        };
+        for sender in ["alice", "bob"] {
+            let res = execute(
+                deps.as_mut(),
+                mock_env(),
+                mock_info(sender, &coins(100, "uusd")),
+                send.clone(),
+            )
+            .unwrap();
+            assert!(res.messages.is_empty());
+        }
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::Escrow { id: 1 }).unwrap();
+        let value: EscrowInfo = from_json(res).unwrap();
+        assert_eq!(value.arbiter, Some("judge".to_string()));
+        assert!(!value.disputed);
+
+        // the arbiter only steps in once a dispute was raised
+        let resolve = |id: u64, release: bool| ExecuteMsg::Resolve { id, release };
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("judge", &[]),
+            resolve(1, true),
+        );
+        match res {
+            Err(ContractError::NotDisputed {}) => {}
+            _ => panic!("Must return NotDisputed error"),
+        }
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("carol", &[]),
+            ExecuteMsg::Dispute { id: 1 },
+        );
+        match res {
+            Err(ContractError::Unauthorized {}) => {}
+            _ => panic!("Must return unauthorized error"),
+        }
+        for (sender, id) in [("alice", 1), ("treasury", 2)] {
+            let _res = execute(
+                deps.as_mut(),
+                mock_env(),
+                mock_info(sender, &[]),
+                ExecuteMsg::Dispute { id },
+            )
+            .unwrap();
+        }
+
+        // a disputed escrow cannot be settled by its sides, even after it expired
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("treasury", &[]),
+            ExecuteMsg::Release {
+                id: 1,
+                preimage: None,
+            },
+        );
+        match res {
+            Err(ContractError::EscrowDisputed {}) => {}
+            _ => panic!("Must return EscrowDisputed error"),
+        }
+        let mut env = mock_env();
+        env.block.time = env.block.time.plus_seconds(100);
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("bob", &[]),
+            ExecuteMsg::Refund { id: 2 },
+        );
+        match res {
+            Err(ContractError::EscrowDisputed {}) => {}
+            _ => panic!("Must return EscrowDisputed error"),
+        }
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("alice", &[]),
+            resolve(1, true),
+        );
+        match res {
+            Err(ContractError::Unauthorized {}) => {}
+            _ => panic!("Must return unauthorized error"),
+        }
+
+        // a release is charged the fee, a refund goes back in full
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("judge", &[]),
+            resolve(1, true),
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages,
+            vec![
+                SubMsg::new(BankMsg::Send {
+                    to_address: "collector".to_string(),
+                    amount: coins(10, "uusd"),
+                }),
+                SubMsg::new(BankMsg::Send {
+                    to_address: "treasury".to_string(),
+                    amount: coins(90, "uusd"),
+                }),
+            ]
+        );
+        let receipt: SendReceipt = from_json(res.data.unwrap()).unwrap();
+        assert_eq!(receipt.id, 1);
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("judge", &[]),
+            resolve(2, false),
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: "bob".to_string(),
+                amount: coins(100, "uusd"),
+            })]
+        );
+        let res = query(deps.as_ref(), env, QueryMsg::Escrow { id: 2 });
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn stale_claims() {
+        let mut deps = mock_dependencies(&[]);
+        let msg = InstantiateMsg {
+            receiver: "treasury".to_string(),
+            pull: true,
+            claim_expiry: Some(1000),
+            ..Default::default()
+        };
+        let _res = instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+        let send = ExecuteMsg::TokenSend {
+            recipient: None,
+            route: None,
+            memo: None,
+            on_behalf_of: None,
+            category: None,
+        };
+        let _res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("alice", &coins(100, "uusd")),
+            send,
+        )
+        .unwrap();
+        let now = mock_env().block.time.seconds();
+        let msg = QueryMsg::GetClaimable {
+            address: "treasury".to_string(),
+        };
+        let res = query(deps.as_ref(), mock_env(), msg.clone()).unwrap();
+        let value: ClaimableResponse = from_json(res).unwrap();
+        assert_eq!(value.expires_at, Some(now + 1000));
+
+        // a withdrawal restarts the expiry
+        let mut env = mock_env();
+        env.block.time = env.block.time.plus_seconds(500);
+        let _res = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("treasury", &[]),
+            ExecuteMsg::Withdraw {
+                denom: Some("uusd".to_string()),
+                amount: Some(Uint128::new(40)),
+            },
+        )
+        .unwrap();
+        let redirect = ExecuteMsg::RedirectStale {
+            receiver: "treasury".to_string(),
+        };
+        env.block.time = env.block.time.plus_seconds(999);
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("anyone", &[]),
+            redirect.clone(),
+        );
+        match res {
+            Err(ContractError::ClaimNotExpired {}) => {}
+            _ => panic!("Must return ClaimNotExpired error"),
+        }
+
+        // an expired balance needs somewhere to go
+        env.block.time = env.block.time.plus_seconds(1);
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("anyone", &[]),
+            redirect.clone(),
+        );
+        match res {
+            Err(ContractError::NoFallbackReceiver {}) => {}
+            _ => panic!("Must return NoFallbackReceiver error"),
+        }
+        let fallback = ExecuteMsg::SetFallbackReceiver {
+            fallback_receiver: Some("backup".to_string()),
+        };
+        let _res = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("creator", &[]),
+            fallback,
+        )
+        .unwrap();
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("anyone", &[]),
+            redirect.clone(),
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: "backup".to_string(),
+                amount: coins(60, "uusd"),
+            })]
+        );
+        let res = query(deps.as_ref(), env.clone(), msg).unwrap();
+        let value: ClaimableResponse = from_json(res).unwrap();
+        assert_eq!(
+            value,
+            ClaimableResponse {
+                claimable: vec![],
+                expires_at: None,
+                unbonding: vec![],
+            }
+        );
+        let res = execute(deps.as_mut(), env, mock_info("anyone", &[]), redirect);
+        match res {
+            Err(ContractError::ClaimNotExpired {}) => {}
+            _ => panic!("Must return ClaimNotExpired error"),
+        }
+    }
 
-pub fn init<S: Storage, A: Api, Q: Querier>(
-    deps: &mut Extern<S, A, Q>,
-    env: Env,
-    msg: InitMsg,
-) -> StdResult<InitResponse> {
-    let receiver = HumanAddr::from(msg.receiver);
-    let state = State {
-        receiver: deps.api.canonical_address(&receiver)?,
-        owner: deps.api.canonical_address(&env.message.sender)?,
-    };
+    #[test]
+    fn denom_rules() {
+        let mut deps = mock_dependencies(&[]);
+        let msg = InstantiateMsg {
+            receiver: "treasury".to_string(),
+            fee_rate: Decimal::percent(10),
+            fee_collector: Some("collector".to_string()),
+            denom_rules: vec![DenomRuleSettings {
+                denom: "uluna".to_string(),
+                fee_rate: Some(Decimal::percent(20)),
+                receiver: Some("luna_desk".to_string()),
+            }],
+            ..Default::default()
+        };
+        let _res = instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
 
-    config(&mut deps.storage).save(&state)?;
+        // every denom is charged its own rate and routed to its own receiver in one send
+        let info = mock_info("alice", &[coin(100, "uusd"), coin(50, "uluna")]);
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            info,
+            ExecuteMsg::TokenSend {
+                recipient: None,
+                route: None,
+                memo: None,
+                on_behalf_of: None,
+                category: None,
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages,
+            vec![
+                SubMsg::new(BankMsg::Send {
+                    to_address: "collector".to_string(),
+                    amount: vec![coin(10, "uusd"), coin(10, "uluna")],
+                }),
+                SubMsg::new(BankMsg::Send {
+                    to_address: "treasury".to_string(),
+                    amount: coins(90, "uusd"),
+                }),
+                SubMsg::new(BankMsg::Send {
+                    to_address: "luna_desk".to_string(),
+                    amount: coins(40, "uluna"),
+                }),
+            ]
+        );
+        let receipt: SendReceipt = from_json(res.data.unwrap()).unwrap();
+        assert_eq!(
+            receipt.items,
+            vec![
+                DenomRouting {
+                    denom: "uusd".to_string(),
+                    amount: Uint128::new(100),
+                    fee: Uint128::new(10),
+                    payouts: vec![(Addr::unchecked("treasury"), Uint128::new(90))],
+                },
+                DenomRouting {
+                    denom: "uluna".to_string(),
+                    amount: Uint128::new(50),
+                    fee: Uint128::new(10),
+                    payouts: vec![(Addr::unchecked("luna_desk"), Uint128::new(40))],
+                },
+            ]
+        );
 
-    Ok(InitResponse::default())
-}
+        let rule = DenomRuleSettings {
+            denom: "uusd".to_string(),
+            fee_rate: Some(Decimal::zero()),
+            receiver: None,
+        };
+        let msg = ExecuteMsg::SetDenomRules {
+            denom_rules: vec![rule.clone(), rule],
+        };
+        let res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg);
+        match res {
+            Err(ContractError::DuplicateDenomRule { denom }) => assert_eq!(denom, "uusd"),
+            _ => panic!("Must return DuplicateDenomRule error"),
+        }
 
-pub fn handle<S: Storage, A: Api, Q: Querier>(
-    deps: &mut Extern<S, A, Q>,
-    env: Env,
-    msg: HandleMsg,
-) -> StdResult<HandleResponse> {
-    match msg {
-        HandleMsg::TokenSend {} => try_tokensend(deps, env),
-        HandleMsg::ResetReceiver { receiver } => try_reset(
-            deps,
-            env,
-            deps.api.canonical_address(&HumanAddr::from(receiver))?,
-        ),
+        // a denom rule cannot pay the contract itself
+        let msg = ExecuteMsg::SetDenomRules {
+            denom_rules: vec![DenomRuleSettings {
+                denom: "uusd".to_string(),
+                fee_rate: None,
+                receiver: Some(MOCK_CONTRACT_ADDR.to_string()),
+            }],
+        };
+        let res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg);
+        match res {
+            Err(ContractError::SelfReceiver {}) => {}
+            _ => panic!("Must return SelfReceiver error"),
+        }
+
+        // the collector cannot be dropped while the uluna rule charges a fee
+        let msg = ExecuteMsg::SetFee {
+            fee_rate: Decimal::zero(),
+            fee_collector: None,
+            fee_cap: vec![],
+        };
+        let res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg);
+        match res {
+            Err(ContractError::NoFeeCollector {}) => {}
+            _ => panic!("Must return NoFeeCollector error"),
+        }
     }
-}
 
-pub fn try_tokensend<S: Storage, A: Api, Q: Querier>(
-    deps: &mut Extern<S, A, Q>,
-    env: Env,
-) -> StdResult<HandleResponse> {
-    let funds = env.message.sent_funds;
-    if funds
-        .clone()
-        .into_iter()
-        .find(|x| x.denom == "uusd" && x.amount > Uint128(0))
-        .is_none()
-    {
-        return Err(StdError::generic_err("You must pass some UST"));
+    #[test]
+    fn receiver_attestation() {
+        let mut deps = mock_dependencies(&[]);
+        mock_registry(&mut deps.querier, "registry", "kyc", &["verified"]);
+        let msg = InstantiateMsg {
+            receiver: "treasury".to_string(),
+            attestation: Some(AttestationSettings {
+                registry: "registry".to_string(),
+                credential: "kyc".to_string(),
+            }),
+            ..Default::default()
+        };
+        let _res = instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+
+        let msg = ExecuteMsg::ResetReceiver {
+            receiver: "anonymous".to_string(),
+        };
+        let res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg);
+        match res {
+            Err(ContractError::MissingCredential {
+                address,
+                credential,
+            }) => {
+                assert_eq!(address, "anonymous");
+                assert_eq!(credential, "kyc");
+            }
+            _ => panic!("Must return MissingCredential error"),
+        }
+        let msg = ExecuteMsg::ResetReceiver {
+            receiver: "verified".to_string(),
+        };
+        let _res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetReceiver {}).unwrap();
+        let value: ReceiverResponse = from_json(res).unwrap();
+        assert_eq!(value.receiver, "verified");
+
+        // receivers scheduled to take over or to share the deposits need the credential too
+        let msg = ExecuteMsg::SetRotation {
+            rotation: vec![RotationEntry {
+                receiver: "anonymous".to_string(),
+                active_from: mock_env().block.height + 10,
+            }],
+        };
+        let res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg);
+        match res {
+            Err(ContractError::MissingCredential { address, .. }) => {
+                assert_eq!(address, "anonymous")
+            }
+            _ => panic!("Must return MissingCredential error"),
+        }
+        let msg = ExecuteMsg::SetReceivers {
+            receivers: vec![
+                ("verified".to_string(), Decimal::percent(50)),
+                ("anonymous".to_string(), Decimal::percent(50)),
+            ],
+        };
+        let res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg);
+        match res {
+            Err(ContractError::MissingCredential { address, .. }) => {
+                assert_eq!(address, "anonymous")
+            }
+            _ => panic!("Must return MissingCredential error"),
+        }
+
+        // so do aliases, wallet and contract destinations and receivers set by the chain
+        let msgs = vec![
+            ExecuteMsg::SetAlias {
+                name: "anon".to_string(),
+                address: "anonymous".to_string(),
+            },
+            ExecuteMsg::SetDestination {
+                name: "anon".to_string(),
+                destination: Destination::Wallet(Addr::unchecked("anonymous")),
+            },
+            ExecuteMsg::SetDestination {
+                name: "anon".to_string(),
+                destination: Destination::Contract {
+                    addr: Addr::unchecked("anonymous"),
+                    msg: Binary::from(br#"{"deposit":{}}"#.to_vec()),
+                },
+            },
+        ];
+        for msg in msgs {
+            let res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg);
+            match res {
+                Err(ContractError::MissingCredential { address, .. }) => {
+                    assert_eq!(address, "anonymous")
+                }
+                _ => panic!("Must return MissingCredential error"),
+            }
+        }
+        let msg = SudoMsg::SetReceiver {
+            receiver: "anonymous".to_string(),
+        };
+        let res = sudo(deps.as_mut(), mock_env(), msg);
+        match res {
+            Err(ContractError::MissingCredential { address, .. }) => {
+                assert_eq!(address, "anonymous")
+            }
+            _ => panic!("Must return MissingCredential error"),
+        }
+
+        // without an attestation any receiver can be set
+        let msg = ExecuteMsg::SetAttestation { attestation: None };
+        let _res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+        let msg = ExecuteMsg::ResetReceiver {
+            receiver: "anonymous".to_string(),
+        };
+        let _res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetReceiver {}).unwrap();
+        let value: ReceiverResponse = from_json(res).unwrap();
+        assert_eq!(value.receiver, "anonymous");
+
+        // an alias registered before the attestation was set is checked when the chain uses it
+        let msg = ExecuteMsg::SetAlias {
+            name: "anon".to_string(),
+            address: "anonymous".to_string(),
+        };
+        let _res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+        let msg = ExecuteMsg::SetAttestation {
+            attestation: Some(AttestationSettings {
+                registry: "registry".to_string(),
+                credential: "kyc".to_string(),
+            }),
+        };
+        let _res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+        let msg = SudoMsg::SetReceiver {
+            receiver: "anon".to_string(),
+        };
+        let res = sudo(deps.as_mut(), mock_env(), msg);
+        match res {
+            Err(ContractError::MissingCredential { address, .. }) => {
+                assert_eq!(address, "anonymous")
+            }
+            _ => panic!("Must return MissingCredential error"),
+        }
     }
 
-    let state = config_read(&deps.storage).load()?;
-    let recipient = deps.api.human_address(&state.receiver)?;
-    let log = vec![log("action", "send"), log("recipient", recipient.as_str())];
-    let from_address = env.contract.address.clone();
-    let to_address = recipient.clone();
+    #[test]
+    fn signed_permits() {
+        use k256::ecdsa::signature::hazmat::PrehashSigner;
+        use k256::ecdsa::{Signature, SigningKey};
 
-    let r = HandleResponse {
-        messages: vec![CosmosMsg::Bank(BankMsg::Send {
-            from_address,
-            to_address,
-            amount: funds,
-        })],
-        log,
-        data: None,
-    };
-    Ok(r)
-}
+        let key = SigningKey::from_bytes(&[7u8; 32].into()).unwrap();
+        let pubkey = Binary::from(key.verifying_key().to_sec1_bytes().to_vec());
+        let sign = |key: &SigningKey, params: PermitParams| {
+            let hash = Sha256::digest(to_json_vec(&params).unwrap());
+            let signature: Signature = key.sign_prehash(&hash).unwrap();
+            Permit {
+                params,
+                signature: Binary::from(signature.to_bytes().to_vec()),
+            }
+        };
 
-pub fn try_reset<S: Storage, A: Api, Q: Querier>(
-    deps: &mut Extern<S, A, Q>,
-    env: Env,
-    receiver: CanonicalAddr,
-) -> StdResult<HandleResponse> {
-    let api = &deps.api;
-    config(&mut deps.storage).update(|mut state| {
-        if api.canonical_address(&env.message.sender)? != state.owner {
-            return Err(StdError::unauthorized());
+        let mut deps = mock_dependencies(&[]);
+        let env = mock_env();
+        let msg = InstantiateMsg {
+            receiver: "treasury".to_string(),
+            owner_pubkey: Some(pubkey),
+            ..Default::default()
+        };
+        let _res = instantiate(deps.as_mut(), env.clone(), mock_info("creator", &[]), msg).unwrap();
+
+        let params = |receiver: &str, nonce: u64| PermitParams {
+            contract: env.contract.address.to_string(),
+            chain_id: env.block.chain_id.clone(),
+            receiver: receiver.to_string(),
+            nonce,
+            expires: Expiration::AtHeight(env.block.height + 10),
+        };
+        let exec = |deps: &mut OwnedDeps<_, _, _, _>, permit: Permit| {
+            let msg = ExecuteMsg::ExecPermit { permit };
+            execute(deps.as_mut(), mock_env(), mock_info("relayer", &[]), msg)
+        };
+
+        // a permit signed with another key is rejected
+        let other = SigningKey::from_bytes(&[9u8; 32].into()).unwrap();
+        match exec(&mut deps, sign(&other, params("mallory", 0))) {
+            Err(ContractError::InvalidSignature {}) => {}
+            _ => panic!("Must return InvalidSignature error"),
         }
-        state.receiver = receiver;
-        Ok(state)
-    })?;
-    Ok(HandleResponse::default())
-}
 
-pub fn query<S: Storage, A: Api, Q: Querier>(
-    deps: &Extern<S, A, Q>,
-    msg: QueryMsg,
-) -> StdResult<Binary> {
-    match msg {
-        QueryMsg::GetReceiver {} => to_binary(&query_receiver(deps)?),
+        // anyone can submit the owner's permit
+        let permit = sign(&key, params("ops", 0));
+        let res = exec(&mut deps, permit.clone()).unwrap();
+        assert!(res.attributes.contains(&attr("permit_nonce", "0")));
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetReceiver {}).unwrap();
+        let value: ReceiverResponse = from_json(res).unwrap();
+        assert_eq!(value.receiver, "ops");
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::PermitNonce {}).unwrap();
+        let value: PermitNonceResponse = from_json(res).unwrap();
+        assert_eq!(value.nonce, 1);
+
+        // it cannot be used twice
+        match exec(&mut deps, permit) {
+            Err(ContractError::InvalidNonce { expected }) => assert_eq!(expected, 1),
+            _ => panic!("Must return InvalidNonce error"),
+        }
+
+        let mut expired = params("ops2", 1);
+        expired.expires = Expiration::AtHeight(env.block.height);
+        match exec(&mut deps, sign(&key, expired)) {
+            Err(ContractError::PermitExpired {}) => {}
+            _ => panic!("Must return PermitExpired error"),
+        }
+        let mut elsewhere = params("ops2", 1);
+        elsewhere.chain_id = "other-chain".to_string();
+        match exec(&mut deps, sign(&key, elsewhere)) {
+            Err(ContractError::PermitMismatch {}) => {}
+            _ => panic!("Must return PermitMismatch error"),
+        }
+
+        // without a pubkey permits are turned off
+        let msg = ExecuteMsg::SetOwnerPubkey { owner_pubkey: None };
+        let _res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+        match exec(&mut deps, sign(&key, params("ops2", 1))) {
+            Err(ContractError::NoOwnerPubkey {}) => {}
+            _ => panic!("Must return NoOwnerPubkey error"),
+        }
     }
-}
 
-fn query_receiver<S: Storage, A: Api, Q: Querier>(
-    deps: &Extern<S, A, Q>,
-) -> StdResult<ReceiverResponse> {
-    let state = config_read(&deps.storage).load()?;
-    Ok(ReceiverResponse {
-        receiver: deps.api.human_address(&state.receiver)?.to_string(),
-    })
-}
+    #[test]
+    fn congestion_minimum() {
+        let mut deps = mock_dependencies(&[]);
+        let msg = InstantiateMsg {
+            receiver: "treasury".to_string(),
+            min_amount: coins(10, "uusd"),
+            congestion: Some(Congestion {
+                window: 60,
+                tiers: vec![(2, Decimal::percent(200)), (3, Decimal::percent(500))],
+            }),
+            ..Default::default()
+        };
+        let _res = instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use cosmwasm_std::testing::{mock_dependencies, mock_env};
-    use cosmwasm_std::{coins, from_binary, StdError};
+        let send = ExecuteMsg::TokenSend {
+            recipient: None,
+            route: None,
+            memo: None,
+            on_behalf_of: None,
+            category: None,
+        };
+        for _ in 0..2 {
+            let info = mock_info("spammer", &coins(10, "uusd"));
+            let _res = execute(deps.as_mut(), mock_env(), info, send.clone()).unwrap();
+        }
+
+        // two forwards in the window double the minimum
+        let info = mock_info("alice", &coins(10, "uusd"));
+        let res = execute(deps.as_mut(), mock_env(), info, send.clone());
+        match res {
+            Err(ContractError::BelowMinimum { min, .. }) => assert_eq!(min, Uint128::new(20)),
+            _ => panic!("Must return BelowMinimum error"),
+        }
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::EffectiveMinimum {}).unwrap();
+        let value: EffectiveMinimumResponse = from_json(res).unwrap();
+        let now = mock_env().block.time.seconds();
+        assert_eq!(
+            value,
+            EffectiveMinimumResponse {
+                min_amount: coins(20, "uusd"),
+                multiplier: Decimal::percent(200),
+                forwards: 2,
+                resets_at: Some(now + 60),
+            }
+        );
+        let info = mock_info("alice", &coins(20, "uusd"));
+        let _res = execute(deps.as_mut(), mock_env(), info, send.clone()).unwrap();
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::EffectiveMinimum {}).unwrap();
+        let value: EffectiveMinimumResponse = from_json(res).unwrap();
+        assert_eq!(value.min_amount, coins(50, "uusd"));
+
+        // once the window is over the minimum is back to normal
+        let mut env = mock_env();
+        env.block.time = env.block.time.plus_seconds(60);
+        let info = mock_info("alice", &coins(10, "uusd"));
+        let _res = execute(deps.as_mut(), env, info, send).unwrap();
+
+        let msg = ExecuteMsg::SetCongestion {
+            congestion: Some(Congestion {
+                window: 60,
+                tiers: vec![(5, Decimal::percent(200)), (5, Decimal::percent(300))],
+            }),
+        };
+        let res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg);
+        match res {
+            Err(ContractError::InvalidCongestion {}) => {}
+            _ => panic!("Must return InvalidCongestion error"),
+        }
+    }
 
     #[test]
-    fn proper_initialization() {
-        let mut deps = mock_dependencies(44, &[]);
+    fn reconciliation() {
+        let mut deps = mock_dependencies(&[coin(1000, "uusd"), coin(50, "ukrw")]);
+        let msg = InstantiateMsg {
+            receiver: "treasury".to_string(),
+            hold: true,
+            ..Default::default()
+        };
+        let _res = instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+        // the held uluna never reached the balance, so it cannot be covered
+        let _res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("alice", &[coin(200, "uusd"), coin(30, "uluna")]),
+            ExecuteMsg::TokenSend {
+                recipient: None,
+                route: None,
+                memo: None,
+                on_behalf_of: None,
+                category: None,
+            },
+        )
+        .unwrap();
 
-        let msg = InitMsg {
-            receiver: "terra1j40dd3k6f3wmlx8h00eg5avasjygvsh3pg3g5p".to_string(),
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::Reconciliation {}).unwrap();
+        let value: ReconciliationResponse = from_json(res).unwrap();
+        let held = value
+            .ledgers
+            .iter()
+            .find(|ledger| ledger.name == "held")
+            .unwrap();
+        assert_eq!(held.total, vec![coin(200, "uusd"), coin(30, "uluna")]);
+        assert!(value
+            .ledgers
+            .iter()
+            .filter(|ledger| ledger.name != "held")
+            .all(|ledger| ledger.total.is_empty()));
+        assert_eq!(
+            value.denoms,
+            vec![
+                DenomReconciliation {
+                    denom: "ukrw".to_string(),
+                    balance: Uint128::new(50),
+                    liabilities: Uint128::zero(),
+                    surplus: Uint128::new(50),
+                    deficit: Uint128::zero(),
+                },
+                DenomReconciliation {
+                    denom: "uluna".to_string(),
+                    balance: Uint128::zero(),
+                    liabilities: Uint128::new(30),
+                    surplus: Uint128::zero(),
+                    deficit: Uint128::new(30),
+                },
+                DenomReconciliation {
+                    denom: "uusd".to_string(),
+                    balance: Uint128::new(1000),
+                    liabilities: Uint128::new(200),
+                    surplus: Uint128::new(800),
+                    deficit: Uint128::zero(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn unbonding_reconciliation() {
+        let mut deps = mock_dependencies(&[]);
+        deps.querier.update_staking(
+            "uluna",
+            &[],
+            &[FullDelegation {
+                delegator: Addr::unchecked(MOCK_CONTRACT_ADDR),
+                validator: "terravaloper1".to_string(),
+                amount: coin(500, "uluna"),
+                can_redelegate: coin(500, "uluna"),
+                accumulated_rewards: vec![],
+            }],
+        );
+        let msg = InstantiateMsg {
+            receiver: "treasury".to_string(),
+            validator: Some("terravaloper1".to_string()),
+            ..Default::default()
+        };
+        let _res = instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+        let msg = ExecuteMsg::Undelegate {
+            amount: coin(200, "uluna"),
         };
-        let env = mock_env("creator", &coins(1000, "uusd"));
+        let _res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
 
-        // we can just call .unwrap() to assert this was a success
-        let res = init(&mut deps, env, msg).unwrap();
-        assert_eq!(0, res.messages.len());
+        let ledger = |value: &ReconciliationResponse, name: &str| {
+            value
+                .ledgers
+                .iter()
+                .find(|ledger| ledger.name == name)
+                .unwrap()
+                .total
+                .clone()
+        };
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::Reconciliation {}).unwrap();
+        let value: ReconciliationResponse = from_json(res).unwrap();
+        assert_eq!(ledger(&value, "unbonding"), coins(200, "uluna"));
+        assert_eq!(ledger(&value, "unbonded"), vec![]);
 
-        // it worked, let's query the state
-        let res = query(&deps, QueryMsg::GetReceiver {}).unwrap();
-        let value: ReceiverResponse = from_binary(&res).unwrap();
+        // once it completed the undelegation is in the balance but still owed to the receiver
+        deps.querier
+            .update_balance(MOCK_CONTRACT_ADDR, coins(200, "uluna"));
+        let mut env = mock_env();
+        env.block.time = env.block.time.plus_seconds(UNBONDING_PERIOD);
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::Reconciliation {}).unwrap();
+        let value: ReconciliationResponse = from_json(res).unwrap();
+        assert_eq!(ledger(&value, "unbonding"), vec![]);
+        assert_eq!(ledger(&value, "unbonded"), coins(200, "uluna"));
         assert_eq!(
-            "terra1j40dd3k6f3wmlx8h00eg5avasjygvsh3pg3g5p",
-            value.receiver.to_string()
+            value.denoms,
+            vec![DenomReconciliation {
+                denom: "uluna".to_string(),
+                balance: Uint128::new(200),
+                liabilities: Uint128::new(200),
+                surplus: Uint128::zero(),
+                deficit: Uint128::zero(),
+            }]
+        );
+
+        // a sweep leaves the completed undelegation to the receiver
+        deps.querier
+            .update_balance(MOCK_CONTRACT_ADDR, coins(250, "uluna"));
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("creator", &[]),
+            ExecuteMsg::Sweep { denom: None },
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: "treasury".to_string(),
+                amount: coins(50, "uluna"),
+            })]
         );
     }
 
     #[test]
-    fn failed_tokensend() {
-        let mut deps = mock_dependencies(44, &coins(2, "token"));
+    fn ibc_forwarding() {
+        let mut deps = mock_dependencies(&[]);
 
-        let msg = InitMsg {
-            receiver: "terra1w548z72h5mgf6cgdkrx5h7fqk3e5wdejkv22d5".to_string(),
+        let msg = InstantiateMsg {
+            receiver: "treasury".to_string(),
+            fee_rate: Decimal::percent(10),
+            fee_collector: Some("collector".to_string()),
+            ibc: Some(IbcForwarding {
+                channel: "channel-7".to_string(),
+                remote_receiver: "osmo1treasury".to_string(),
+                timeout: 600,
+            }),
+            ..Default::default()
+        };
+        let _res = instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+
+        // the receiver's share is transferred to the remote receiver, the fee is paid here
+        let send = ExecuteMsg::TokenSend {
+            recipient: None,
+            route: None,
+            memo: None,
+            on_behalf_of: None,
+            category: None,
+        };
+        let info = mock_info("alice", &coins(100, "uusd"));
+        let res = execute(deps.as_mut(), mock_env(), info, send.clone()).unwrap();
+        let transfer = |amount: u128| {
+            transfer_msg(
+                &Addr::unchecked(MOCK_CONTRACT_ADDR),
+                "channel-7",
+                "osmo1treasury",
+                &coin(amount, "uusd"),
+                mock_env().block.time.plus_seconds(600),
+            )
+        };
+        assert_eq!(
+            res.messages,
+            vec![
+                SubMsg::new(BankMsg::Send {
+                    to_address: "collector".to_string(),
+                    amount: coins(10, "uusd"),
+                }),
+                SubMsg::reply_on_success(transfer(90), IBC_REPLY_ID + 1),
+            ]
+        );
+        // the transfer asks ibc-hooks to report back to the contract how it ended
+        match transfer(90) {
+            CosmosMsg::Stargate { type_url, value } => {
+                assert_eq!(type_url, MSG_TRANSFER);
+                assert_eq!(transfer_amount(&value), Some(coin(90, "uusd")));
+                let memo = format!(r#"{{"ibc_callback":"{}"}}"#, MOCK_CONTRACT_ADDR);
+                assert!(value.windows(memo.len()).any(|w| w == memo.as_bytes()));
+            }
+            _ => panic!("Must be a MsgTransfer"),
+        }
+        let info = mock_info("bob", &coins(50, "uusd"));
+        let res = execute(deps.as_mut(), mock_env(), info, send).unwrap();
+        assert_eq!(
+            res.messages[1],
+            SubMsg::reply_on_success(transfer(45), IBC_REPLY_ID + 2)
+        );
+
+        // the replies tell which packet each transfer was sent as
+        let sent = |id: u64, sequence: &str| Reply {
+            id: IBC_REPLY_ID + id,
+            result: SubMsgResult::Ok(SubMsgResponse {
+                events: vec![Event::new("send_packet").add_attribute("packet_sequence", sequence)],
+                data: None,
+            }),
         };
-        let env = mock_env("creator", &coins(1000, "token"));
+        let _res = reply(deps.as_mut(), mock_env(), sent(1, "4")).unwrap();
+        let _res = reply(deps.as_mut(), mock_env(), sent(2, "5")).unwrap();
+
+        // a transfer that timed out is owed back to its sender, an acked one is done
+        let msg = SudoMsg::IbcLifecycleComplete(IbcLifecycleComplete::IbcTimeout {
+            channel: "channel-7".to_string(),
+            sequence: 4,
+        });
+        let res = sudo(deps.as_mut(), mock_env(), msg).unwrap();
+        assert_eq!(res.attributes[0], attr("action", "ibc_refundable"));
+        let msg = SudoMsg::IbcLifecycleComplete(IbcLifecycleComplete::IbcAck {
+            channel: "channel-7".to_string(),
+            sequence: 5,
+            ack: "AQ==".to_string(),
+            success: true,
+        });
+        let res = sudo(deps.as_mut(), mock_env(), msg).unwrap();
+        assert_eq!(res.attributes[0], attr("action", "ibc_acked"));
+        let msg = QueryMsg::Refunds {
+            start_after: None,
+            limit: None,
+        };
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let value: RefundsResponse = from_json(res).unwrap();
+        assert_eq!(
+            value.refunds,
+            vec![RefundInfo {
+                sender: "alice".to_string(),
+                amount: coins(90, "uusd"),
+            }]
+        );
 
-        let _res = init(&mut deps, env, msg).unwrap();
+        // every packet completes once
+        let msg = SudoMsg::IbcLifecycleComplete(IbcLifecycleComplete::IbcTimeout {
+            channel: "channel-7".to_string(),
+            sequence: 5,
+        });
+        match sudo(deps.as_mut(), mock_env(), msg) {
+            Err(ContractError::UnknownIbcTransfer { sequence, .. }) => assert_eq!(sequence, 5),
+            _ => panic!("Must return UnknownIbcTransfer error"),
+        }
+
+        // one-off recipients are paid on this chain
+        let msg = ExecuteMsg::TokenSend {
+            recipient: Some("vendor".to_string()),
+            route: None,
+            memo: None,
+            on_behalf_of: None,
+            category: None,
+        };
+        let info = mock_info("creator", &coins(100, "uusd"));
+        let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        assert_eq!(
+            res.messages[1],
+            SubMsg::new(BankMsg::Send {
+                to_address: "vendor".to_string(),
+                amount: coins(90, "uusd"),
+            })
+        );
 
-        let env = mock_env("anyone", &[]);
-        let msg = HandleMsg::TokenSend {};
-        let res = handle(&mut deps, env, msg);
+        let msg = ExecuteMsg::SetIbc {
+            ibc: Some(IbcForwarding {
+                channel: "channel-7".to_string(),
+                remote_receiver: "osmo1treasury".to_string(),
+                timeout: 0,
+            }),
+        };
+        let res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg);
         match res {
-            Ok(_) => panic!("expected error"),
-            Err(StdError::GenericErr { msg, .. }) => {
-                assert_eq!(msg, "You must pass some UST")
+            Err(ContractError::InvalidIbc {}) => {}
+            _ => panic!("Must return InvalidIbc error"),
+        }
+    }
+
+    #[test]
+    fn proposal_attestation() {
+        let mut deps = mock_dependencies(&[]);
+        mock_registry(&mut deps.querier, "registry", "kyc", &["verified"]);
+        let msg = InstantiateMsg {
+            receiver: "treasury".to_string(),
+            governance: Some(GovernanceSettings {
+                voters: vec![("alice".to_string(), 1)],
+                quorum: Decimal::percent(50),
+                voting_period: Duration::Height(10),
+            }),
+            attestation: Some(AttestationSettings {
+                registry: "registry".to_string(),
+                credential: "kyc".to_string(),
+            }),
+            ..Default::default()
+        };
+        let _res = instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+
+        for receiver in ["anonymous", "verified"] {
+            let msg = ExecuteMsg::ProposeReceiver {
+                receiver: receiver.to_string(),
+            };
+            let _res = execute(deps.as_mut(), mock_env(), mock_info("alice", &[]), msg).unwrap();
+        }
+        let mut env = mock_env();
+        env.block.height += 10;
+
+        // a passed proposal cannot install a receiver without the credential
+        let msg = ExecuteMsg::ExecuteProposal { proposal_id: 1 };
+        let res = execute(deps.as_mut(), env.clone(), mock_info("alice", &[]), msg);
+        match res {
+            Err(ContractError::MissingCredential { address, .. }) => {
+                assert_eq!(address, "anonymous")
             }
-            Err(e) => panic!("unexpected error: {:?}", e),
+            _ => panic!("Must return MissingCredential error"),
         }
+        let msg = ExecuteMsg::ExecuteProposal { proposal_id: 2 };
+        let _res = execute(deps.as_mut(), env.clone(), mock_info("alice", &[]), msg).unwrap();
+        let res = query(deps.as_ref(), env, QueryMsg::GetReceiver {}).unwrap();
+        let value: ReceiverResponse = from_json(res).unwrap();
+        assert_eq!(value.receiver, "verified");
     }
 
     #[test]
-    fn tokensend() {
-        let mut deps = mock_dependencies(44, &coins(2, "uusd"));
+    fn recurring_subscriptions() {
+        let mut deps = mock_dependencies(&[]);
 
-        let msg = InitMsg {
-            receiver: "terra1w548z72h5mgf6cgdkrx5h7fqk3e5wdejkv22d5".to_string(),
+        let msg = InstantiateMsg {
+            receiver: "treasury".to_string(),
+            ..Default::default()
         };
-        let env = mock_env("creator", &coins(1000, "uusd"));
+        let _res = instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
 
-        let _res = init(&mut deps, env, msg).unwrap();
-
-        let balance = coins(100, "uusd");
-        let env = mock_env("anyone", &balance);
-        let msg = HandleMsg::TokenSend {};
+        let msg = ExecuteMsg::Subscribe {
+            amount: coin(10, "uusd"),
+            interval: 100,
+        };
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("alice", &coins(5, "uusd")),
+            msg.clone(),
+        );
+        match res {
+            Err(ContractError::SubscriptionUnderfunded { denom }) => assert_eq!(denom, "uusd"),
+            _ => panic!("Must return SubscriptionUnderfunded error"),
+        }
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("alice", &[coin(10, "uusd"), coin(10, "ukrw")]),
+            msg.clone(),
+        );
+        match res {
+            Err(ContractError::UnsupportedDenom { denom }) => assert_eq!(denom, "ukrw"),
+            _ => panic!("Must return UnsupportedDenom error"),
+        }
+        let _res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("alice", &coins(25, "uusd")),
+            msg,
+        )
+        .unwrap();
+        let msg = ExecuteMsg::FundSubscription { id: 1 };
+        let _res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("bob", &coins(10, "uusd")),
+            msg,
+        )
+        .unwrap();
 
-        //deps.querier.update_balance("anyone", coins(200, "token"));
-        //let query_balance = deps.querier.query_all_balances("anyone");
-        //println!("Balance {:#?}", query_balance);
+        // the first installment is due right away
+        let start = mock_env().block.time.seconds();
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::UpcomingCharges { limit: None },
+        )
+        .unwrap();
+        let value: UpcomingChargesResponse = from_json(&res).unwrap();
+        assert_eq!(
+            value.charges,
+            vec![Charge {
+                subscription_id: 1,
+                sender: "alice".to_string(),
+                amount: coin(10, "uusd"),
+                due: start,
+            }]
+        );
 
-        let res = handle(&mut deps, env, msg).unwrap();
-        let msg = res.messages.get(0).expect("no message");
+        let process = ExecuteMsg::ProcessSubscriptions { limit: None };
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("anyone", &[]),
+            process.clone(),
+        )
+        .unwrap();
         assert_eq!(
-            msg,
-            &CosmosMsg::Bank(BankMsg::Send {
-                from_address: HumanAddr::from("cosmos2contract"),
-                to_address: HumanAddr::from("terra1w548z72h5mgf6cgdkrx5h7fqk3e5wdejkv22d5"),
-                amount: coins(100, "uusd"),
-            })
+            res.messages,
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: "treasury".to_string(),
+                amount: coins(10, "uusd"),
+            })]
         );
+        // nothing is due until the interval passed
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("anyone", &[]),
+            process.clone(),
+        )
+        .unwrap();
+        assert!(res.messages.is_empty());
+
+        // missed installments are charged together, the subscription ends once its balance
+        // cannot pay another one
+        let mut env = mock_env();
+        env.block.time = Timestamp::from_seconds(start + 250);
+        let res = execute(deps.as_mut(), env, mock_info("anyone", &[]), process).unwrap();
         assert_eq!(
-            res.log,
+            res.messages,
             vec![
-                log("action", "send"),
-                log("recipient", "terra1w548z72h5mgf6cgdkrx5h7fqk3e5wdejkv22d5"),
+                SubMsg::new(BankMsg::Send {
+                    to_address: "treasury".to_string(),
+                    amount: coins(20, "uusd"),
+                }),
+                SubMsg::new(BankMsg::Send {
+                    to_address: "alice".to_string(),
+                    amount: coins(5, "uusd"),
+                }),
             ]
         );
+        assert!(res.attributes.contains(&attr("subscription_ended", "1")));
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::Subscription { id: 1 });
+        assert!(res.is_err());
+
+        // the sender can cancel and gets the balance back
+        let msg = ExecuteMsg::Subscribe {
+            amount: coin(10, "uusd"),
+            interval: 100,
+        };
+        let _res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("alice", &coins(30, "uusd")),
+            msg,
+        )
+        .unwrap();
+        let cancel = ExecuteMsg::CancelSubscription { id: 2 };
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("bob", &[]),
+            cancel.clone(),
+        );
+        match res {
+            Err(ContractError::Unauthorized {}) => {}
+            _ => panic!("Must return unauthorized error"),
+        }
+        let res = execute(deps.as_mut(), mock_env(), mock_info("alice", &[]), cancel).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: "alice".to_string(),
+                amount: coins(30, "uusd"),
+            })]
+        );
     }
 
     #[test]
-    fn reset() {
-        let mut deps = mock_dependencies(44, &coins(2, "token"));
+    fn stream() {
+        let mut deps = mock_dependencies(&[]);
 
-        let msg = InitMsg {
-            receiver: "terra1j40dd3k6f3wmlx8h00eg5avasjygvsh3pg3g5p".to_string(),
+        let msg = InstantiateMsg {
+            receiver: "treasury".to_string(),
+            stream_duration: Some(100),
+            ..Default::default()
         };
-        let env = mock_env("creator", &coins(2, "token"));
-        let _res = init(&mut deps, env, msg).unwrap();
+        let _res = instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
 
-        // beneficiary can release it
-        let unauth_env = mock_env("anyone", &coins(2, "token"));
-        let msg = HandleMsg::ResetReceiver {
-            receiver: "terra1w548z72h5mgf6cgdkrx5h7fqk3e5wdejkv22d5".to_string(),
+        let start = mock_env().block.time.seconds();
+        let at = |time: u64| {
+            let mut env = mock_env();
+            env.block.time = Timestamp::from_seconds(time);
+            env
+        };
+        let msg = ExecuteMsg::TokenSend {
+            recipient: None,
+            route: None,
+            memo: None,
+            on_behalf_of: None,
+            category: None,
         };
-        let res = handle(&mut deps, unauth_env, msg);
+        let res = execute(
+            deps.as_mut(),
+            at(start),
+            mock_info("alice", &coins(100, "uusd")),
+            msg.clone(),
+        )
+        .unwrap();
+        assert!(res.messages.is_empty());
+        let res = execute(
+            deps.as_mut(),
+            at(start),
+            mock_info("anyone", &[]),
+            ExecuteMsg::Claim {},
+        );
         match res {
-            Err(StdError::Unauthorized { .. }) => {}
-            _ => panic!("Must return unauthorized error"),
+            Err(ContractError::NothingToClaim {}) => {}
+            _ => panic!("Must return NothingToClaim error"),
         }
 
-        // only the original creator can reset the receiver
-        let auth_env = mock_env("creator", &coins(2, "token"));
-        let msg = HandleMsg::ResetReceiver {
-            receiver: "terra1w548z72h5mgf6cgdkrx5h7fqk3e5wdejkv22d5".to_string(),
+        // the deposit vests linearly
+        let res = execute(
+            deps.as_mut(),
+            at(start + 25),
+            mock_info("treasury", &[]),
+            ExecuteMsg::Claim {},
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: "treasury".to_string(),
+                amount: coins(25, "uusd"),
+            })]
+        );
+        let res = query(deps.as_ref(), at(start + 50), QueryMsg::GetStream {}).unwrap();
+        let value: StreamResponse = from_json(&res).unwrap();
+        assert_eq!(
+            value,
+            StreamResponse {
+                total: coins(100, "uusd"),
+                vested: coins(50, "uusd"),
+                claimed: coins(25, "uusd"),
+                outstanding: coins(50, "uusd"),
+                ends_at: Some(start + 100),
+            }
+        );
+
+        // every deposit vests on its own schedule
+        let _res = execute(
+            deps.as_mut(),
+            at(start + 50),
+            mock_info("bob", &coins(100, "uusd")),
+            msg,
+        )
+        .unwrap();
+        let res = execute(
+            deps.as_mut(),
+            at(start + 100),
+            mock_info("treasury", &[]),
+            ExecuteMsg::Claim {},
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: "treasury".to_string(),
+                amount: coins(125, "uusd"),
+            })]
+        );
+        let res = query(deps.as_ref(), at(start + 100), QueryMsg::GetStream {}).unwrap();
+        let value: StreamResponse = from_json(&res).unwrap();
+        assert_eq!(
+            value,
+            StreamResponse {
+                total: coins(100, "uusd"),
+                vested: coins(50, "uusd"),
+                claimed: coins(50, "uusd"),
+                outstanding: coins(50, "uusd"),
+                ends_at: Some(start + 150),
+            }
+        );
+    }
+
+    #[test]
+    fn vesting_schedule() {
+        let mut deps = mock_dependencies(&[]);
+
+        let msg = InstantiateMsg {
+            receiver: "treasury".to_string(),
+            ..Default::default()
         };
-        let _res = handle(&mut deps, auth_env, msg).unwrap();
+        let _res = instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
 
-        // should now be 5
-        let res = query(&deps, QueryMsg::GetReceiver {}).unwrap();
-        let value: ReceiverResponse = from_binary(&res).unwrap();
+        let start = mock_env().block.time.seconds();
+        let at = |time: u64| {
+            let mut env = mock_env();
+            env.block.time = Timestamp::from_seconds(time);
+            env
+        };
+        let vest = |cliff: u64, end: u64, revocable: bool| ExecuteMsg::Vest {
+            schedule: VestingSchedule {
+                cliff,
+                end,
+                revocable,
+            },
+            memo: None,
+        };
+        let res = execute(
+            deps.as_mut(),
+            at(start),
+            mock_info("alice", &coins(100, "uusd")),
+            vest(start - 1, start + 100, true),
+        );
+        match res {
+            Err(ContractError::InvalidSchedule {}) => {}
+            _ => panic!("Must return InvalidSchedule error"),
+        }
+        let _res = execute(
+            deps.as_mut(),
+            at(start),
+            mock_info("alice", &coins(100, "uusd")),
+            vest(start + 50, start + 100, true),
+        )
+        .unwrap();
+
+        // nothing vests before the cliff
+        let res = query(deps.as_ref(), at(start + 10), QueryMsg::Stream { id: 1 }).unwrap();
+        let value: StreamInfo = from_json(&res).unwrap();
         assert_eq!(
-            "terra1w548z72h5mgf6cgdkrx5h7fqk3e5wdejkv22d5",
-            value.receiver.to_string()
+            value,
+            StreamInfo {
+                id: 1,
+                sender: "alice".to_string(),
+                amount: coins(100, "uusd"),
+                vested: coins(0, "uusd"),
+                claimed: vec![],
+                start,
+                cliff: start + 50,
+                end: start + 100,
+                revocable: true,
+                next_unlock: Some(start + 50),
+            }
+        );
+        let res = execute(
+            deps.as_mut(),
+            at(start + 10),
+            mock_info("treasury", &[]),
+            ExecuteMsg::Claim {},
+        );
+        match res {
+            Err(ContractError::NothingToClaim {}) => {}
+            _ => panic!("Must return NothingToClaim error"),
+        }
+
+        // at the cliff everything since the start vests at once
+        let res = execute(
+            deps.as_mut(),
+            at(start + 50),
+            mock_info("treasury", &[]),
+            ExecuteMsg::Claim {},
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: "treasury".to_string(),
+                amount: coins(50, "uusd"),
+            })]
+        );
+        let res = query(deps.as_ref(), at(start + 50), QueryMsg::Stream { id: 1 }).unwrap();
+        let value: StreamInfo = from_json(&res).unwrap();
+        assert_eq!(value.next_unlock, Some(start + 51));
+
+        // the owner revokes what has not vested yet, it goes back to the sender
+        let res = execute(
+            deps.as_mut(),
+            at(start + 60),
+            mock_info("alice", &[]),
+            ExecuteMsg::Revoke { id: 1 },
+        );
+        match res {
+            Err(ContractError::Unauthorized {}) => {}
+            _ => panic!("Must return unauthorized error"),
+        }
+        let res = execute(
+            deps.as_mut(),
+            at(start + 60),
+            mock_info("creator", &[]),
+            ExecuteMsg::Revoke { id: 1 },
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: "alice".to_string(),
+                amount: coins(40, "uusd"),
+            })]
+        );
+        let res = execute(
+            deps.as_mut(),
+            at(start + 70),
+            mock_info("treasury", &[]),
+            ExecuteMsg::Claim {},
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: "treasury".to_string(),
+                amount: coins(10, "uusd"),
+            })]
         );
+        let res = query(
+            deps.as_ref(),
+            at(start + 70),
+            QueryMsg::Streams {
+                start_after: None,
+                limit: None,
+            },
+        )
+        .unwrap();
+        let value: StreamsResponse = from_json(&res).unwrap();
+        assert!(value.streams.is_empty());
+
+        // irrevocable schedules cannot be revoked
+        let _res = execute(
+            deps.as_mut(),
+            at(start),
+            mock_info("alice", &coins(100, "uusd")),
+            vest(start, start + 100, false),
+        )
+        .unwrap();
+        let res = execute(
+            deps.as_mut(),
+            at(start + 10),
+            mock_info("creator", &[]),
+            ExecuteMsg::Revoke { id: 2 },
+        );
+        match res {
+            Err(ContractError::Irrevocable {}) => {}
+            _ => panic!("Must return Irrevocable error"),
+        }
     }
 }
@@ -0,0 +1,332 @@
+use cosmwasm_std::{OverflowError, StdError, Uint128};
+use snafu::Snafu;
+
+// ContractError holds everything that can go wrong in instantiate, execute and migrate.
+// Clients that still expect a StdError can convert it, see From<ContractError> below.
+#[derive(Debug, Snafu)]
+#[snafu(visibility = "pub")]
+pub enum ContractError {
+    #[snafu(display("{}", source))]
+    Std { source: StdError },
+
+    #[snafu(display("Unauthorized"))]
+    Unauthorized {},
+
+    #[snafu(display("Contract is paused"))]
+    Paused {},
+
+    #[snafu(display("Sender is not allowed to deposit"))]
+    SenderNotAllowed {},
+
+    #[snafu(display("Sender is blocked"))]
+    SenderBlocked {},
+
+    #[snafu(display("No funds were sent"))]
+    NoFundsSent {},
+
+    #[snafu(display("Denom {} is not supported", denom))]
+    UnsupportedDenom { denom: String },
+
+    #[snafu(display("Sent {}{}, the minimum is {}{}", got, denom, min, denom))]
+    BelowMinimum {
+        denom: String,
+        min: Uint128,
+        got: Uint128,
+    },
+
+    #[snafu(display("Accepted and unsupported denoms cannot be mixed"))]
+    MixedDenomsNotAllowed {},
+
+    #[snafu(display("Amount too large, the maximum is {}{}", max, denom))]
+    AmountTooLarge { denom: String, max: Uint128 },
+
+    #[snafu(display("The minimum amount of {} is above its maximum", denom))]
+    InvalidLimits { denom: String },
+
+    #[snafu(display("Deposit is worth {}uusd, the minimum is {}uusd", value, min))]
+    BelowUsdFloor { value: Uint128, min: Uint128 },
+
+    #[snafu(display("Rate limit exceeded, {}{} left in this window", remaining, denom))]
+    RateLimitExceeded { denom: String, remaining: Uint128 },
+
+    #[snafu(display("Daily cap exceeded, {}{} left today", remaining, denom))]
+    DailyCapExceeded { denom: String, remaining: Uint128 },
+
+    #[snafu(display("Lifetime cap exceeded, {}{} left", remaining, denom))]
+    LifetimeCapExceeded { denom: String, remaining: Uint128 },
+
+    #[snafu(display("Receiver weights must be positive"))]
+    ZeroWeight {},
+
+    #[snafu(display("Receiver weights must add up to 1"))]
+    InvalidWeights {},
+
+    #[snafu(display("Fee rate cannot exceed 100%"))]
+    FeeTooHigh {},
+
+    #[snafu(display("A fee collector is required to charge fees"))]
+    NoFeeCollector {},
+
+    #[snafu(display("A denom is required to withdraw an amount"))]
+    WithdrawDenomRequired {},
+
+    #[snafu(display("Nothing to withdraw"))]
+    NothingToWithdraw {},
+
+    #[snafu(display("Cannot withdraw more than is claimable"))]
+    InsufficientClaimable {},
+
+    #[snafu(display("Nothing to flush"))]
+    NothingToFlush {},
+
+    #[snafu(display("Escrow cannot be refunded before it expires"))]
+    EscrowNotExpired {},
+
+    #[snafu(display("Escrow is disputed, only its arbiter can settle it"))]
+    EscrowDisputed {},
+
+    #[snafu(display("Escrow has no arbiter"))]
+    NoArbiter {},
+
+    #[snafu(display("Escrow is not disputed"))]
+    NotDisputed {},
+
+    #[snafu(display("Invoice {} was paid already", id))]
+    InvoicePaid { id: u64 },
+
+    #[snafu(display("Invoice {} expired", id))]
+    InvoiceExpired { id: u64 },
+
+    #[snafu(display("Invoice must be paid in {}", denom))]
+    InvoiceDenomMismatch { denom: String },
+
+    #[snafu(display("Only {} is left to pay on the invoice", remaining))]
+    InvoiceOverpaid { remaining: String },
+
+    #[snafu(display("An invoice must have an amount and expire in the future"))]
+    InvalidInvoice {},
+
+    #[snafu(display("Subscription amount and interval must be positive"))]
+    InvalidSubscription {},
+
+    #[snafu(display("You must prefund at least one installment of {}", denom))]
+    SubscriptionUnderfunded { denom: String },
+
+    #[snafu(display("Nothing to claim"))]
+    NothingToClaim {},
+
+    #[snafu(display("A vesting schedule must not start before now or end before its cliff"))]
+    InvalidSchedule {},
+
+    #[snafu(display("Stream cannot be revoked"))]
+    Irrevocable {},
+
+    #[snafu(display("Hash must be a hex encoded SHA-256 hash"))]
+    InvalidHash {},
+
+    #[snafu(display("Preimage does not match the hashlock"))]
+    WrongPreimage {},
+
+    #[snafu(display("Hashlock expired"))]
+    HashlockExpired {},
+
+    #[snafu(display("No validator is configured"))]
+    NoValidator {},
+
+    #[snafu(display("Unknown reply id {}", id))]
+    UnknownReply { id: u64 },
+
+    #[snafu(display("Payout failed: {}", error))]
+    PayoutFailed { error: String },
+
+    #[snafu(display("Nothing to sweep"))]
+    NothingToSweep {},
+
+    #[snafu(display("The claimable balance has not expired"))]
+    ClaimNotExpired {},
+
+    #[snafu(display("A fallback receiver is required"))]
+    NoFallbackReceiver {},
+
+    #[snafu(display("Denom {} has more than one rule", denom))]
+    DuplicateDenomRule { denom: String },
+
+    #[snafu(display("{} does not hold the {} credential", address, credential))]
+    MissingCredential { address: String, credential: String },
+
+    #[snafu(display("No owner pubkey to check permits with"))]
+    NoOwnerPubkey {},
+
+    #[snafu(display("Permit is for another contract or chain"))]
+    PermitMismatch {},
+
+    #[snafu(display("Permit expired"))]
+    PermitExpired {},
+
+    #[snafu(display("Permit nonce must be {}", expected))]
+    InvalidNonce { expected: u64 },
+
+    #[snafu(display("Permit signature is invalid"))]
+    InvalidSignature {},
+
+    #[snafu(display("There are no staking rewards to forward"))]
+    NoRewards {},
+
+    #[snafu(display("Invalid receiver address {}", address))]
+    InvalidReceiver { address: String },
+
+    #[snafu(display("The contract cannot be its own receiver"))]
+    SelfReceiver {},
+
+    #[snafu(display("{} is not a contract", address))]
+    NotAContract { address: String },
+
+    #[snafu(display("Message must be an object with a single method"))]
+    InvalidExecMsg {},
+
+    #[snafu(display("Method {} is not allowed", method))]
+    MethodNotAllowed { method: String },
+
+    #[snafu(display("The sent funds do not match the payments"))]
+    BatchMismatch {},
+
+    #[snafu(display("Unknown alias {}", name))]
+    UnknownAlias { name: String },
+
+    #[snafu(display("Unknown category {}", name))]
+    UnknownCategory { name: String },
+
+    #[snafu(display("Unknown referral code {}", code))]
+    UnknownReferral { code: String },
+
+    #[snafu(display("Referral rate cannot exceed 100%"))]
+    ReferralRateTooHigh {},
+
+    #[snafu(display("No recovery address is configured"))]
+    NoRecoveryAddress {},
+
+    #[snafu(display("No emergency withdrawal was announced"))]
+    NoEmergencyWithdrawal {},
+
+    #[snafu(display(
+        "The emergency withdrawal can be executed from height {}",
+        execute_after
+    ))]
+    EmergencyNotReady { execute_after: u64 },
+
+    #[snafu(display("No payouts are queued"))]
+    NothingToDistribute {},
+
+    #[snafu(display("Nothing to do"))]
+    NothingToDo {},
+
+    #[snafu(display("The contract is winding down and takes no deposits"))]
+    WindingDown {},
+
+    #[snafu(display("The contract is terminated"))]
+    Terminated {},
+
+    #[snafu(display("The contract is not winding down"))]
+    NoWindDown {},
+
+    #[snafu(display("Funds can be claimed until height {}", claim_deadline))]
+    ClaimPeriodNotOver { claim_deadline: u64 },
+
+    #[snafu(display("Governance needs voters and a quorum of at most 100%"))]
+    InvalidGovernance {},
+
+    #[snafu(display("Receivers are only changed through governance proposals"))]
+    Governed {},
+
+    #[snafu(display("Governance is not enabled"))]
+    NoGovernance {},
+
+    #[snafu(display("Already voted on proposal {}", id))]
+    AlreadyVoted { id: u64 },
+
+    #[snafu(display("Voting on proposal {} is closed", id))]
+    VotingClosed { id: u64 },
+
+    #[snafu(display("Proposal {} has not passed", id))]
+    ProposalNotPassed { id: u64 },
+
+    #[snafu(display("A multisig needs admins and a threshold between 1 and their number"))]
+    InvalidMultisig {},
+
+    #[snafu(display("This action has to be approved by the admins"))]
+    ApprovalRequired {},
+
+    #[snafu(display("No multisig is configured"))]
+    NoMultisig {},
+
+    #[snafu(display("Already approved admin action {}", id))]
+    AlreadyApproved { id: u64 },
+
+    #[snafu(display("Admin action {} was already executed", id))]
+    AlreadyExecuted { id: u64 },
+
+    #[snafu(display("No campaign is configured"))]
+    NoCampaign {},
+
+    #[snafu(display("The campaign is not open for deposits"))]
+    CampaignNotOpen {},
+
+    #[snafu(display("The campaign has not ended yet"))]
+    CampaignNotEnded {},
+
+    #[snafu(display("The campaign did not meet its goal"))]
+    GoalNotMet {},
+
+    #[snafu(display("The campaign met its goal, deposits cannot be refunded"))]
+    GoalMet {},
+
+    #[snafu(display("The deposits of the current campaign were not paid out yet"))]
+    CampaignActive {},
+
+    #[snafu(display("Cannot migrate from {}", contract))]
+    CannotMigrate { contract: String },
+
+    #[snafu(display("Ownership expiry is already set"))]
+    OwnershipExpirySet {},
+
+    #[snafu(display("{} is already used by another route", name))]
+    NameTaken { name: String },
+
+    #[snafu(display("Route {} is paused", name))]
+    RoutePaused { name: String },
+
+    #[snafu(display("Rotation heights must be in the future and ascending"))]
+    InvalidRotation {},
+
+    #[snafu(display(
+        "Congestion needs a window, tiers ascending by forwards and multipliers of at least 1"
+    ))]
+    InvalidCongestion {},
+
+    #[snafu(display("IBC mode needs a channel, a remote receiver and a timeout"))]
+    InvalidIbc {},
+
+    #[snafu(display("No IBC transfer was sent as packet {} on {}", sequence, channel))]
+    UnknownIbcTransfer { channel: String, sequence: u64 },
+}
+
+impl From<StdError> for ContractError {
+    fn from(source: StdError) -> Self {
+        ContractError::Std { source }
+    }
+}
+
+impl From<OverflowError> for ContractError {
+    fn from(err: OverflowError) -> Self {
+        ContractError::Std { source: err.into() }
+    }
+}
+
+impl From<ContractError> for StdError {
+    fn from(err: ContractError) -> Self {
+        match err {
+            ContractError::Std { source } => source,
+            err => StdError::generic_err(err.to_string()),
+        }
+    }
+}
@@ -0,0 +1,102 @@
+use cosmwasm_std::{Addr, Binary, Coin, CosmosMsg, Timestamp, Uint128};
+
+// ICS-20 transfers asking the ibc-hooks module for a callback. IbcMsg::Transfer has no memo
+// in CosmWasm 1.x, so the MsgTransfer is encoded here. Only the fields set by the contract
+// are encoded and decoded.
+
+pub const MSG_TRANSFER: &str = "/ibc.applications.transfer.v1.MsgTransfer";
+
+// transfer_msg transfers coin over channel to receiver. The memo asks ibc-hooks to report to
+// the contract how the transfer ended, see SudoMsg::IbcLifecycleComplete
+pub fn transfer_msg(
+    contract: &Addr,
+    channel: &str,
+    receiver: &str,
+    coin: &Coin,
+    timeout: Timestamp,
+) -> CosmosMsg {
+    let mut token = vec![];
+    put_bytes(&mut token, 1, coin.denom.as_bytes());
+    put_bytes(&mut token, 2, coin.amount.to_string().as_bytes());
+
+    let memo = format!(r#"{{"ibc_callback":"{}"}}"#, contract);
+    let mut value = vec![];
+    put_bytes(&mut value, 1, b"transfer");
+    put_bytes(&mut value, 2, channel.as_bytes());
+    put_bytes(&mut value, 3, &token);
+    put_bytes(&mut value, 4, contract.as_bytes());
+    put_bytes(&mut value, 5, receiver.as_bytes());
+    put_varint(&mut value, 7 << 3);
+    put_varint(&mut value, timeout.nanos());
+    put_bytes(&mut value, 8, memo.as_bytes());
+    CosmosMsg::Stargate {
+        type_url: MSG_TRANSFER.to_string(),
+        value: Binary::from(value),
+    }
+}
+
+// transfer_amount returns the coin a MsgTransfer made by transfer_msg moves
+pub fn transfer_amount(value: &[u8]) -> Option<Coin> {
+    let token = fields(value)?.into_iter().find(|(field, _)| *field == 3)?.1;
+    let mut coin = Coin {
+        denom: String::new(),
+        amount: Uint128::zero(),
+    };
+    for (field, bytes) in fields(token)? {
+        match field {
+            1 => coin.denom = String::from_utf8(bytes.to_vec()).ok()?,
+            2 => coin.amount = std::str::from_utf8(bytes).ok()?.parse().ok()?,
+            _ => {}
+        }
+    }
+    Some(coin)
+}
+
+fn put_varint(buf: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        buf.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    buf.push(value as u8);
+}
+
+fn put_bytes(buf: &mut Vec<u8>, field: u64, bytes: &[u8]) {
+    put_varint(buf, field << 3 | 2);
+    put_varint(buf, bytes.len() as u64);
+    buf.extend_from_slice(bytes);
+}
+
+fn get_varint(buf: &[u8], pos: &mut usize) -> Option<u64> {
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+        let byte = *buf.get(*pos)?;
+        *pos += 1;
+        value |= u64::from(byte & 0x7f) << shift;
+        if byte < 0x80 {
+            return Some(value);
+        }
+    }
+    None
+}
+
+// fields returns the length delimited fields of a message by number, varints are skipped
+fn fields(buf: &[u8]) -> Option<Vec<(u64, &[u8])>> {
+    let mut fields = vec![];
+    let mut pos = 0;
+    while pos < buf.len() {
+        let key = get_varint(buf, &mut pos)?;
+        match key & 7 {
+            0 => {
+                get_varint(buf, &mut pos)?;
+            }
+            2 => {
+                let len = get_varint(buf, &mut pos)? as usize;
+                let end = pos.checked_add(len).filter(|end| *end <= buf.len())?;
+                fields.push((key >> 3, &buf[pos..end]));
+                pos = end;
+            }
+            _ => return None,
+        }
+    }
+    Some(fields)
+}
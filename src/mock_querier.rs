@@ -0,0 +1,118 @@
+use std::marker::PhantomData;
+
+use cosmwasm_std::testing::{MockApi, MockQuerier, MockStorage, MOCK_CONTRACT_ADDR};
+use cosmwasm_std::{
+    from_json, to_json_binary, Coin, ContractInfoResponse, ContractResult, Decimal, OwnedDeps,
+    SystemError, SystemResult, Uint128, WasmQuery,
+};
+use terra_cosmwasm::{TaxCapResponse, TaxRateResponse, TerraQuery, TerraQueryWrapper};
+
+use crate::attestation::{CredentialResponse, RegistryQueryMsg};
+use crate::oracle::{OracleQueryMsg, PriceResponse};
+
+// mock_dependencies is cosmwasm_std::testing::mock_dependencies with a querier that also
+// answers Terra tax queries, no tax is charged
+pub fn mock_dependencies(
+    contract_balance: &[Coin],
+) -> OwnedDeps<MockStorage, MockApi, MockQuerier<TerraQueryWrapper>> {
+    mock_dependencies_with_tax(contract_balance, Decimal::zero(), &[])
+}
+
+// mock_dependencies_with_tax charges tax_rate on sends, capped per denom by tax_caps
+pub fn mock_dependencies_with_tax(
+    contract_balance: &[Coin],
+    tax_rate: Decimal,
+    tax_caps: &[(&str, u128)],
+) -> OwnedDeps<MockStorage, MockApi, MockQuerier<TerraQueryWrapper>> {
+    let tax_caps: Vec<(String, Uint128)> = tax_caps
+        .iter()
+        .map(|(denom, cap)| (denom.to_string(), Uint128::new(*cap)))
+        .collect();
+    let querier = MockQuerier::new(&[(MOCK_CONTRACT_ADDR, contract_balance)]).with_custom_handler(
+        move |query: &TerraQueryWrapper| {
+            let res = match &query.query_data {
+                TerraQuery::TaxRate {} => to_json_binary(&TaxRateResponse { rate: tax_rate }),
+                TerraQuery::TaxCap { denom } => {
+                    let cap = tax_caps
+                        .iter()
+                        .find(|(d, _)| d == denom)
+                        .map(|(_, cap)| *cap)
+                        .unwrap_or_default();
+                    to_json_binary(&TaxCapResponse { cap })
+                }
+                _ => panic!("unsupported terra query"),
+            };
+            SystemResult::Ok(ContractResult::from(res))
+        },
+    );
+    OwnedDeps {
+        storage: MockStorage::default(),
+        api: MockApi::default(),
+        querier,
+        custom_query_type: PhantomData,
+    }
+}
+
+// mock_contract makes contract answer contract info queries, every other address is an account
+pub fn mock_contract(querier: &mut MockQuerier<TerraQueryWrapper>, contract: &'static str) {
+    querier.update_wasm(move |query| match query {
+        WasmQuery::ContractInfo { contract_addr } if contract_addr == contract => {
+            let info = ContractInfoResponse::default();
+            SystemResult::Ok(ContractResult::from(to_json_binary(&info)))
+        }
+        WasmQuery::ContractInfo { contract_addr } => {
+            SystemResult::Err(SystemError::NoSuchContract {
+                addr: contract_addr.clone(),
+            })
+        }
+        _ => panic!("unsupported wasm query"),
+    });
+}
+
+// mock_oracle makes oracle answer price queries with prices, other denoms have no price
+pub fn mock_oracle(
+    querier: &mut MockQuerier<TerraQueryWrapper>,
+    oracle: &'static str,
+    prices: &[(&str, Decimal)],
+) {
+    let prices: Vec<(String, Decimal)> = prices
+        .iter()
+        .map(|(denom, price)| (denom.to_string(), *price))
+        .collect();
+    querier.update_wasm(move |query| match query {
+        WasmQuery::Smart { contract_addr, msg } if contract_addr == oracle => {
+            let OracleQueryMsg::Price { denom } = from_json(msg).unwrap();
+            match prices.iter().find(|(d, _)| *d == denom) {
+                Some((_, price)) => {
+                    SystemResult::Ok(ContractResult::from(to_json_binary(&PriceResponse {
+                        price: *price,
+                    })))
+                }
+                None => SystemResult::Ok(ContractResult::Err(format!("no price for {}", denom))),
+            }
+        }
+        _ => panic!("unsupported wasm query"),
+    });
+}
+
+// mock_registry makes registry attest that holders hold credential, no one else holds any
+pub fn mock_registry(
+    querier: &mut MockQuerier<TerraQueryWrapper>,
+    registry: &'static str,
+    credential: &'static str,
+    holders: &'static [&'static str],
+) {
+    querier.update_wasm(move |query| match query {
+        WasmQuery::Smart { contract_addr, msg } if contract_addr == registry => {
+            let RegistryQueryMsg::HasCredential {
+                address,
+                credential: held,
+            } = from_json(msg).unwrap();
+            let valid = held == credential && holders.contains(&address.as_str());
+            SystemResult::Ok(ContractResult::from(to_json_binary(&CredentialResponse {
+                valid,
+            })))
+        }
+        _ => panic!("unsupported wasm query"),
+    });
+}
@@ -1,23 +1,1234 @@
+use cosmwasm_schema::QueryResponses;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
+use cosmwasm_std::{Binary, Coin, CosmosMsg, Decimal, Uint128};
+use cw20::Cw20ReceiveMsg;
+
+use crate::split::Rounding;
+use crate::state::{
+    Campaign, Congestion, DenomRouting, Destination, Duration, Expiration, FundsPolicy,
+    GovernanceChange, IbcForwarding, Overpayment, RateLimit, ReceiptStatus, Retention, Unbonding,
+};
+
+#[derive(Serialize, Deserialize, Clone, Default, Debug, PartialEq, JsonSchema)]
+pub struct InstantiateMsg {
+    pub receiver: String,
+    // receivers optionally splits incoming funds by weight, the weights must add up to 1
+    #[serde(default)]
+    pub receivers: Vec<(String, Decimal)>,
+    // fee_rate of every forward is paid to fee_collector, fee_cap optionally limits the fee
+    // per denom (cw20 tokens are capped by their contract address)
+    #[serde(default)]
+    pub fee_rate: Decimal,
+    pub fee_collector: Option<String>,
+    #[serde(default)]
+    pub fee_cap: Vec<Coin>,
+    // pull keeps native deposits in the contract until the receivers withdraw them
+    #[serde(default)]
+    pub pull: bool,
+    // min_amount rejects deposits below the minimum of their denom
+    #[serde(default)]
+    pub min_amount: Vec<Coin>,
+    // a deposit has to include one of accepted_denoms, when empty every denom is accepted.
+    // cw20 tokens are accepted by their contract address. funds_policy forwards, refunds or rejects the other attached denoms
+    #[serde(default)]
+    pub accepted_denoms: Vec<String>,
+    #[serde(default)]
+    pub funds_policy: FundsPolicy,
+    // allowlist may route deposits to any recipient, just like the owner
+    #[serde(default)]
+    pub allowlist: Vec<String>,
+    // hold keeps native deposits until the balance of their denom reaches flush_threshold,
+    // denoms without a threshold are only forwarded by Flush
+    #[serde(default)]
+    pub hold: bool,
+    #[serde(default)]
+    pub flush_threshold: Vec<Coin>,
+    // receiver_delay is how long a new receiver waits before it takes over
+    pub receiver_delay: Option<Duration>,
+    // restrict_senders only accepts deposits from senders added with AddSender
+    #[serde(default)]
+    pub restrict_senders: bool,
+    // rate_limit caps deposits per sender and per day
+    pub rate_limit: Option<RateLimit>,
+    // escrow keeps every native deposit until the receiver releases it, the sender can
+    // refund it once escrow has passed
+    pub escrow: Option<Duration>,
+    // stream_duration vests every native deposit to the receivers linearly over that many
+    // seconds, Claim pays out what has vested
+    pub stream_duration: Option<u64>,
+    // approval_threshold parks TokenSend deposits with a coin at or above the threshold of
+    // its denom until the owner approves or rejects them
+    #[serde(default)]
+    pub approval_threshold: Vec<Coin>,
+    // callbacks makes contract receivers get executed with the forwarded funds instead of
+    // a bank send, e.g. ("vault", {"deposit":{}})
+    #[serde(default)]
+    pub callbacks: Vec<(String, Binary)>,
+    // validator turns on staking mode, deposits of the bonded denom are delegated to it
+    // instead of being forwarded
+    pub validator: Option<String>,
+    // swap sells deposits of the listed denoms for the target denom first, the proceeds are
+    // forwarded
+    pub swap: Option<SwapSettings>,
+    // hold_failed keeps payouts that fail for RetryFailed instead of reverting the deposit
+    #[serde(default)]
+    pub hold_failed: bool,
+    // fallback_receiver is paid whatever a receiver cannot be paid
+    pub fallback_receiver: Option<String>,
+    // require_acceptance makes a new receiver take over only once it called
+    // AcceptReceivership
+    #[serde(default)]
+    pub require_acceptance: bool,
+    // recovery_address is where EmergencyWithdraw moves the funds
+    pub recovery_address: Option<String>,
+    // dust_threshold is the smallest amount of each denom worth a send, smaller fees and
+    // shares are collected as dust
+    #[serde(default)]
+    pub dust_threshold: Vec<Coin>,
+    // max_amount rejects deposits above the maximum of their denom
+    #[serde(default)]
+    pub max_amount: Vec<Coin>,
+    // waterfall lists receivers that are paid first, in order, each until it got its cap.
+    // A receiver only takes the denoms it has a cap for, the rest is split as usual
+    #[serde(default)]
+    pub waterfall: Vec<(String, Vec<Coin>)>,
+    // governance hands receiver changes to voters, ResetReceiver is then disabled
+    pub governance: Option<GovernanceSettings>,
+    // multisig makes ResetReceiver, SetFee and the emergency withdrawal need admin approval
+    pub multisig: Option<MultisigSettings>,
+    // campaign limits deposits to a window and keeps them until it ends
+    pub campaign: Option<Campaign>,
+    // receipt_nft mints a receipt nft for large deposits while mint_receipts is set
+    pub receipt_nft: Option<ReceiptNftSettings>,
+    #[serde(default)]
+    pub mint_receipts: bool,
+    // referral_rate of a deposit goes to the referrer if its memo is a registered code
+    #[serde(default)]
+    pub referral_rate: Decimal,
+    // usd_floor rejects deposits worth less than a minimum in uusd
+    pub usd_floor: Option<UsdFloorSettings>,
+    // max_payouts queues the payouts of a forward to more receivers for Distribute
+    pub max_payouts: Option<u32>,
+    // claim_rebate is paid from the rebate pool on top of every withdrawal, so a receiver
+    // without funds for gas can still claim
+    pub claim_rebate: Option<Coin>,
+    // retention prunes old transfers from the history
+    pub retention: Option<Retention>,
+    // ownership_expires_at removes the owner once it passed
+    pub ownership_expires_at: Option<Expiration>,
+    // rounding decides who gets what is left once the split is rounded down
+    #[serde(default)]
+    pub rounding: Rounding,
+    // refund_failed owes bank payouts that fail back to the sender instead of holding them
+    #[serde(default)]
+    pub refund_failed: bool,
+    // anchor deposits forwarded stable coins into Anchor Earn, the receivers get the aTerra
+    pub anchor: Option<AnchorSettings>,
+    // claim_expiry lets anyone redirect a pull mode balance unclaimed for that many seconds
+    // to the fallback receiver
+    #[serde(default)]
+    pub claim_expiry: Option<u64>,
+    // denom_rules override the fee rate or the receivers of single denoms
+    #[serde(default)]
+    pub denom_rules: Vec<DenomRuleSettings>,
+    // attestation requires every receiver set later on to hold a credential of a registry
+    #[serde(default)]
+    pub attestation: Option<AttestationSettings>,
+    // owner_pubkey is the secp256k1 key the owner signs permits with
+    #[serde(default)]
+    pub owner_pubkey: Option<Binary>,
+    // congestion raises the minimum deposits while many deposits are forwarded
+    #[serde(default)]
+    pub congestion: Option<Congestion>,
+    // ibc transfers what the receiver is paid to a receiver on another chain. Failed transfers
+    // are only refunded on chains running the ibc-hooks module, see SudoMsg
+    #[serde(default)]
+    pub ibc: Option<IbcForwarding>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct DenomRuleSettings {
+    pub denom: String,
+    pub fee_rate: Option<Decimal>,
+    pub receiver: Option<String>,
+}
+
+// Permit is a receiver reset the owner signed off-chain. signature is the owner's secp256k1
+// signature of the sha256 hash of the JSON encoded params
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Permit {
+    pub params: PermitParams,
+    pub signature: Binary,
+}
+
+// PermitParams is what the owner signs. contract and chain_id keep the permit from being
+// used elsewhere, nonce has to be the permit nonce of the contract so it is used once
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
-pub struct InitMsg {
+pub struct PermitParams {
+    pub contract: String,
+    pub chain_id: String,
     pub receiver: String,
+    pub nonce: u64,
+    pub expires: Expiration,
+}
+
+// AttestationSettings is the registry contract attesting receivers and the credential they
+// must hold
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct AttestationSettings {
+    pub registry: String,
+    pub credential: String,
+}
+
+// UsdFloorSettings is the oracle contract pricing deposits and the minimum value in uusd
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct UsdFloorSettings {
+    pub oracle: String,
+    pub min_value: Uint128,
+}
+
+// ReceiptNftSettings is the cw721 contract minting the receipts and the deposit of each
+// denom that earns one
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ReceiptNftSettings {
+    pub contract: String,
+    pub threshold: Vec<Coin>,
+}
+
+// SwapSettings lists the pair contract swapping each denom to target_denom, e.g.
+// ("uluna", luna-ust pair). max_slippage is the max spread passed to the pairs
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct SwapSettings {
+    pub target_denom: String,
+    pub pairs: Vec<(String, String)>,
+    pub max_slippage: Decimal,
+}
+
+// AnchorSettings is the Anchor money market forwarded coins of denom, e.g. uusd, are
+// deposited into and its aTerra token, e.g. aUST
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct AnchorSettings {
+    pub money_market: String,
+    pub aterra: String,
+    pub denom: String,
+}
+
+// RouteSettings is a named route: deposits to it are split between receivers, or all sent
+// to receiver without any, with its own fee and denom policy
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct RouteSettings {
+    pub receiver: String,
+    #[serde(default)]
+    pub receivers: Vec<(String, Decimal)>,
+    #[serde(default)]
+    pub fee_rate: Decimal,
+    pub fee_collector: Option<String>,
+    #[serde(default)]
+    pub fee_cap: Vec<Coin>,
+    #[serde(default)]
+    pub accepted_denoms: Vec<String>,
+    #[serde(default)]
+    pub funds_policy: FundsPolicy,
+}
+
+// GovernanceSettings lists the voters with their voting weight, give every voter a weight of
+// 1 to count members or their token balance to weigh by tokens. A proposal passes if, once
+// voting_period is over, the cast votes reach quorum of the total weight and there are more
+// yes than no votes
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct GovernanceSettings {
+    pub voters: Vec<(String, u64)>,
+    pub quorum: Decimal,
+    pub voting_period: Duration,
+}
+
+// MultisigSettings lists the admins, threshold of them have to approve an AdminAction
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct MultisigSettings {
+    pub admins: Vec<String>,
+    pub threshold: u64,
+}
+
+// AdminAction is a sensitive owner action, with a multisig it only runs once enough admins
+// approved it. It runs as if the owner sent it. Every action deciding who is paid is one,
+// sent directly they fail with ApprovalRequired
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum AdminAction {
+    ResetReceiver {
+        receiver: String,
+    },
+    SetRotation {
+        rotation: Vec<RotationEntry>,
+    },
+    SetReceivers {
+        receivers: Vec<(String, Decimal)>,
+    },
+    SetWaterfall {
+        tiers: Vec<(String, Vec<Coin>)>,
+    },
+    SetRoute {
+        name: String,
+        route: RouteSettings,
+    },
+    SetCategory {
+        name: String,
+        receiver: Option<String>,
+    },
+    SetAlias {
+        name: String,
+        address: String,
+    },
+    SetDestination {
+        name: String,
+        destination: Destination,
+    },
+    SetDenomRules {
+        denom_rules: Vec<DenomRuleSettings>,
+    },
+    SetFallbackReceiver {
+        fallback_receiver: Option<String>,
+    },
+    SetIbc {
+        ibc: Option<IbcForwarding>,
+    },
+    SetFee {
+        fee_rate: Decimal,
+        fee_collector: Option<String>,
+        fee_cap: Vec<Coin>,
+    },
+    AnnounceEmergencyWithdraw {},
+    EmergencyWithdraw {},
+    SetMultisig {
+        multisig: Option<MultisigSettings>,
+    },
+    RenounceOwnership {},
+    WindDown {},
+}
+
+// ConfigUpdate changes the settings it has, the others keep their value. Clearing
+// fee_collector or fallback_receiver takes SetFee or SetFallbackReceiver
+#[derive(Serialize, Deserialize, Clone, Default, Debug, PartialEq, JsonSchema)]
+pub struct ConfigUpdate {
+    pub fee_rate: Option<Decimal>,
+    pub fee_collector: Option<String>,
+    pub fee_cap: Option<Vec<Coin>>,
+    pub min_amount: Option<Vec<Coin>>,
+    pub max_amount: Option<Vec<Coin>>,
+    pub accepted_denoms: Option<Vec<String>>,
+    pub funds_policy: Option<FundsPolicy>,
+    pub paused: Option<bool>,
+    pub fallback_receiver: Option<String>,
+}
+
+impl ConfigUpdate {
+    // changes_fee tells if the update touches the fee, which a multisig has to approve
+    pub fn changes_fee(&self) -> bool {
+        self.fee_rate.is_some() || self.fee_collector.is_some() || self.fee_cap.is_some()
+    }
+
+    // needs_approval tells if a multisig has to approve the update, which it has to when it
+    // changes the fee or who is paid
+    pub fn needs_approval(&self) -> bool {
+        self.changes_fee() || self.fallback_receiver.is_some()
+    }
+}
+
+// Permission is an owner right the owner can grant to other addresses. Receiver, fee,
+// ownership and emergency actions stay with the owner
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Permission {
+    // Pause, Unpause, PauseRoute and UnpauseRoute
+    Pause,
+    // SetAcceptedDenoms and SetFundsPolicy
+    UpdateDenoms,
+    // SetMinAmount, SetMaxAmount, SetRateLimit, SetLifetimeCap and SetCongestion
+    UpdateLimits,
+    // SetRestrictSenders, AddSender, RemoveSender, BlockSender and UnblockSender
+    UpdateSenders,
+    // SetAlias, SetDestination and RemoveAlias
+    ManageAliases,
+    // Approve and Reject
+    ApproveForwards,
+    // Sweep, SweepCw20, WithdrawRewards and ForwardRewards, for keepers doing maintenance.
+    // Flush, ProcessSubscriptions, Distribute and RetryFailed are open to anyone anyway
+    Operate,
+}
+
+impl Permission {
+    pub const ALL: [Permission; 7] = [
+        Permission::Pause,
+        Permission::UpdateDenoms,
+        Permission::UpdateLimits,
+        Permission::UpdateSenders,
+        Permission::ManageAliases,
+        Permission::ApproveForwards,
+        Permission::Operate,
+    ];
+
+    // bit is the bit of the permission in a permission mask
+    pub fn bit(self) -> u64 {
+        1 << self as u64
+    }
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
-pub enum HandleMsg {
-    TokenSend {},
-    ResetReceiver { receiver: String },
+pub enum ExecuteMsg {
+    // TokenSend forwards the sent funds. The owner and allowlisted addresses can send them
+    // to recipient instead, for anyone else it is ignored
+    // memo is kept with the transfer so payments can be reconciled. Anyone can route the
+    // funds to a registered alias instead. An allowlisted contract depositing for a user
+    // passes them as on_behalf_of, the deposit is then recorded as theirs
+    TokenSend {
+        recipient: Option<String>,
+        #[serde(default)]
+        route: Option<String>,
+        memo: Option<String>,
+        #[serde(default)]
+        on_behalf_of: Option<String>,
+        // category tags the deposit with one of the categories the owner defined
+        #[serde(default)]
+        category: Option<String>,
+    },
+    // ForwardExec forwards the sent funds like TokenSend, executing the receiver contract
+    // with msg. The top-level key of msg must be a method the owner allowed
+    ForwardExec {
+        msg: Binary,
+    },
+    // ResetReceiver proposes a new receiver, it takes over once the receiver delay passed
+    // Owner only, with governance enabled receivers are changed through ProposeReceiver. With
+    // an attestation set the receiver must hold its credential
+    ResetReceiver {
+        receiver: String,
+    },
+    // SetRotation schedules the receivers to take over in turn, each at its active_from
+    // height, replacing the schedule. Heights must be in the future and ascending. Owner
+    // only, with governance enabled receivers are changed through ProposeReceiver
+    SetRotation {
+        rotation: Vec<RotationEntry>,
+    },
+    // SetAlias registers name for address, recipients and receivers can be given by name.
+    // SetDestination registers name as a route to destination instead. RemoveAlias drops
+    // either. Owner only
+    SetAlias {
+        name: String,
+        address: String,
+    },
+    SetDestination {
+        name: String,
+        destination: Destination,
+    },
+    RemoveAlias {
+        name: String,
+    },
+    // SetRoute registers a named route with its own receivers, fee and denom policy, a
+    // TokenSend with it as route is forwarded with them. A name already used by an alias
+    // or destination cannot be taken. RemoveRoute drops it. Owner only
+    SetRoute {
+        name: String,
+        route: RouteSettings,
+    },
+    RemoveRoute {
+        name: String,
+    },
+    // SetCategory defines a category senders can tag deposits with, deposits tagged with it
+    // are forwarded to receiver if one is set. RemoveCategory drops it, its totals are kept.
+    // Owner only
+    SetCategory {
+        name: String,
+        receiver: Option<String>,
+    },
+    RemoveCategory {
+        name: String,
+    },
+    // PauseRoute stops deposits to a route until UnpauseRoute, other routes and plain
+    // deposits go on
+    PauseRoute {
+        name: String,
+    },
+    UnpauseRoute {
+        name: String,
+    },
+    // RegisterReferral maps code to referrer, a TokenSend with code as its memo pays the
+    // referrer the referral rate of what the deposit forwards or adds to the held funds.
+    // Escrowed, streamed, pending and campaign deposits pay no commission and do not count
+    // for the code. RemoveReferral drops the code and its statistics. Owner only
+    RegisterReferral {
+        code: String,
+        referrer: String,
+    },
+    RemoveReferral {
+        code: String,
+    },
+    SetReferralRate {
+        referral_rate: Decimal,
+    },
+    SetReceiverDelay {
+        receiver_delay: Option<Duration>,
+    },
+    SetRequireAcceptance {
+        require_acceptance: bool,
+    },
+    // AcceptReceivership is called by the proposed receiver to confirm it can receive funds
+    AcceptReceivership {},
+    // SetGovernance enables voting on receiver changes. While it is enabled the split,
+    // waterfall, routes, categories, aliases, destinations, denom rules, fallback receiver,
+    // referrals, callbacks and IBC mode cannot be changed either. Owner only, once governance
+    // is enabled it is changed or turned off through ProposeGovernance
+    SetGovernance {
+        governance: Option<GovernanceSettings>,
+    },
+    // ProposeReceiver opens a vote on receiver, only voters can propose and vote
+    ProposeReceiver {
+        receiver: String,
+    },
+    // ProposeGovernance opens a vote on replacing the governance settings, None turns voting
+    // off and gives receiver changes back to the owner
+    ProposeGovernance {
+        governance: Option<GovernanceSettings>,
+    },
+    Vote {
+        proposal_id: u64,
+        approve: bool,
+    },
+    // ExecuteProposal resets the receiver or the governance to that of a passed proposal,
+    // anyone can call it
+    ExecuteProposal {
+        proposal_id: u64,
+    },
+    // SetMultisig configures the admins, None leaves the owner in sole control. Owner only,
+    // once a multisig is configured it is changed through ProposeAction
+    SetMultisig {
+        multisig: Option<MultisigSettings>,
+    },
+    // ProposeAction opens action for approval, ApproveAction approves it and runs it once
+    // threshold admins approved. Only admins can call them, proposing counts as approving
+    ProposeAction {
+        action: AdminAction,
+    },
+    ApproveAction {
+        id: u64,
+    },
+    // GrantPermissions lets address run the actions of permissions, RevokePermissions takes
+    // them back. Owner only
+    GrantPermissions {
+        address: String,
+        permissions: Vec<Permission>,
+    },
+    RevokePermissions {
+        address: String,
+        permissions: Vec<Permission>,
+    },
+    // SetReceivers replaces the weighted split, an empty list sends everything to the receiver
+    SetReceivers {
+        receivers: Vec<(String, Decimal)>,
+    },
+    // SetWaterfall replaces or reorders the waterfall tiers, what the receivers got so far
+    // still counts. ResetWaterfall starts filling all tiers from zero. Both are owner only
+    SetWaterfall {
+        tiers: Vec<(String, Vec<Coin>)>,
+    },
+    ResetWaterfall {},
+    SetFee {
+        fee_rate: Decimal,
+        fee_collector: Option<String>,
+        fee_cap: Vec<Coin>,
+    },
+    // Pause stops accepting deposits until Unpause is called, both are owner only
+    Pause {},
+    Unpause {},
+    SetPullMode {
+        pull: bool,
+    },
+    SetMinAmount {
+        min_amount: Vec<Coin>,
+    },
+    SetMaxAmount {
+        max_amount: Vec<Coin>,
+    },
+    SetUsdFloor {
+        usd_floor: Option<UsdFloorSettings>,
+    },
+    SetAcceptedDenoms {
+        accepted_denoms: Vec<String>,
+    },
+    SetFundsPolicy {
+        funds_policy: FundsPolicy,
+    },
+    SetAllowlist {
+        allowlist: Vec<String>,
+    },
+    SetHold {
+        hold: bool,
+        flush_threshold: Vec<Coin>,
+    },
+    // Flush forwards everything held in hold mode, anyone can call it
+    Flush {},
+    SetDustThreshold {
+        dust_threshold: Vec<Coin>,
+    },
+    SetReceiptNft {
+        receipt_nft: Option<ReceiptNftSettings>,
+    },
+    SetMintReceipts {
+        mint_receipts: bool,
+    },
+    // FlushDust pays the collected dust to the receiver like a forward, anyone can call it
+    FlushDust {},
+    // SetCampaign starts a new campaign, once the last one was paid out. Owner only
+    SetCampaign {
+        campaign: Option<Campaign>,
+    },
+    // ClaimCampaign forwards the deposits of a campaign that met its goal, only the receiver
+    // can call it. RefundCampaign returns the deposits of the sender if it did not
+    ClaimCampaign {},
+    RefundCampaign {},
+    // SetRestrictSenders turns the sender allowlist on or off, AddSender and RemoveSender
+    // maintain it. All of them are owner only
+    SetRestrictSenders {
+        restrict_senders: bool,
+    },
+    AddSender {
+        address: String,
+    },
+    SetRateLimit {
+        rate_limit: Option<RateLimit>,
+    },
+    // SetCongestion sets how the minimum deposits rise while many deposits are forwarded,
+    // none keeps them as they are
+    SetCongestion {
+        congestion: Option<Congestion>,
+    },
+    RemoveSender {
+        address: String,
+    },
+    // BlockSender stops address from depositing, UnblockSender lifts it. Owner only
+    BlockSender {
+        address: String,
+    },
+    UnblockSender {
+        address: String,
+    },
+    // AllowMethod lets ForwardExec call method on the receiver, DisallowMethod takes it back
+    // Owner only
+    AllowMethod {
+        method: String,
+    },
+    DisallowMethod {
+        method: String,
+    },
+    // Withdraw pays out funds accrued to the sender in pull mode. Without a denom
+    // everything is withdrawn, without an amount the whole balance of the denom.
+    Withdraw {
+        denom: Option<String>,
+        amount: Option<Uint128>,
+    },
+    SetEscrow {
+        escrow: Option<Duration>,
+    },
+    // Release forwards an escrowed deposit, only its receiver can call it. A deposit locked
+    // by a hash needs the hex encoded preimage and can only be released before it expires
+    Release {
+        id: u64,
+        preimage: Option<String>,
+    },
+    // Refund returns an expired escrowed deposit to its sender, only the sender can call it
+    Refund {
+        id: u64,
+    },
+    // ArbitratedSend deposits the sent funds in escrow for the receiver with arbiter to
+    // settle disputes, the sender can refund them once timeout has passed. Dispute stops
+    // the sender and receiver of escrow id from settling it, only its sender or receiver can
+    // call it. Resolve lets the arbiter of a disputed escrow release it to the receiver or
+    // refund it to the sender
+    ArbitratedSend {
+        arbiter: String,
+        timeout: Duration,
+        memo: Option<String>,
+    },
+    Dispute {
+        id: u64,
+    },
+    Resolve {
+        id: u64,
+        release: bool,
+    },
+    SetStreamDuration {
+        stream_duration: Option<u64>,
+    },
+    // HashlockSend deposits the sent funds in escrow for the receiver, locked by the hex
+    // encoded SHA-256 hash. The sender can refund them once timeout has passed
+    HashlockSend {
+        hash: String,
+        timeout: Duration,
+        memo: Option<String>,
+    },
+    // Vest deposits the sent funds on schedule, they vest to the receivers like in stream
+    // mode. Claim pays them out
+    Vest {
+        schedule: VestingSchedule,
+        memo: Option<String>,
+    },
+    // BatchSend pays every payment from the sent funds, which must add up to exactly the
+    // payments. The receivers, fee and split do not apply
+    BatchSend {
+        payments: Vec<Payment>,
+        memo: Option<String>,
+    },
+    // Revoke ends revocable stream id, what has not vested yet is returned to its sender.
+    // Only the owner can call it
+    Revoke {
+        id: u64,
+    },
+    // Claim forwards everything that vested in stream mode and was not claimed yet, anyone
+    // can call it
+    Claim {},
+    // Subscribe registers a subscription paying amount to the receivers every interval
+    // seconds, starting now. It is prefunded with the sent funds of the amount denom
+    Subscribe {
+        amount: Coin,
+        interval: u64,
+    },
+    // SubscribeCw20 registers a subscription paying amount of token every interval seconds,
+    // starting now. Every installment is pulled with TransferFrom, the sender grants the
+    // contract an allowance instead of prefunding it
+    SubscribeCw20 {
+        token: String,
+        amount: Uint128,
+        interval: u64,
+    },
+    // CreateInvoice requests amount from payer until expires, overpayment decides what
+    // happens to a payment above what is left to pay. Owner only. PayInvoice pays towards
+    // invoice id in its denom and forwards the payment to the receivers, only the payer can
+    // call it. Payments made before an invoice expired are not refunded
+    CreateInvoice {
+        payer: String,
+        amount: Coin,
+        expires: Expiration,
+        #[serde(default)]
+        overpayment: Overpayment,
+    },
+    PayInvoice {
+        id: u64,
+    },
+    // FundSubscription adds the sent funds to the balance of subscription id
+    FundSubscription {
+        id: u64,
+    },
+    // CancelSubscription ends subscription id and refunds its balance, only the sender of
+    // the subscription can call it
+    CancelSubscription {
+        id: u64,
+    },
+    // ProcessSubscriptions forwards the installments that are due, at most limit
+    // subscriptions are charged per call. Anyone can call it. A subscription ends once its
+    // balance cannot pay an installment, the rest is refunded. A cw20 subscription whose
+    // allowance or balance cannot pay an installment, or whose charge the token rejects, is
+    // marked delinquent and skips it. Nothing is charged while the contract is paused
+    ProcessSubscriptions {
+        limit: Option<u32>,
+    },
+    // ProcessDue charges the due subscriptions and pays the queued payouts, up to limit of
+    // each. FlushIfAboveThreshold forwards the held denoms that reached their flush
+    // threshold. Both are meant for keepers, anyone can call them. They set WorkDone as data
+    // and fail with NothingToDo if there was no work
+    ProcessDue {
+        limit: Option<u32>,
+    },
+    FlushIfAboveThreshold {},
+    SetApprovalThreshold {
+        approval_threshold: Vec<Coin>,
+    },
+    // Approve forwards pending deposit id, Reject refunds it to its sender. Both are owner
+    // only
+    Approve {
+        id: u64,
+    },
+    Reject {
+        id: u64,
+    },
+    // SetCallbacks replaces the callbacks of the contract receivers
+    SetCallbacks {
+        callbacks: Vec<(String, Binary)>,
+    },
+    // SetValidator moves the delegation to validator. Without a validator staking mode ends
+    // and the delegation is undelegated to the receiver
+    SetValidator {
+        validator: Option<String>,
+    },
+    // Undelegate unbonds amount from the validator, the receiver can withdraw it like in
+    // pull mode once unbonding completed
+    Undelegate {
+        amount: Coin,
+    },
+    // WithdrawRewards pays the staking rewards out to the receiver. Owner or operator
+    WithdrawRewards {},
+    // ForwardRewards withdraws the rewards of every delegation of the contract to the
+    // contract and forwards them like a deposit in the same transaction. Owner or operator
+    ForwardRewards {},
+    SetSwap {
+        swap: Option<SwapSettings>,
+    },
+    SetHoldFailed {
+        hold_failed: bool,
+    },
+    // SetAnchor turns anchor mode on or off. Owner only
+    SetAnchor {
+        anchor: Option<AnchorSettings>,
+    },
+    // SetIbc turns IBC mode on or off, in IBC mode the receiver's payouts are transferred to
+    // the remote receiver. Only turn it on where ibc-hooks reports back how transfers ended.
+    // Owner only
+    SetIbc {
+        ibc: Option<IbcForwarding>,
+    },
+    // SetRefundFailed owes bank payouts that fail back to the sender of the deposit, after
+    // the fallback receiver failed too. Owner only
+    SetRefundFailed {
+        refund_failed: bool,
+    },
+    // ClaimRefund pays the sender what its failed payouts owe it
+    ClaimRefund {},
+    // SetClaimExpiry sets how long a pull mode balance may go unclaimed. Owner only.
+    // RedirectStale sends the balance of receiver to the fallback receiver once it expired,
+    // anyone can call it. Balances pool many deposits, so they cannot go back to senders
+    SetClaimExpiry {
+        claim_expiry: Option<u64>,
+    },
+    // SetDenomRules replaces the per denom fee rates and receivers. Owner only
+    SetDenomRules {
+        denom_rules: Vec<DenomRuleSettings>,
+    },
+    // SetAttestation sets the registry a new receiver must hold a credential of, none turns
+    // the check off. Owner only
+    SetAttestation {
+        attestation: Option<AttestationSettings>,
+    },
+    // SetOwnerPubkey sets the key permits must be signed with, none turns permits off.
+    // Owner only
+    SetOwnerPubkey {
+        owner_pubkey: Option<Binary>,
+    },
+    // ExecPermit resets the receiver as the owner signed it in permit, anyone can submit it
+    ExecPermit {
+        permit: Permit,
+    },
+    RedirectStale {
+        receiver: String,
+    },
+    // RetryFailed sends failed payout id again, anyone can call it
+    RetryFailed {
+        id: u64,
+    },
+    // SetMaxPayouts sets how many receivers a forward pays at most, a forward to more of them
+    // queues the payouts. Owner only. Distribute pays up to limit queued payouts and can be
+    // called by anyone until the queue is empty
+    SetMaxPayouts {
+        max_payouts: Option<u32>,
+    },
+    Distribute {
+        limit: Option<u32>,
+    },
+    // SetClaimRebate sets the most a withdrawal gets from the rebate pool, TopUpRebates adds
+    // the sent funds to the pool. Owner only
+    SetClaimRebate {
+        claim_rebate: Option<Coin>,
+    },
+    TopUpRebates {},
+    // SetRetention sets how much transfer history is kept, Prune removes the transfers up to
+    // up_to_id right away. Owner only
+    SetRetention {
+        retention: Option<Retention>,
+    },
+    Prune {
+        up_to_id: u64,
+    },
+    SetFallbackReceiver {
+        fallback_receiver: Option<String>,
+    },
+    SetRecoveryAddress {
+        recovery_address: Option<String>,
+    },
+    // AnnounceEmergencyWithdraw starts the delay after which EmergencyWithdraw moves the
+    // whole native balance to the recovery address and pauses the contract.
+    // CancelEmergencyWithdraw drops the announcement. All of them are owner only
+    AnnounceEmergencyWithdraw {},
+    EmergencyWithdraw {},
+    CancelEmergencyWithdraw {},
+    // RenounceOwnership removes the owner for good, the settings can never change again.
+    // Owner only
+    RenounceOwnership {},
+    // SetOwnershipExpiry removes the owner once ownership_expires_at passed, after that no
+    // owner only message works anymore. It can only be set once. Owner only
+    SetOwnershipExpiry {
+        ownership_expires_at: Expiration,
+    },
+    // WindDown stops deposits for good and starts the claim period, in which escrows can be
+    // refunded before they expire. Owner only. Terminate sends what is left to the receiver
+    // once the claim period is over, after that every message is rejected
+    WindDown {},
+    Terminate {},
+    // Sweep forwards native funds sent to the contract outside of a deposit, only of denom
+    // if given. Funds kept for escrows, streams, subscriptions etc. are not touched. Owner
+    // or operator
+    Sweep {
+        denom: Option<String>,
+    },
+    // SweepCw20 forwards the balance of cw20 token the contract holds, e.g. tokens that were
+    // transferred instead of sent. Owner or operator
+    SweepCw20 {
+        token: String,
+    },
+    // PullCw20 transfers amount of cw20 token from the sender using the allowance the sender
+    // granted to the contract, and forwards it as a deposit of the sender
+    PullCw20 {
+        token: String,
+        amount: Uint128,
+    },
+    // SetLifetimeCap limits what address may deposit in total, an empty cap removes the
+    // limit. Owner only
+    SetLifetimeCap {
+        address: String,
+        cap: Vec<Coin>,
+    },
+    // UpdateConfig applies the update at once, the resulting config is validated as a
+    // whole. Owner only
+    UpdateConfig(ConfigUpdate),
+    // Receive is called by a cw20 token contract when tokens are sent to us
+    Receive(Cw20ReceiveMsg),
+}
+
+// VestingSchedule vests a deposit linearly from now until end, nothing vests before cliff.
+// Times are in seconds. The owner can revoke revocable schedules
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct VestingSchedule {
+    pub cliff: u64,
+    pub end: u64,
+    pub revocable: bool,
 }
 
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Payment {
+    pub recipient: String,
+    pub amount: Uint128,
+    pub denom: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct MigrateMsg {}
+
+// SudoMsg is sent by the chain, e.g. from a governance proposal, no owner is needed
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
+pub enum SudoMsg {
+    // SetReceiver replaces the receiver right away, skipping the receiver delay
+    SetReceiver { receiver: String },
+    Pause {},
+    Unpause {},
+    // IbcLifecycleComplete reports how an IBC transfer of the contract ended, a transfer that
+    // failed or timed out sent the funds back to the contract. It is not a standard CosmWasm
+    // callback, the ibc-hooks module of the chain sends it for transfers that asked for it.
+    // Without ibc-hooks it never arrives and the funds of failed transfers are not refunded
+    IbcLifecycleComplete(IbcLifecycleComplete),
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum IbcLifecycleComplete {
+    IbcAck {
+        channel: String,
+        sequence: u64,
+        ack: String,
+        success: bool,
+    },
+    IbcTimeout {
+        channel: String,
+        sequence: u64,
+    },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema, QueryResponses)]
+#[serde(rename_all = "snake_case")]
 pub enum QueryMsg {
     // GetReceiver returns the current receiver as a json-encoded address
+    #[returns(ReceiverResponse)]
     GetReceiver {},
+    // ReceiverStatus returns the receiver with its balances and contract info, if it is one
+    #[returns(ReceiverStatusResponse)]
+    ReceiverStatus {},
+    // ListAllowedSenders pages through the sender allowlist
+    #[returns(AllowedSendersResponse)]
+    ListAllowedSenders {
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+    // ResolveAlias returns the address registered for name
+    #[returns(AliasInfo)]
+    ResolveAlias { name: String },
+    // ListAliases pages through the aliases by name
+    #[returns(AliasesResponse)]
+    ListAliases {
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+    // ListDestinations pages through the routes to special destinations by name
+    #[returns(DestinationsResponse)]
+    ListDestinations {
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+    // ListRoutes pages through the named routes by name
+    #[returns(RoutesResponse)]
+    ListRoutes {
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+    // RouteStats returns the forwarding totals of a route and whether it is paused
+    #[returns(RouteStatsResponse)]
+    RouteStats { name: String },
+    // ListCategories pages through the categories by name, with what was deposited under
+    // each of them
+    #[returns(CategoriesResponse)]
+    ListCategories {
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+    // Referral returns the referrer of code and what was deposited with it
+    #[returns(ReferralInfo)]
+    Referral { code: String },
+    // ListReferrals pages through the referral codes
+    #[returns(ReferralsResponse)]
+    ListReferrals {
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+    // ListBlockedSenders pages through the sender blocklist
+    #[returns(BlockedSendersResponse)]
+    ListBlockedSenders {
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+    // ListAllowedMethods pages through the methods ForwardExec may call
+    #[returns(AllowedMethodsResponse)]
+    ListAllowedMethods {
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+    // GetQuota returns what address can still deposit under the rate limit
+    #[returns(QuotaResponse)]
+    GetQuota { address: String },
+    // EffectiveMinimum returns the minimum deposits once congestion is accounted for
+    #[returns(EffectiveMinimumResponse)]
+    EffectiveMinimum {},
+    // Reconciliation compares what the contract keeps for someone with its bank balance
+    #[returns(ReconciliationResponse)]
+    Reconciliation {},
+    // Rotation returns the active receiver and the ones scheduled to take over
+    #[returns(RotationResponse)]
+    Rotation {},
+    // GetPendingReceiver returns the proposed receiver and when it takes over
+    #[returns(PendingReceiverResponse)]
+    GetPendingReceiver {},
+    // Proposal returns receiver proposal proposal_id and how the vote stands
+    #[returns(ProposalInfo)]
+    Proposal { proposal_id: u64 },
+    // ListProposals pages through the receiver proposals by id
+    #[returns(ProposalsResponse)]
+    ListProposals {
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
+    // AdminProposal returns admin action id and who approved it
+    #[returns(AdminProposalInfo)]
+    AdminProposal { id: u64 },
+    // ListAdminProposals pages through the admin actions by id
+    #[returns(AdminProposalsResponse)]
+    ListAdminProposals {
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
+    // PendingEmergency returns the announced emergency withdrawal, if any
+    #[returns(PendingEmergencyResponse)]
+    PendingEmergency {},
+    // WindDown returns the end of the claim period if the contract is winding down
+    #[returns(WindDownResponse)]
+    WindDown {},
+    // Ownership returns the owner and when ownership expires
+    #[returns(OwnershipResponse)]
+    Ownership {},
+    // GetConfig returns the owner and all settings
+    #[returns(ConfigResponse)]
+    GetConfig {},
+    // Permissions returns what address was granted
+    #[returns(PermissionsResponse)]
+    Permissions { address: String },
+    // GetPaused returns whether deposits are currently paused
+    #[returns(PausedResponse)]
+    GetPaused {},
+    // GetClaimable returns the funds an address can withdraw in pull mode
+    #[returns(ClaimableResponse)]
+    GetClaimable { address: String },
+    // ReceiverHistory pages through the receiver changes, oldest first
+    #[returns(ReceiverHistoryResponse)]
+    ReceiverHistory {
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
+    // TransferHistory pages through recorded transfers, oldest first
+    #[returns(TransferHistoryResponse)]
+    TransferHistory {
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
+    // TransfersByMemo pages through the transfers sent with memo, oldest first
+    #[returns(TransferHistoryResponse)]
+    TransfersByMemo {
+        memo: String,
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
+    // ReceiptsBySender pages through the receipts of the deposits made by sender, oldest
+    // first, with where each stands
+    #[returns(ReceiptsResponse)]
+    ReceiptsBySender {
+        sender: String,
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
+    // TransfersBySender pages through the transfers sent by addr, oldest first
+    #[returns(TransferHistoryResponse)]
+    TransfersBySender {
+        addr: String,
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
+    // TransfersByReceiver pages through the transfers that paid addr, oldest first
+    #[returns(TransferHistoryResponse)]
+    TransfersByReceiver {
+        addr: String,
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
+    // Receipt returns the receipt of transfer id
+    #[returns(SendReceipt)]
+    Receipt { id: u64 },
+    // Escrow returns the open escrow of deposit id
+    #[returns(EscrowInfo)]
+    Escrow { id: u64 },
+    // EscrowsBySender and EscrowsByReceiver page through the open escrows of an address,
+    // oldest first
+    #[returns(EscrowsResponse)]
+    EscrowsBySender {
+        sender: String,
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
+    #[returns(EscrowsResponse)]
+    EscrowsByReceiver {
+        receiver: String,
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
+    #[returns(InvoiceInfo)]
+    Invoice { id: u64 },
+    // InvoicesByPayer pages through the invoices of payer, oldest first. With status only
+    // the invoices in that status are returned
+    #[returns(InvoicesResponse)]
+    InvoicesByPayer {
+        payer: String,
+        status: Option<InvoiceStatus>,
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
+    // GetStream returns the state of the streamed deposits
+    #[returns(StreamResponse)]
+    GetStream {},
+    // Stream returns the schedule of stream id
+    #[returns(StreamInfo)]
+    Stream { id: u64 },
+    // Streams pages through the streams by id
+    #[returns(StreamsResponse)]
+    Streams {
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
+    // Subscription returns subscription id
+    #[returns(SubscriptionInfo)]
+    Subscription { id: u64 },
+    // UpcomingCharges lists the next installment of the subscriptions, earliest first
+    #[returns(UpcomingChargesResponse)]
+    UpcomingCharges { limit: Option<u32> },
+    // PendingForwards pages through the deposits waiting for approval, oldest first
+    #[returns(PendingForwardsResponse)]
+    PendingForwards {
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
+    // Contributions returns everything sender deposited so far
+    #[returns(ContributionsResponse)]
+    Contributions { sender: String },
+    // TopContributors lists the senders that deposited the most of denom, largest first
+    #[returns(TopContributorsResponse)]
+    TopContributors { denom: String, limit: Option<u32> },
+    // Allowance returns the lifetime cap of sender and what is left of it
+    #[returns(AllowanceResponse)]
+    Allowance { sender: String },
+    // Stats returns the forwarding totals
+    #[returns(StatsResponse)]
+    Stats {},
+    // StatsAt returns the forwarding totals as they were at the end of block height
+    #[returns(StatsResponse)]
+    StatsAt { height: u64 },
+    // Waterfall returns the waterfall tiers and how far they are filled
+    #[returns(WaterfallResponse)]
+    Waterfall {},
+    // Campaign returns the campaign, what it raised so far and its status
+    #[returns(CampaignResponse)]
+    Campaign {},
+    // CampaignDeposits pages through what each sender deposited during the campaign
+    #[returns(CampaignDepositsResponse)]
+    CampaignDeposits {
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+    // SimulateSend previews a TokenSend of amount by sender without changing anything
+    #[returns(SimulateSendResponse)]
+    SimulateSend { amount: Vec<Coin>, sender: String },
+    // PreviewSplit returns what every receiver gets of amount after the fee, and what the
+    // rounding leaves over
+    #[returns(SplitPreviewResponse)]
+    PreviewSplit { amount: Coin },
+    // Dust returns the dust collected so far
+    #[returns(DustResponse)]
+    Dust {},
+    // RebatePool returns what is left to pay claim rebates with
+    #[returns(RebatePoolResponse)]
+    RebatePool {},
+    // Refunds pages through what failed payouts owe each sender, by sender
+    #[returns(RefundsResponse)]
+    Refunds {
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+    // FailedPayouts pages through the payouts held for a retry, oldest first
+    #[returns(FailedPayoutsResponse)]
+    FailedPayouts {
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
+    // PayoutQueue pages through the payouts queued for Distribute, oldest first
+    #[returns(PayoutQueueResponse)]
+    PayoutQueue {
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
+    // PermitNonce returns the nonce the next permit has to use
+    #[returns(PermitNonceResponse)]
+    PermitNonce {},
 }
 
 // We define a custom struct for each query response
@@ -25,3 +1236,657 @@ pub enum QueryMsg {
 pub struct ReceiverResponse {
     pub receiver: String,
 }
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ReceiverStatusResponse {
+    pub receiver: String,
+    pub balances: Vec<Coin>,
+    // None when the receiver is a plain account
+    pub contract: Option<ReceiverContractInfo>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ReceiverContractInfo {
+    pub code_id: u64,
+    pub creator: String,
+    pub admin: Option<String>,
+}
+
+// RotationEntry is a receiver taking over at height active_from
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct RotationEntry {
+    pub receiver: String,
+    pub active_from: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct RotationResponse {
+    pub active: String,
+    pub upcoming: Vec<RotationEntry>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PendingReceiverResponse {
+    pub receiver: Option<String>,
+    pub ready_at: Option<Expiration>,
+    pub awaiting_acceptance: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ProposalStatus {
+    Open,
+    Passed,
+    Rejected,
+    Executed,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ProposalInfo {
+    pub id: u64,
+    pub receiver: Option<String>,
+    pub governance: Option<GovernanceChange>,
+    pub proposer: String,
+    pub yes: u64,
+    pub no: u64,
+    pub ends: Expiration,
+    pub status: ProposalStatus,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ProposalsResponse {
+    pub proposals: Vec<ProposalInfo>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct AdminProposalInfo {
+    pub id: u64,
+    pub action: AdminAction,
+    pub proposer: String,
+    pub approvals: Vec<String>,
+    pub executed: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct AdminProposalsResponse {
+    pub proposals: Vec<AdminProposalInfo>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PendingEmergencyResponse {
+    pub recipient: Option<String>,
+    pub execute_after: Option<u64>,
+}
+
+// OwnershipResponse has expired set once the owner is gone for good because
+// ownership_expires_at passed
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct OwnershipResponse {
+    pub owner: Option<String>,
+    pub expires_at: Option<Expiration>,
+    pub expired: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct WindDownResponse {
+    pub claim_deadline: Option<u64>,
+    pub terminated: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ConfigResponse {
+    // None once ownership was renounced
+    pub owner: Option<String>,
+    pub receiver: String,
+    pub receivers: Vec<(String, Decimal)>,
+    pub fee_rate: Decimal,
+    pub fee_collector: Option<String>,
+    pub fee_cap: Vec<Coin>,
+    pub paused: bool,
+    pub pull: bool,
+    pub min_amount: Vec<Coin>,
+    pub accepted_denoms: Vec<String>,
+    pub funds_policy: FundsPolicy,
+    pub allowlist: Vec<String>,
+    pub hold: bool,
+    pub flush_threshold: Vec<Coin>,
+    pub receiver_delay: Option<Duration>,
+    pub restrict_senders: bool,
+    pub rate_limit: Option<RateLimit>,
+    pub escrow: Option<Duration>,
+    pub stream_duration: Option<u64>,
+    pub approval_threshold: Vec<Coin>,
+    pub callbacks: Vec<(String, Binary)>,
+    pub validator: Option<String>,
+    pub swap: Option<SwapSettings>,
+    pub hold_failed: bool,
+    pub fallback_receiver: Option<String>,
+    pub require_acceptance: bool,
+    pub recovery_address: Option<String>,
+    pub dust_threshold: Vec<Coin>,
+    pub max_amount: Vec<Coin>,
+    pub waterfall: Vec<(String, Vec<Coin>)>,
+    pub governance: Option<GovernanceSettings>,
+    pub multisig: Option<MultisigSettings>,
+    pub campaign: Option<Campaign>,
+    pub receipt_nft: Option<ReceiptNftSettings>,
+    pub mint_receipts: bool,
+    pub referral_rate: Decimal,
+    pub usd_floor: Option<UsdFloorSettings>,
+    pub max_payouts: Option<u32>,
+    pub claim_rebate: Option<Coin>,
+    pub retention: Option<Retention>,
+    pub ownership_expires_at: Option<Expiration>,
+    pub rounding: Rounding,
+    pub refund_failed: bool,
+    pub anchor: Option<AnchorSettings>,
+    pub claim_expiry: Option<u64>,
+    pub denom_rules: Vec<DenomRuleSettings>,
+    pub attestation: Option<AttestationSettings>,
+    pub owner_pubkey: Option<Binary>,
+    pub congestion: Option<Congestion>,
+    pub ibc: Option<IbcForwarding>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PermissionsResponse {
+    pub address: String,
+    pub permissions: Vec<Permission>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PausedResponse {
+    pub paused: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ClaimableResponse {
+    pub claimable: Vec<Coin>,
+    // expires_at is when the balance can be redirected, if claim_expiry is set
+    pub expires_at: Option<u64>,
+    // unbonding are undelegations that are added to claimable once they completed
+    pub unbonding: Vec<Unbonding>,
+}
+
+// SendReceipt is set as data of every deposit so calling contracts can read the result,
+// and again once a held deposit is released, approved, rejected or refunded. id stays the
+// same throughout. amount is what was deposited, recipients are who it was forwarded to
+// and sender who the deposit is recorded for
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct SendReceipt {
+    pub id: u64,
+    pub sender: String,
+    pub recipients: Vec<String>,
+    pub amount: Vec<Coin>,
+    pub height: u64,
+    // minted is the aTerra the deposit was turned into in anchor mode, it is known once the
+    // money market replied
+    pub minted: Option<Coin>,
+    pub category: Option<String>,
+    // items is where each denom of a forwarded deposit went
+    pub items: Vec<DenomRouting>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ReceiptInfo {
+    pub id: u64,
+    pub amount: Vec<Coin>,
+    pub status: ReceiptStatus,
+    pub height: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ReceiptsResponse {
+    pub receipts: Vec<ReceiptInfo>,
+}
+
+// WorkDone is what a keeper call did
+#[derive(Serialize, Deserialize, Clone, Default, Debug, PartialEq, JsonSchema)]
+pub struct WorkDone {
+    pub subscriptions_charged: u32,
+    pub payouts_sent: u32,
+    pub flushed: Vec<Coin>,
+}
+
+// ReceiverChangeInfo is a receiver change, time is in seconds
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ReceiverChangeInfo {
+    pub id: u64,
+    pub old_receiver: String,
+    pub new_receiver: String,
+    pub actor: String,
+    pub height: u64,
+    pub time: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ReceiverHistoryResponse {
+    pub changes: Vec<ReceiverChangeInfo>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct TransferInfo {
+    pub id: u64,
+    pub sender: String,
+    pub amount: Vec<Coin>,
+    pub recipients: Vec<String>,
+    pub height: u64,
+    pub memo: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct TransferHistoryResponse {
+    pub transfers: Vec<TransferInfo>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct AllowedSendersResponse {
+    pub senders: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct BlockedSendersResponse {
+    pub senders: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct AllowedMethodsResponse {
+    pub methods: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct AliasInfo {
+    pub name: String,
+    pub address: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct AliasesResponse {
+    pub aliases: Vec<AliasInfo>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct DestinationInfo {
+    pub name: String,
+    pub destination: Destination,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct DestinationsResponse {
+    pub destinations: Vec<DestinationInfo>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct RouteInfo {
+    pub name: String,
+    pub route: RouteSettings,
+    pub paused: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct RoutesResponse {
+    pub routes: Vec<RouteInfo>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct CategoryInfo {
+    pub name: String,
+    pub receiver: Option<String>,
+    pub total: Vec<Coin>,
+    pub deposits: u64,
+    pub last_deposit_height: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct CategoriesResponse {
+    pub categories: Vec<CategoryInfo>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ReferralInfo {
+    pub code: String,
+    pub referrer: String,
+    pub deposits: u64,
+    pub volume: Vec<Coin>,
+    pub commission: Vec<Coin>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ReferralsResponse {
+    pub referrals: Vec<ReferralInfo>,
+}
+
+// QuotaResponse holds the deposits left in the current windows, the full limits apply
+// again from sender_resets_at and daily_resets_at on (in seconds)
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct QuotaResponse {
+    pub sender_remaining: Vec<Coin>,
+    pub sender_resets_at: Option<u64>,
+    pub daily_remaining: Vec<Coin>,
+    pub daily_resets_at: Option<u64>,
+}
+
+// EffectiveMinimumResponse is the minimum deposits in force, the configured ones times
+// multiplier, and how many deposits were forwarded in the congestion window that ends at
+// resets_at (in seconds)
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct EffectiveMinimumResponse {
+    pub min_amount: Vec<Coin>,
+    pub multiplier: Decimal,
+    pub forwards: u64,
+    pub resets_at: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct EscrowInfo {
+    pub id: u64,
+    pub sender: String,
+    pub receiver: String,
+    pub amount: Vec<Coin>,
+    pub expires: Expiration,
+    pub hashlock: Option<String>,
+    pub arbiter: Option<String>,
+    pub disputed: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct EscrowsResponse {
+    pub escrows: Vec<EscrowInfo>,
+}
+
+// InvoiceStatus is Open or PartiallyPaid until the invoice is paid in full or expires
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum InvoiceStatus {
+    Open,
+    PartiallyPaid,
+    Paid,
+    Expired,
+}
+
+// InvoiceInfo is an invoice, receipt_id is the deposit that paid it
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct InvoiceInfo {
+    pub id: u64,
+    pub payer: String,
+    pub amount: Coin,
+    pub expires: Expiration,
+    pub status: InvoiceStatus,
+    pub receipt_id: Option<u64>,
+    pub received: Uint128,
+    pub remaining: Uint128,
+    pub overpayment: Overpayment,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct InvoicesResponse {
+    pub invoices: Vec<InvoiceInfo>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct SubscriptionInfo {
+    pub id: u64,
+    pub sender: String,
+    pub amount: Coin,
+    pub interval: u64,
+    pub next_charge: u64,
+    pub balance: Uint128,
+    pub token: Option<String>,
+    pub delinquent: bool,
+}
+
+// Charge is an upcoming installment of a subscription, due is in seconds
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Charge {
+    pub subscription_id: u64,
+    pub sender: String,
+    pub amount: Coin,
+    pub due: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct UpcomingChargesResponse {
+    pub charges: Vec<Charge>,
+}
+
+// StreamResponse sums up the streams: vested includes what was claimed already, outstanding
+// is what still has to vest until ends_at (in seconds)
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct StreamResponse {
+    pub total: Vec<Coin>,
+    pub vested: Vec<Coin>,
+    pub claimed: Vec<Coin>,
+    pub outstanding: Vec<Coin>,
+    pub ends_at: Option<u64>,
+}
+
+// StreamInfo is the schedule of a stream, next_unlock is when more of it vests next
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct StreamInfo {
+    pub id: u64,
+    pub sender: String,
+    pub amount: Vec<Coin>,
+    pub vested: Vec<Coin>,
+    pub claimed: Vec<Coin>,
+    pub start: u64,
+    pub cliff: u64,
+    pub end: u64,
+    pub revocable: bool,
+    pub next_unlock: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct StreamsResponse {
+    pub streams: Vec<StreamInfo>,
+}
+
+// PendingForwardInfo is a deposit waiting for approval, recipient is set for deposits sent
+// to a one-off recipient
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PendingForwardInfo {
+    pub id: u64,
+    pub sender: String,
+    pub recipient: Option<String>,
+    pub amount: Vec<Coin>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PendingForwardsResponse {
+    pub pending: Vec<PendingForwardInfo>,
+}
+
+// FailedPayoutInfo is a payout held for a retry, msg is sent again by RetryFailed
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct FailedPayoutInfo {
+    pub id: u64,
+    pub recipient: String,
+    pub msg: CosmosMsg,
+    pub error: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct FailedPayoutsResponse {
+    pub payouts: Vec<FailedPayoutInfo>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct RefundInfo {
+    pub sender: String,
+    pub amount: Vec<Coin>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct RefundsResponse {
+    pub refunds: Vec<RefundInfo>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct QueuedPayoutInfo {
+    pub id: u64,
+    pub receiver: String,
+    pub amount: Vec<Coin>,
+}
+
+// PayoutQueueResponse lists queued payouts, cursor is the id of the last one paid
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PayoutQueueResponse {
+    pub cursor: u64,
+    pub payouts: Vec<QueuedPayoutInfo>,
+}
+
+// StatsResponse sums up every forward: forwarded is the total per denom before fees, cw20
+// tokens use their contract address as denom
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct StatsResponse {
+    pub forwarded: Vec<Coin>,
+    pub forwards: u64,
+    pub last_forward_height: Option<u64>,
+}
+
+// RouteStatsResponse sums up the deposits sent through a route, as they came in
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct RouteStatsResponse {
+    pub name: String,
+    pub paused: bool,
+    pub forwarded: Vec<Coin>,
+    pub forwards: u64,
+    pub last_forward_height: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct WaterfallTier {
+    pub receiver: String,
+    pub cap: Vec<Coin>,
+    pub filled: Vec<Coin>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct WaterfallResponse {
+    pub tiers: Vec<WaterfallTier>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum CampaignStatus {
+    Upcoming,
+    Open,
+    Succeeded,
+    Failed,
+    Claimed,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct CampaignResponse {
+    pub campaign: Option<Campaign>,
+    pub raised: Vec<Coin>,
+    pub status: Option<CampaignStatus>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct CampaignDeposit {
+    pub sender: String,
+    pub amount: Vec<Coin>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct CampaignDepositsResponse {
+    pub deposits: Vec<CampaignDeposit>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct SplitPreviewResponse {
+    pub shares: Vec<Share>,
+    pub left_over: Uint128,
+}
+
+// SimulateSendResponse is what a TokenSend would do. A rejected deposit only has the error
+// it fails with. Shares are what every receiver gets once fee and tax are paid and net is
+// their total. A campaign, pending, escrow or stream deposit pays out nothing yet
+#[derive(Serialize, Deserialize, Clone, Default, Debug, PartialEq, JsonSchema)]
+pub struct SimulateSendResponse {
+    pub accepted: bool,
+    pub error: Option<String>,
+    pub action: String,
+    pub usd_value: Option<Uint128>,
+    pub fee: Vec<Coin>,
+    pub tax: Vec<Coin>,
+    pub net: Vec<Coin>,
+    pub shares: Vec<Share>,
+    pub dust: Vec<Coin>,
+    pub held: Vec<Coin>,
+    pub swapped: Vec<Coin>,
+    pub delegated: Vec<Coin>,
+    pub refund: Vec<Coin>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Share {
+    pub receiver: String,
+    pub amount: Vec<Coin>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct DustResponse {
+    pub dust: Vec<Coin>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct RebatePoolResponse {
+    pub balance: Vec<Coin>,
+    pub claim_rebate: Option<Coin>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ContributionsResponse {
+    pub sender: String,
+    pub contributions: Vec<Coin>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Contributor {
+    pub sender: String,
+    pub amount: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct TopContributorsResponse {
+    pub contributors: Vec<Contributor>,
+}
+
+// AllowanceResponse is empty for senders without a lifetime cap
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct AllowanceResponse {
+    pub sender: String,
+    pub cap: Vec<Coin>,
+    pub remaining: Vec<Coin>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PermitNonceResponse {
+    pub nonce: u64,
+}
+
+// ReconciliationResponse is the native funds the contract keeps for someone by ledger and,
+// by denom, how they compare with its bank balance. surplus is what it holds beyond its
+// liabilities, e.g. funds sent to it directly, deficit is what it lacks to cover them.
+// Undelegations still unbonding are not in the balance yet and count towards the deficit
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ReconciliationResponse {
+    pub ledgers: Vec<LedgerInfo>,
+    pub denoms: Vec<DenomReconciliation>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct LedgerInfo {
+    pub name: String,
+    pub total: Vec<Coin>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct DenomReconciliation {
+    pub denom: String,
+    pub balance: Uint128,
+    pub liabilities: Uint128,
+    pub surplus: Uint128,
+    pub deficit: Uint128,
+}
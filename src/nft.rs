@@ -0,0 +1,64 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use cosmwasm_std::{to_json_binary, Addr, Coin, StdResult, WasmMsg};
+
+// The mint message of cw721-base style contracts with on-chain metadata. Only what is needed
+// to mint a receipt is defined here.
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Cw721ExecuteMsg {
+    // Mint creates token_id for owner, only the minter of the contract can call it
+    Mint {
+        token_id: String,
+        owner: String,
+        token_uri: Option<String>,
+        extension: Metadata,
+    },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Metadata {
+    pub attributes: Vec<Trait>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Trait {
+    pub trait_type: String,
+    pub value: String,
+}
+
+// mint_msg mints receipt token_id to owner with the amount and denom of every deposited coin
+pub fn mint_msg(
+    contract: &Addr,
+    token_id: String,
+    owner: &Addr,
+    amount: &[Coin],
+) -> StdResult<WasmMsg> {
+    let attributes = amount
+        .iter()
+        .flat_map(|coin| {
+            vec![
+                Trait {
+                    trait_type: "amount".to_string(),
+                    value: coin.amount.to_string(),
+                },
+                Trait {
+                    trait_type: "denom".to_string(),
+                    value: coin.denom.clone(),
+                },
+            ]
+        })
+        .collect();
+    Ok(WasmMsg::Execute {
+        contract_addr: contract.to_string(),
+        msg: to_json_binary(&Cw721ExecuteMsg::Mint {
+            token_id,
+            owner: owner.to_string(),
+            token_uri: None,
+            extension: Metadata { attributes },
+        })?,
+        funds: vec![],
+    })
+}
@@ -0,0 +1,41 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use cosmwasm_std::{Addr, Coin, Decimal, QuerierWrapper, StdResult, Uint128};
+
+// The price query of oracle contracts quoting denoms in uusd. Only what is needed to value a
+// deposit is defined here.
+
+// USD is what deposits are valued in, it needs no price
+pub const USD_DENOM: &str = "uusd";
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum OracleQueryMsg {
+    // Price returns what one unit of denom is worth in uusd
+    Price { denom: String },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PriceResponse {
+    pub price: Decimal,
+}
+
+// usd_value adds up what funds are worth in uusd at the prices of oracle
+pub fn usd_value(querier: &QuerierWrapper, oracle: &Addr, funds: &[Coin]) -> StdResult<Uint128> {
+    let mut value = Uint128::zero();
+    for coin in funds {
+        if coin.denom == USD_DENOM {
+            value += coin.amount;
+            continue;
+        }
+        let res: PriceResponse = querier.query_wasm_smart(
+            oracle,
+            &OracleQueryMsg::Price {
+                denom: coin.denom.clone(),
+            },
+        )?;
+        value += coin.amount * res.price;
+    }
+    Ok(value)
+}
@@ -0,0 +1,68 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
+
+use cosmwasm_std::{Decimal, StdResult, Uint128, Uint256};
+
+// The split math: an amount is shared out by weight, every share is rounded down and the
+// rounding policy decides who gets the units left over. Nothing here touches storage.
+
+// Rounding picks who gets the units left over once every share is rounded down
+#[derive(Serialize, Deserialize, Clone, Copy, Default, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Rounding {
+    // the first receiver gets all of them
+    #[default]
+    FirstReceiver,
+    // one each to the receivers whose shares lost the most to rounding, the earlier
+    // receiver on a tie
+    LargestRemainder,
+    // nobody, they are kept as dust
+    Floor,
+}
+
+// split_amount shares amount out by weights, which add up to one. It returns the shares in
+// the order of weights and what is left over, only ever non-zero with Rounding::Floor.
+// Together they always add up to amount
+pub fn split_amount(
+    amount: Uint128,
+    weights: &[Decimal],
+    rounding: Rounding,
+) -> StdResult<(Vec<Uint128>, Uint128)> {
+    let fractional = Uint256::from(Decimal::one().atomics());
+    let mut shares = Vec::with_capacity(weights.len());
+    let mut remainders = Vec::with_capacity(weights.len());
+    for weight in weights {
+        let exact = amount.full_mul(weight.atomics());
+        shares.push(Uint128::try_from(exact / fractional)?);
+        remainders.push(exact % fractional);
+    }
+    let assigned = shares
+        .iter()
+        .try_fold(Uint128::zero(), |total, share| total.checked_add(*share))?;
+    let mut left = amount.checked_sub(assigned)?;
+    if shares.is_empty() {
+        return Ok((shares, left));
+    }
+
+    match rounding {
+        Rounding::FirstReceiver => {
+            shares[0] += left;
+            left = Uint128::zero();
+        }
+        Rounding::LargestRemainder => {
+            // less than one unit per receiver is left, as the weights add up to one
+            let mut order: Vec<usize> = (0..shares.len()).collect();
+            order.sort_by(|a, b| remainders[*b].cmp(&remainders[*a]));
+            for i in order.into_iter().cycle() {
+                if left.is_zero() {
+                    break;
+                }
+                shares[i] += Uint128::one();
+                left -= Uint128::one();
+            }
+        }
+        Rounding::Floor => {}
+    }
+    Ok((shares, left))
+}
@@ -1,21 +1,1051 @@
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-use cosmwasm_std::{CanonicalAddr, Storage};
-use cosmwasm_storage::{singleton, singleton_read, ReadonlySingleton, Singleton};
+use cosmwasm_std::{
+    Addr, Binary, BlockInfo, CanonicalAddr, Coin, CosmosMsg, Decimal, Empty, Storage, Uint128,
+};
+use cw_storage_plus::{
+    Index, IndexList, IndexedMap, Item, Map, MultiIndex, SnapshotItem, Strategy,
+};
 
-pub static CONFIG_KEY: &[u8] = b"config";
+use crate::msg::AdminAction;
+use crate::split::Rounding;
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
-pub struct State {
+pub struct Config {
+    pub receiver: Addr,
+    // None once ownership was renounced
+    pub owner: Option<Addr>,
+    // weighted split of incoming funds, when empty everything goes to receiver
+    pub receivers: Vec<(Addr, Decimal)>,
+    // share of every forward that goes to fee_collector, limited per denom by fee_cap
+    pub fee_rate: Decimal,
+    pub fee_collector: Option<Addr>,
+    pub fee_cap: Vec<Coin>,
+    // while paused no deposits are accepted
+    pub paused: bool,
+    // in pull mode native deposits are kept and receivers withdraw their share themselves
+    #[serde(default)]
+    pub pull: bool,
+    // deposits below the minimum configured for their denom are rejected
+    #[serde(default)]
+    pub min_amount: Vec<Coin>,
+    // native denoms a deposit must include, when empty every denom is accepted. What happens
+    // to the other attached denoms is up to funds_policy
+    #[serde(default)]
+    pub accepted_denoms: Vec<String>,
+    #[serde(default)]
+    pub funds_policy: FundsPolicy,
+    // besides the owner these addresses may send a deposit to a recipient of their choice
+    #[serde(default)]
+    pub allowlist: Vec<Addr>,
+    // in hold mode native deposits are kept until their denom reaches flush_threshold
+    #[serde(default)]
+    pub hold: bool,
+    #[serde(default)]
+    pub flush_threshold: Vec<Coin>,
+    // a new receiver only takes over once receiver_delay has passed
+    #[serde(default)]
+    pub receiver_delay: Option<Duration>,
+    // when set only allowed senders may deposit
+    #[serde(default)]
+    pub restrict_senders: bool,
+    #[serde(default)]
+    pub rate_limit: Option<RateLimit>,
+    // in escrow mode native deposits are kept per deposit until the receiver releases them,
+    // the sender can refund a deposit once escrow has passed
+    #[serde(default)]
+    pub escrow: Option<Duration>,
+    // in stream mode native deposits vest to the receivers linearly over stream_duration
+    // seconds and are paid out by Claim
+    #[serde(default)]
+    pub stream_duration: Option<u64>,
+    // deposits with a coin at or above approval_threshold of its denom wait for the owner to
+    // approve or reject them
+    #[serde(default)]
+    pub approval_threshold: Vec<Coin>,
+    // receivers with a callback are paid by executing it with the funds attached, cw20
+    // tokens are sent along with it
+    #[serde(default)]
+    pub callbacks: Vec<(Addr, Binary)>,
+    // in staking mode deposits of the bonded denom are delegated to validator, the rewards
+    // are withdrawn to the receiver
+    #[serde(default)]
+    pub validator: Option<String>,
+    // swap sells deposits of the denoms it has a pair for and forwards the proceeds
+    #[serde(default)]
+    pub swap: Option<SwapConfig>,
+    // with hold_failed a payout that fails is held for a retry instead of reverting the
+    // deposit
+    #[serde(default)]
+    pub hold_failed: bool,
+    // payouts that fail are sent to fallback_receiver instead
+    #[serde(default)]
+    pub fallback_receiver: Option<Addr>,
+    // a proposed receiver has to accept before it takes over
+    #[serde(default)]
+    pub require_acceptance: bool,
+    // EmergencyWithdraw moves the funds to recovery_address
+    #[serde(default)]
+    pub recovery_address: Option<Addr>,
+    // fees and shares below dust_threshold are collected as dust instead of being sent
+    #[serde(default)]
+    pub dust_threshold: Vec<Coin>,
+    // deposits above the maximum configured for their denom are rejected
+    #[serde(default)]
+    pub max_amount: Vec<Coin>,
+    // receivers paid first, in order, until they got their cap of a denom
+    #[serde(default)]
+    pub waterfall: Vec<(Addr, Vec<Coin>)>,
+    // voters deciding on receiver changes instead of the owner
+    #[serde(default)]
+    pub governance: Option<GovernanceConfig>,
+    // admins approving sensitive actions instead of the owner alone
+    #[serde(default)]
+    pub multisig: Option<MultisigConfig>,
+    // deposits are only taken between the campaign start and end
+    #[serde(default)]
+    pub campaign: Option<Campaign>,
+    // cw721 contract minting receipts for large deposits, while mint_receipts is on
+    #[serde(default)]
+    pub receipt_nft: Option<ReceiptNft>,
+    #[serde(default)]
+    pub mint_receipts: bool,
+    // share of a deposit naming a referral code in its memo that goes to the referrer
+    #[serde(default)]
+    pub referral_rate: Decimal,
+    // deposits worth less than this in uusd are rejected
+    #[serde(default)]
+    pub usd_floor: Option<UsdFloor>,
+    // a forward paying more receivers than this queues the payouts for Distribute
+    #[serde(default)]
+    pub max_payouts: Option<u32>,
+    // every withdrawal gets up to this from the rebate pool
+    #[serde(default)]
+    pub claim_rebate: Option<Coin>,
+    // older transfers are pruned from the history as new ones are recorded
+    #[serde(default)]
+    pub retention: Option<Retention>,
+    // the owner is removed once this passed, just like renouncing ownership
+    #[serde(default)]
+    pub ownership_expires_at: Option<Expiration>,
+    // who gets what is left once the receivers' shares are rounded down
+    #[serde(default)]
+    pub rounding: Rounding,
+    // with refund_failed a bank payout that fails is owed back to the sender of the deposit
+    #[serde(default)]
+    pub refund_failed: bool,
+    // anchor deposits what is forwarded of its denom into the money market, the receivers
+    // get the minted aTerra
+    #[serde(default)]
+    pub anchor: Option<AnchorConfig>,
+    // claim_expiry is how many seconds a pull mode balance may go unclaimed before anyone can
+    // redirect it to the fallback receiver
+    #[serde(default)]
+    pub claim_expiry: Option<u64>,
+    // denom_rules override the fee rate or the receivers of single denoms
+    #[serde(default)]
+    pub denom_rules: Vec<DenomRule>,
+    // attestation is the registry a new receiver must hold a credential of
+    #[serde(default)]
+    pub attestation: Option<Attestation>,
+    // owner_pubkey is the secp256k1 key the owner signs permits with
+    #[serde(default)]
+    pub owner_pubkey: Option<Binary>,
+    // congestion raises the minimum deposits while many deposits are forwarded
+    #[serde(default)]
+    pub congestion: Option<Congestion>,
+    // in IBC mode native funds paid to the receiver are transferred to a receiver on another
+    // chain instead
+    #[serde(default)]
+    pub ibc: Option<IbcForwarding>,
+}
+
+// DenomRule charges fee_rate instead of the contract's fee rate on its denom, and pays
+// what is left of it to receiver instead of splitting it
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct DenomRule {
+    pub denom: String,
+    pub fee_rate: Option<Decimal>,
+    pub receiver: Option<Addr>,
+}
+
+// DenomRouting is where the part of a deposit in denom went: fee to the fee collector and
+// payouts to the receivers
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct DenomRouting {
+    pub denom: String,
+    pub amount: Uint128,
+    pub fee: Uint128,
+    pub payouts: Vec<(Addr, Uint128)>,
+}
+
+// SwapConfig lists the pair contract selling each denom for target_denom. A swap fails if
+// the price is more than max_slippage worse than the pool price
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct SwapConfig {
+    pub target_denom: String,
+    pub pairs: Vec<(String, Addr)>,
+    pub max_slippage: Decimal,
+}
+
+// AnchorConfig is the Anchor money market deposits of denom go to and its aTerra token
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct AnchorConfig {
+    pub money_market: Addr,
+    pub aterra: Addr,
+    pub denom: String,
+}
+
+// Attestation is the registry contract and the credential it must attest receivers hold
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Attestation {
+    pub registry: Addr,
+    pub credential: String,
+}
+
+impl Config {
+    pub fn is_owner(&self, addr: &Addr) -> bool {
+        self.owner.as_ref() == Some(addr)
+    }
+}
+
+// MultisigConfig lists the admins, see MultisigSettings
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct MultisigConfig {
+    pub admins: Vec<Addr>,
+    pub threshold: u64,
+}
+
+// AdminProposal is an admin action waiting for approvals, executed once it ran
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct AdminProposal {
+    pub action: AdminAction,
+    pub proposer: Addr,
+    pub approvals: Vec<Addr>,
+    pub executed: bool,
+}
+
+// GovernanceConfig lists the voters and their weight, see GovernanceSettings
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct GovernanceConfig {
+    pub voters: Vec<(Addr, u64)>,
+    pub quorum: Decimal,
+    pub voting_period: Duration,
+}
+
+impl GovernanceConfig {
+    pub fn weight(&self, voter: &Addr) -> Option<u64> {
+        self.voters
+            .iter()
+            .find(|(addr, _)| addr == voter)
+            .map(|(_, weight)| *weight)
+    }
+
+    pub fn total_weight(&self) -> u64 {
+        self.voters.iter().map(|(_, weight)| weight).sum()
+    }
+}
+
+// GovernanceChange holds the governance a proposal installs, None turns voting off
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct GovernanceChange {
+    pub governance: Option<GovernanceConfig>,
+}
+
+// ReceiptNft is the cw721 contract minting a receipt to the sender of a deposit with a coin
+// at or above the threshold of its denom. The contract has to be its minter
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ReceiptNft {
+    pub contract: Addr,
+    pub threshold: Vec<Coin>,
+}
+
+// UsdFloor is the minimum a deposit has to be worth in uusd, valued at the prices of oracle
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct UsdFloor {
+    pub oracle: Addr,
+    pub min_value: Uint128,
+}
+
+// FundsPolicy decides what happens to attached denoms that are not accepted
+#[derive(Serialize, Deserialize, Clone, Copy, Default, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum FundsPolicy {
+    // forward every attached denom
+    ForwardAll,
+    // forward the accepted denoms and refund the rest to the sender
+    #[default]
+    ForwardAccepted,
+    // reject deposits that include a denom that is not accepted
+    RejectMixed,
+}
+
+// Retention decides how much transfer history is kept: the last count transfers, or those
+// recorded in the last blocks blocks
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Retention {
+    MaxEntries { count: u64 },
+    MaxAge { blocks: u64 },
+}
+
+// RateLimit caps what a sender can deposit within window seconds, counting from their
+// first deposit in the window, and what all senders together can deposit per day. Denoms
+// without a limit are not limited, cw20 tokens use their contract address as denom.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct RateLimit {
+    pub per_sender: Vec<Coin>,
+    pub window: u64,
+    pub daily_cap: Vec<Coin>,
+}
+
+// Campaign takes deposits from start until end and keeps them. If the deposits of the goal
+// denom reached the goal they go to the receivers, otherwise every sender can take theirs back
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Campaign {
+    pub start: Expiration,
+    pub end: Expiration,
+    pub goal: Coin,
+}
+
+impl Campaign {
+    pub fn is_open(&self, block: &BlockInfo) -> bool {
+        self.start.is_expired(block) && !self.end.is_expired(block)
+    }
+}
+
+// CampaignProgress is what the campaign raised and whether it was paid out
+#[derive(Serialize, Deserialize, Clone, Default, Debug, PartialEq, JsonSchema)]
+pub struct CampaignProgress {
+    pub raised: Vec<Coin>,
+    pub claimed: bool,
+}
+
+// Congestion raises the minimum deposits while the contract is busy. Once the forwards of a
+// tier were forwarded within window seconds, the minimums are multiplied by its multiplier.
+// Tiers are ordered by forwards, the highest one reached applies
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Congestion {
+    pub window: u64,
+    pub tiers: Vec<(u64, Decimal)>,
+}
+
+impl Congestion {
+    pub fn multiplier(&self, forwards: u64) -> Decimal {
+        self.tiers
+            .iter()
+            .rev()
+            .find(|(at, _)| forwards >= *at)
+            .map(|(_, multiplier)| *multiplier)
+            .unwrap_or_else(Decimal::one)
+    }
+}
+
+// IbcForwarding transfers what the receiver is paid over channel to remote_receiver, an
+// address on the chain at the other end. A transfer times out after timeout seconds
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct IbcForwarding {
+    pub channel: String,
+    pub remote_receiver: String,
+    pub timeout: u64,
+}
+
+// IbcTransfer is a transfer to the remote receiver waiting for its ack. sender made the
+// deposit it pays, if it pays a single deposit. sequence is set once the packet was sent
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct IbcTransfer {
+    pub sender: Option<Addr>,
+    pub amount: Coin,
+    pub channel: String,
+    pub sequence: Option<u64>,
+}
+
+// Traffic is how many deposits were forwarded since window_start
+#[derive(Serialize, Deserialize, Clone, Default, Debug, PartialEq, JsonSchema)]
+pub struct Traffic {
+    pub window_start: u64,
+    pub forwards: u64,
+}
+
+// Usage is what was deposited since window_start
+#[derive(Serialize, Deserialize, Clone, Default, Debug, PartialEq, JsonSchema)]
+pub struct Usage {
+    pub window_start: u64,
+    pub used: Vec<Coin>,
+}
+
+// Duration is a number of blocks or seconds
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Duration {
+    Height(u64),
+    Time(u64),
+}
+
+impl Duration {
+    // after returns when the duration has passed, counting from block
+    pub fn after(&self, block: &BlockInfo) -> Expiration {
+        match self {
+            Duration::Height(h) => Expiration::AtHeight(block.height + h),
+            Duration::Time(t) => Expiration::AtTime(block.time.seconds() + t),
+        }
+    }
+}
+
+// Expiration is a block height or time in seconds
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Expiration {
+    AtHeight(u64),
+    AtTime(u64),
+}
+
+impl Expiration {
+    pub fn is_expired(&self, block: &BlockInfo) -> bool {
+        match self {
+            Expiration::AtHeight(height) => block.height >= *height,
+            Expiration::AtTime(time) => block.time.seconds() >= *time,
+        }
+    }
+}
+
+// Stats counts what was forwarded per denom (cw20 tokens by their contract address), how
+// many forwards there were and the height of the last one
+#[derive(Serialize, Deserialize, Clone, Default, Debug, PartialEq, JsonSchema)]
+pub struct Stats {
+    pub forwarded: Vec<Coin>,
+    pub forwards: u64,
+    pub last_height: Option<u64>,
+}
+
+// PendingReceiver is a proposed receiver that replaces the current one at ready_at, once
+// it accepted if awaiting_acceptance is set
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PendingReceiver {
+    pub receiver: Addr,
+    pub ready_at: Expiration,
+    #[serde(default)]
+    pub awaiting_acceptance: bool,
+    // who proposed the receiver, recorded in the receiver history once it takes over
+    #[serde(default)]
+    pub proposer: Option<Addr>,
+}
+
+// ReceiverChange records receiver taking over from old, at height and time in seconds.
+// actor is who changed or proposed the receiver
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ReceiverChange {
+    pub old: Addr,
+    pub receiver: Addr,
+    pub actor: Addr,
+    pub height: u64,
+    pub time: u64,
+}
+
+// Proposal is a vote on making receiver the new receiver, or on replacing the governance
+// settings when governance is set, open until ends
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Proposal {
+    #[serde(default)]
+    pub receiver: Option<Addr>,
+    #[serde(default)]
+    pub governance: Option<GovernanceChange>,
+    pub proposer: Addr,
+    pub yes: u64,
+    pub no: u64,
+    pub ends: Expiration,
+    pub executed: bool,
+}
+
+// Referral is the referrer of a code and what was deposited with it
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Referral {
+    pub referrer: Addr,
+    pub deposits: u64,
+    pub volume: Vec<Coin>,
+    pub commission: Vec<Coin>,
+}
+
+// Transfer records a deposit and who it was forwarded to. cw20 amounts use the token
+// contract address as denom.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Transfer {
+    pub sender: Addr,
+    pub amount: Vec<Coin>,
+    pub recipients: Vec<Addr>,
+    pub height: u64,
+    #[serde(default)]
+    pub memo: Option<String>,
+    #[serde(default)]
+    pub status: ReceiptStatus,
+    // the aTerra minted for the deposit in anchor mode
+    #[serde(default)]
+    pub minted: Option<Coin>,
+    #[serde(default)]
+    pub category: Option<String>,
+    // items break a forwarded deposit down by denom
+    #[serde(default)]
+    pub items: Vec<DenomRouting>,
+}
+
+// ReceiptStatus is where a deposit stands: Pending while it waits for approval or in
+// escrow, Held while hold mode keeps it, Locked while it streams or a campaign keeps it,
+// Forwarded once paid out and Refunded once it went back to the sender
+#[derive(Serialize, Deserialize, Clone, Copy, Default, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ReceiptStatus {
+    #[default]
+    Forwarded,
+    Pending,
+    Held,
+    Locked,
+    Refunded,
+}
+
+// Escrow is a deposit kept in escrow mode or locked by a hash, it is stored under the id of
+// its transfer. hashlock is the hex encoded SHA-256 hash the receiver needs the preimage of.
+// Once either side disputes an escrow with an arbiter only the arbiter can settle it
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Escrow {
+    pub sender: Addr,
+    pub receiver: Addr,
+    pub amount: Vec<Coin>,
+    pub expires: Expiration,
+    #[serde(default)]
+    pub hashlock: Option<String>,
+    #[serde(default)]
+    pub arbiter: Option<Addr>,
+    #[serde(default)]
+    pub disputed: bool,
+}
+
+// Payout is a message paying recipient in hold_failed mode. It is kept until its reply,
+// error is why it failed
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Payout {
+    pub recipient: Addr,
+    pub msg: CosmosMsg,
+    pub error: Option<String>,
+    // who made the deposit the payout comes from, if known
+    #[serde(default)]
+    pub sender: Option<Addr>,
+}
+
+// PendingForward is a deposit above the approval threshold waiting for the owner, it is
+// stored under the id of its transfer. recipient is the one-off recipient it was sent to
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PendingForward {
+    pub sender: Addr,
+    pub recipient: Option<Addr>,
+    pub amount: Vec<Coin>,
+}
+
+// Stream is a deposit vesting linearly from start to end (in seconds), nothing vests
+// before cliff. It is stored under the id of its transfer. claimed is what was paid out
+// already, the owner can revoke what has not vested yet of revocable streams.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Stream {
+    pub sender: Addr,
+    pub amount: Vec<Coin>,
+    pub claimed: Vec<Coin>,
+    pub start: u64,
+    #[serde(default)]
+    pub cliff: u64,
+    pub end: u64,
+    #[serde(default)]
+    pub revocable: bool,
+}
+
+impl Stream {
+    // vested returns how much of the stream has vested at time
+    pub fn vested(&self, time: u64) -> Vec<Coin> {
+        let duration = self.end - self.start;
+        let elapsed = time.min(self.end).saturating_sub(self.start);
+        self.amount
+            .iter()
+            .map(|coin| Coin {
+                denom: coin.denom.clone(),
+                amount: if time < self.cliff {
+                    Uint128::zero()
+                } else if duration == 0 {
+                    coin.amount
+                } else {
+                    coin.amount.multiply_ratio(elapsed, duration)
+                },
+            })
+            .collect()
+    }
+
+    // next_unlock returns when more of the stream vests after time, None once it fully vested
+    pub fn next_unlock(&self, time: u64) -> Option<u64> {
+        if time < self.cliff {
+            return Some(self.cliff);
+        }
+        if time >= self.end {
+            return None;
+        }
+        let duration = (self.end - self.start) as u128;
+        self.vested(time)
+            .iter()
+            .zip(self.amount.iter())
+            .filter(|(vested, total)| vested.amount < total.amount)
+            .map(|(vested, total)| {
+                // the first second at which one more unit of total has vested
+                let total = total.amount.u128();
+                let elapsed = ((vested.amount.u128() + 1) * duration).div_ceil(total);
+                self.start + elapsed as u64
+            })
+            .min()
+    }
+}
+
+// Subscription pays amount to the receivers every interval seconds out of balance, which
+// the sender prefunded. The next installment is due at next_charge (in seconds). A cw20
+// subscription pulls its installments of token with the allowance of the sender instead,
+// amount uses the token address as denom. It is delinquent once an installment could not
+// be pulled
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Subscription {
+    pub sender: Addr,
+    pub amount: Coin,
+    pub interval: u64,
+    pub next_charge: u64,
+    pub balance: Uint128,
+    #[serde(default)]
+    pub token: Option<Addr>,
+    #[serde(default)]
+    pub delinquent: bool,
+}
+
+// LegacyState is the config layout of instances deployed before version tracking,
+// which only knew a single receiver. It is read once when migrating them.
+// Unbonding is an undelegation paid to a receiver, completes_at is when the chain returns
+// the funds in seconds
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Unbonding {
+    pub amount: Coin,
+    pub completes_at: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct LegacyState {
     pub receiver: CanonicalAddr,
     pub owner: CanonicalAddr,
 }
 
-pub fn config<S: Storage>(storage: &mut S) -> Singleton<S, State> {
-    singleton(storage, CONFIG_KEY)
+pub const CONFIG: Item<Config> = Item::new("config");
+pub const LEGACY_CONFIG: Item<LegacyState> = Item::new("config");
+
+// CLAIMABLE holds the native funds each receiver can withdraw in pull mode
+pub const CLAIMABLE: Map<&Addr, Vec<Coin>> = Map::new("claimable");
+
+// UNBONDING holds the undelegated funds of each receiver that are still unbonding, they
+// become claimable once completed
+pub const UNBONDING: Map<&Addr, Vec<Unbonding>> = Map::new("unbonding");
+
+// CLAIMED_AT holds when each receiver with a claimable balance last withdrew, or when it was
+// credited first since, in seconds
+pub const CLAIMED_AT: Map<&Addr, u64> = Map::new("claimed_at");
+
+// PERMISSIONS holds the permission mask granted to every address, see Permission::bit
+pub const PERMISSIONS: Map<&Addr, u64> = Map::new("permissions");
+
+// RECEIVER_CHANGES holds the receiver history by id, RECEIVER_CHANGE_COUNT is the id of the
+// last change
+pub const RECEIVER_CHANGES: Map<u64, ReceiverChange> = Map::new("receiver_change");
+pub const RECEIVER_CHANGE_COUNT: Item<u64> = Item::new("receiver_change_count");
+
+// TRANSFERS holds the transfer history by id, TRANSFER_COUNT is the id of the last one
+pub const TRANSFERS: Map<u64, Transfer> = Map::new("transfer");
+pub const TRANSFER_COUNT: Item<u64> = Item::new("transfer_count");
+// TRANSFERS_PRUNED is the id of the last transfer pruned from the history
+pub const TRANSFERS_PRUNED: Item<u64> = Item::new("transfers_pruned");
+
+// MEMO_TRANSFERS indexes the ids of the transfers sent with a memo
+pub const MEMO_TRANSFERS: Map<(&str, u64), Empty> = Map::new("memo");
+
+// SENDER_TRANSFERS and RECEIVER_TRANSFERS index the ids of the transfers by sender and by
+// every recipient paid
+pub const SENDER_TRANSFERS: Map<(&Addr, u64), Empty> = Map::new("sender_transfer");
+pub const RECEIVER_TRANSFERS: Map<(&Addr, u64), Empty> = Map::new("receiver_transfer");
+
+// STATS keeps a changelog entry per block it changed in, so the totals can be read back at
+// any height
+pub const STATS: SnapshotItem<Stats> = SnapshotItem::new(
+    "stats",
+    "stats__checkpoints",
+    "stats__changelog",
+    Strategy::EveryBlock,
+);
+
+// HELD is the balance kept in hold mode that is not forwarded yet
+pub const HELD: Item<Vec<Coin>> = Item::new("held");
+
+// HELD_RECEIPTS are the ids of the held deposits, they are settled as forwarded once none
+// of their denoms is held anymore
+pub const HELD_RECEIPTS: Item<Vec<u64>> = Item::new("held_receipts");
+
+// WATERFALL_FILLED holds how much of each denom a waterfall receiver got so far
+pub const WATERFALL_FILLED: Map<(&Addr, &str), Uint128> = Map::new("waterfall_filled");
+
+pub const CAMPAIGN_PROGRESS: Item<CampaignProgress> = Item::new("campaign_progress");
+// CAMPAIGN_DEPOSITS holds what each sender deposited during the campaign
+pub const CAMPAIGN_DEPOSITS: Map<&Addr, Vec<Coin>> = Map::new("campaign_deposit");
+
+// DUST collects the fees and shares too small to send, FlushDust pays it to the receiver
+pub const DUST: Item<Vec<Coin>> = Item::new("dust");
+
+pub const PENDING_RECEIVER: Item<PendingReceiver> = Item::new("pending_receiver");
+
+// ScheduledReceiver is a receiver that takes over at height active_from, scheduled_by is
+// recorded as the actor of the change
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ScheduledReceiver {
+    pub receiver: Addr,
+    pub active_from: u64,
+    pub scheduled_by: Addr,
+}
+
+// ROTATION holds the upcoming receivers by ascending active_from
+pub const ROTATION: Item<Vec<ScheduledReceiver>> = Item::new("rotation");
+
+pub const PROPOSALS: Map<u64, Proposal> = Map::new("proposal");
+pub const PROPOSAL_COUNT: Item<u64> = Item::new("proposal_count");
+// VOTES records who voted on a proposal and whether they approved
+pub const VOTES: Map<(u64, &Addr), bool> = Map::new("vote");
+
+pub const ADMIN_PROPOSALS: Map<u64, AdminProposal> = Map::new("admin_proposal");
+pub const ADMIN_PROPOSAL_COUNT: Item<u64> = Item::new("admin_proposal_count");
+
+// EmergencyWithdrawal is an announced EmergencyWithdraw to recipient, it can be executed
+// from height execute_after on
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct EmergencyWithdrawal {
+    pub recipient: Addr,
+    pub execute_after: u64,
+}
+
+pub const PENDING_EMERGENCY: Item<EmergencyWithdrawal> = Item::new("pending_emergency");
+
+// WindDown is set once the owner winds the contract down. Escrowed and pending funds can be
+// claimed until height claim_deadline, after that the rest goes to the receiver and the
+// contract is terminated
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct WindDown {
+    pub claim_deadline: u64,
+    pub terminated: bool,
+}
+
+pub const WIND_DOWN: Item<WindDown> = Item::new("wind_down");
+
+// ALLOWED_SENDERS holds the senders that may deposit when senders are restricted
+pub const ALLOWED_SENDERS: Map<&Addr, Empty> = Map::new("allowed_sender");
+// ALIASES maps short names to recipients
+pub const ALIASES: Map<&str, Addr> = Map::new("alias");
+
+// Destination is where a route sends deposits and which message pays it: Burn burns them,
+// CommunityPool funds the community pool of the chain, Wallet is a bank send, Contract
+// executes msg with the deposit attached and Validator delegates it, the contract holding
+// the delegation
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Destination {
+    Burn,
+    CommunityPool,
+    Wallet(Addr),
+    Contract { addr: Addr, msg: Binary },
+    Validator(String),
+}
+
+impl Destination {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Destination::Burn => "burn",
+            Destination::CommunityPool => "community_pool",
+            Destination::Wallet(_) => "wallet",
+            Destination::Contract { .. } => "contract",
+            Destination::Validator(_) => "validator",
+        }
+    }
+}
+
+// DESTINATIONS maps route names to special destinations, a name is either an alias or a
+// destination
+pub const DESTINATIONS: Map<&str, Destination> = Map::new("destination");
+
+// Route is a named receiver set with its own fee and denom policy. A deposit routed to it
+// is split between its receivers as if they were the contract's
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Route {
+    pub receiver: Addr,
+    pub receivers: Vec<(Addr, Decimal)>,
+    pub fee_rate: Decimal,
+    pub fee_collector: Option<Addr>,
+    pub fee_cap: Vec<Coin>,
+    pub accepted_denoms: Vec<String>,
+    pub funds_policy: FundsPolicy,
+}
+
+// ROUTES maps route names to routes, a name is an alias, a destination or a route
+pub const ROUTES: Map<&str, Route> = Map::new("route");
+
+// PAUSED_ROUTES holds the routes that take no deposits while the rest of the contract runs
+pub const PAUSED_ROUTES: Map<&str, Empty> = Map::new("paused_route");
+
+// ROUTE_STATS sums up the deposits forwarded through each route
+pub const ROUTE_STATS: Map<&str, Stats> = Map::new("route_stats");
+
+// Category is a tag a sender can attach to a deposit. A deposit tagged with a category that
+// has a receiver is forwarded to it instead of the contract's receivers
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Category {
+    pub receiver: Option<Addr>,
+}
+
+// CATEGORIES maps the tags the owner defined to their category
+pub const CATEGORIES: Map<&str, Category> = Map::new("category");
+
+// CATEGORY_STATS sums up the deposits tagged with each category
+pub const CATEGORY_STATS: Map<&str, Stats> = Map::new("category_stats");
+
+pub const REFERRALS: Map<&str, Referral> = Map::new("referral");
+
+// BLOCKED_SENDERS holds the senders that may never deposit, restricted or not
+pub const BLOCKED_SENDERS: Map<&Addr, Empty> = Map::new("blocked_sender");
+
+// ALLOWED_METHODS holds the receiver methods ForwardExec may call
+pub const ALLOWED_METHODS: Map<&str, Empty> = Map::new("allowed_method");
+
+pub const SENDER_USAGE: Map<&Addr, Usage> = Map::new("sender_usage");
+pub const DAILY_USAGE: Item<Usage> = Item::new("daily_usage");
+pub const TRAFFIC: Item<Traffic> = Item::new("traffic");
+
+pub const IBC_TRANSFER_COUNT: Item<u64> = Item::new("ibc_transfer_count");
+pub const IBC_TRANSFERS: Map<u64, IbcTransfer> = Map::new("ibc_transfer");
+// IBC_SEQUENCES finds the transfer the packet with a channel and sequence belongs to
+pub const IBC_SEQUENCES: Map<(&str, u64), u64> = Map::new("ibc_sequence");
+
+pub struct EscrowIndexes<'a> {
+    pub sender: MultiIndex<'a, Addr, Escrow, u64>,
+    pub receiver: MultiIndex<'a, Addr, Escrow, u64>,
+}
+
+impl IndexList<Escrow> for EscrowIndexes<'_> {
+    fn get_indexes(&'_ self) -> Box<dyn Iterator<Item = &'_ dyn Index<Escrow>> + '_> {
+        let v: Vec<&dyn Index<Escrow>> = vec![&self.sender, &self.receiver];
+        Box::new(v.into_iter())
+    }
+}
+
+// escrows holds the open escrows by id, indexed by sender and receiver
+pub fn escrows<'a>() -> IndexedMap<'a, u64, Escrow, EscrowIndexes<'a>> {
+    let indexes = EscrowIndexes {
+        sender: MultiIndex::new(
+            |_, escrow| escrow.sender.clone(),
+            "escrow",
+            "escrow__sender",
+        ),
+        receiver: MultiIndex::new(
+            |_, escrow| escrow.receiver.clone(),
+            "escrow",
+            "escrow__receiver",
+        ),
+    };
+    IndexedMap::new("escrow", indexes)
+}
+
+// Contribution is what sender deposited of denom so far
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Contribution {
+    pub sender: Addr,
+    pub denom: String,
+    pub amount: Uint128,
+}
+
+pub struct ContributionIndexes<'a> {
+    pub amount: MultiIndex<'a, (String, u128), Contribution, (&'a Addr, &'a str)>,
+}
+
+impl IndexList<Contribution> for ContributionIndexes<'_> {
+    fn get_indexes(&'_ self) -> Box<dyn Iterator<Item = &'_ dyn Index<Contribution>> + '_> {
+        let v: Vec<&dyn Index<Contribution>> = vec![&self.amount];
+        Box::new(v.into_iter())
+    }
+}
+
+// contributions holds the contributions by sender and denom, indexed by denom and amount
+// for the leaderboard
+pub fn contributions<'a>(
+) -> IndexedMap<'a, (&'a Addr, &'a str), Contribution, ContributionIndexes<'a>> {
+    let indexes = ContributionIndexes {
+        amount: MultiIndex::new(
+            |_, contribution| (contribution.denom.clone(), contribution.amount.u128()),
+            "contribution",
+            "contribution__amount",
+        ),
+    };
+    IndexedMap::new("contribution", indexes)
+}
+
+// LIFETIME_CAPS limits what a sender may contribute in total, denoms without a cap are not
+// limited
+pub const LIFETIME_CAPS: Map<&Addr, Vec<Coin>> = Map::new("lifetime_cap");
+
+pub struct SubscriptionIndexes<'a> {
+    pub next_charge: MultiIndex<'a, u64, Subscription, u64>,
+}
+
+impl IndexList<Subscription> for SubscriptionIndexes<'_> {
+    fn get_indexes(&'_ self) -> Box<dyn Iterator<Item = &'_ dyn Index<Subscription>> + '_> {
+        let v: Vec<&dyn Index<Subscription>> = vec![&self.next_charge];
+        Box::new(v.into_iter())
+    }
+}
+
+// subscriptions holds the subscriptions by id, indexed by when they are charged next
+pub fn subscriptions<'a>() -> IndexedMap<'a, u64, Subscription, SubscriptionIndexes<'a>> {
+    let indexes = SubscriptionIndexes {
+        next_charge: MultiIndex::new(
+            |_, subscription| subscription.next_charge,
+            "subscription",
+            "subscription__next_charge",
+        ),
+    };
+    IndexedMap::new("subscription", indexes)
+}
+
+pub const STREAMS: Map<u64, Stream> = Map::new("stream");
+
+// SWAP_BALANCE is the target denom balance before the next swap of a deposit, the proceeds
+// of a swap are what the balance grew by
+pub const SWAP_BALANCE: Item<Uint128> = Item::new("swap_balance");
+
+// AnchorDeposit is the aTerra balance before a deposit into the money market and the receipt
+// of the deposit, the minted aTerra is what the balance grew by
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct AnchorDeposit {
+    pub balance: Uint128,
+    pub receipt_id: u64,
+}
+
+pub const ANCHOR_DEPOSIT: Item<AnchorDeposit> = Item::new("anchor_deposit");
+
+// PAYOUT_SENDER is the sender of the deposit being paid out, payouts made meanwhile record it
+pub const PAYOUT_SENDER: Item<Addr> = Item::new("payout_sender");
+
+// REFUNDS holds what failed payouts owe back to each sender, ClaimRefund pays it
+pub const REFUNDS: Map<&Addr, Vec<Coin>> = Map::new("refund");
+
+// PAYOUTS holds the payouts waiting for their reply, FAILED_PAYOUTS the ones that failed.
+// PAYOUT_COUNT is the id of the last payout
+pub const PAYOUTS: Map<u64, Payout> = Map::new("payout");
+pub const FAILED_PAYOUTS: Map<u64, Payout> = Map::new("failed_payout");
+pub const PAYOUT_COUNT: Item<u64> = Item::new("payout_count");
+
+// PAYOUT_QUEUE holds the payouts queued for Distribute by id. PAYOUT_QUEUE_COUNT is the id
+// of the last queued payout and PAYOUT_QUEUE_CURSOR the id of the last one paid
+pub const PAYOUT_QUEUE: Map<u64, (Addr, Vec<Coin>)> = Map::new("payout_queue");
+pub const PAYOUT_QUEUE_COUNT: Item<u64> = Item::new("payout_queue_count");
+pub const PAYOUT_QUEUE_CURSOR: Item<u64> = Item::new("payout_queue_cursor");
+
+// REBATE_POOL holds the funds claim rebates are paid from
+pub const REBATE_POOL: Item<Vec<Coin>> = Item::new("rebate_pool");
+
+pub const PENDING_FORWARDS: Map<u64, PendingForward> = Map::new("pending_forward");
+
+// SUBSCRIPTION_COUNT is the id of the last registered subscription
+pub const SUBSCRIPTION_COUNT: Item<u64> = Item::new("subscription_count");
+
+// CHARGES holds what a cw20 subscription is charged until its token replied, by subscription
+// id. The installment is forwarded once it arrived
+pub const CHARGES: Map<u64, Uint128> = Map::new("charge");
+
+// Invoice is a payment the owner requested from payer. It can be paid in parts, received
+// is what was paid towards it so far and paid the receipt of the payment that settled it
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Invoice {
+    pub payer: Addr,
+    pub amount: Coin,
+    pub expires: Expiration,
+    pub paid: Option<u64>,
+    #[serde(default)]
+    pub received: Uint128,
+    #[serde(default)]
+    pub overpayment: Overpayment,
+}
+
+impl Invoice {
+    pub fn remaining(&self) -> Uint128 {
+        self.amount.amount.saturating_sub(self.received)
+    }
+}
+
+// Overpayment decides what happens to a payment above what is left to pay on an invoice
+#[derive(Serialize, Deserialize, Clone, Copy, Default, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Overpayment {
+    // reject the payment
+    #[default]
+    Reject,
+    // forward the excess together with the payment
+    ForwardExcess,
+    // forward what is left to pay and refund the excess to the payer
+    RefundExcess,
+}
+
+pub struct InvoiceIndexes<'a> {
+    pub payer: MultiIndex<'a, Addr, Invoice, u64>,
+}
+
+impl IndexList<Invoice> for InvoiceIndexes<'_> {
+    fn get_indexes(&'_ self) -> Box<dyn Iterator<Item = &'_ dyn Index<Invoice>> + '_> {
+        let v: Vec<&dyn Index<Invoice>> = vec![&self.payer];
+        Box::new(v.into_iter())
+    }
+}
+
+// invoices holds every invoice by id, indexed by payer
+pub fn invoices<'a>() -> IndexedMap<'a, u64, Invoice, InvoiceIndexes<'a>> {
+    let indexes = InvoiceIndexes {
+        payer: MultiIndex::new(
+            |_, invoice| invoice.payer.clone(),
+            "invoice",
+            "invoice__payer",
+        ),
+    };
+    IndexedMap::new("invoice", indexes)
+}
+
+// INVOICE_COUNT is the id of the last invoice
+pub const INVOICE_COUNT: Item<u64> = Item::new("invoice_count");
+
+// PERMIT_NONCE is the nonce the next permit has to use
+pub const PERMIT_NONCE: Item<u64> = Item::new("permit_nonce");
+
+// cosmwasm-storage kept singletons under their length prefixed key while an Item uses the
+// plain key. Maps share the bucket layout and need no move.
+const SINGLETON_KEYS: [&str; 5] = [
+    "config",
+    "transfer_count",
+    "held",
+    "pending_receiver",
+    "daily_usage",
+];
+
+// move_singletons moves the values stored by cosmwasm-storage singletons to their Item
+pub fn move_singletons(storage: &mut dyn Storage) {
+    for key in SINGLETON_KEYS {
+        let singleton_key = length_prefixed(key);
+        if let Some(value) = storage.get(&singleton_key) {
+            storage.set(key.as_bytes(), &value);
+            storage.remove(&singleton_key);
+        }
+    }
 }
 
-pub fn config_read<S: Storage>(storage: &S) -> ReadonlySingleton<S, State> {
-    singleton_read(storage, CONFIG_KEY)
+pub fn length_prefixed(key: &str) -> Vec<u8> {
+    let mut prefixed = (key.len() as u16).to_be_bytes().to_vec();
+    prefixed.extend_from_slice(key.as_bytes());
+    prefixed
 }
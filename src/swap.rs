@@ -0,0 +1,51 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use cosmwasm_std::{to_json_binary, Addr, Coin, Decimal, StdResult, Uint128, WasmMsg};
+
+// The messages of Terraswap style pair contracts, Astroport pairs accept the same swap.
+// Only what is needed to swap native coins is defined here.
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum PairExecuteMsg {
+    // Swap sells offer_asset, the pair fails if the price moved more than max_spread
+    Swap {
+        offer_asset: Asset,
+        belief_price: Option<Decimal>,
+        max_spread: Option<Decimal>,
+        to: Option<String>,
+    },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Asset {
+    pub info: AssetInfo,
+    pub amount: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum AssetInfo {
+    Token { contract_addr: String },
+    NativeToken { denom: String },
+}
+
+// swap_msg sells coin on pair, the proceeds are sent back to the caller
+pub fn swap_msg(pair: &Addr, coin: Coin, max_spread: Decimal) -> StdResult<WasmMsg> {
+    Ok(WasmMsg::Execute {
+        contract_addr: pair.to_string(),
+        msg: to_json_binary(&PairExecuteMsg::Swap {
+            offer_asset: Asset {
+                info: AssetInfo::NativeToken {
+                    denom: coin.denom.clone(),
+                },
+                amount: coin.amount,
+            },
+            belief_price: None,
+            max_spread: Some(max_spread),
+            to: None,
+        })?,
+        funds: vec![coin],
+    })
+}
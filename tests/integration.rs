@@ -0,0 +1,343 @@
+// Integration tests run the contract in a multi-test App, so deposits move real bank
+// balances instead of only emitting messages.
+
+use std::fmt::Debug;
+
+use anyhow::{bail, Result as AnyResult};
+use cosmwasm_std::testing::{MockApi, MockStorage};
+use cosmwasm_std::{
+    coin, coins, to_json_binary, Addr, Api, Binary, BlockInfo, Coin, CustomQuery, Decimal, Empty,
+    Querier, Storage, Uint128,
+};
+use cw_multi_test::{
+    App, AppResponse, BankKeeper, BasicAppBuilder, ContractWrapper, CosmosRouter, Executor, Module,
+    WasmKeeper,
+};
+use schemars::JsonSchema;
+use serde::de::DeserializeOwned;
+use terra_cosmwasm::{TaxCapResponse, TaxRateResponse, TerraQuery, TerraQueryWrapper};
+
+use spar_test::contract::{execute, instantiate, migrate, query};
+use spar_test::msg::{ClaimableResponse, ExecuteMsg, InstantiateMsg, QueryMsg};
+use spar_test::state::FundsPolicy;
+
+// TerraTax answers the Terra tax queries of the contract. Unlike the chain the bank of the
+// App does not charge the tax, so what is deducted for it stays in the contract.
+struct TerraTax {
+    rate: Decimal,
+    caps: Vec<(String, Uint128)>,
+}
+
+impl Module for TerraTax {
+    type ExecT = Empty;
+    type QueryT = TerraQueryWrapper;
+    type SudoT = Empty;
+
+    fn execute<ExecC, QueryC>(
+        &self,
+        _api: &dyn Api,
+        _storage: &mut dyn Storage,
+        _router: &dyn CosmosRouter<ExecC = ExecC, QueryC = QueryC>,
+        _block: &BlockInfo,
+        _sender: Addr,
+        _msg: Empty,
+    ) -> AnyResult<AppResponse>
+    where
+        ExecC: Debug + Clone + PartialEq + JsonSchema + DeserializeOwned + 'static,
+        QueryC: CustomQuery + DeserializeOwned + 'static,
+    {
+        bail!("unexpected custom message")
+    }
+
+    fn query(
+        &self,
+        _api: &dyn Api,
+        _storage: &dyn Storage,
+        _querier: &dyn Querier,
+        _block: &BlockInfo,
+        request: TerraQueryWrapper,
+    ) -> AnyResult<Binary> {
+        match request.query_data {
+            TerraQuery::TaxRate {} => Ok(to_json_binary(&TaxRateResponse { rate: self.rate })?),
+            TerraQuery::TaxCap { denom } => {
+                let cap = self
+                    .caps
+                    .iter()
+                    .find(|(d, _)| *d == denom)
+                    .map(|(_, cap)| *cap)
+                    .unwrap_or_default();
+                Ok(to_json_binary(&TaxCapResponse { cap })?)
+            }
+            query => bail!("unsupported terra query {:?}", query),
+        }
+    }
+
+    fn sudo<ExecC, QueryC>(
+        &self,
+        _api: &dyn Api,
+        _storage: &mut dyn Storage,
+        _router: &dyn CosmosRouter<ExecC = ExecC, QueryC = QueryC>,
+        _block: &BlockInfo,
+        _msg: Empty,
+    ) -> AnyResult<AppResponse>
+    where
+        ExecC: Debug + Clone + PartialEq + JsonSchema + DeserializeOwned + 'static,
+        QueryC: CustomQuery + DeserializeOwned + 'static,
+    {
+        bail!("unexpected sudo message")
+    }
+}
+
+type TerraApp =
+    App<BankKeeper, MockApi, MockStorage, TerraTax, WasmKeeper<Empty, TerraQueryWrapper>>;
+
+// mock_app funds the given accounts and charges tax_rate on native sends, capped per denom
+// by tax_caps
+fn mock_app(
+    balances: &[(&str, Vec<Coin>)],
+    tax_rate: Decimal,
+    tax_caps: &[(&str, u128)],
+) -> TerraApp {
+    let tax = TerraTax {
+        rate: tax_rate,
+        caps: tax_caps
+            .iter()
+            .map(|(denom, cap)| (denom.to_string(), Uint128::new(*cap)))
+            .collect(),
+    };
+    BasicAppBuilder::<Empty, TerraQueryWrapper>::new_custom()
+        .with_custom(tax)
+        .build(|router, _, storage| {
+            for (addr, amount) in balances {
+                router
+                    .bank
+                    .init_balance(storage, &Addr::unchecked(*addr), amount.clone())
+                    .unwrap();
+            }
+        })
+}
+
+fn instantiate_contract(app: &mut TerraApp, msg: InstantiateMsg) -> Addr {
+    let contract =
+        ContractWrapper::new_with_empty(execute, instantiate, query).with_migrate_empty(migrate);
+    let code_id = app.store_code(Box::new(contract));
+    app.instantiate_contract(
+        code_id,
+        Addr::unchecked("creator"),
+        &msg,
+        &[],
+        "spar-test",
+        None,
+    )
+    .unwrap()
+}
+
+fn balance(app: &TerraApp, addr: &str, denom: &str) -> u128 {
+    app.wrap().query_balance(addr, denom).unwrap().amount.u128()
+}
+
+fn token_send() -> ExecuteMsg {
+    ExecuteMsg::TokenSend {
+        recipient: None,
+        route: None,
+        memo: None,
+        on_behalf_of: None,
+        category: None,
+    }
+}
+
+#[test]
+fn tokensend_moves_funds() {
+    let mut app = mock_app(&[("alice", coins(1000, "uusd"))], Decimal::zero(), &[]);
+    let contract = instantiate_contract(
+        &mut app,
+        InstantiateMsg {
+            receiver: "treasury".to_string(),
+            ..Default::default()
+        },
+    );
+
+    app.execute_contract(
+        Addr::unchecked("alice"),
+        contract.clone(),
+        &token_send(),
+        &coins(100, "uusd"),
+    )
+    .unwrap();
+
+    assert_eq!(balance(&app, "alice", "uusd"), 900);
+    assert_eq!(balance(&app, "treasury", "uusd"), 100);
+    assert_eq!(balance(&app, contract.as_str(), "uusd"), 0);
+}
+
+#[test]
+fn split_and_fee() {
+    let mut app = mock_app(&[("alice", coins(1000, "uusd"))], Decimal::zero(), &[]);
+    let contract = instantiate_contract(
+        &mut app,
+        InstantiateMsg {
+            receiver: "treasury".to_string(),
+            receivers: vec![
+                ("bob".to_string(), Decimal::percent(60)),
+                ("carol".to_string(), Decimal::percent(40)),
+            ],
+            fee_rate: Decimal::percent(10),
+            fee_collector: Some("collector".to_string()),
+            ..Default::default()
+        },
+    );
+
+    app.execute_contract(
+        Addr::unchecked("alice"),
+        contract.clone(),
+        &token_send(),
+        &coins(1000, "uusd"),
+    )
+    .unwrap();
+
+    assert_eq!(balance(&app, "collector", "uusd"), 100);
+    assert_eq!(balance(&app, "bob", "uusd"), 540);
+    assert_eq!(balance(&app, "carol", "uusd"), 360);
+    assert_eq!(balance(&app, "treasury", "uusd"), 0);
+    assert_eq!(balance(&app, contract.as_str(), "uusd"), 0);
+}
+
+#[test]
+fn refund_unaccepted_denoms() {
+    let mut app = mock_app(
+        &[("alice", vec![coin(1000, "uusd"), coin(1000, "ukrw")])],
+        Decimal::zero(),
+        &[],
+    );
+    let contract = instantiate_contract(
+        &mut app,
+        InstantiateMsg {
+            receiver: "treasury".to_string(),
+            accepted_denoms: vec!["uusd".to_string()],
+            funds_policy: FundsPolicy::ForwardAccepted,
+            ..Default::default()
+        },
+    );
+
+    app.execute_contract(
+        Addr::unchecked("alice"),
+        contract.clone(),
+        &token_send(),
+        &[coin(100, "ukrw"), coin(100, "uusd")],
+    )
+    .unwrap();
+
+    assert_eq!(balance(&app, "treasury", "uusd"), 100);
+    assert_eq!(balance(&app, "treasury", "ukrw"), 0);
+    assert_eq!(balance(&app, "alice", "uusd"), 900);
+    assert_eq!(balance(&app, "alice", "ukrw"), 1000);
+    assert_eq!(balance(&app, contract.as_str(), "ukrw"), 0);
+}
+
+#[test]
+fn pull_mode_withdraw() {
+    let mut app = mock_app(&[("alice", coins(1000, "uusd"))], Decimal::zero(), &[]);
+    let contract = instantiate_contract(
+        &mut app,
+        InstantiateMsg {
+            receiver: "treasury".to_string(),
+            pull: true,
+            ..Default::default()
+        },
+    );
+
+    app.execute_contract(
+        Addr::unchecked("alice"),
+        contract.clone(),
+        &token_send(),
+        &coins(100, "uusd"),
+    )
+    .unwrap();
+
+    // the deposit stays in the contract until the receiver withdraws it
+    assert_eq!(balance(&app, contract.as_str(), "uusd"), 100);
+    assert_eq!(balance(&app, "treasury", "uusd"), 0);
+    let claimable: ClaimableResponse = app
+        .wrap()
+        .query_wasm_smart(
+            contract.as_str(),
+            &QueryMsg::GetClaimable {
+                address: "treasury".to_string(),
+            },
+        )
+        .unwrap();
+    assert_eq!(claimable.claimable, coins(100, "uusd"));
+
+    app.execute_contract(
+        Addr::unchecked("treasury"),
+        contract.clone(),
+        &ExecuteMsg::Withdraw {
+            denom: None,
+            amount: None,
+        },
+        &[],
+    )
+    .unwrap();
+
+    assert_eq!(balance(&app, contract.as_str(), "uusd"), 0);
+    assert_eq!(balance(&app, "treasury", "uusd"), 100);
+}
+
+#[test]
+fn tax_is_deducted() {
+    let mut app = mock_app(
+        &[("alice", coins(1000, "uusd"))],
+        Decimal::percent(1),
+        &[("uusd", 1_000_000)],
+    );
+    let contract = instantiate_contract(
+        &mut app,
+        InstantiateMsg {
+            receiver: "treasury".to_string(),
+            ..Default::default()
+        },
+    );
+
+    app.execute_contract(
+        Addr::unchecked("alice"),
+        contract.clone(),
+        &token_send(),
+        &coins(101, "uusd"),
+    )
+    .unwrap();
+
+    // the receiver gets what is left once the tax is paid
+    assert_eq!(balance(&app, "treasury", "uusd"), 100);
+    assert_eq!(balance(&app, contract.as_str(), "uusd"), 1);
+}
+
+#[test]
+fn failed_deposit_keeps_funds() {
+    let mut app = mock_app(&[("alice", coins(1000, "uusd"))], Decimal::zero(), &[]);
+    let contract = instantiate_contract(
+        &mut app,
+        InstantiateMsg {
+            receiver: "treasury".to_string(),
+            ..Default::default()
+        },
+    );
+    app.execute_contract(
+        Addr::unchecked("creator"),
+        contract.clone(),
+        &ExecuteMsg::Pause {},
+        &[],
+    )
+    .unwrap();
+
+    app.execute_contract(
+        Addr::unchecked("alice"),
+        contract.clone(),
+        &token_send(),
+        &coins(100, "uusd"),
+    )
+    .unwrap_err();
+
+    assert_eq!(balance(&app, "alice", "uusd"), 1000);
+    assert_eq!(balance(&app, "treasury", "uusd"), 0);
+    assert_eq!(balance(&app, contract.as_str(), "uusd"), 0);
+}